@@ -0,0 +1,69 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2024 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Feeds arbitrary input through message deserialization. Bad input may fail to deserialize, or may
+//! deserialize into a message that fails validation, but it must never panic.
+
+#![no_main]
+
+use buttplug::core::message::{
+  serializer::{
+    ButtplugClientJSONSerializer,
+    ButtplugMessageSerializer,
+    ButtplugSerializedMessage,
+    ButtplugServerJSONSerializer,
+  },
+  ButtplugClientMessageV0,
+  ButtplugClientMessageV1,
+  ButtplugClientMessageV2,
+  ButtplugClientMessageV3,
+  ButtplugClientMessageV4,
+  ButtplugMessageValidator,
+  ButtplugServerMessageV0,
+  ButtplugServerMessageV1,
+  ButtplugServerMessageV2,
+  ButtplugServerMessageV3,
+  ButtplugServerMessageV4,
+  SensorDeviceMessageAttributesV3,
+};
+use libfuzzer_sys::fuzz_target;
+use serde::de::DeserializeOwned;
+
+fn deserialize_and_validate<T>(json: &str)
+where
+  T: DeserializeOwned + ButtplugMessageValidator,
+{
+  if let Ok(msgs) = serde_json::from_str::<Vec<T>>(json) {
+    for msg in msgs {
+      let _ = msg.is_valid();
+    }
+  }
+}
+
+fuzz_target!(|data: &[u8]| {
+  let Ok(json) = std::str::from_utf8(data) else {
+    return;
+  };
+  deserialize_and_validate::<ButtplugClientMessageV0>(json);
+  deserialize_and_validate::<ButtplugClientMessageV1>(json);
+  deserialize_and_validate::<ButtplugClientMessageV2>(json);
+  deserialize_and_validate::<ButtplugClientMessageV3>(json);
+  deserialize_and_validate::<ButtplugClientMessageV4>(json);
+  deserialize_and_validate::<ButtplugServerMessageV0>(json);
+  deserialize_and_validate::<ButtplugServerMessageV1>(json);
+  deserialize_and_validate::<ButtplugServerMessageV2>(json);
+  deserialize_and_validate::<ButtplugServerMessageV3>(json);
+  deserialize_and_validate::<ButtplugServerMessageV4>(json);
+  // Sensor ranges are serialized by hand, so make sure the derived deserializer copes with
+  // anything the other side might send.
+  let _ = serde_json::from_str::<SensorDeviceMessageAttributesV3>(json);
+  // Also run the input through the same path messages from a connector take, which includes schema
+  // validation.
+  let msg = ButtplugSerializedMessage::Text(json.to_owned());
+  let _ = ButtplugServerJSONSerializer::default().deserialize(&msg);
+  let _ = ButtplugClientJSONSerializer::default().deserialize(&msg);
+});
@@ -0,0 +1,80 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2024 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+use super::{ButtplugClient, MessageRecorder};
+use std::{sync::Arc, time::Duration};
+
+/// Default number of events held for each [ButtplugClient::event_stream()] subscriber.
+pub const DEFAULT_EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// Configures and creates [ButtplugClient] instances.
+pub struct ButtplugClientBuilder {
+  /// Name of the client, sent to the server as part of the connection handshake.
+  name: String,
+  /// Number of events each event stream subscriber can fall behind before events are dropped.
+  event_channel_capacity: usize,
+  /// Maximum time [ButtplugClient::connect()] waits for the connection and handshake. If None,
+  /// connecting waits indefinitely.
+  connection_timeout: Option<Duration>,
+  /// Maximum time to wait for the server to reply to any message. If None, waits indefinitely.
+  message_timeout: Option<Duration>,
+  /// Recorder handed every message sent to or received from the server.
+  message_recorder: Option<Arc<dyn MessageRecorder>>,
+}
+
+impl ButtplugClientBuilder {
+  pub fn new(name: &str) -> Self {
+    Self {
+      name: name.to_owned(),
+      event_channel_capacity: DEFAULT_EVENT_CHANNEL_CAPACITY,
+      connection_timeout: None,
+      message_timeout: None,
+      message_recorder: None,
+    }
+  }
+
+  /// Set how many events a [ButtplugClient::event_stream()] subscriber can fall behind before the
+  /// oldest events are dropped for it. Values below 1 are treated as 1.
+  pub fn event_channel_capacity(&mut self, capacity: usize) -> &mut Self {
+    self.event_channel_capacity = capacity.max(1);
+    self
+  }
+
+  /// Set the maximum time every [ButtplugClient::connect()] call will wait for the connector to
+  /// connect and the handshake to finish. See [ButtplugClient::connect_with_timeout()].
+  pub fn connection_timeout(&mut self, timeout: Duration) -> &mut Self {
+    self.connection_timeout = Some(timeout);
+    self
+  }
+
+  /// Set the maximum time the client will wait for the server to reply to a message. Commands that
+  /// go unanswered fail with
+  /// [ButtplugClientError::CommandTimeout](super::ButtplugClientError::CommandTimeout). Without
+  /// this, a server that never replies leaves the command waiting forever.
+  pub fn default_message_timeout(&mut self, timeout: Duration) -> &mut Self {
+    self.message_timeout = Some(timeout);
+    self
+  }
+
+  /// Record all messages the client sends to and receives from the server, for debugging. See
+  /// [VecMessageRecorder](super::VecMessageRecorder) and [playback](super::playback).
+  pub fn record_messages(&mut self, recorder: Arc<dyn MessageRecorder>) -> &mut Self {
+    self.message_recorder = Some(recorder);
+    self
+  }
+
+  /// Build a [ButtplugClient] using the parameters given.
+  pub fn finish(&self) -> ButtplugClient {
+    ButtplugClient::new_with_options(
+      &self.name,
+      self.event_channel_capacity,
+      self.connection_timeout,
+      self.message_timeout,
+      self.message_recorder.clone(),
+    )
+  }
+}
@@ -10,27 +10,33 @@
 use super::{
   client_message_sorter::ClientMessageSorter,
   device::{ButtplugClientDevice, ButtplugClientDeviceEvent},
+  message_recorder::MessageRecorder,
   ButtplugClientEvent,
   ButtplugClientMessageFuturePair,
   ButtplugClientMessageSender,
+  ButtplugServerMessageStateShared,
 };
 use crate::core::{
   connector::{ButtplugConnector, ButtplugConnectorStateShared},
   errors::{ButtplugDeviceError, ButtplugError},
   message::{
-    ButtplugClientMessageV3,
+    ButtplugClientMessageCurrent,
     ButtplugDeviceMessage,
     ButtplugMessageValidator,
-    ButtplugServerMessageV3,
+    ButtplugServerMessageCurrent,
     DeviceListV3,
     DeviceMessageInfoV3,
+    ErrorCode,
   },
 };
 use dashmap::DashMap;
 use futures::FutureExt;
-use std::sync::{
-  atomic::{AtomicBool, Ordering},
-  Arc,
+use std::{
+  collections::HashMap,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
 };
 use tokio::sync::{broadcast, mpsc};
 
@@ -47,6 +53,8 @@ pub enum ButtplugClientRequest {
   /// Bundled future should have reply set and waker called when this is
   /// finished.
   Message(ButtplugClientMessageFuturePair),
+  /// Client stopped waiting on the reply to a message, so stop tracking its future.
+  CancelMessage(ButtplugServerMessageStateShared),
 }
 
 /// Event loop for running [ButtplugClient] connections.
@@ -79,16 +87,21 @@ pub enum ButtplugClientRequest {
 /// async channels, and those channels should never have backpressure. We hope.
 pub(super) struct ButtplugClientEventLoop<ConnectorType>
 where
-  ConnectorType: ButtplugConnector<ButtplugClientMessageV3, ButtplugServerMessageV3> + 'static,
+  ConnectorType:
+    ButtplugConnector<ButtplugClientMessageCurrent, ButtplugServerMessageCurrent> + 'static,
 {
   /// Connected status from client, managed by the event loop in case of disconnect.
   connected_status: Arc<AtomicBool>,
   /// Connector the event loop will use to communicate with the [ButtplugServer]
   connector: ConnectorType,
   /// Receiver for messages send from the [ButtplugServer] via the connector.
-  from_connector_receiver: mpsc::Receiver<ButtplugServerMessageV3>,
+  from_connector_receiver: mpsc::Receiver<ButtplugServerMessageCurrent>,
   /// Map of devices shared between the client and the event loop
   device_map: Arc<DashMap<u32, Arc<ButtplugClientDevice>>>,
+  /// Devices removed during this connection, kept so that if the server adds the same device back
+  /// at the same index, callers holding the old handle see it reconnect. Cleared whenever a scan
+  /// finishes, as anything that hasn't come back by then is treated as gone.
+  disconnected_devices: HashMap<u32, Arc<ButtplugClientDevice>>,
   /// Sends events to the [ButtplugClient] instance.
   to_client_sender: broadcast::Sender<ButtplugClientEvent>,
   /// Sends events to the client receiver. Stored here so it can be handed to
@@ -97,35 +110,46 @@ where
   /// Receives incoming messages from client instances.
   from_client_receiver: broadcast::Receiver<ButtplugClientRequest>,
   sorter: ClientMessageSorter,
+  /// Client's scanning status, cleared when the server reports scanning has finished.
+  scanning: Arc<AtomicBool>,
+  /// Receives a copy of all traffic with the server, if set.
+  message_recorder: Option<Arc<dyn MessageRecorder>>,
 }
 
 impl<ConnectorType> ButtplugClientEventLoop<ConnectorType>
 where
-  ConnectorType: ButtplugConnector<ButtplugClientMessageV3, ButtplugServerMessageV3> + 'static,
+  ConnectorType:
+    ButtplugConnector<ButtplugClientMessageCurrent, ButtplugServerMessageCurrent> + 'static,
 {
   /// Creates a new [ButtplugClientEventLoop].
   ///
   /// Given the [ButtplugClientConnector] object, as well as the channels used
   /// for communicating with the client, creates an event loop structure and
   /// returns it.
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
     connected_status: Arc<AtomicBool>,
     connector: ConnectorType,
-    from_connector_receiver: mpsc::Receiver<ButtplugServerMessageV3>,
+    from_connector_receiver: mpsc::Receiver<ButtplugServerMessageCurrent>,
     to_client_sender: broadcast::Sender<ButtplugClientEvent>,
     from_client_sender: Arc<ButtplugClientMessageSender>,
     device_map: Arc<DashMap<u32, Arc<ButtplugClientDevice>>>,
+    scanning: Arc<AtomicBool>,
+    message_recorder: Option<Arc<dyn MessageRecorder>>,
   ) -> Self {
     trace!("Creating ButtplugClientEventLoop instance.");
     Self {
       connected_status,
       device_map,
+      disconnected_devices: HashMap::new(),
       from_client_receiver: from_client_sender.subscribe(),
       from_client_sender,
       to_client_sender,
       from_connector_receiver,
       connector,
       sorter: ClientMessageSorter::default(),
+      scanning,
+      message_recorder,
     }
   }
 
@@ -147,6 +171,18 @@ where
       }
       // If it doesn't, insert it.
       None => {
+        // If the same device was connected at this index earlier in the session, hand back the
+        // handle we already gave out instead of making a new one.
+        if let Some(device) = self.disconnected_devices.remove(&info.device_index()) {
+          if device.name() == info.device_name()
+            && device.message_attributes() == info.device_messages()
+          {
+            debug!("Device reconnected, reusing previous entry.");
+            device.set_device_connected(true);
+            self.device_map.insert(info.device_index(), device.clone());
+            return device;
+          }
+        }
         debug!("Device does not exist, creating new entry.");
         let device = Arc::new(ButtplugClientDevice::new_from_device_info(
           info,
@@ -161,18 +197,14 @@ where
   fn send_client_event(&mut self, event: ButtplugClientEvent) {
     trace!("Forwarding event {:?} to client", event);
 
-    if self.to_client_sender.receiver_count() == 0 {
+    // Sending only fails if there are no receivers. Full channels never block or fail, they
+    // overwrite the oldest event and lagging receivers skip ahead.
+    if let Err(err) = self.to_client_sender.send(event) {
       error!(
         "Client event {:?} dropped, no client event listener available.",
-        event
+        err.0
       );
-      return;
     }
-
-    self
-      .to_client_sender
-      .send(event)
-      .expect("Already checked for receivers.");
   }
 
   fn disconnect_device(&mut self, device_index: u32) {
@@ -189,6 +221,9 @@ where
     device.queue_event(ButtplugClientDeviceEvent::DeviceRemoved);
     // Then remove it from our storage map
     self.device_map.remove(&device_index);
+    self
+      .disconnected_devices
+      .insert(device_index, device.clone());
     self.send_client_event(ButtplugClientEvent::DeviceRemoved(device));
   }
 
@@ -198,7 +233,10 @@ where
   /// server, it will catch [DeviceAdded]/[DeviceList]/[DeviceRemoved] messages
   /// and update its map accordingly. After that, it will pass the information
   /// on as a [ButtplugClientEvent] to the [ButtplugClient].
-  async fn parse_connector_message(&mut self, msg: ButtplugServerMessageV3) {
+  async fn parse_connector_message(&mut self, msg: ButtplugServerMessageCurrent) {
+    if let Some(recorder) = &self.message_recorder {
+      recorder.record_received(&msg);
+    }
     if self.sorter.maybe_resolve_result(&msg) {
       trace!("Message future found, returning");
       return;
@@ -211,7 +249,7 @@ where
     trace!("Message future not found, assuming server event.");
     info!("{:?}", msg);
     match msg {
-      ButtplugServerMessageV3::DeviceAdded(dev) => {
+      ButtplugServerMessageCurrent::DeviceAdded(dev) => {
         trace!("Device added, updating map and sending to client");
         // We already have this device. Emit an error to let the client know the
         // server is being weird.
@@ -228,7 +266,7 @@ where
         let device = self.create_client_device(&info);
         self.send_client_event(ButtplugClientEvent::DeviceAdded(device));
       }
-      ButtplugServerMessageV3::DeviceRemoved(dev) => {
+      ButtplugServerMessageCurrent::DeviceRemoved(dev) => {
         if self.device_map.contains_key(&dev.device_index()) {
           trace!("Device removed, updating map and sending to client");
           self.disconnect_device(dev.device_index());
@@ -237,31 +275,48 @@ where
           self.send_client_event(ButtplugClientEvent::Error(ButtplugDeviceError::DeviceConnectionError("Device removal requested for a device the client does not know about. Server may be in a weird state.".to_owned()).into()));
         }
       }
-      ButtplugServerMessageV3::ScanningFinished(_) => {
+      ButtplugServerMessageCurrent::ScanningFinished(_) => {
         trace!("Scanning finished event received, forwarding to client.");
+        self.scanning.store(false, Ordering::SeqCst);
+        self.disconnected_devices.clear();
         self.send_client_event(ButtplugClientEvent::ScanningFinished);
       }
-      ButtplugServerMessageV3::RawReading(msg) => {
+      ButtplugServerMessageCurrent::RawReading(msg) => {
         let device_idx = msg.device_index();
-        if let Some(device) = self.device_map.get(&device_idx) {
-          device
-            .value()
-            .queue_event(ButtplugClientDeviceEvent::Message(
-              ButtplugServerMessageV3::from(msg),
-            ));
+        let device = self.device_map.get(&device_idx).map(|x| x.value().clone());
+        if let Some(device) = device {
+          device.queue_raw_reading(&msg);
+          device.queue_event(ButtplugClientDeviceEvent::Message(
+            ButtplugServerMessageCurrent::from(msg.clone()),
+          ));
+          self.send_client_event(ButtplugClientEvent::RawReading {
+            device_index: device_idx,
+            endpoint: msg.endpoint(),
+            data: msg.data().clone(),
+          });
         }
       }
-      ButtplugServerMessageV3::SensorReading(msg) => {
+      ButtplugServerMessageCurrent::SensorReading(msg) => {
         let device_idx = msg.device_index();
-        if let Some(device) = self.device_map.get(&device_idx) {
-          device
-            .value()
-            .queue_event(ButtplugClientDeviceEvent::Message(
-              ButtplugServerMessageV3::from(msg),
-            ));
+        let device = self.device_map.get(&device_idx).map(|x| x.value().clone());
+        if let Some(device) = device {
+          device.queue_event(ButtplugClientDeviceEvent::Message(
+            ButtplugServerMessageCurrent::from(msg.clone()),
+          ));
+          self.send_client_event(ButtplugClientEvent::SensorReading {
+            device_index: device_idx,
+            sensor_index: msg.sensor_index(),
+            sensor_type: msg.sensor_type(),
+            data: msg.data().clone(),
+          });
         }
       }
-      ButtplugServerMessageV3::Error(e) => {
+      ButtplugServerMessageCurrent::Error(e) => {
+        // The server sends a ping error as an event right before it stops talking to us, so let
+        // anyone listening know specifically that we timed out.
+        if e.error_code() == ErrorCode::ErrorPing {
+          self.send_client_event(ButtplugClientEvent::PingTimeout);
+        }
         self.send_client_event(ButtplugClientEvent::Error(e.into()));
       }
       _ => error!("Cannot process message, dropping: {:?}", msg),
@@ -280,6 +335,9 @@ where
 
     trace!("Sending message to connector: {:?}", msg_fut.msg);
     self.sorter.register_future(&mut msg_fut);
+    if let Some(recorder) = &self.message_recorder {
+      recorder.record_sent(&msg_fut.msg);
+    }
     if self.connector.send(msg_fut.msg).await.is_err() {
       error!("Sending message failed, connector most likely no longer connected.");
     }
@@ -300,6 +358,10 @@ where
         self.send_message(msg_fut).await;
         true
       }
+      ButtplugClientRequest::CancelMessage(state) => {
+        self.sorter.remove_future(&state);
+        true
+      }
       ButtplugClientRequest::Disconnect(state) => {
         trace!("Client requested disconnect");
         state.set_reply(self.connector.disconnect().await);
@@ -355,7 +417,10 @@ where
     device_indexes
       .iter()
       .for_each(|k| self.disconnect_device(*k));
+    // Nothing can reconnect once the connection is gone.
+    self.disconnected_devices.clear();
     self.connected_status.store(false, Ordering::SeqCst);
+    self.sorter.reject_pending_futures();
     self.send_client_event(ButtplugClientEvent::ServerDisconnect);
 
     debug!("Exiting client event loop.");
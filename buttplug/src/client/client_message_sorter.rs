@@ -13,7 +13,10 @@ use crate::{
     ButtplugClientMessageFuturePair,
     ButtplugServerMessageStateShared,
   },
-  core::message::{ButtplugMessage, ButtplugMessageValidator, ButtplugServerMessageV3},
+  core::{
+    connector::ButtplugConnectorError,
+    message::{ButtplugMessage, ButtplugMessageValidator, ButtplugServerMessageCurrent},
+  },
 };
 use dashmap::DashMap;
 use std::sync::{
@@ -65,6 +68,10 @@ pub struct ClientMessageSorter {
   /// Every time we add a message to the future_map, we need it to have a unique `id`. We assume
   /// that unsigned 2^32 will be enough (Buttplug isn't THAT chatty), and use it as a monotonically
   /// increasing counter for setting `id`s.
+  ///
+  /// Each client event loop owns its own sorter, so `id`s are only unique (and sequential) per
+  /// client. That's all we need, since replies are only ever matched against the map above, which
+  /// belongs to the same client.
   current_id: Arc<AtomicU32>,
 }
 
@@ -74,18 +81,17 @@ impl ClientMessageSorter {
   /// Given a message and its related future, set the message's `id`, and match that id with the
   /// future to be resolved when we get a response back.
   pub fn register_future(&self, msg_fut: &mut ButtplugClientMessageFuturePair) {
-    let id = self.current_id.load(Ordering::Relaxed);
+    let id = self.current_id.fetch_add(1, Ordering::Relaxed);
     trace!("Setting message id to {}", id);
     msg_fut.msg.set_id(id);
     self.future_map.insert(id, msg_fut.waker.clone());
-    self.current_id.store(id + 1, Ordering::Relaxed);
   }
 
   /// Given a response message from the server, resolve related future if we have one.
   ///
   /// Returns true if the response message was resolved to a future via matching `id`, otherwise
   /// returns false. False returns mean the message should be considered as an *event*.
-  pub fn maybe_resolve_result(&self, msg: &ButtplugServerMessageV3) -> bool {
+  pub fn maybe_resolve_result(&self, msg: &ButtplugServerMessageCurrent) -> bool {
     trace!("{:?}", msg);
     let id = msg.id();
     trace!("Trying to resolve message future for id {}.", id);
@@ -95,7 +101,7 @@ impl ClientMessageSorter {
         if let Err(e) = msg.is_valid() {
           error!("Message not valid: {:?} - Error: {}", msg, e);
           state.set_reply(Err(ButtplugClientError::ButtplugError(e.into())));
-        } else if let ButtplugServerMessageV3::Error(e) = msg {
+        } else if let ButtplugServerMessageCurrent::Error(e) = msg {
           state.set_reply(Err(e.original_error().into()))
         } else {
           state.set_reply(Ok(msg.clone()))
@@ -108,6 +114,36 @@ impl ClientMessageSorter {
       }
     }
   }
+
+  /// Stop waiting on a response for the given future, if it's still registered.
+  ///
+  /// Used when whoever was awaiting the future has given up on it, so it doesn't sit in the map
+  /// forever. If the server replies later, the reply will be treated as an event.
+  pub fn remove_future(&self, state: &ButtplugServerMessageStateShared) {
+    let id = self
+      .future_map
+      .iter()
+      .find(|x| x.value().is_same_state(state))
+      .map(|x| *x.key());
+    if let Some(id) = id {
+      trace!("Removing abandoned future for id {}.", id);
+      self.future_map.remove(&id);
+    }
+  }
+
+  /// Resolve every future still waiting on a response with a not connected error.
+  ///
+  /// Once the connection is gone, we'll never see replies to these, so anything awaiting them would
+  /// otherwise hang forever.
+  pub fn reject_pending_futures(&self) {
+    let ids: Vec<u32> = self.future_map.iter().map(|x| *x.key()).collect();
+    for id in ids {
+      if let Some((_, state)) = self.future_map.remove(&id) {
+        trace!("Rejecting pending future for id {}.", id);
+        state.set_reply(Err(ButtplugConnectorError::ConnectorNotConnected.into()));
+      }
+    }
+  }
 }
 
 impl Default for ClientMessageSorter {
@@ -9,23 +9,27 @@
 
 use super::{
   create_boxed_future_client_error,
+  ButtplugClientError,
   ButtplugClientMessageSender,
   ButtplugClientResultFuture,
+  ButtplugServerMessageResultFuture,
 };
 use crate::{
   core::{
     errors::{ButtplugDeviceError, ButtplugError, ButtplugMessageError},
     message::{
       ActuatorType,
-      ButtplugClientMessageV3,
+      ButtplugClientMessageCurrent,
       ButtplugDeviceMessageType,
-      ButtplugServerMessageV3,
+      ButtplugServerMessageCurrent,
       ClientDeviceMessageAttributesV3,
       ClientGenericDeviceMessageAttributesV3,
       DeviceMessageInfoV3,
       Endpoint,
       LinearCmdV1,
+      RawDeviceMessageAttributesV2,
       RawReadCmdV2,
+      RawReadingV2,
       RawSubscribeCmdV2,
       RawUnsubscribeCmdV2,
       RawWriteCmdV2,
@@ -33,6 +37,7 @@ use crate::{
       RotationSubcommandV1,
       ScalarCmdV3,
       ScalarSubcommandV3,
+      SensorDeviceMessageAttributesV3,
       SensorReadCmdV3,
       SensorSubscribeCmdV3,
       SensorType,
@@ -41,19 +46,32 @@ use crate::{
       VectorSubcommandV1,
     },
   },
-  util::stream::convert_broadcast_receiver_to_stream,
+  util::{async_manager, sleep, stream::convert_broadcast_receiver_to_stream},
 };
-use futures::{FutureExt, Stream};
+use futures::{future, select, FutureExt, Stream, StreamExt};
 use getset::{CopyGetters, Getters};
 use std::{
   collections::HashMap,
   fmt,
+  ops::RangeInclusive,
+  pin::Pin,
   sync::{
-    atomic::{AtomicBool, Ordering},
+    atomic::{AtomicBool, AtomicUsize, Ordering},
     Arc,
   },
+  task::{Context, Poll},
+  time::Duration,
 };
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, Mutex};
+use tokio_util::sync::CancellationToken;
+
+/// Time between updates sent when simulating binaural beats via
+/// [ButtplugClientDevice::vibrate_binaural].
+const BINAURAL_UPDATE_INTERVAL_MS: u64 = 50;
+
+/// Number of raw readings buffered for each raw endpoint before slow
+/// [ButtplugClientDevice::raw_subscription_stream] streams start missing them.
+const RAW_READING_CHANNEL_CAPACITY: usize = 256;
 
 /// Enum for messages going to a [ButtplugClientDevice] instance.
 #[derive(Clone, Debug)]
@@ -67,7 +85,7 @@ pub enum ButtplugClientDeviceEvent {
   /// Client has disconnected from server.
   ClientDisconnect,
   /// Message was received from server for that specific device.
-  Message(ButtplugServerMessageV3),
+  Message(ButtplugServerMessageCurrent),
 }
 
 /// Convenience enum for forming [VibrateCmd] commands.
@@ -140,6 +158,276 @@ pub enum LinearCommand {
   LinearMap(HashMap<u32, (u32, f64)>),
 }
 
+/// Something on a device that can be subscribed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum SubscriptionTarget {
+  Raw(Endpoint),
+  Sensor(u32, SensorType),
+}
+
+/// Who is holding the subscription to a [SubscriptionTarget]. The server only keeps one
+/// subscription per target, which is shared by every subscription stream on the target, as well as
+/// a subscription made directly (i.e. via [ButtplugClientDevice::raw_subscribe] or
+/// [ButtplugClientDevice::subscribe_sensor]).
+#[derive(Debug, Default)]
+struct SubscriptionHolders {
+  streams: usize,
+  direct: bool,
+}
+
+/// Tracks the holders of each subscription on a device, so the unsubscribe message is only sent
+/// once nothing is using the subscription anymore.
+#[derive(Debug, Clone, Default)]
+struct DeviceSubscriptions(Arc<std::sync::Mutex<HashMap<SubscriptionTarget, SubscriptionHolders>>>);
+
+impl DeviceSubscriptions {
+  fn holders(&self) -> std::sync::MutexGuard<'_, HashMap<SubscriptionTarget, SubscriptionHolders>> {
+    self
+      .0
+      .lock()
+      .expect("Subscription lock should never be poisoned")
+  }
+
+  fn add_stream(&self, target: SubscriptionTarget) {
+    self.holders().entry(target).or_default().streams += 1;
+  }
+
+  /// Returns true if the stream was the last holder, meaning we should unsubscribe.
+  fn remove_stream(&self, target: SubscriptionTarget) -> bool {
+    let mut holders = self.holders();
+    let holder = holders.entry(target).or_default();
+    holder.streams = holder.streams.saturating_sub(1);
+    let last = holder.streams == 0 && !holder.direct;
+    if last {
+      holders.remove(&target);
+    }
+    last
+  }
+
+  fn add_direct(&self, target: SubscriptionTarget) {
+    self.holders().entry(target).or_default().direct = true;
+  }
+
+  /// Returns true if there are no streams left holding the subscription, meaning we should
+  /// unsubscribe.
+  fn remove_direct(&self, target: SubscriptionTarget) -> bool {
+    let mut holders = self.holders();
+    match holders.get_mut(&target) {
+      Some(holder) if holder.streams > 0 => {
+        holder.direct = false;
+        false
+      }
+      _ => {
+        holders.remove(&target);
+        true
+      }
+    }
+  }
+}
+
+/// Stream of data received from a subscribed raw endpoint or sensor on a
+/// [ButtplugClientDevice].
+///
+/// Created via [ButtplugClientDevice::raw_subscription_stream] or
+/// [ButtplugClientDevice::sensor_subscription_stream]. Streams on the same target share one
+/// subscription, and the unsubscribe message is only sent once the last of them is dropped (and
+/// there's no direct subscription still held), so dropping one stream never ends another.
+struct ButtplugClientDeviceSubscriptionStream<T> {
+  target: SubscriptionTarget,
+  subscriptions: DeviceSubscriptions,
+  unsubscribe_msg: ButtplugClientMessageCurrent,
+  event_loop_sender: Arc<ButtplugClientMessageSender>,
+  stream: Pin<Box<dyn Stream<Item = T> + Send>>,
+}
+
+impl<T> ButtplugClientDeviceSubscriptionStream<T> {
+  fn new(
+    target: SubscriptionTarget,
+    subscriptions: &DeviceSubscriptions,
+    unsubscribe_msg: ButtplugClientMessageCurrent,
+    event_loop_sender: &Arc<ButtplugClientMessageSender>,
+    stream: impl Stream<Item = T> + Send + 'static,
+  ) -> Self {
+    subscriptions.add_stream(target);
+    Self {
+      target,
+      subscriptions: subscriptions.clone(),
+      unsubscribe_msg,
+      event_loop_sender: event_loop_sender.clone(),
+      stream: Box::pin(stream),
+    }
+  }
+}
+
+impl<T> Stream for ButtplugClientDeviceSubscriptionStream<T> {
+  type Item = T;
+
+  fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    self.stream.as_mut().poll_next(cx)
+  }
+}
+
+impl<T> Drop for ButtplugClientDeviceSubscriptionStream<T> {
+  fn drop(&mut self) {
+    if !self.subscriptions.remove_stream(self.target) {
+      return;
+    }
+    let msg = self.unsubscribe_msg.clone();
+    let fut = self.event_loop_sender.send_message_expect_ok(msg.clone());
+    async_manager::spawn(async move {
+      if let Err(e) = fut.await {
+        debug!("Error sending {:?} for dropped stream: {:?}", msg, e);
+      }
+    });
+  }
+}
+
+/// Raw endpoint exposed by a [ButtplugClientDevice], along with the raw commands it accepts.
+///
+/// Only available if the server allows raw messages for the device. Obtained via
+/// [ButtplugClientDevice::raw_endpoints] or [ButtplugClientDevice::raw_endpoint].
+#[derive(Debug, Clone, CopyGetters)]
+pub struct ButtplugDeviceRawEndpoint {
+  /// Endpoint on the device.
+  #[getset(get_copy = "pub")]
+  endpoint: Endpoint,
+  /// True if the endpoint accepts RawReadCmd.
+  #[getset(get_copy = "pub")]
+  readable: bool,
+  /// True if the endpoint accepts RawWriteCmd.
+  #[getset(get_copy = "pub")]
+  writable: bool,
+  /// True if the endpoint accepts RawSubscribeCmd/RawUnsubscribeCmd.
+  #[getset(get_copy = "pub")]
+  subscribable: bool,
+  /// Carries data from RawReading messages for this endpoint to
+  /// [ButtplugClientDevice::raw_subscription_stream] streams.
+  reading_sender: broadcast::Sender<Vec<u8>>,
+}
+
+impl ButtplugDeviceRawEndpoint {
+  /// Builds one entry per endpoint listed in any of the raw command attributes, in the order they
+  /// first appear (read, then write, then subscribe).
+  fn from_message_attributes(attributes: &ClientDeviceMessageAttributesV3) -> Vec<Self> {
+    let endpoints_of = |attrs: &Option<RawDeviceMessageAttributesV2>| {
+      attrs
+        .as_ref()
+        .map(|x| x.endpoints().clone())
+        .unwrap_or_default()
+    };
+    let read = endpoints_of(attributes.raw_read_cmd());
+    let write = endpoints_of(attributes.raw_write_cmd());
+    let subscribe = endpoints_of(attributes.raw_subscribe_cmd());
+    let mut raw_endpoints: Vec<Self> = vec![];
+    for endpoint in read.iter().chain(write.iter()).chain(subscribe.iter()) {
+      if raw_endpoints.iter().any(|x| x.endpoint == *endpoint) {
+        continue;
+      }
+      raw_endpoints.push(Self {
+        endpoint: *endpoint,
+        readable: read.contains(endpoint),
+        writable: write.contains(endpoint),
+        subscribable: subscribe.contains(endpoint),
+        reading_sender: broadcast::channel(RAW_READING_CHANNEL_CAPACITY).0,
+      });
+    }
+    raw_endpoints
+  }
+}
+
+// Endpoints are equal if they offer the same capabilities. The reading channel is plumbing, not
+// part of what the endpoint is.
+impl PartialEq for ButtplugDeviceRawEndpoint {
+  fn eq(&self, other: &Self) -> bool {
+    self.endpoint == other.endpoint
+      && self.readable == other.readable
+      && self.writable == other.writable
+      && self.subscribable == other.subscribable
+  }
+}
+
+impl Eq for ButtplugDeviceRawEndpoint {
+}
+
+/// Sensor exposed by a [ButtplugClientDevice], along with the sensor commands it accepts.
+///
+/// Obtained via [ButtplugClientDevice::sensors].
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+pub struct ButtplugDeviceSensor {
+  #[getset(get = "pub")]
+  feature_descriptor: String,
+  #[getset(get_copy = "pub")]
+  sensor_type: SensorType,
+  #[getset(get = "pub")]
+  sensor_range: Vec<RangeInclusive<i32>>,
+  /// Sensor index to use with SensorReadCmd, if the sensor can be read.
+  #[getset(get_copy = "pub")]
+  read_index: Option<u32>,
+  /// Sensor index to use with SensorSubscribeCmd/SensorUnsubscribeCmd, if the sensor can be
+  /// subscribed to.
+  #[getset(get_copy = "pub")]
+  subscribe_index: Option<u32>,
+}
+
+impl ButtplugDeviceSensor {
+  /// True if the sensor accepts SensorReadCmd.
+  pub fn readable(&self) -> bool {
+    self.read_index.is_some()
+  }
+
+  /// True if the sensor accepts SensorSubscribeCmd/SensorUnsubscribeCmd.
+  pub fn subscribable(&self) -> bool {
+    self.subscribe_index.is_some()
+  }
+
+  /// Builds one entry per sensor listed in the SensorReadCmd and SensorSubscribeCmd attributes.
+  ///
+  /// Sensor indexes are positions in each message's attribute list, so the same sensor may have a
+  /// different index in each. A subscribable sensor is merged into the first readable sensor with
+  /// the same type, descriptor and range that hasn't already been merged, which pairs them up in
+  /// device feature order.
+  fn from_message_attributes(attributes: &ClientDeviceMessageAttributesV3) -> Vec<Self> {
+    let mut sensors: Vec<Self> = attributes
+      .sensor_read_cmd()
+      .iter()
+      .flatten()
+      .enumerate()
+      .map(|(index, attr)| Self {
+        feature_descriptor: attr.feature_descriptor().clone(),
+        sensor_type: *attr.sensor_type(),
+        sensor_range: attr.sensor_range().clone(),
+        read_index: Some(index as u32),
+        subscribe_index: None,
+      })
+      .collect();
+    for (index, attr) in attributes
+      .sensor_subscribe_cmd()
+      .iter()
+      .flatten()
+      .enumerate()
+    {
+      let matching = sensors.iter_mut().find(|x| {
+        x.subscribe_index.is_none()
+          && x.sensor_type == *attr.sensor_type()
+          && x.feature_descriptor == *attr.feature_descriptor()
+          && x.sensor_range == *attr.sensor_range()
+      });
+      if let Some(sensor) = matching {
+        sensor.subscribe_index = Some(index as u32);
+      } else {
+        sensors.push(Self {
+          feature_descriptor: attr.feature_descriptor().clone(),
+          sensor_type: *attr.sensor_type(),
+          sensor_range: attr.sensor_range().clone(),
+          read_index: None,
+          subscribe_index: Some(index as u32),
+        });
+      }
+    }
+    sensors
+  }
+}
+
 #[derive(Getters, CopyGetters)]
 /// Client-usable representation of device connected to the corresponding
 /// [ButtplugServer][crate::server::ButtplugServer]
@@ -154,6 +442,11 @@ pub struct ButtplugClientDevice {
   /// Display name of the device
   #[getset(get = "pub")]
   display_name: Option<String>,
+  /// Minimum time, in milliseconds, the server expects between changes to a single actuator on this
+  /// device, if the device configuration specifies one. Changes sent faster than this are rejected
+  /// with [ButtplugDeviceError::CommandThrottled].
+  #[getset(get_copy = "pub")]
+  message_timing_gap: Option<u32>,
   /// Index of the device, matching the index in the
   /// [ButtplugServer][crate::server::ButtplugServer]'s
   /// [DeviceManager][crate::server::device_manager::DeviceManager].
@@ -176,6 +469,132 @@ pub struct ButtplugClientDevice {
   /// [ButtplugClientDevice] instance is still connected to the
   /// [ButtplugServer][crate::server::ButtplugServer].
   client_connected: Arc<AtomicBool>,
+  /// Number of actuator commands sent that haven't received a reply yet.
+  pending_commands: Arc<AtomicUsize>,
+  /// Maximum number of pending actuator commands before new ones are rejected. 0 means no limit.
+  max_queue_depth: Arc<AtomicUsize>,
+  /// If true, each command waits for the previous one to be acknowledged before being sent.
+  serialize_commands: Arc<AtomicBool>,
+  /// Held for the duration of each command while `serialize_commands` is set.
+  command_lock: Arc<Mutex<()>>,
+  /// Raw endpoints built from `message_attributes`, so lookups don't have to rebuild them.
+  raw_endpoints: Vec<ButtplugDeviceRawEndpoint>,
+  /// Sensors built from `message_attributes`.
+  sensors: Vec<ButtplugDeviceSensor>,
+  /// Holders of each raw endpoint and sensor subscription.
+  subscriptions: DeviceSubscriptions,
+}
+
+/// Counts a command as pending for as long as it's alive, so the count is correct whether the
+/// command future resolves or is dropped early.
+struct PendingCommandGuard(Arc<AtomicUsize>);
+
+impl Drop for PendingCommandGuard {
+  fn drop(&mut self) {
+    self.0.fetch_sub(1, Ordering::SeqCst);
+  }
+}
+
+/// Everything needed to send commands to a device, split out of [ButtplugClientDevice] so that
+/// commands which keep sending after the call returns, like [ButtplugClientDevice::vibrate_binaural],
+/// still go through the queue depth limit and command serialization.
+#[derive(Clone)]
+struct DeviceCommandSender {
+  index: u32,
+  event_loop_sender: Arc<ButtplugClientMessageSender>,
+  pending_commands: Arc<AtomicUsize>,
+  max_queue_depth: Arc<AtomicUsize>,
+  serialize_commands: Arc<AtomicBool>,
+  command_lock: Arc<Mutex<()>>,
+}
+
+impl DeviceCommandSender {
+  fn send_message(&self, msg: ButtplugClientMessageCurrent) -> ButtplugServerMessageResultFuture {
+    let serialize = self.serialize_commands.load(Ordering::SeqCst);
+    let command_lock = self.command_lock.clone();
+    let event_loop_sender = self.event_loop_sender.clone();
+    async move {
+      let _lock = if serialize {
+        Some(command_lock.lock().await)
+      } else {
+        None
+      };
+      // Busy devices get their own error so callers can tell them apart from devices that failed.
+      event_loop_sender
+        .send_message(msg)
+        .await
+        .map_err(|err| match err {
+          ButtplugClientError::ButtplugError(ButtplugError::ButtplugDeviceError(
+            ButtplugDeviceError::DeviceBusy(index),
+          )) => ButtplugClientError::DeviceBusy(index),
+          err => err,
+        })
+    }
+    .boxed()
+  }
+
+  fn send_message_expect_ok(
+    &self,
+    msg: ButtplugClientMessageCurrent,
+  ) -> ButtplugClientResultFuture {
+    let send_fut = self.send_message(msg);
+    async move { send_fut.await.map(|_| ()) }.boxed()
+  }
+
+  fn send_actuator_command(&self, msg: ButtplugClientMessageCurrent) -> ButtplugClientResultFuture {
+    let max_depth = self.max_queue_depth.load(Ordering::SeqCst);
+    if self
+      .pending_commands
+      .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |pending| {
+        (max_depth == 0 || pending < max_depth).then_some(pending + 1)
+      })
+      .is_err()
+    {
+      return future::ready(Err(ButtplugClientError::DeviceBusy(self.index))).boxed();
+    }
+    let guard = PendingCommandGuard(self.pending_commands.clone());
+    let send_fut = self.send_message_expect_ok(msg);
+    async move {
+      let _guard = guard;
+      send_fut.await
+    }
+    .boxed()
+  }
+}
+
+/// Holds the stop command for a timed scalar command, such as [ButtplugClientDevice::vibrate_for].
+/// If the command future is dropped before the stop is sent, it gets sent from a detached task
+/// instead, so the device isn't left running.
+struct TimedCommandStopGuard {
+  stop_msg: Option<ButtplugClientMessageCurrent>,
+  event_loop_sender: Arc<ButtplugClientMessageSender>,
+}
+
+impl TimedCommandStopGuard {
+  fn stop(mut self) -> ButtplugClientResultFuture {
+    let msg = self
+      .stop_msg
+      .take()
+      .expect("Stop message is only taken once");
+    self.event_loop_sender.send_message_expect_ok(msg)
+  }
+
+  fn disarm(mut self) {
+    self.stop_msg = None;
+  }
+}
+
+impl Drop for TimedCommandStopGuard {
+  fn drop(&mut self) {
+    if let Some(msg) = self.stop_msg.take() {
+      let fut = self.event_loop_sender.send_message_expect_ok(msg.clone());
+      async_manager::spawn(async move {
+        if let Err(e) = fut.await {
+          debug!("Error sending {:?} for dropped timed command: {:?}", msg, e);
+        }
+      });
+    }
+  }
 }
 
 impl ButtplugClientDevice {
@@ -195,6 +614,7 @@ impl ButtplugClientDevice {
   pub(super) fn new(
     name: &str,
     display_name: &Option<String>,
+    message_timing_gap: Option<u32>,
     index: u32,
     message_attributes: &ClientDeviceMessageAttributesV3,
     message_sender: &Arc<ButtplugClientMessageSender>,
@@ -210,12 +630,20 @@ impl ButtplugClientDevice {
     Self {
       name: name.to_owned(),
       display_name: display_name.clone(),
+      message_timing_gap,
       index,
       message_attributes: message_attributes.clone(),
       event_loop_sender: message_sender.clone(),
       internal_event_sender: event_sender,
       device_connected,
       client_connected,
+      pending_commands: Arc::new(AtomicUsize::new(0)),
+      max_queue_depth: Arc::new(AtomicUsize::new(0)),
+      serialize_commands: Arc::new(AtomicBool::new(false)),
+      command_lock: Arc::new(Mutex::new(())),
+      raw_endpoints: ButtplugDeviceRawEndpoint::from_message_attributes(message_attributes),
+      sensors: ButtplugDeviceSensor::from_message_attributes(message_attributes),
+      subscriptions: DeviceSubscriptions::default(),
     }
   }
 
@@ -226,6 +654,7 @@ impl ButtplugClientDevice {
     ButtplugClientDevice::new(
       info.device_name(),
       info.device_display_name(),
+      *info.device_message_timing_gap(),
       info.device_index(),
       info.device_messages(),
       sender,
@@ -236,6 +665,65 @@ impl ButtplugClientDevice {
     self.device_connected.load(Ordering::SeqCst)
   }
 
+  /// Name to show where space is limited, e.g. in a device picker. Uses the display name if one is
+  /// set, otherwise the device name. See the [Display](fmt::Display) impl for a longer form that
+  /// includes the index.
+  pub fn to_short_string(&self) -> String {
+    self.display_name.as_ref().unwrap_or(&self.name).clone()
+  }
+
+  /// Number of actuator commands (scalar, linear and rotate) that have been sent to the device but
+  /// haven't received a reply yet.
+  pub fn pending_commands(&self) -> usize {
+    self.pending_commands.load(Ordering::SeqCst)
+  }
+
+  /// Limits how many actuator commands can be waiting on replies at once. Once the limit is
+  /// reached, new actuator commands fail immediately with [ButtplugClientError::DeviceBusy] instead
+  /// of queuing up behind older ones and arriving late. A depth of 0 (the default) disables the
+  /// limit.
+  pub fn set_max_queue_depth(&self, depth: usize) -> &Self {
+    self.max_queue_depth.store(depth, Ordering::SeqCst);
+    self
+  }
+
+  /// If true, commands sent to this device are sent one at a time, with each waiting for the
+  /// previous command to be acknowledged by the server. This keeps commands issued from different
+  /// tasks from interleaving on hardware that can't handle concurrent writes, at the cost of
+  /// latency. Off by default, for callers that manage their own ordering.
+  ///
+  /// [ButtplugClientDevice::emergency_stop] always skips the line.
+  pub fn serialize_commands(&self, serialize: bool) -> &Self {
+    self.serialize_commands.store(serialize, Ordering::SeqCst);
+    self
+  }
+
+  fn command_sender(&self) -> DeviceCommandSender {
+    DeviceCommandSender {
+      index: self.index,
+      event_loop_sender: self.event_loop_sender.clone(),
+      pending_commands: self.pending_commands.clone(),
+      max_queue_depth: self.max_queue_depth.clone(),
+      serialize_commands: self.serialize_commands.clone(),
+      command_lock: self.command_lock.clone(),
+    }
+  }
+
+  fn send_message(&self, msg: ButtplugClientMessageCurrent) -> ButtplugServerMessageResultFuture {
+    self.command_sender().send_message(msg)
+  }
+
+  fn send_message_expect_ok(
+    &self,
+    msg: ButtplugClientMessageCurrent,
+  ) -> ButtplugClientResultFuture {
+    self.command_sender().send_message_expect_ok(msg)
+  }
+
+  fn send_actuator_command(&self, msg: ButtplugClientMessageCurrent) -> ButtplugClientResultFuture {
+    self.command_sender().send_actuator_command(msg)
+  }
+
   pub fn event_stream(&self) -> Box<dyn Stream<Item = ButtplugClientDeviceEvent> + Send + Unpin> {
     Box::new(Box::pin(convert_broadcast_receiver_to_stream(
       self.internal_event_sender.subscribe(),
@@ -265,6 +753,93 @@ impl ButtplugClientDevice {
     }
   }
 
+  fn has_attributes<T>(attrs: &Option<Vec<T>>) -> bool {
+    attrs.as_ref().is_some_and(|x| !x.is_empty())
+  }
+
+  /// Returns true if the device takes ScalarCmd for any of its actuators.
+  ///
+  /// Handy for checking capabilities before sending commands, i.e.
+  ///
+  /// ```no_run
+  /// # use buttplug::client::{ButtplugClientDevice, ScalarValueCommand};
+  /// # async fn example(device: &ButtplugClientDevice) {
+  /// if device.supports_scalar() {
+  ///   device.vibrate(&ScalarValueCommand::ScalarValue(0.5)).await.unwrap();
+  /// }
+  /// # }
+  /// ```
+  pub fn supports_scalar(&self) -> bool {
+    Self::has_attributes(self.message_attributes.scalar_cmd())
+  }
+
+  /// Returns true if the device takes RotateCmd.
+  pub fn supports_rotation(&self) -> bool {
+    Self::has_attributes(self.message_attributes.rotate_cmd())
+  }
+
+  /// Returns true if the device takes LinearCmd.
+  pub fn supports_linear(&self) -> bool {
+    Self::has_attributes(self.message_attributes.linear_cmd())
+  }
+
+  /// Returns true if the device has any sensors that can be read or subscribed to.
+  pub fn supports_sensor(&self) -> bool {
+    Self::has_attributes(self.message_attributes.sensor_read_cmd())
+      || Self::has_attributes(self.message_attributes.sensor_subscribe_cmd())
+  }
+
+  /// Returns true if the device has a sensor of the given type that can be read with SensorReadCmd.
+  pub fn supports_sensor_read(&self, sensor_type: SensorType) -> bool {
+    Self::has_sensor_type(self.message_attributes.sensor_read_cmd(), sensor_type)
+  }
+
+  /// Returns true if the device has a sensor of the given type that can be subscribed to with
+  /// SensorSubscribeCmd.
+  pub fn supports_sensor_subscribe(&self, sensor_type: SensorType) -> bool {
+    Self::has_sensor_type(self.message_attributes.sensor_subscribe_cmd(), sensor_type)
+  }
+
+  fn has_sensor_type(
+    attrs: &Option<Vec<SensorDeviceMessageAttributesV3>>,
+    sensor_type: SensorType,
+  ) -> bool {
+    attrs
+      .as_ref()
+      .is_some_and(|attrs| attrs.iter().any(|x| *x.sensor_type() == sensor_type))
+  }
+
+  /// Returns true if raw messages are allowed for this device. Raw messages are only available if
+  /// the server has been set up to allow them.
+  pub fn supports_raw(&self) -> bool {
+    self.message_attributes.raw_read_cmd().is_some()
+      || self.message_attributes.raw_write_cmd().is_some()
+      || self.message_attributes.raw_subscribe_cmd().is_some()
+  }
+
+  /// Returns true if any of the device's ScalarCmd, RotateCmd, or LinearCmd actuators are of the
+  /// given type.
+  ///
+  /// ```no_run
+  /// # use buttplug::{client::ButtplugClientDevice, core::message::ActuatorType};
+  /// # fn example(device: &ButtplugClientDevice) {
+  /// if device.supports_actuator_type(ActuatorType::Oscillate) {
+  ///   println!("{} can oscillate", device.name());
+  /// }
+  /// # }
+  /// ```
+  pub fn supports_actuator_type(&self, actuator_type: ActuatorType) -> bool {
+    [
+      self.message_attributes.scalar_cmd(),
+      self.message_attributes.rotate_cmd(),
+      self.message_attributes.linear_cmd(),
+    ]
+    .iter()
+    .filter_map(|x| x.as_ref())
+    .flatten()
+    .any(|x| *x.actuator_type() == actuator_type)
+  }
+
   // The amount of hoop jumping it takes to pull this off is fucking ridiculous.
   //
   // In what will probably be the last time I use arrays with contextual indexing in Buttplug
@@ -335,7 +910,43 @@ impl ButtplugClientDevice {
       }
     }
     let msg = ScalarCmdV3::new(self.index, scalar_vec).into();
-    self.event_loop_sender.send_message_expect_ok(msg)
+    self.send_actuator_command(msg)
+  }
+
+  /// Sets all features of `actuator` to `speed`, waits for `duration`, then sets them back to 0.
+  fn scalar_value_for(
+    &self,
+    speed: f64,
+    duration: Duration,
+    actuator: ActuatorType,
+  ) -> ButtplugClientResultFuture {
+    let attrs = self.scalar_value_attributes(&actuator);
+    let start_fut =
+      self.scalar_from_value_command(&ScalarValueCommand::ScalarValue(speed), &actuator, &attrs);
+    let stop_msg = ScalarCmdV3::new(
+      self.index,
+      attrs
+        .iter()
+        .map(|attr| ScalarSubcommandV3::new(*attr.index(), 0.0, actuator))
+        .collect(),
+    )
+    .into();
+    let event_loop_sender = self.event_loop_sender.clone();
+    async move {
+      // Armed before the start command goes out, so dropping the future at any point after that
+      // still stops the device.
+      let guard = TimedCommandStopGuard {
+        stop_msg: Some(stop_msg),
+        event_loop_sender,
+      };
+      if let Err(e) = start_fut.await {
+        guard.disarm();
+        return Err(e);
+      }
+      sleep(duration).await;
+      guard.stop().await
+    }
+    .boxed()
   }
 
   pub fn vibrate_attributes(&self) -> Vec<ClientGenericDeviceMessageAttributesV3> {
@@ -351,6 +962,105 @@ impl ButtplugClientDevice {
     )
   }
 
+  /// Vibrates all vibrators on the device at `speed` for `duration`, then stops them.
+  ///
+  /// The returned future resolves once the stop command has been acknowledged by the server. If
+  /// it is dropped before then, the stop command is still sent in the background.
+  pub fn vibrate_for(&self, speed: f64, duration: Duration) -> ButtplugClientResultFuture {
+    self.scalar_value_for(speed, duration, ActuatorType::Vibrate)
+  }
+
+  /// Simulates binaural beats by driving the first two vibrators of a device at slightly different
+  /// frequencies.
+  ///
+  /// The first vibrator has its speed modulated at `base_hz`, the second at `base_hz + beat_hz`,
+  /// both peaking at `amplitude`. Updates for both motors are sent together in a single
+  /// [ScalarCmdV3] every [BINAURAL_UPDATE_INTERVAL_MS] milliseconds, so carrier frequencies must be
+  /// below the resulting nyquist frequency (10hz). Once `duration` has elapsed, both vibrators are
+  /// stopped.
+  ///
+  /// If sending an update fails, or the returned future is dropped, both vibrators are still
+  /// stopped. On failure the stop is sent before the error is returned.
+  pub fn vibrate_binaural(
+    &self,
+    base_hz: f64,
+    beat_hz: f64,
+    amplitude: f64,
+    duration: Duration,
+  ) -> ButtplugClientResultFuture {
+    let attrs = self.vibrate_attributes();
+    if attrs.len() < 2 {
+      return create_boxed_future_client_error(
+        ButtplugDeviceError::DeviceFeatureCountMismatch(attrs.len() as u32, 2).into(),
+      );
+    }
+    let nyquist_hz = 1000.0 / (2 * BINAURAL_UPDATE_INTERVAL_MS) as f64;
+    if base_hz <= 0.0 || beat_hz < 0.0 || base_hz + beat_hz >= nyquist_hz {
+      return create_boxed_future_client_error(
+        ButtplugMessageError::InvalidMessageContents(format!(
+          "Binaural frequencies must be positive and below {nyquist_hz}hz, got base {base_hz}hz and beat {beat_hz}hz"
+        ))
+        .into(),
+      );
+    }
+    if !(0.0..=1.0).contains(&amplitude) {
+      return create_boxed_future_client_error(
+        ButtplugMessageError::InvalidMessageContents(format!(
+          "Binaural amplitude must be between 0.0 and 1.0, got {amplitude}"
+        ))
+        .into(),
+      );
+    }
+
+    let device_index = self.index;
+    let command_sender = self.command_sender();
+    let motor_indexes = [*attrs[0].index(), *attrs[1].index()];
+    let carriers = [base_hz, base_hz + beat_hz];
+    let interval = Duration::from_millis(BINAURAL_UPDATE_INTERVAL_MS);
+    let sample_count = duration.as_millis() as u64 / BINAURAL_UPDATE_INTERVAL_MS;
+    async move {
+      let scalar_cmd = |values: [f64; 2]| -> ButtplugClientMessageCurrent {
+        ScalarCmdV3::new(
+          device_index,
+          motor_indexes
+            .iter()
+            .zip(values)
+            .map(|(index, value)| ScalarSubcommandV3::new(*index, value, ActuatorType::Vibrate))
+            .collect(),
+        )
+        .into()
+      };
+      // Armed before the first sample goes out, so an error partway through or dropping the future
+      // still stops both motors instead of leaving them at the last sample's speed.
+      let guard = TimedCommandStopGuard {
+        stop_msg: Some(scalar_cmd([0.0, 0.0])),
+        event_loop_sender: command_sender.event_loop_sender.clone(),
+      };
+      for sample in 0..sample_count {
+        // Sample times are calculated from the sample index instead of a clock, so that messaging
+        // latency doesn't shift the phase between the two motors.
+        let time = (sample * BINAURAL_UPDATE_INTERVAL_MS) as f64 / 1000.0;
+        let values = carriers
+          .map(|hz| amplitude * (0.5 + 0.5 * (2.0 * std::f64::consts::PI * hz * time).sin()));
+        if let Err(e) = command_sender
+          .send_actuator_command(scalar_cmd(values))
+          .await
+        {
+          if let Err(stop_err) = guard.stop().await {
+            debug!(
+              "Error stopping binaural vibration after failed update: {:?}",
+              stop_err
+            );
+          }
+          return Err(e);
+        }
+        sleep(interval).await;
+      }
+      guard.stop().await
+    }
+    .boxed()
+  }
+
   pub fn oscillate_attributes(&self) -> Vec<ClientGenericDeviceMessageAttributesV3> {
     self.scalar_value_attributes(&ActuatorType::Oscillate)
   }
@@ -364,6 +1074,12 @@ impl ButtplugClientDevice {
     )
   }
 
+  /// Oscillates all oscillators on the device at `speed` for `duration`, then stops them. See
+  /// [ButtplugClientDevice::vibrate_for] for how the returned future behaves.
+  pub fn oscillate_for(&self, speed: f64, duration: Duration) -> ButtplugClientResultFuture {
+    self.scalar_value_for(speed, duration, ActuatorType::Oscillate)
+  }
+
   pub fn scalar(&self, scalar_cmd: &ScalarCommand) -> ButtplugClientResultFuture {
     if self.message_attributes.scalar_cmd().is_none() {
       return create_boxed_future_client_error(
@@ -415,7 +1131,30 @@ impl ButtplugClientDevice {
       }
     }
     let msg = ScalarCmdV3::new(self.index, scalar_vec).into();
-    self.event_loop_sender.send_message_expect_ok(msg)
+    self.send_actuator_command(msg)
+  }
+
+  /// Sets a single scalar feature, leaving the others as they are. Shorthand for
+  /// [ButtplugClientDevice::scalar] with a one entry [ScalarCommand::ScalarMap].
+  ///
+  /// `index` is the scalar feature index, as listed in
+  /// [ButtplugClientDevice::scalar_attributes].
+  pub fn set_scalar(
+    &self,
+    index: u32,
+    value: f64,
+    actuator_type: ActuatorType,
+  ) -> ButtplugClientResultFuture {
+    self.scalar(&ScalarCommand::ScalarMap(HashMap::from([(
+      index,
+      (value, actuator_type),
+    )])))
+  }
+
+  /// Sets the speed of a single vibrator. Shorthand for [ButtplugClientDevice::set_scalar] with
+  /// [ActuatorType::Vibrate], so `index` is also the scalar feature index.
+  pub fn set_vibration(&self, index: u32, speed: f64) -> ButtplugClientResultFuture {
+    self.set_scalar(index, speed, ActuatorType::Vibrate)
   }
 
   pub fn linear_attributes(&self) -> Vec<ClientGenericDeviceMessageAttributesV3> {
@@ -473,7 +1212,122 @@ impl ButtplugClientDevice {
       }
     }
     let msg = LinearCmdV1::new(self.index, linear_vec).into();
-    self.event_loop_sender.send_message_expect_ok(msg)
+    self.send_actuator_command(msg)
+  }
+
+  /// Moves all linear features of a device from `from` to `to` over `total_duration`, split into
+  /// `steps` evenly spaced [LinearCmdV1] messages.
+  ///
+  /// Each step is sent once the previous step's duration has elapsed. Cancelling `cancel_token`
+  /// stops sending further steps and resolves the future successfully, leaving the device wherever
+  /// the last step sent it.
+  pub fn linear_ramp(
+    &self,
+    from: f64,
+    to: f64,
+    total_duration: Duration,
+    steps: u32,
+    cancel_token: CancellationToken,
+  ) -> ButtplugClientResultFuture {
+    let ramp = self.linear_ramp_messages(from, to, total_duration, steps);
+    let command_sender = self.command_sender();
+    async move {
+      let (msgs, step_duration) = ramp?;
+      Self::send_linear_steps(&command_sender, msgs, step_duration, &cancel_token).await?;
+      Ok(())
+    }
+    .boxed()
+  }
+
+  /// Moves all linear features of a device back and forth between `low` and `high` for `cycles`
+  /// full cycles, each taking `period`. Each half of a cycle is a [ButtplugClientDevice::linear_ramp]
+  /// with `steps` steps.
+  pub fn linear_oscillate(
+    &self,
+    low: f64,
+    high: f64,
+    period: Duration,
+    steps: u32,
+    cycles: u32,
+    cancel_token: CancellationToken,
+  ) -> ButtplugClientResultFuture {
+    let up = self.linear_ramp_messages(low, high, period / 2, steps);
+    let down = self.linear_ramp_messages(high, low, period / 2, steps);
+    let command_sender = self.command_sender();
+    async move {
+      let (up_msgs, step_duration) = up?;
+      let (down_msgs, _) = down?;
+      for _ in 0..cycles {
+        for msgs in [&up_msgs, &down_msgs] {
+          if !Self::send_linear_steps(&command_sender, msgs.clone(), step_duration, &cancel_token)
+            .await?
+          {
+            return Ok(());
+          }
+        }
+      }
+      Ok(())
+    }
+    .boxed()
+  }
+
+  fn linear_ramp_messages(
+    &self,
+    from: f64,
+    to: f64,
+    total_duration: Duration,
+    steps: u32,
+  ) -> Result<(Vec<ButtplugClientMessageCurrent>, Duration), ButtplugError> {
+    let linear_count = if let Some(attrs) = self.message_attributes.linear_cmd() {
+      attrs.len() as u32
+    } else {
+      return Err(
+        ButtplugDeviceError::MessageNotSupported(ButtplugDeviceMessageType::LinearCmd).into(),
+      );
+    };
+    if steps == 0 || total_duration.is_zero() {
+      return Err(
+        ButtplugMessageError::InvalidMessageContents(format!(
+          "Linear ramps need at least 1 step and a non-zero duration, got {steps} steps over {total_duration:?}"
+        ))
+        .into(),
+      );
+    }
+    let step_duration = total_duration / steps;
+    let msgs = (1..=steps)
+      .map(|step| {
+        let position = from + (to - from) * (step as f64 / steps as f64);
+        LinearCmdV1::new(
+          self.index,
+          (0..linear_count)
+            .map(|i| VectorSubcommandV1::new(i, step_duration.as_millis() as u32, position))
+            .collect(),
+        )
+        .into()
+      })
+      .collect();
+    Ok((msgs, step_duration))
+  }
+
+  /// Sends ramp steps in order, waiting a step's duration between each. Returns false if the ramp
+  /// was cancelled before finishing.
+  async fn send_linear_steps(
+    command_sender: &DeviceCommandSender,
+    msgs: Vec<ButtplugClientMessageCurrent>,
+    step_duration: Duration,
+    cancel_token: &CancellationToken,
+  ) -> Result<bool, ButtplugClientError> {
+    for msg in msgs {
+      if cancel_token.is_cancelled() {
+        return Ok(false);
+      }
+      command_sender.send_actuator_command(msg).await?;
+      select! {
+        _ = sleep(step_duration).fuse() => {},
+        _ = cancel_token.cancelled().fuse() => return Ok(false),
+      }
+    }
+    Ok(true)
   }
 
   pub fn rotate_attributes(&self) -> Vec<ClientGenericDeviceMessageAttributesV3> {
@@ -531,10 +1385,10 @@ impl ButtplugClientDevice {
       }
     }
     let msg = RotateCmdV1::new(self.index, rotate_vec).into();
-    self.event_loop_sender.send_message_expect_ok(msg)
+    self.send_actuator_command(msg)
   }
 
-  pub fn subscribe_sensor(
+  fn send_sensor_subscribe(
     &self,
     sensor_index: u32,
     sensor_type: SensorType,
@@ -546,9 +1400,27 @@ impl ButtplugClientDevice {
       );
     }
     let msg = SensorSubscribeCmdV3::new(self.index, sensor_index, sensor_type).into();
-    self.event_loop_sender.send_message_expect_ok(msg)
+    self.send_message_expect_ok(msg)
+  }
+
+  pub fn subscribe_sensor(
+    &self,
+    sensor_index: u32,
+    sensor_type: SensorType,
+  ) -> ButtplugClientResultFuture {
+    let subscribe_fut = self.send_sensor_subscribe(sensor_index, sensor_type);
+    let subscriptions = self.subscriptions.clone();
+    async move {
+      subscribe_fut.await?;
+      subscriptions.add_direct(SubscriptionTarget::Sensor(sensor_index, sensor_type));
+      Ok(())
+    }
+    .boxed()
   }
 
+  /// Ends a subscription made via [ButtplugClientDevice::subscribe_sensor]. If any
+  /// [ButtplugClientDevice::sensor_subscription_stream] streams are still open on the sensor, the
+  /// server side subscription is left in place for them, and dropped along with the last stream.
   pub fn unsubscribe_sensor(
     &self,
     sensor_index: u32,
@@ -560,8 +1432,56 @@ impl ButtplugClientDevice {
           .into(),
       );
     }
+    if !self
+      .subscriptions
+      .remove_direct(SubscriptionTarget::Sensor(sensor_index, sensor_type))
+    {
+      debug!(
+        "Streams still open on {} sensor {} of device {}, leaving subscription in place.",
+        sensor_type, sensor_index, self.index
+      );
+      return future::ready(Ok(())).boxed();
+    }
     let msg = SensorUnsubscribeCmdV3::new(self.index, sensor_index, sensor_type).into();
-    self.event_loop_sender.send_message_expect_ok(msg)
+    self.send_message_expect_ok(msg)
+  }
+
+  /// Subscribes to a sensor and returns a stream of the readings sent for it.
+  ///
+  /// As with [ButtplugClientDevice::raw_subscription_stream], the listener is set up before the
+  /// subscription message is sent, and streams on the same sensor share its subscription. Dropping
+  /// the last of them unsubscribes from the sensor, unless it's still subscribed via
+  /// [ButtplugClientDevice::subscribe_sensor].
+  pub fn sensor_subscription_stream(
+    &self,
+    sensor_index: u32,
+    sensor_type: SensorType,
+  ) -> ButtplugClientResultFuture<impl Stream<Item = Vec<i32>>> {
+    let readings = convert_broadcast_receiver_to_stream(self.internal_event_sender.subscribe())
+      .filter_map(move |event| {
+        let data = match event {
+          ButtplugClientDeviceEvent::Message(ButtplugServerMessageCurrent::SensorReading(
+            reading,
+          )) if reading.sensor_index() == sensor_index && reading.sensor_type() == sensor_type => {
+            Some(reading.data().clone())
+          }
+          _ => None,
+        };
+        futures::future::ready(data)
+      });
+    let stream = ButtplugClientDeviceSubscriptionStream::new(
+      SubscriptionTarget::Sensor(sensor_index, sensor_type),
+      &self.subscriptions,
+      SensorUnsubscribeCmdV3::new(self.index, sensor_index, sensor_type).into(),
+      &self.event_loop_sender,
+      readings,
+    );
+    let subscribe_fut = self.send_sensor_subscribe(sensor_index, sensor_type);
+    async move {
+      subscribe_fut.await?;
+      Ok(stream)
+    }
+    .boxed()
   }
 
   fn read_single_sensor(&self, sensor_type: &SensorType) -> ButtplugClientResultFuture<Vec<i32>> {
@@ -585,10 +1505,35 @@ impl ButtplugClientDevice {
         ButtplugDeviceError::ProtocolSensorNotSupported(*sensor_type).into(),
       );
     }
-    let msg = SensorReadCmdV3::new(self.index, sensor_indexes[0], *sensor_type).into();
-    let reply = self.event_loop_sender.send_message(msg);
+    self.read_sensor(sensor_indexes[0], *sensor_type)
+  }
+
+  /// Reads a sensor by index, returning the raw reading data.
+  ///
+  /// Works for any sensor the device lists as readable, including types that don't have a typed
+  /// helper like [ButtplugClientDevice::battery_level].
+  pub fn read_sensor(
+    &self,
+    sensor_index: u32,
+    sensor_type: SensorType,
+  ) -> ButtplugClientResultFuture<Vec<i32>> {
+    let Some(sensors) = self.message_attributes.sensor_read_cmd() else {
+      return create_boxed_future_client_error(
+        ButtplugDeviceError::MessageNotSupported(ButtplugDeviceMessageType::SensorReadCmd).into(),
+      );
+    };
+    if !sensors
+      .get(sensor_index as usize)
+      .is_some_and(|x| *x.sensor_type() == sensor_type)
+    {
+      return create_boxed_future_client_error(
+        ButtplugDeviceError::ProtocolSensorNotSupported(sensor_type).into(),
+      );
+    }
+    let msg = SensorReadCmdV3::new(self.index, sensor_index, sensor_type).into();
+    let reply = self.send_message(msg);
     async move {
-      if let ButtplugServerMessageV3::SensorReading(data) = reply.await? {
+      if let ButtplugServerMessageCurrent::SensorReading(data) = reply.await? {
         Ok(data.data().clone())
       } else {
         Err(
@@ -602,37 +1547,116 @@ impl ButtplugClientDevice {
     .boxed()
   }
 
-  fn has_sensor_read(&self, sensor_type: SensorType) -> bool {
-    if let Some(sensor_attrs) = self.message_attributes.sensor_read_cmd() {
-      sensor_attrs.iter().any(|x| *x.sensor_type() == sensor_type)
-    } else {
-      false
+  /// Error for a sensor reading that doesn't carry the data its sensor type calls for. Readings are
+  /// built by the server, so this means something went wrong on the other end.
+  fn malformed_reading_error(sensor_type: SensorType, data: &[i32]) -> ButtplugError {
+    ButtplugMessageError::InvalidMessageContents(format!(
+      "{sensor_type} sensor reading has malformed data: {data:?}"
+    ))
+    .into()
+  }
+
+  /// Reads a single value sensor, returning the first (and only) value of the reading.
+  fn read_single_sensor_value(&self, sensor_type: SensorType) -> ButtplugClientResultFuture<i32> {
+    let send_fut = self.read_single_sensor(&sensor_type);
+    async move {
+      let data = send_fut.await?;
+      data
+        .first()
+        .copied()
+        .ok_or_else(|| Self::malformed_reading_error(sensor_type, &data).into())
     }
+    .boxed()
   }
 
   pub fn has_battery_level(&self) -> bool {
-    self.has_sensor_read(SensorType::Battery)
+    self.supports_sensor_read(SensorType::Battery)
   }
 
   pub fn battery_level(&self) -> ButtplugClientResultFuture<f64> {
-    let send_fut = self.read_single_sensor(&SensorType::Battery);
+    let send_fut = self.read_single_sensor_value(SensorType::Battery);
     Box::pin(async move {
-      let data = send_fut.await?;
-      let battery_level = data[0];
+      let battery_level = send_fut.await?;
       Ok(battery_level as f64 / 100.0f64)
     })
   }
 
   pub fn has_rssi_level(&self) -> bool {
-    self.has_sensor_read(SensorType::RSSI)
+    self.supports_sensor_read(SensorType::RSSI)
   }
 
   pub fn rssi_level(&self) -> ButtplugClientResultFuture<i32> {
-    let send_fut = self.read_single_sensor(&SensorType::RSSI);
-    Box::pin(async move {
-      let data = send_fut.await?;
-      Ok(data[0])
-    })
+    self.read_single_sensor_value(SensorType::RSSI)
+  }
+
+  pub fn has_temperature(&self) -> bool {
+    self.supports_sensor_read(SensorType::Temperature)
+  }
+
+  pub fn temperature(&self) -> ButtplugClientResultFuture<i32> {
+    self.read_single_sensor_value(SensorType::Temperature)
+  }
+
+  /// Subscribes to the device accelerometer, returning a stream of (x, y, z) readings. Readings
+  /// that don't carry exactly 3 axes show up as errors on the stream.
+  pub fn accelerometer_stream(
+    &self,
+  ) -> ButtplugClientResultFuture<impl Stream<Item = Result<[i32; 3], ButtplugError>>> {
+    self.axis_sensor_stream(SensorType::Accelerometer)
+  }
+
+  /// Subscribes to the device gyroscope, returning a stream of (x, y, z) readings. Readings that
+  /// don't carry exactly 3 axes show up as errors on the stream.
+  pub fn gyroscope_stream(
+    &self,
+  ) -> ButtplugClientResultFuture<impl Stream<Item = Result<[i32; 3], ButtplugError>>> {
+    self.axis_sensor_stream(SensorType::Gyroscope)
+  }
+
+  fn axis_sensor_stream(
+    &self,
+    sensor_type: SensorType,
+  ) -> ButtplugClientResultFuture<impl Stream<Item = Result<[i32; 3], ButtplugError>>> {
+    let sensor_indexes: Vec<u32> = self
+      .message_attributes
+      .sensor_subscribe_cmd()
+      .iter()
+      .flatten()
+      .enumerate()
+      .filter(|x| *x.1.sensor_type() == sensor_type)
+      .map(|x| x.0 as u32)
+      .collect();
+    // The stream type isn't Sync, so we can't use create_boxed_future_client_error here, and have
+    // to resolve the error in the future instead.
+    let stream_fut = if sensor_indexes.len() == 1 {
+      Ok(self.sensor_subscription_stream(sensor_indexes[0], sensor_type))
+    } else {
+      Err(ButtplugError::from(
+        ButtplugDeviceError::ProtocolSensorNotSupported(sensor_type),
+      ))
+    };
+    async move {
+      let stream = stream_fut?.await?;
+      Ok(stream.map(move |data| {
+        <[i32; 3]>::try_from(data).map_err(|data| Self::malformed_reading_error(sensor_type, &data))
+      }))
+    }
+    .boxed()
+  }
+
+  /// Returns all sensors on this device that can be read or subscribed to.
+  pub fn sensors(&self) -> &[ButtplugDeviceSensor] {
+    &self.sensors
+  }
+
+  /// Returns all raw endpoints available on this device. Empty if raw messages aren't allowed.
+  pub fn raw_endpoints(&self) -> &[ButtplugDeviceRawEndpoint] {
+    &self.raw_endpoints
+  }
+
+  /// Returns the raw endpoint matching `endpoint`, if the device exposes it.
+  pub fn raw_endpoint(&self, endpoint: Endpoint) -> Option<&ButtplugDeviceRawEndpoint> {
+    self.raw_endpoints.iter().find(|x| x.endpoint == endpoint)
   }
 
   pub fn raw_write(
@@ -646,13 +1670,13 @@ impl ButtplugClientDevice {
         ButtplugDeviceError::MessageNotSupported(ButtplugDeviceMessageType::RawWriteCmd).into(),
       );
     }
-    let msg = ButtplugClientMessageV3::RawWriteCmd(RawWriteCmdV2::new(
+    let msg = ButtplugClientMessageCurrent::RawWriteCmd(RawWriteCmdV2::new(
       self.index,
       endpoint,
       data,
       write_with_response,
     ));
-    self.event_loop_sender.send_message_expect_ok(msg)
+    self.send_message_expect_ok(msg)
   }
 
   pub fn raw_read(
@@ -666,17 +1690,17 @@ impl ButtplugClientDevice {
         ButtplugDeviceError::MessageNotSupported(ButtplugDeviceMessageType::RawReadCmd).into(),
       );
     }
-    let msg = ButtplugClientMessageV3::RawReadCmd(RawReadCmdV2::new(
+    let msg = ButtplugClientMessageCurrent::RawReadCmd(RawReadCmdV2::new(
       self.index,
       endpoint,
       expected_length,
       timeout,
     ));
-    let send_fut = self.event_loop_sender.send_message(msg);
+    let send_fut = self.send_message(msg);
     async move {
       match send_fut.await? {
-        ButtplugServerMessageV3::RawReading(reading) => Ok(reading.data().clone()),
-        ButtplugServerMessageV3::Error(err) => Err(ButtplugError::from(err).into()),
+        ButtplugServerMessageCurrent::RawReading(reading) => Ok(reading.data().clone()),
+        ButtplugServerMessageCurrent::Error(err) => Err(ButtplugError::from(err).into()),
         msg => Err(
           ButtplugError::from(ButtplugMessageError::UnexpectedMessageType(format!(
             "{:?}",
@@ -689,31 +1713,116 @@ impl ButtplugClientDevice {
     .boxed()
   }
 
-  pub fn raw_subscribe(&self, endpoint: Endpoint) -> ButtplugClientResultFuture {
+  fn send_raw_subscribe(&self, endpoint: Endpoint) -> ButtplugClientResultFuture {
     if self.message_attributes.raw_subscribe_cmd().is_none() {
       return create_boxed_future_client_error(
         ButtplugDeviceError::MessageNotSupported(ButtplugDeviceMessageType::RawSubscribeCmd).into(),
       );
     }
     let msg =
-      ButtplugClientMessageV3::RawSubscribeCmd(RawSubscribeCmdV2::new(self.index, endpoint));
-    self.event_loop_sender.send_message_expect_ok(msg)
+      ButtplugClientMessageCurrent::RawSubscribeCmd(RawSubscribeCmdV2::new(self.index, endpoint));
+    self.send_message_expect_ok(msg)
+  }
+
+  pub fn raw_subscribe(&self, endpoint: Endpoint) -> ButtplugClientResultFuture {
+    let subscribe_fut = self.send_raw_subscribe(endpoint);
+    let subscriptions = self.subscriptions.clone();
+    async move {
+      subscribe_fut.await?;
+      subscriptions.add_direct(SubscriptionTarget::Raw(endpoint));
+      Ok(())
+    }
+    .boxed()
   }
 
+  /// Ends a subscription made via [ButtplugClientDevice::raw_subscribe]. If any
+  /// [ButtplugClientDevice::raw_subscription_stream] streams are still open on the endpoint, the
+  /// server side subscription is left in place for them, and dropped along with the last stream.
   pub fn raw_unsubscribe(&self, endpoint: Endpoint) -> ButtplugClientResultFuture {
     if self.message_attributes.raw_subscribe_cmd().is_none() {
       return create_boxed_future_client_error(
         ButtplugDeviceError::MessageNotSupported(ButtplugDeviceMessageType::RawSubscribeCmd).into(),
       );
     }
-    let msg =
-      ButtplugClientMessageV3::RawUnsubscribeCmd(RawUnsubscribeCmdV2::new(self.index, endpoint));
-    self.event_loop_sender.send_message_expect_ok(msg)
+    if !self
+      .subscriptions
+      .remove_direct(SubscriptionTarget::Raw(endpoint))
+    {
+      debug!(
+        "Streams still open on endpoint {} of device {}, leaving subscription in place.",
+        endpoint, self.index
+      );
+      return future::ready(Ok(())).boxed();
+    }
+    let msg = ButtplugClientMessageCurrent::RawUnsubscribeCmd(RawUnsubscribeCmdV2::new(
+      self.index, endpoint,
+    ));
+    self.send_message_expect_ok(msg)
+  }
+
+  /// Subscribes to a raw endpoint and returns a stream of the data received on it.
+  ///
+  /// The endpoint listener is set up before the subscription message is sent, so no readings will
+  /// be missed between subscribing and receiving the stream. Only readings for this endpoint are
+  /// delivered. Any number of streams can be open on an endpoint at once, and dropping the last of
+  /// them unsubscribes from the endpoint, unless it's still subscribed via
+  /// [ButtplugClientDevice::raw_subscribe].
+  pub fn raw_subscription_stream(
+    &self,
+    endpoint: Endpoint,
+  ) -> ButtplugClientResultFuture<impl Stream<Item = Vec<u8>>> {
+    let Some(raw_endpoint) = self.raw_endpoint(endpoint) else {
+      let err = if self.supports_raw() {
+        ButtplugDeviceError::InvalidEndpoint(endpoint)
+      } else {
+        ButtplugDeviceError::MessageNotSupported(ButtplugDeviceMessageType::RawSubscribeCmd)
+      };
+      return future::ready(Err(ButtplugError::from(err).into())).boxed();
+    };
+    // Create the stream before subscribing, so it counts as a holder of the subscription from the
+    // start, and releases it if subscribing fails.
+    let stream = ButtplugClientDeviceSubscriptionStream::new(
+      SubscriptionTarget::Raw(endpoint),
+      &self.subscriptions,
+      ButtplugClientMessageCurrent::RawUnsubscribeCmd(RawUnsubscribeCmdV2::new(
+        self.index, endpoint,
+      )),
+      &self.event_loop_sender,
+      convert_broadcast_receiver_to_stream(raw_endpoint.reading_sender.subscribe()),
+    );
+    let subscribe_fut = self.send_raw_subscribe(endpoint);
+    async move {
+      subscribe_fut.await?;
+      Ok(stream)
+    }
+    .boxed()
   }
 
   /// Commands device to stop all movement.
+  ///
+  /// Sends a StopDeviceCmd, so the server decides how to stop each actuator (zero speed scalars,
+  /// zero speed rotation, etc...). Any command the server is still sending to the device when the
+  /// stop arrives will have its remaining writes dropped.
   pub fn stop(&self) -> ButtplugClientResultFuture {
     // All devices accept StopDeviceCmd
+    self.send_message_expect_ok(StopDeviceCmdV0::new(self.index).into())
+  }
+
+  /// Commands device to stop all movement immediately, resolving only once
+  /// the server has acknowledged the stop.
+  ///
+  /// Unlike [ButtplugClientDevice::stop], this checks device connection status
+  /// up front, so callers get an error instead of a silently dropped stop if
+  /// the device has already gone away. The client does not dedupe or rate limit
+  /// device commands, and this bypasses both the queue depth limit and command
+  /// serialization, so the StopDeviceCmd is handed straight to the event loop
+  /// and sent to the server ahead of anything issued after it.
+  pub fn emergency_stop(&self) -> ButtplugClientResultFuture {
+    if !self.connected() {
+      return create_boxed_future_client_error(
+        ButtplugDeviceError::DeviceNotConnected(self.name.clone()).into(),
+      );
+    }
     self
       .event_loop_sender
       .send_message_expect_ok(StopDeviceCmdV0::new(self.index).into())
@@ -727,6 +1836,20 @@ impl ButtplugClientDevice {
     self.client_connected.store(connected, Ordering::SeqCst);
   }
 
+  /// Sends the data from a RawReading to the stream subscribers of the endpoint it came from.
+  pub(super) fn queue_raw_reading(&self, reading: &RawReadingV2) {
+    let Some(raw_endpoint) = self.raw_endpoint(reading.endpoint()) else {
+      debug!(
+        "Raw reading received for endpoint {} not exposed by device {}, dropping.",
+        reading.endpoint(),
+        self.index
+      );
+      return;
+    };
+    // No receivers just means nothing is streaming this endpoint right now.
+    let _ = raw_endpoint.reading_sender.send(reading.data().clone());
+  }
+
   pub(super) fn queue_event(&self, event: ButtplugClientDeviceEvent) {
     if self.internal_event_sender.receiver_count() == 0 {
       // We can drop devices before we've hooked up listeners or after the device manager drops,
@@ -758,3 +1881,15 @@ impl fmt::Debug for ButtplugClientDevice {
       .finish()
   }
 }
+
+/// Formats as `[index] name`, followed by ` (display name)` if the device has a display name that
+/// differs from its name.
+impl fmt::Display for ButtplugClientDevice {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "[{}] {}", self.index, self.name)?;
+    match &self.display_name {
+      Some(display_name) if *display_name != self.name => write!(f, " ({display_name})"),
+      _ => Ok(()),
+    }
+  }
+}
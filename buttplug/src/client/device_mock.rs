@@ -0,0 +1,245 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2024 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Standalone [ButtplugClientDevice]s for testing application code without a running server.
+
+use super::{
+  client_event_loop::ButtplugClientRequest,
+  ButtplugClientDevice,
+  ButtplugClientMessageSender,
+};
+use crate::{
+  core::message::{
+    ButtplugClientMessageCurrent,
+    ButtplugMessage,
+    ButtplugServerMessageCurrent,
+    ClientDeviceMessageAttributesV3,
+    OkV0,
+  },
+  util::async_manager,
+};
+use std::{
+  collections::VecDeque,
+  sync::{
+    atomic::{AtomicBool, AtomicU32, Ordering},
+    Arc,
+    Mutex,
+  },
+};
+use tokio::sync::broadcast;
+
+/// Stands in for the server behind devices built with [ButtplugClientDeviceBuilder].
+///
+/// Every message the devices send is recorded, in order. Replies are taken from the queue of
+/// canned responses, or are [OkV0] if the queue is empty. Messages are given ids starting at 1, the
+/// same way the client would number them, and canned responses have their id set to match.
+#[derive(Clone)]
+pub struct MockMessageSender {
+  sent: Arc<Mutex<Vec<ButtplugClientMessageCurrent>>>,
+  responses: Arc<Mutex<VecDeque<ButtplugServerMessageCurrent>>>,
+  current_id: Arc<AtomicU32>,
+}
+
+impl Default for MockMessageSender {
+  fn default() -> Self {
+    Self {
+      sent: Arc::new(Mutex::new(vec![])),
+      responses: Arc::new(Mutex::new(VecDeque::new())),
+      current_id: Arc::new(AtomicU32::new(1)),
+    }
+  }
+}
+
+impl MockMessageSender {
+  /// Queues `response` as the reply to the next message sent that doesn't already have one.
+  /// Replying with an [ErrorV0](crate::core::message::ErrorV0) makes the command fail with that
+  /// error.
+  pub fn queue_response(&self, response: ButtplugServerMessageCurrent) {
+    self
+      .responses
+      .lock()
+      .expect("Mock sender lock should never be poisoned")
+      .push_back(response);
+  }
+
+  /// Returns a copy of all messages sent so far.
+  pub fn sent_messages(&self) -> Vec<ButtplugClientMessageCurrent> {
+    self
+      .sent
+      .lock()
+      .expect("Mock sender lock should never be poisoned")
+      .clone()
+  }
+
+  fn reply_to(&self, msg: &mut ButtplugClientMessageCurrent) -> ButtplugServerMessageCurrent {
+    let id = self.current_id.fetch_add(1, Ordering::Relaxed);
+    msg.set_id(id);
+    self
+      .sent
+      .lock()
+      .expect("Mock sender lock should never be poisoned")
+      .push(msg.clone());
+    let mut response = self
+      .responses
+      .lock()
+      .expect("Mock sender lock should never be poisoned")
+      .pop_front()
+      .unwrap_or_else(|| OkV0::new(id).into());
+    response.set_id(id);
+    response
+  }
+
+  /// Creates the sender handed to devices, along with a task answering everything sent through it.
+  /// The task exits once all devices using the sender are dropped.
+  fn create_client_sender(&self) -> Arc<ButtplugClientMessageSender> {
+    let (request_sender, mut request_receiver) = broadcast::channel(256);
+    let client_sender = Arc::new(ButtplugClientMessageSender::new(
+      &request_sender,
+      &Arc::new(AtomicBool::new(true)),
+      None,
+    ));
+    let mock = self.clone();
+    async_manager::spawn(async move {
+      loop {
+        match request_receiver.recv().await {
+          Ok(ButtplugClientRequest::Message(mut msg_fut)) => {
+            match mock.reply_to(&mut msg_fut.msg) {
+              ButtplugServerMessageCurrent::Error(e) => {
+                msg_fut.waker.set_reply(Err(e.original_error().into()))
+              }
+              response => msg_fut.waker.set_reply(Ok(response)),
+            }
+          }
+          Ok(_) => continue,
+          Err(broadcast::error::RecvError::Lagged(_)) => continue,
+          Err(broadcast::error::RecvError::Closed) => break,
+        }
+      }
+    });
+    client_sender
+  }
+}
+
+/// Builds a [ButtplugClientDevice] connected to a [MockMessageSender] instead of a server.
+///
+/// Only available in the library's own tests, or with the `testing` feature enabled.
+pub struct ButtplugClientDeviceBuilder {
+  name: String,
+  display_name: Option<String>,
+  message_timing_gap: Option<u32>,
+  index: u32,
+  message_attributes: ClientDeviceMessageAttributesV3,
+}
+
+impl ButtplugClientDeviceBuilder {
+  pub fn new(name: &str, message_attributes: &ClientDeviceMessageAttributesV3) -> Self {
+    Self {
+      name: name.to_owned(),
+      display_name: None,
+      message_timing_gap: None,
+      index: 0,
+      message_attributes: message_attributes.clone(),
+    }
+  }
+
+  /// Sets the device index. Defaults to 0.
+  pub fn index(&mut self, index: u32) -> &mut Self {
+    self.index = index;
+    self
+  }
+
+  pub fn display_name(&mut self, display_name: &str) -> &mut Self {
+    self.display_name = Some(display_name.to_owned());
+    self
+  }
+
+  pub fn message_timing_gap(&mut self, message_timing_gap: u32) -> &mut Self {
+    self.message_timing_gap = Some(message_timing_gap);
+    self
+  }
+
+  /// Builds the device. Every command it sends is recorded and answered by `sender`.
+  pub fn finish(&self, sender: &MockMessageSender) -> ButtplugClientDevice {
+    ButtplugClientDevice::new(
+      &self.name,
+      &self.display_name,
+      self.message_timing_gap,
+      self.index,
+      &self.message_attributes,
+      &sender.create_client_sender(),
+    )
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{ButtplugClientDeviceBuilder, MockMessageSender};
+  use crate::{
+    client::{ButtplugClientError, ScalarValueCommand},
+    core::{
+      errors::{ButtplugDeviceError, ButtplugError},
+      message::{
+        ActuatorType,
+        ButtplugClientMessageCurrent,
+        ButtplugDeviceMessage,
+        ButtplugMessage,
+        ClientDeviceMessageAttributesV3Builder,
+        ClientGenericDeviceMessageAttributesV3,
+        ErrorV0,
+      },
+    },
+  };
+
+  #[tokio::test]
+  async fn test_mock_device_records_messages_and_replies() {
+    let vibrator =
+      ClientGenericDeviceMessageAttributesV3::new("Vibrator", 20, ActuatorType::Vibrate);
+    let mut attributes_builder = ClientDeviceMessageAttributesV3Builder::default();
+    attributes_builder.scalar_cmd(&[vibrator]);
+    let attributes = attributes_builder
+      .finish()
+      .expect("Test, assuming infallible.");
+    let sender = MockMessageSender::default();
+    let device = ButtplugClientDeviceBuilder::new("Test Vibrator", &attributes)
+      .index(3)
+      .finish(&sender);
+
+    device
+      .vibrate(&ScalarValueCommand::ScalarValue(0.5))
+      .await
+      .expect("Test, assuming infallible.");
+    sender.queue_response(
+      ErrorV0::from(ButtplugError::from(
+        ButtplugDeviceError::DeviceNotConnected("Test".to_owned()),
+      ))
+      .into(),
+    );
+    let err = device
+      .stop()
+      .await
+      .expect_err("Canned error response should fail the command");
+    assert!(matches!(
+      err,
+      ButtplugClientError::ButtplugError(ButtplugError::ButtplugDeviceError(
+        ButtplugDeviceError::DeviceNotConnected(_)
+      ))
+    ));
+
+    let sent = sender.sent_messages();
+    assert_eq!(sent.len(), 2);
+    let ButtplugClientMessageCurrent::ScalarCmd(scalar_cmd) = &sent[0] else {
+      panic!("Expected ScalarCmd, got {:?}", sent[0]);
+    };
+    assert_eq!(scalar_cmd.device_index(), 3);
+    assert_eq!(scalar_cmd.scalars()[0].scalar(), 0.5);
+    assert!(matches!(
+      sent[1],
+      ButtplugClientMessageCurrent::StopDeviceCmd(_)
+    ));
+    assert_eq!(sent.iter().map(|x| x.id()).collect::<Vec<_>>(), vec![1, 2]);
+  }
+}
@@ -0,0 +1,136 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2024 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Recording and playback of client/server message traffic, for reproducing bugs.
+
+use super::{ButtplugClient, ButtplugClientError};
+use crate::{
+  core::message::{ButtplugClientMessageCurrent, ButtplugServerMessageCurrent},
+  util::sleep,
+};
+use futures::future;
+use getset::{CopyGetters, Getters};
+use instant::Instant;
+use std::{
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+
+/// Receives every message a [ButtplugClient] exchanges with its server, set via
+/// [ButtplugClientBuilder::record_messages](super::ButtplugClientBuilder::record_messages).
+///
+/// Both methods are called inline by the client event loop, so implementations should return
+/// quickly.
+pub trait MessageRecorder: Send + Sync {
+  /// Called with each message right before it is handed to the connector.
+  fn record_sent(&self, msg: &ButtplugClientMessageCurrent);
+  /// Called with each message as soon as it is received from the connector, before the client
+  /// handles it.
+  fn record_received(&self, msg: &ButtplugServerMessageCurrent);
+}
+
+/// Direction and contents of a recorded message.
+#[derive(Debug, Clone, PartialEq)]
+// Recording is a debugging tool, so we'd rather keep messages unboxed and easy to match on.
+#[allow(clippy::large_enum_variant)]
+pub enum RecordedMessage {
+  Sent(ButtplugClientMessageCurrent),
+  Received(ButtplugServerMessageCurrent),
+}
+
+/// A message stored by [VecMessageRecorder].
+#[derive(Debug, Clone, PartialEq, Getters, CopyGetters)]
+pub struct TimestampedMessage {
+  /// Time since the recorder was created.
+  #[getset(get_copy = "pub")]
+  timestamp: Duration,
+  #[getset(get = "pub")]
+  message: RecordedMessage,
+}
+
+/// [MessageRecorder] that keeps every message in memory, in the order they were seen.
+#[derive(Clone)]
+pub struct VecMessageRecorder {
+  start: Instant,
+  messages: Arc<Mutex<Vec<TimestampedMessage>>>,
+}
+
+impl Default for VecMessageRecorder {
+  fn default() -> Self {
+    Self {
+      start: Instant::now(),
+      messages: Arc::new(Mutex::new(vec![])),
+    }
+  }
+}
+
+impl VecMessageRecorder {
+  /// Returns a copy of all messages recorded so far.
+  pub fn messages(&self) -> Vec<TimestampedMessage> {
+    self
+      .messages
+      .lock()
+      .expect("Message recorder lock should never be poisoned")
+      .clone()
+  }
+
+  fn record(&self, message: RecordedMessage) {
+    let timestamp = self.start.elapsed();
+    self
+      .messages
+      .lock()
+      .expect("Message recorder lock should never be poisoned")
+      .push(TimestampedMessage { timestamp, message });
+  }
+}
+
+impl MessageRecorder for VecMessageRecorder {
+  fn record_sent(&self, msg: &ButtplugClientMessageCurrent) {
+    self.record(RecordedMessage::Sent(msg.clone()));
+  }
+
+  fn record_received(&self, msg: &ButtplugServerMessageCurrent) {
+    self.record(RecordedMessage::Received(msg.clone()));
+  }
+}
+
+/// Sends the client messages in `recorded` through `client` again, on the same schedule they were
+/// originally sent on.
+///
+/// The handshake is skipped, since `client` is expected to already be connected. Device indexes are
+/// replayed as recorded, so the server needs the same devices connected at the same indexes as the
+/// original session, or commands will go to the wrong devices. Messages don't wait on replies to
+/// earlier ones, so commands that were in flight at the same time originally are again, and a
+/// message failing doesn't stop the rest from being sent. Returns the reply to each message, in
+/// the order the messages were sent, once all of them have been answered.
+pub async fn playback(
+  recorded: &[TimestampedMessage],
+  client: &ButtplugClient,
+) -> Vec<Result<ButtplugServerMessageCurrent, ButtplugClientError>> {
+  let messages: Vec<_> = recorded
+    .iter()
+    .filter_map(|recorded| match &recorded.message {
+      RecordedMessage::Sent(ButtplugClientMessageCurrent::RequestServerInfo(_)) => None,
+      RecordedMessage::Sent(msg) => Some((recorded.timestamp, msg.clone())),
+      RecordedMessage::Received(_) => None,
+    })
+    .collect();
+  let Some((first_timestamp, _)) = messages.first() else {
+    return vec![];
+  };
+  let first_timestamp = *first_timestamp;
+  let start = Instant::now();
+  future::join_all(messages.into_iter().map(|(timestamp, msg)| async move {
+    let offset = timestamp.saturating_sub(first_timestamp);
+    let elapsed = start.elapsed();
+    if offset > elapsed {
+      sleep(offset - elapsed).await;
+    }
+    client.message_sender.send_message(msg).await
+  }))
+  .await
+}
@@ -6,20 +6,26 @@
 // for full license information.
 
 //! Communications API for accessing Buttplug Servers
+mod client_builder;
 pub mod client_event_loop;
 pub mod client_message_sorter;
 pub mod device;
+#[cfg(any(test, feature = "testing"))]
+pub mod device_mock;
+pub mod message_recorder;
 
 use crate::{
   core::{
     connector::{ButtplugConnector, ButtplugConnectorError, ButtplugConnectorFuture},
-    errors::{ButtplugError, ButtplugHandshakeError},
+    errors::{ButtplugDeviceError, ButtplugError, ButtplugHandshakeError},
     message::{
-      ButtplugClientMessageV3,
-      ButtplugServerMessageV3,
+      ButtplugClientMessageCurrent,
+      ButtplugServerMessageCurrent,
+      Endpoint,
       PingV0,
       RequestDeviceListV0,
       RequestServerInfoV1,
+      SensorType,
       StartScanningV0,
       StopAllDevicesV0,
       StopScanningV0,
@@ -29,31 +35,54 @@ use crate::{
   util::{
     async_manager,
     future::{ButtplugFuture, ButtplugFutureStateShared},
-    stream::convert_broadcast_receiver_to_stream,
+    sleep,
+    stream::convert_broadcast_receiver_to_lossy_stream,
   },
 };
+pub use client_builder::{ButtplugClientBuilder, DEFAULT_EVENT_CHANNEL_CAPACITY};
 use client_event_loop::{ButtplugClientEventLoop, ButtplugClientRequest};
 use dashmap::DashMap;
 pub use device::{
   ButtplugClientDevice,
   ButtplugClientDeviceEvent,
+  ButtplugDeviceRawEndpoint,
+  ButtplugDeviceSensor,
   LinearCommand,
   RotateCommand,
   ScalarCommand,
   ScalarValueCommand,
 };
+#[cfg(any(test, feature = "testing"))]
+pub use device_mock::{ButtplugClientDeviceBuilder, MockMessageSender};
 use futures::{
   future::{self, BoxFuture, FutureExt},
+  pin_mut,
+  select,
   Stream,
+  StreamExt,
 };
-use std::sync::{
-  atomic::{AtomicBool, Ordering},
-  Arc,
+pub use message_recorder::{
+  playback,
+  MessageRecorder,
+  RecordedMessage,
+  TimestampedMessage,
+  VecMessageRecorder,
+};
+use std::{
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  time::Duration,
 };
 use thiserror::Error;
 use tokio::sync::{broadcast, mpsc, Mutex};
 use tracing_futures::Instrument;
 
+/// How long [ButtplugClient::scan_for] waits for the server to report scanning finished after
+/// stopping its scan.
+pub const SCAN_FOR_FINISHED_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Result type used for public APIs.
 ///
 /// Allows us to differentiate between an issue with the connector (as a
@@ -63,8 +92,9 @@ type ButtplugClientResult<T = ()> = Result<T, ButtplugClientError>;
 type ButtplugClientResultFuture<T = ()> = BoxFuture<'static, ButtplugClientResult<T>>;
 
 /// Result type used for passing server responses.
-pub type ButtplugServerMessageResult = ButtplugClientResult<ButtplugServerMessageV3>;
-pub type ButtplugServerMessageResultFuture = ButtplugClientResultFuture<ButtplugServerMessageV3>;
+pub type ButtplugServerMessageResult = ButtplugClientResult<ButtplugServerMessageCurrent>;
+pub type ButtplugServerMessageResultFuture =
+  ButtplugClientResultFuture<ButtplugServerMessageCurrent>;
 /// Future state type for returning server responses across futures.
 pub(crate) type ButtplugServerMessageStateShared =
   ButtplugFutureStateShared<ButtplugServerMessageResult>;
@@ -85,12 +115,12 @@ pub(crate) type ButtplugServerMessageFuture = ButtplugFuture<ButtplugServerMessa
 /// continue execution.
 #[derive(Clone)]
 pub struct ButtplugClientMessageFuturePair {
-  msg: ButtplugClientMessageV3,
+  msg: ButtplugClientMessageCurrent,
   waker: ButtplugServerMessageStateShared,
 }
 
 impl ButtplugClientMessageFuturePair {
-  pub fn new(msg: ButtplugClientMessageV3, waker: ButtplugServerMessageStateShared) -> Self {
+  pub fn new(msg: ButtplugClientMessageCurrent, waker: ButtplugServerMessageStateShared) -> Self {
     Self { msg, waker }
   }
 }
@@ -110,6 +140,17 @@ pub enum ButtplugClientError {
   /// Protocol error
   #[error(transparent)]
   ButtplugError(#[from] ButtplugError),
+  /// Device is still busy with earlier commands, either because it has the maximum number of
+  /// commands waiting on replies, or because the server rejected the command while the device was
+  /// processing a previous one.
+  #[error("Device {0} is busy, command was not sent.")]
+  DeviceBusy(u32),
+  /// Connecting to the server took longer than the configured timeout
+  #[error("Connection to server timed out after {0:?}.")]
+  ConnectionTimeout(Duration),
+  /// Server did not reply to a message within the timeout
+  #[error("Server did not reply to command within {0:?}.")]
+  CommandTimeout(Duration),
 }
 
 /// Enum representing different events that can be emitted by a client.
@@ -128,6 +169,24 @@ pub enum ButtplugClientEvent {
   /// Emitted when a device has been removed from the server. Includes a
   /// [ButtplugClientDevice] object representing the device.
   DeviceRemoved(Arc<ButtplugClientDevice>),
+  /// Emitted when a subscribed sensor sends a reading. Readings are also
+  /// relayed to the device event stream, this just allows handling all sensor
+  /// events in one place.
+  SensorReading {
+    device_index: u32,
+    sensor_index: u32,
+    sensor_type: SensorType,
+    data: Vec<i32>,
+  },
+  /// Emitted when a device sends data on a subscribed raw endpoint. Readings
+  /// are also relayed to the stream for the endpoint (see
+  /// [ButtplugClientDevice::raw_subscription_stream]), this allows observing
+  /// all raw traffic without subscribing to each endpoint's stream.
+  RawReading {
+    device_index: u32,
+    endpoint: Endpoint,
+    data: Vec<u8>,
+  },
   /// Emitted when a client has not pinged the server in a sufficient amount of
   /// time.
   PingTimeout,
@@ -155,16 +214,20 @@ where
 pub(super) struct ButtplugClientMessageSender {
   message_sender: broadcast::Sender<ButtplugClientRequest>,
   connected: Arc<AtomicBool>,
+  /// Maximum time to wait for a reply to any message, if set.
+  message_timeout: Option<Duration>,
 }
 
 impl ButtplugClientMessageSender {
   fn new(
     message_sender: &broadcast::Sender<ButtplugClientRequest>,
     connected: &Arc<AtomicBool>,
+    message_timeout: Option<Duration>,
   ) -> Self {
     Self {
       message_sender: message_sender.clone(),
       connected: connected.clone(),
+      message_timeout,
     }
   }
 
@@ -195,11 +258,37 @@ impl ButtplugClientMessageSender {
     self.message_sender.subscribe()
   }
 
-  pub fn send_message(&self, msg: ButtplugClientMessageV3) -> ButtplugServerMessageResultFuture {
+  /// Sends a ButtplugMessage from client to server, using the client's default message timeout if
+  /// one was set via [ButtplugClientBuilder::default_message_timeout()].
+  pub fn send_message(
+    &self,
+    msg: ButtplugClientMessageCurrent,
+  ) -> ButtplugServerMessageResultFuture {
+    match self.message_timeout {
+      Some(timeout) => self.send_message_with_timeout(msg, timeout),
+      None => self.send_message_with_optional_timeout(msg, None),
+    }
+  }
+
+  /// Sends a ButtplugMessage from client to server, failing with
+  /// [ButtplugClientError::CommandTimeout] if the server hasn't replied within `timeout`.
+  pub fn send_message_with_timeout(
+    &self,
+    msg: ButtplugClientMessageCurrent,
+    timeout: Duration,
+  ) -> ButtplugServerMessageResultFuture {
+    self.send_message_with_optional_timeout(msg, Some(timeout))
+  }
+
+  fn send_message_with_optional_timeout(
+    &self,
+    msg: ButtplugClientMessageCurrent,
+    timeout: Option<Duration>,
+  ) -> ButtplugServerMessageResultFuture {
     if !self.connected.load(Ordering::Relaxed) {
       future::ready(Err(ButtplugConnectorError::ConnectorNotConnected.into())).boxed()
     } else {
-      self.send_message_ignore_connect_status(msg)
+      self.send_message_ignore_connect_status_with_optional_timeout(msg, timeout)
     }
   }
 
@@ -207,32 +296,87 @@ impl ButtplugClientMessageSender {
   /// ButtplugMessage back from the server.
   pub fn send_message_ignore_connect_status(
     &self,
-    msg: ButtplugClientMessageV3,
+    msg: ButtplugClientMessageCurrent,
+  ) -> ButtplugServerMessageResultFuture {
+    self.send_message_ignore_connect_status_with_optional_timeout(msg, self.message_timeout)
+  }
+
+  fn send_message_ignore_connect_status_with_optional_timeout(
+    &self,
+    msg: ButtplugClientMessageCurrent,
+    timeout: Option<Duration>,
   ) -> ButtplugServerMessageResultFuture {
     // Create a future to pair with the message being resolved.
     let fut = ButtplugServerMessageFuture::default();
-    let internal_msg = ButtplugClientRequest::Message(ButtplugClientMessageFuturePair::new(
-      msg,
-      fut.get_state_clone(),
-    ));
+    let state = fut.get_state_clone();
+    let internal_msg =
+      ButtplugClientRequest::Message(ButtplugClientMessageFuturePair::new(msg, state.clone()));
 
     // Send message to internal loop and wait for return.
     let send_fut = self.send_message_to_event_loop(internal_msg);
+    let message_sender = self.message_sender.clone();
     async move {
       send_fut.await?;
-      fut.await
+      let Some(timeout) = timeout else {
+        return fut.await;
+      };
+      select! {
+        result = fut.fuse() => return result,
+        _ = sleep(timeout).fuse() => {},
+      }
+      warn!("Server did not reply to message within {:?}.", timeout);
+      // Have the event loop forget the future, otherwise it'd wait on a reply forever. If the event
+      // loop is gone, there's nothing left to clean up.
+      let _ = message_sender.send(ButtplugClientRequest::CancelMessage(state));
+      Err(ButtplugClientError::CommandTimeout(timeout))
     }
     .boxed()
   }
 
   /// Sends a ButtplugMessage from client to server. Expects to receive an [Ok]
   /// type ButtplugMessage back from the server.
-  pub fn send_message_expect_ok(&self, msg: ButtplugClientMessageV3) -> ButtplugClientResultFuture {
+  pub fn send_message_expect_ok(
+    &self,
+    msg: ButtplugClientMessageCurrent,
+  ) -> ButtplugClientResultFuture {
     let send_fut = self.send_message(msg);
     async move { send_fut.await.map(|_| ()) }.boxed()
   }
 }
 
+/// Releases the scan claimed by [ButtplugClient::scan_for] if it doesn't finish normally, i.e. it
+/// returns an error or its future is dropped. If the scan was started, it's stopped from a detached
+/// task first, and the claim is only released once that's done, so the stop can't end a scan started
+/// after it.
+struct ScanForGuard {
+  scanning: Arc<AtomicBool>,
+  message_sender: Arc<ButtplugClientMessageSender>,
+  started: bool,
+  finished: bool,
+}
+
+impl Drop for ScanForGuard {
+  fn drop(&mut self) {
+    if self.finished {
+      return;
+    }
+    if !self.started {
+      self.scanning.store(false, Ordering::SeqCst);
+      return;
+    }
+    let stop_fut = self
+      .message_sender
+      .send_message_expect_ok(StopScanningV0::default().into());
+    let scanning = self.scanning.clone();
+    async_manager::spawn(async move {
+      if let Err(e) = stop_fut.await {
+        debug!("Error stopping scan for unfinished scan_for: {:?}", e);
+      }
+      scanning.store(false, Ordering::SeqCst);
+    });
+  }
+}
+
 /// Struct used by applications to communicate with a Buttplug Server.
 ///
 /// Buttplug Clients provide an API layer on top of the Buttplug Protocol that
@@ -246,8 +390,9 @@ impl ButtplugClientMessageSender {
 /// - Holding state related to the server (i.e. what devices are currently
 ///   connected, etc...)
 ///
-/// Clients are created by the [ButtplugClient::new()] method, which also
-/// handles spinning up the event loop and connecting the client to the server.
+/// Clients are created by the [ButtplugClient::new()] method or a
+/// [ButtplugClientBuilder], which also handles spinning up the event loop and
+/// connecting the client to the server.
 /// Closures passed to the run() method can access and use the Client object.
 pub struct ButtplugClient {
   /// The client name. Depending on the connection type and server being used,
@@ -260,12 +405,29 @@ pub struct ButtplugClient {
   message_sender: Arc<ButtplugClientMessageSender>,
   connected: Arc<AtomicBool>,
   device_map: Arc<DashMap<u32, Arc<ButtplugClientDevice>>>,
+  /// Timeout applied to every [ButtplugClient::connect()] call, if set.
+  connection_timeout: Option<Duration>,
+  /// Receives a copy of all traffic with the server, if set.
+  message_recorder: Option<Arc<dyn MessageRecorder>>,
+  /// True from a successful [ButtplugClient::start_scanning()] until scanning is stopped or the
+  /// server reports it finished.
+  scanning: Arc<AtomicBool>,
 }
 
 impl ButtplugClient {
   pub fn new(name: &str) -> Self {
+    ButtplugClientBuilder::new(name).finish()
+  }
+
+  fn new_with_options(
+    name: &str,
+    event_channel_capacity: usize,
+    connection_timeout: Option<Duration>,
+    message_timeout: Option<Duration>,
+    message_recorder: Option<Arc<dyn MessageRecorder>>,
+  ) -> Self {
     let (message_sender, _) = broadcast::channel(256);
-    let (event_stream, _) = broadcast::channel(256);
+    let (event_stream, _) = broadcast::channel(event_channel_capacity);
     let connected = Arc::new(AtomicBool::new(false));
     Self {
       client_name: name.to_owned(),
@@ -274,18 +436,76 @@ impl ButtplugClient {
       message_sender: Arc::new(ButtplugClientMessageSender::new(
         &message_sender,
         &connected,
+        message_timeout,
       )),
       connected,
       device_map: Arc::new(DashMap::new()),
+      connection_timeout,
+      message_recorder,
+      scanning: Arc::new(AtomicBool::new(false)),
     }
   }
 
+  /// Connects to a server using the given connector and runs the handshake.
+  ///
+  /// If the client was built with [ButtplugClientBuilder::connection_timeout()], this behaves like
+  /// [ButtplugClient::connect_with_timeout()] with that timeout. Otherwise it waits as long as the
+  /// connector and server take.
   pub async fn connect<ConnectorType>(
+    &self,
+    connector: ConnectorType,
+  ) -> Result<(), ButtplugClientError>
+  where
+    ConnectorType:
+      ButtplugConnector<ButtplugClientMessageCurrent, ButtplugServerMessageCurrent> + 'static,
+  {
+    match self.connection_timeout {
+      Some(timeout) => self.connect_with_timeout(connector, timeout).await,
+      None => self.connect_and_handshake(connector).await,
+    }
+  }
+
+  /// Connects to a server, failing with [ButtplugClientError::ConnectionTimeout] if the connector
+  /// hasn't connected and the handshake hasn't finished within `timeout`.
+  ///
+  /// On timeout, the client is left disconnected. If the connector was still connecting it is
+  /// dropped, otherwise it is disconnected and dropped by the client event loop.
+  pub async fn connect_with_timeout<ConnectorType>(
+    &self,
+    connector: ConnectorType,
+    timeout: Duration,
+  ) -> Result<(), ButtplugClientError>
+  where
+    ConnectorType:
+      ButtplugConnector<ButtplugClientMessageCurrent, ButtplugServerMessageCurrent> + 'static,
+  {
+    {
+      let connect_fut = self.connect_and_handshake(connector).fuse();
+      pin_mut!(connect_fut);
+      select! {
+        result = connect_fut => return result,
+        _ = sleep(timeout).fuse() => {},
+      }
+    }
+    error!("Connection to server timed out after {:?}.", timeout);
+    self.connected.store(false, Ordering::SeqCst);
+    // If the event loop is already running, it owns the connector, so have it shut down. If it
+    // isn't, the connector was dropped with the connect future and there's no one to hear this.
+    let fut = ButtplugConnectorFuture::default();
+    let _ = self
+      .message_sender
+      .send_message_to_event_loop(ButtplugClientRequest::Disconnect(fut.get_state_clone()))
+      .await;
+    Err(ButtplugClientError::ConnectionTimeout(timeout))
+  }
+
+  async fn connect_and_handshake<ConnectorType>(
     &self,
     mut connector: ConnectorType,
   ) -> Result<(), ButtplugClientError>
   where
-    ConnectorType: ButtplugConnector<ButtplugClientMessageV3, ButtplugServerMessageV3> + 'static,
+    ConnectorType:
+      ButtplugConnector<ButtplugClientMessageCurrent, ButtplugServerMessageCurrent> + 'static,
   {
     if self.connected() {
       return Err(ButtplugClientError::ButtplugConnectorError(
@@ -303,6 +523,7 @@ impl ButtplugClient {
       ButtplugClientError::from(e)
     })?;
     info!("Connection to server succeeded.");
+    self.scanning.store(false, Ordering::SeqCst);
     let mut client_event_loop = ButtplugClientEventLoop::new(
       self.connected.clone(),
       connector,
@@ -310,6 +531,8 @@ impl ButtplugClient {
       self.event_stream.clone(),
       self.message_sender.clone(),
       self.device_map.clone(),
+      self.scanning.clone(),
+      self.message_recorder.clone(),
     );
 
     // Start the event loop before we run the handshake.
@@ -322,6 +545,40 @@ impl ButtplugClient {
     self.run_handshake().await
   }
 
+  /// Connects to a server again after a disconnect, using a new connector.
+  ///
+  /// If the client is still connected, this disconnects first and waits for the old connection to
+  /// shut down. Anything still waiting on a reply from the old connection resolves with
+  /// [ButtplugConnectorError::ConnectorNotConnected], and devices from the old connection are
+  /// dropped. The handshake then requests the server's device list again, so devices that are
+  /// still connected to the server will be emitted as [ButtplugClientEvent::DeviceAdded] events.
+  /// Device handles from before the reconnect should be dropped in favor of the new ones, as device
+  /// indexes are not guaranteed to match across connections.
+  pub async fn reconnect<ConnectorType>(
+    &self,
+    connector: ConnectorType,
+  ) -> Result<(), ButtplugClientError>
+  where
+    ConnectorType:
+      ButtplugConnector<ButtplugClientMessageCurrent, ButtplugServerMessageCurrent> + 'static,
+  {
+    if self.connected() {
+      // Subscribe before disconnecting so we can't miss the event loop shutting down. The event
+      // loop only emits ServerDisconnect once it has stopped touching client state.
+      let mut events = self.event_stream.subscribe();
+      self.disconnect().await?;
+      loop {
+        match events.recv().await {
+          Ok(ButtplugClientEvent::ServerDisconnect) | Err(broadcast::error::RecvError::Closed) => {
+            break
+          }
+          _ => continue,
+        }
+      }
+    }
+    self.connect(connector).await
+  }
+
   /// Creates the ButtplugClient instance and tries to establish a connection.
   ///
   /// Takes all of the components needed to build a [ButtplugClient], creates
@@ -339,7 +596,7 @@ impl ButtplugClient {
       .await?;
 
     debug!("Got ServerInfo return.");
-    if let ButtplugServerMessageV3::ServerInfo(server_info) = msg {
+    if let ButtplugServerMessageCurrent::ServerInfo(server_info) = msg {
       info!("Connected to {}", server_info.server_name());
       *self.server_name.lock().await = Some(server_info.server_name().clone());
       // Don't set ourselves as connected until after ServerInfo has been
@@ -354,7 +611,7 @@ impl ButtplugClient {
         .message_sender
         .send_message(RequestDeviceListV0::default().into())
         .await?;
-      if let ButtplugServerMessageV3::DeviceList(m) = msg {
+      if let ButtplugServerMessageCurrent::DeviceList(m) = msg {
         self
           .message_sender
           .send_message_to_event_loop(ButtplugClientRequest::HandleDeviceList(m))
@@ -402,9 +659,16 @@ impl ButtplugClient {
   /// Returns Err([ButtplugClientError]) if request fails due to issues with
   /// DeviceManagers on the server, disconnection, etc.
   pub fn start_scanning(&self) -> ButtplugClientResultFuture {
-    self
+    let send_fut = self
       .message_sender
-      .send_message_expect_ok(StartScanningV0::default().into())
+      .send_message_expect_ok(StartScanningV0::default().into());
+    let scanning = self.scanning.clone();
+    async move {
+      send_fut.await?;
+      scanning.store(true, Ordering::SeqCst);
+      Ok(())
+    }
+    .boxed()
   }
 
   /// Tells server to stop scanning for devices.
@@ -412,9 +676,99 @@ impl ButtplugClient {
   /// Returns Err([ButtplugClientError]) if request fails due to issues with
   /// DeviceManagers on the server, disconnection, etc.
   pub fn stop_scanning(&self) -> ButtplugClientResultFuture {
-    self
+    let send_fut = self
       .message_sender
-      .send_message_expect_ok(StopScanningV0::default().into())
+      .send_message_expect_ok(StopScanningV0::default().into());
+    let scanning = self.scanning.clone();
+    async move {
+      send_fut.await?;
+      scanning.store(false, Ordering::SeqCst);
+      Ok(())
+    }
+    .boxed()
+  }
+
+  /// Scans for devices for `duration`, then stops scanning and returns the devices that were added
+  /// during the scan.
+  ///
+  /// Waits for the server to report that the scan has finished before resolving, so streams from
+  /// [ButtplugClient::event_stream()] won't see a late
+  /// [ButtplugClientEvent::ScanningFinished] for this scan afterward. If that report doesn't come
+  /// within [SCAN_FOR_FINISHED_TIMEOUT] of stopping the scan, the devices found so far are returned
+  /// anyway.
+  ///
+  /// Returns Err([ButtplugDeviceError::DeviceScanningAlreadyStarted]) if a scan is already in
+  /// progress, instead of starting another one.
+  pub fn scan_for(
+    &self,
+    duration: Duration,
+  ) -> ButtplugClientResultFuture<Vec<Arc<ButtplugClientDevice>>> {
+    // Subscribe before scanning starts, so we can't miss any events from this scan.
+    let mut event_stream = self.event_stream();
+    let message_sender = self.message_sender.clone();
+    let scanning = self.scanning.clone();
+    async move {
+      // Claim the scan before sending anything, so two calls racing each other can't both start
+      // one. This happens when the future is first polled, so a future that never runs doesn't
+      // hold the claim.
+      if scanning
+        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+      {
+        return Err(ButtplugError::from(ButtplugDeviceError::DeviceScanningAlreadyStarted).into());
+      }
+      let mut guard = ScanForGuard {
+        scanning: scanning.clone(),
+        message_sender: message_sender.clone(),
+        started: false,
+        finished: false,
+      };
+      message_sender
+        .send_message_expect_ok(StartScanningV0::default().into())
+        .await?;
+      guard.started = true;
+      let mut devices = vec![];
+      let mut stop_fut =
+        Some(message_sender.send_message_expect_ok(StopScanningV0::default().into()));
+      let mut scanning_finished = false;
+      let timeout = sleep(duration).fuse();
+      pin_mut!(timeout);
+      loop {
+        select! {
+          event = event_stream.next().fuse() => match event {
+            Some(ButtplugClientEvent::DeviceAdded(device)) => devices.push(device),
+            Some(ButtplugClientEvent::ScanningFinished) => {
+              scanning_finished = true;
+              if stop_fut.is_none() {
+                break;
+              }
+            }
+            Some(_) => {}
+            None => return Err(ButtplugConnectorError::ConnectorNotConnected.into()),
+          },
+          _ = timeout => match stop_fut.take() {
+            // Keep collecting after stopping until the server reports the scan as finished, since
+            // devices found while scanning winds down may still be on their way.
+            Some(stop_fut) => {
+              stop_fut.await?;
+              scanning.store(false, Ordering::SeqCst);
+              // The scan is stopped and released, so it may already belong to someone else.
+              guard.finished = true;
+              if scanning_finished {
+                break;
+              }
+              timeout.set(sleep(SCAN_FOR_FINISHED_TIMEOUT).fuse());
+            }
+            None => {
+              warn!("Server did not report scanning finished after scan_for stopped scanning.");
+              break;
+            }
+          }
+        }
+      }
+      Ok(devices)
+    }
+    .boxed()
   }
 
   /// Tells server to stop all devices.
@@ -427,8 +781,14 @@ impl ButtplugClient {
       .send_message_expect_ok(StopAllDevicesV0::default().into())
   }
 
+  /// Returns a stream of client events.
+  ///
+  /// Each call creates an independent subscriber that receives every event emitted after the call,
+  /// so multiple streams can be active at once. A subscriber that falls more than the event channel
+  /// capacity (see [ButtplugClientBuilder::event_channel_capacity()]) behind loses the oldest
+  /// events, with a warning logged, but keeps receiving new ones.
   pub fn event_stream(&self) -> impl Stream<Item = ButtplugClientEvent> {
-    let stream = convert_broadcast_receiver_to_stream(self.event_stream.subscribe());
+    let stream = convert_broadcast_receiver_to_lossy_stream(self.event_stream.subscribe());
     // We can either Box::pin here or force the user to pin_mut!() on their
     // end. While this does end up with a dynamic dispatch on our end, it
     // still makes the API nicer for the user, so we'll just eat the perf hit.
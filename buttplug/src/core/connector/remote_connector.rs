@@ -13,13 +13,11 @@ use super::{
   ButtplugConnectorError,
   ButtplugConnectorResultFuture,
 };
+#[cfg(feature = "serialize-json")]
+use crate::core::message::serializer::ButtplugClientJSONSerializer;
 use crate::{
   core::message::{
-    serializer::{
-      ButtplugClientJSONSerializer,
-      ButtplugMessageSerializer,
-      ButtplugSerializedMessage,
-    },
+    serializer::{ButtplugMessageSerializer, ButtplugSerializedMessage},
     ButtplugClientMessageCurrent,
     ButtplugClientMessageVariant,
     ButtplugMessage,
@@ -165,6 +163,7 @@ async fn remote_connector_event_loop<
   }
 }
 
+#[cfg(feature = "serialize-json")]
 pub type ButtplugRemoteClientConnector<
   TransportType,
   SerializerType = ButtplugClientJSONSerializer,
@@ -175,6 +174,15 @@ pub type ButtplugRemoteClientConnector<
   ButtplugServerMessageCurrent,
 >;
 
+/// Without JSON there's no default serializer, so one always has to be given.
+#[cfg(not(feature = "serialize-json"))]
+pub type ButtplugRemoteClientConnector<TransportType, SerializerType> = ButtplugRemoteConnector<
+  TransportType,
+  SerializerType,
+  ButtplugClientMessageCurrent,
+  ButtplugServerMessageCurrent,
+>;
+
 pub type ButtplugRemoteServerConnector<TransportType, SerializerType> = ButtplugRemoteConnector<
   TransportType,
   SerializerType,
@@ -23,6 +23,7 @@ use crate::server::device::hardware::communication::HardwareSpecificError;
 use displaydoc::Display;
 use futures::future::BoxFuture;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use thiserror::Error;
 
 pub type ButtplugResult<T = ()> = Result<T, ButtplugError>;
@@ -41,7 +42,7 @@ where
 }
 
 #[derive(Debug, Error, Display, Clone, PartialEq, Eq)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub enum ButtplugHandshakeError {
   /// Expected either a ServerInfo or Error message, received {0}
   UnexpectedHandshakeMessageReceived(String),
@@ -67,12 +68,16 @@ where
 }
 
 #[derive(Debug, Error, Display, Clone, PartialEq, Eq)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub enum ButtplugMessageError {
   /// Got unexpected message type: {0}
   UnexpectedMessageType(String),
-  /// {0} {1} cannot be converted to {2}
-  VersionError(String, String, String),
+  /// {from_type} {message_name} cannot be converted to message spec {to_type}
+  VersionError {
+    from_type: String,
+    message_name: String,
+    to_type: ButtplugMessageSpecVersion,
+  },
   /// Message conversion error: {0}
   MessageConversionError(String),
   /// Invalid message contents: {0}
@@ -81,6 +86,12 @@ pub enum ButtplugMessageError {
   UnhandledMessage(String),
   /// Message validation error(s): {0}
   ValidationError(String),
+  /// {cmd_type} subcommand index {sent_index} is out of range, max index is {max_index}
+  InvalidSubcommandIndex {
+    cmd_type: ButtplugDeviceMessageType,
+    sent_index: u32,
+    max_index: u32,
+  },
   /// Message serialization error
   #[error(transparent)]
   MessageSerializationError(#[from] ButtplugSerializerError),
@@ -101,7 +112,7 @@ where
 }
 
 #[derive(Debug, Error, Display, Clone, PartialEq, Eq)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub enum ButtplugPingError {
   /// Pinged timer exhausted, system has shut down.
   PingedOut,
@@ -125,7 +136,7 @@ where
   }
 }
 #[derive(Debug, Error, Display, Clone, PartialEq, Eq)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub enum ButtplugDeviceError {
   /// Device {0} not connected
   DeviceNotConnected(String),
@@ -145,6 +156,18 @@ pub enum ButtplugDeviceError {
   InvalidEndpoint(Endpoint),
   /// Device does not handle command type: {0}
   UnhandledCommand(String),
+  /// Device communication timed out after {0:?}
+  CommunicationTimeout(Duration),
+  /// Device hardware has already disconnected
+  HardwareDisconnected,
+  /// Device busy: device {0} is still processing a previous command
+  DeviceBusy(u32),
+  /// Rate limit exceeded for device {0}
+  RateLimitExceeded(u32),
+  /// Command sent before the device message gap of {0:?} elapsed
+  CommandThrottled(Duration),
+  /// Raw messages are denied for this device
+  RawMessagesDenied,
   #[cfg(feature = "server")]
   #[error(transparent)]
   /// Device type specific error: {0}.
@@ -194,7 +217,7 @@ where
 }
 
 #[derive(Debug, Error, Display, Clone, PartialEq, Eq)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub enum ButtplugUnknownError {
   /// Cannot start scanning, no device communication managers available to use for scanning.
   NoDeviceCommManagers,
@@ -208,7 +231,7 @@ pub enum ButtplugUnknownError {
 
 /// Aggregation enum for protocol error types.
 #[derive(Debug, Error, Clone, PartialEq, Eq)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub enum ButtplugError {
   #[error(transparent)]
   ButtplugHandshakeError(#[from] ButtplugHandshakeError),
@@ -6,16 +6,16 @@
 // for full license information.
 
 use super::*;
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde::{Deserialize, Serialize};
 
 /// Battery level request
 #[derive(Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Eq, Clone)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct BatteryLevelCmdV2 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   device_index: u32,
 }
 
@@ -7,18 +7,18 @@
 
 use super::*;
 use getset::CopyGetters;
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde::{Deserialize, Serialize};
 
 /// Battery level response
 #[derive(Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Clone, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct BatteryLevelReadingV2 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "BatteryLevel"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "BatteryLevel"))]
   #[getset(get_copy = "pub")]
   battery_level: f64,
 }
@@ -36,9 +36,6 @@ impl BatteryLevelReadingV2 {
 impl ButtplugMessageValidator for BatteryLevelReadingV2 {
   fn is_valid(&self) -> Result<(), ButtplugMessageError> {
     self.is_not_system_id(self.id)?;
-    self.is_in_command_range(
-      self.battery_level,
-      "BatteryLevelReading must be between 0.0 and 1.0".to_string(),
-    )
+    self.is_in_command_range_named("BatteryLevelReading BatteryLevel", self.battery_level)
   }
 }
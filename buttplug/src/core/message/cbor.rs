@@ -0,0 +1,360 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2024 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Integer map keys for CBOR encoded messages
+//!
+//! CBOR messages use the same serde derives as JSON, but to save space on the wire, map keys (field
+//! names, and the message names used as tags in message enums) are swapped for the integer
+//! constants in this module. CBOR encodes integers below 24 in a single byte, so the most common
+//! fields get the lowest keys.
+//!
+//! Keys without a constant, like fields added to the spec after this table was written, are sent as
+//! their string names. Integer keys that aren't in the table are passed through untouched when
+//! decoding. No message has integer keyed maps of its own, so the two can't be confused.
+//!
+//! These values are part of the wire format. Existing keys must never be renumbered, new keys get
+//! the next unused value.
+
+use ciborium::{de, ser, Value};
+use serde::{de::DeserializeOwned, Serialize};
+
+macro_rules! cbor_keys {
+  ($($name:ident = $value:literal => $field:literal,)*) => {
+    $(
+      #[doc = concat!("CBOR key for `", $field, "`.")]
+      pub const $name: u32 = $value;
+    )*
+
+    const KEYS: &[(u32, &str)] = &[$(($name, $field)),*];
+  };
+}
+
+cbor_keys! {
+  // Fields common to most messages
+  ID = 0 => "Id",
+  DEVICE_INDEX = 1 => "DeviceIndex",
+  INDEX = 2 => "Index",
+  SCALAR = 3 => "Scalar",
+  ACTUATOR_TYPE = 4 => "ActuatorType",
+  SPEED = 5 => "Speed",
+  POSITION = 6 => "Position",
+  DURATION = 7 => "Duration",
+  CLOCKWISE = 8 => "Clockwise",
+  SENSOR_INDEX = 9 => "SensorIndex",
+  SENSOR_TYPE = 10 => "SensorType",
+  DATA = 11 => "Data",
+  ENDPOINT = 12 => "Endpoint",
+  SCALARS = 13 => "Scalars",
+  VECTORS = 14 => "Vectors",
+  ROTATIONS = 15 => "Rotations",
+  SPEEDS = 16 => "Speeds",
+  FEATURE_INDEX = 17 => "FeatureIndex",
+  // Device enumeration fields
+  DEVICE_NAME = 18 => "DeviceName",
+  DEVICE_DISPLAY_NAME = 19 => "DeviceDisplayName",
+  DEVICE_MESSAGE_TIMING_GAP = 20 => "DeviceMessageTimingGap",
+  DEVICE_MESSAGES = 21 => "DeviceMessages",
+  DEVICES = 22 => "Devices",
+  DEVICE_FEATURES = 23 => "DeviceFeatures",
+  FEATURE_DESCRIPTOR = 24 => "FeatureDescriptor",
+  STEP_COUNT = 25 => "StepCount",
+  FEATURE_COUNT = 26 => "FeatureCount",
+  SENSOR_RANGE = 27 => "SensorRange",
+  ENDPOINTS = 28 => "Endpoints",
+  MESSAGES = 29 => "Messages",
+  DESCRIPTION = 30 => "description",
+  FEATURE_TYPE = 31 => "feature-type",
+  ACTUATOR = 32 => "actuator",
+  SENSOR = 33 => "sensor",
+  STEP_RANGE = 34 => "step-range",
+  STEP_LIMIT = 35 => "step-limit",
+  VALUE_RANGE = 36 => "value-range",
+  FEATURE_MESSAGES = 37 => "messages",
+  // Handshake, status and log fields
+  CLIENT_NAME = 38 => "ClientName",
+  MESSAGE_VERSION = 39 => "MessageVersion",
+  SERVER_NAME = 40 => "ServerName",
+  MAJOR_VERSION = 41 => "MajorVersion",
+  MINOR_VERSION = 42 => "MinorVersion",
+  BUILD_VERSION = 43 => "BuildVersion",
+  MAX_PING_TIME = 44 => "MaxPingTime",
+  ERROR_CODE = 45 => "ErrorCode",
+  ERROR_MESSAGE = 46 => "ErrorMessage",
+  LOG_LEVEL = 47 => "LogLevel",
+  LOG_MESSAGE = 48 => "LogMessage",
+  TEST_STRING = 49 => "TestString",
+  // Raw and legacy device command fields
+  WRITE_WITH_RESPONSE = 50 => "WriteWithResponse",
+  EXPECTED_LENGTH = 51 => "ExpectedLength",
+  TIMEOUT = 52 => "Timeout",
+  COMMAND = 53 => "Command",
+  BATTERY_LEVEL = 54 => "BatteryLevel",
+  RSSI_LEVEL = 55 => "RSSILevel",
+  // Message names. Device command names double as the keys of device message attributes.
+  OK = 56 => "Ok",
+  ERROR = 57 => "Error",
+  PING = 58 => "Ping",
+  REQUEST_SERVER_INFO = 59 => "RequestServerInfo",
+  SERVER_INFO = 60 => "ServerInfo",
+  START_SCANNING = 61 => "StartScanning",
+  STOP_SCANNING = 62 => "StopScanning",
+  SCANNING_FINISHED = 63 => "ScanningFinished",
+  REQUEST_DEVICE_LIST = 64 => "RequestDeviceList",
+  DEVICE_LIST = 65 => "DeviceList",
+  DEVICE_ADDED = 66 => "DeviceAdded",
+  DEVICE_REMOVED = 67 => "DeviceRemoved",
+  STOP_DEVICE_CMD = 68 => "StopDeviceCmd",
+  STOP_ALL_DEVICES = 69 => "StopAllDevices",
+  SCALAR_CMD = 70 => "ScalarCmd",
+  LINEAR_CMD = 71 => "LinearCmd",
+  ROTATE_CMD = 72 => "RotateCmd",
+  VIBRATE_CMD = 73 => "VibrateCmd",
+  SENSOR_READ_CMD = 74 => "SensorReadCmd",
+  SENSOR_SUBSCRIBE_CMD = 75 => "SensorSubscribeCmd",
+  SENSOR_UNSUBSCRIBE_CMD = 76 => "SensorUnsubscribeCmd",
+  SENSOR_READING = 77 => "SensorReading",
+  RAW_WRITE_CMD = 78 => "RawWriteCmd",
+  RAW_READ_CMD = 79 => "RawReadCmd",
+  RAW_SUBSCRIBE_CMD = 80 => "RawSubscribeCmd",
+  RAW_UNSUBSCRIBE_CMD = 81 => "RawUnsubscribeCmd",
+  RAW_READING = 82 => "RawReading",
+  BATTERY_LEVEL_CMD = 83 => "BatteryLevelCmd",
+  BATTERY_LEVEL_READING = 84 => "BatteryLevelReading",
+  RSSI_LEVEL_CMD = 85 => "RSSILevelCmd",
+  RSSI_LEVEL_READING = 86 => "RSSILevelReading",
+  REQUEST_LOG = 87 => "RequestLog",
+  LOG = 88 => "Log",
+  TEST = 89 => "Test",
+  SINGLE_MOTOR_VIBRATE_CMD = 90 => "SingleMotorVibrateCmd",
+  FLESHLIGHT_LAUNCH_FW12_CMD = 91 => "FleshlightLaunchFW12Cmd",
+  LOVENSE_CMD = 92 => "LovenseCmd",
+  KIIROO_CMD = 93 => "KiirooCmd",
+  VORZE_A10_CYCLONE_CMD = 94 => "VorzeA10CycloneCmd",
+}
+
+fn encode_key(key: Value) -> Value {
+  match key {
+    Value::Text(name) => match KEYS.iter().find(|(_, field)| *field == name) {
+      Some((value, _)) => Value::Integer((*value).into()),
+      None => Value::Text(name),
+    },
+    other => other,
+  }
+}
+
+fn decode_key(key: Value) -> Value {
+  let name = key
+    .as_integer()
+    .and_then(|value| u32::try_from(value).ok())
+    .and_then(|value| KEYS.iter().find(|(key, _)| *key == value));
+  match name {
+    Some((_, field)) => Value::Text(field.to_string()),
+    None => key,
+  }
+}
+
+/// Swaps every map key in `value` using `swap`, at all depths.
+fn swap_keys(value: Value, swap: fn(Value) -> Value) -> Value {
+  match value {
+    Value::Map(entries) => Value::Map(
+      entries
+        .into_iter()
+        .map(|(key, value)| (swap(key), swap_keys(value, swap)))
+        .collect(),
+    ),
+    Value::Array(values) => Value::Array(
+      values
+        .into_iter()
+        .map(|value| swap_keys(value, swap))
+        .collect(),
+    ),
+    Value::Tag(tag, value) => Value::Tag(tag, Box::new(swap_keys(*value, swap))),
+    other => other,
+  }
+}
+
+pub(super) fn to_cbor<T>(msg: &T) -> Result<Vec<u8>, ser::Error<std::io::Error>>
+where
+  T: Serialize + ?Sized,
+{
+  let value = Value::serialized(msg).map_err(|e| ser::Error::Value(e.to_string()))?;
+  let mut bytes = vec![];
+  ciborium::into_writer(&swap_keys(value, encode_key), &mut bytes)?;
+  Ok(bytes)
+}
+
+pub(super) fn from_cbor<T>(bytes: &[u8]) -> Result<T, de::Error<std::io::Error>>
+where
+  T: DeserializeOwned,
+{
+  let value: Value = ciborium::from_reader(bytes)?;
+  swap_keys(value, decode_key)
+    .deserialized()
+    .map_err(|e| de::Error::Semantic(None, e.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::core::message::{
+    self,
+    ActuatorType,
+    ButtplugActuatorFeatureMessageType,
+    ButtplugClientMessageV3,
+    ButtplugSensorFeatureMessageType,
+    ButtplugServerMessageV3,
+    ButtplugServerMessageV4,
+    ClientDeviceMessageAttributesV3Builder,
+    ClientGenericDeviceMessageAttributesV3,
+    DeviceFeature,
+    DeviceFeatureActuator,
+    DeviceFeatureSensor,
+    FeatureType,
+    SensorDeviceMessageAttributesV3,
+    SensorType,
+  };
+  use std::collections::HashSet;
+
+  fn text_keys(value: &Value, keys: &mut Vec<String>) {
+    match value {
+      Value::Map(entries) => {
+        for (key, value) in entries {
+          if let Value::Text(name) = key {
+            keys.push(name.clone());
+          }
+          text_keys(value, keys);
+        }
+      }
+      Value::Array(values) => values.iter().for_each(|value| text_keys(value, keys)),
+      _ => {}
+    }
+  }
+
+  #[test]
+  fn test_cbor_keys_are_unique() {
+    let values: HashSet<u32> = KEYS.iter().map(|(value, _)| *value).collect();
+    let fields: HashSet<&str> = KEYS.iter().map(|(_, field)| *field).collect();
+    assert_eq!(values.len(), KEYS.len());
+    assert_eq!(fields.len(), KEYS.len());
+  }
+
+  #[test]
+  fn test_cbor_uses_integer_keys() {
+    assert_eq!(
+      to_cbor(&ButtplugServerMessageV3::from(message::OkV0::new(1))).expect("Test"),
+      // {56: {0: 1}}
+      vec![0xA1, 0x18, OK as u8, 0xA1, ID as u8, 0x01]
+    );
+    let mut builder = ClientDeviceMessageAttributesV3Builder::default();
+    builder.scalar_cmd(&[ClientGenericDeviceMessageAttributesV3::new(
+      "Vibrator",
+      20,
+      ActuatorType::Vibrate,
+    )]);
+    builder.sensor_read_cmd(&[SensorDeviceMessageAttributesV3::new(
+      "Battery",
+      SensorType::Battery,
+      &[0..=100],
+    )]);
+    let attrs = builder.finish().expect("Test");
+    let device_info = message::DeviceMessageInfoV3::new(
+      0,
+      "Test Device",
+      &Some("Display".to_owned()),
+      &Some(10),
+      attrs.clone(),
+    );
+    let client_msgs: Vec<ButtplugClientMessageV3> = vec![
+      message::RequestServerInfoV1::new(
+        "Test Client",
+        message::ButtplugMessageSpecVersion::Version3,
+      )
+      .into(),
+      message::ScalarCmdV3::new(
+        0,
+        vec![message::ScalarSubcommandV3::new(
+          0,
+          0.5,
+          ActuatorType::Vibrate,
+        )],
+      )
+      .into(),
+      message::LinearCmdV1::new(0, vec![message::VectorSubcommandV1::new(0, 100, 0.5)]).into(),
+      message::RotateCmdV1::new(0, vec![message::RotationSubcommandV1::new(0, 0.5, true)]).into(),
+      message::RawWriteCmdV2::new(0, message::Endpoint::Tx, &[0x0], false).into(),
+      message::RawReadCmdV2::new(0, message::Endpoint::Rx, 4, 100).into(),
+      message::SensorReadCmdV3::new(0, 0, SensorType::Battery).into(),
+    ];
+    let server_msgs: Vec<ButtplugServerMessageV3> = vec![
+      message::ErrorV0::new(message::ErrorCode::ErrorDevice, "Test Error", None).into(),
+      message::ServerInfoV2::new(
+        "Test Server",
+        message::ButtplugMessageSpecVersion::Version3,
+        0,
+      )
+      .into(),
+      message::DeviceListV3::new(vec![device_info.clone()]).into(),
+      message::DeviceAddedV3::new(
+        0,
+        "Test Device",
+        &Some("Display".to_owned()),
+        &Some(10),
+        &attrs,
+      )
+      .into(),
+      message::SensorReadingV3::new(0, 0, SensorType::Battery, vec![50]).into(),
+    ];
+    let features = vec![
+      DeviceFeature::new(
+        "Vibrator",
+        FeatureType::Vibrate,
+        &Some(DeviceFeatureActuator::new(
+          &(0..=20),
+          &(0..=20),
+          &HashSet::from([ButtplugActuatorFeatureMessageType::ScalarCmd]),
+        )),
+        &None,
+      ),
+      DeviceFeature::new(
+        "Battery",
+        FeatureType::Battery,
+        &None,
+        &Some(DeviceFeatureSensor::new(
+          &vec![0..=100],
+          &HashSet::from([ButtplugSensorFeatureMessageType::SensorReadCmd]),
+        )),
+      ),
+    ];
+    let v4_msgs: Vec<ButtplugServerMessageV4> =
+      vec![message::DeviceAddedV4::new(0, "Test Device", &None, &None, &features).into()];
+    let mut values: Vec<Value> = vec![];
+    for msg in client_msgs {
+      values.push(ciborium::from_reader(&to_cbor(&msg).expect("Test")[..]).expect("Test"));
+    }
+    for msg in server_msgs {
+      values.push(ciborium::from_reader(&to_cbor(&msg).expect("Test")[..]).expect("Test"));
+    }
+    for msg in v4_msgs {
+      values.push(ciborium::from_reader(&to_cbor(&msg).expect("Test")[..]).expect("Test"));
+    }
+    let mut keys = vec![];
+    values.iter().for_each(|value| text_keys(value, &mut keys));
+    assert!(keys.is_empty(), "Keys without a CBOR constant: {keys:?}");
+  }
+
+  #[test]
+  fn test_cbor_unknown_keys_pass_through() {
+    let value = Value::Map(vec![
+      (Value::Text("NewField".to_owned()), Value::Integer(1.into())),
+      (Value::Integer(1000.into()), Value::Integer(2.into())),
+    ]);
+    assert_eq!(
+      swap_keys(swap_keys(value.clone(), encode_key), decode_key),
+      value
+    );
+  }
+}
@@ -20,7 +20,7 @@ use super::{
   FeatureType,
 };
 
-#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, EnumIter)]
 pub enum ActuatorType {
   Unknown,
   Vibrate,
@@ -29,11 +29,35 @@ pub enum ActuatorType {
   Oscillate,
   Constrict,
   Inflate,
+  // Bidirectional grip, from fully open to fully gripped, usually air pressure chambers.
+  Squeeze,
   // For instances where we specify a position to move to ASAP. Usually servos, probably for the
   // OSR-2/SR-6.
   Position,
 }
 
+impl ActuatorType {
+  /// Returns the message type dedicated to this actuator type: [RotateCmd](super::RotateCmdV4) for
+  /// Rotate, [LinearCmd](super::LinearCmdV4) for Position, and [ScalarCmd](super::ScalarCmdV4) for
+  /// everything else.
+  ///
+  /// Rotate and Position features may still be driven through ScalarCmd instead, depending on
+  /// which messages the feature lists. See
+  /// [ButtplugActuatorFeatureMessageType::valid_actuator_types].
+  pub fn message_type(&self) -> ButtplugActuatorFeatureMessageType {
+    match self {
+      ActuatorType::Rotate => ButtplugActuatorFeatureMessageType::RotateCmd,
+      ActuatorType::Position => ButtplugActuatorFeatureMessageType::LinearCmd,
+      ActuatorType::Unknown
+      | ActuatorType::Vibrate
+      | ActuatorType::Oscillate
+      | ActuatorType::Constrict
+      | ActuatorType::Inflate
+      | ActuatorType::Squeeze => ButtplugActuatorFeatureMessageType::ScalarCmd,
+    }
+  }
+}
+
 impl TryFrom<FeatureType> for ActuatorType {
   type Error = String;
   fn try_from(value: FeatureType) -> Result<Self, Self::Error> {
@@ -44,6 +68,7 @@ impl TryFrom<FeatureType> for ActuatorType {
       FeatureType::Oscillate => Ok(ActuatorType::Oscillate),
       FeatureType::Constrict => Ok(ActuatorType::Constrict),
       FeatureType::Inflate => Ok(ActuatorType::Inflate),
+      FeatureType::Squeeze => Ok(ActuatorType::Squeeze),
       FeatureType::Position => Ok(ActuatorType::Position),
       _ => Err(format!(
         "Feature type {value} not valid for ActuatorType conversion"
@@ -52,16 +77,16 @@ impl TryFrom<FeatureType> for ActuatorType {
   }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Display)]
 pub enum SensorType {
   Unknown,
   Battery,
   RSSI,
   Button,
   Pressure,
-  // Temperature,
-  // Accelerometer,
-  // Gyro,
+  Temperature,
+  Accelerometer,
+  Gyroscope,
 }
 
 impl TryFrom<FeatureType> for SensorType {
@@ -73,6 +98,9 @@ impl TryFrom<FeatureType> for SensorType {
       FeatureType::RSSI => Ok(SensorType::RSSI),
       FeatureType::Button => Ok(SensorType::Button),
       FeatureType::Pressure => Ok(SensorType::Pressure),
+      FeatureType::Temperature => Ok(SensorType::Temperature),
+      FeatureType::Accelerometer => Ok(SensorType::Accelerometer),
+      FeatureType::Gyroscope => Ok(SensorType::Gyroscope),
       _ => Err(format!(
         "Feature type {value} not valid for SensorType conversion"
       )),
@@ -89,7 +117,7 @@ impl TryFrom<FeatureType> for SensorType {
 // then we denote this by prefixing the type with Client/Server. Server attributes will usually be
 // hosted in the server/device/configuration module.
 #[derive(Clone, Debug, Default, PartialEq, Eq, Getters, MutGetters, Setters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct ClientDeviceMessageAttributesV3 {
   // Generic commands
   #[getset(get = "pub", get_mut = "pub(super)")]
@@ -163,7 +191,14 @@ impl From<Vec<DeviceFeature>> for ClientDeviceMessageAttributesV3 {
             false
           }
         })
-        .map(|x| x.clone().try_into().unwrap())
+        // Features that can't be represented in the v3 actuator types are skipped rather than
+        // taking down the whole device conversion.
+        .filter_map(|x| {
+          x.clone()
+            .try_into()
+            .map_err(|e| warn!("Skipping feature in v3 attribute conversion: {e}"))
+            .ok()
+        })
         .collect();
       if !attrs.is_empty() {
         Some(attrs)
@@ -182,7 +217,12 @@ impl From<Vec<DeviceFeature>> for ClientDeviceMessageAttributesV3 {
             false
           }
         })
-        .map(|x| x.clone().try_into().unwrap())
+        .filter_map(|x| {
+          x.clone()
+            .try_into()
+            .map_err(|e| warn!("Skipping feature in v3 attribute conversion: {e}"))
+            .ok()
+        })
         .collect();
       if !attrs.is_empty() {
         Some(attrs)
@@ -263,12 +303,29 @@ impl ClientDeviceMessageAttributesV3 {
     }
   }
 
+  /// Assigns each attribute its message index, which is its position within its command list.
+  ///
+  /// This is deliberately not sorted on anything like the feature descriptor. When converting spec
+  /// v3 commands, the server maps subcommand index N to the Nth matching feature of the device, in
+  /// device configuration order, and the attribute lists are built in that same order. Any
+  /// reordering here would send commands to the wrong actuator. Since index is only derived from
+  /// position, calling this multiple times is harmless.
   pub fn finalize(&mut self) {
     if let Some(scalar_attrs) = &mut self.scalar_cmd {
       for (i, attr) in scalar_attrs.iter_mut().enumerate() {
         attr.index = i as u32;
       }
     }
+    if let Some(rotate_attrs) = &mut self.rotate_cmd {
+      for (i, attr) in rotate_attrs.iter_mut().enumerate() {
+        attr.index = i as u32;
+      }
+    }
+    if let Some(linear_attrs) = &mut self.linear_cmd {
+      for (i, attr) in linear_attrs.iter_mut().enumerate() {
+        attr.index = i as u32;
+      }
+    }
     if let Some(sensor_read_attrs) = &mut self.sensor_read_cmd {
       for (i, attr) in sensor_read_attrs.iter_mut().enumerate() {
         attr.index = i as u32;
@@ -328,9 +385,46 @@ impl ClientDeviceMessageAttributesV3Builder {
     self
   }
 
-  pub fn finish(&mut self) -> ClientDeviceMessageAttributesV3 {
+  /// Builds the attributes, returning an error if any generic command attributes carry an
+  /// actuator type their message can't command, as given by
+  /// [ButtplugActuatorFeatureMessageType::valid_actuator_types].
+  pub fn finish(&mut self) -> Result<ClientDeviceMessageAttributesV3, ButtplugDeviceError> {
+    Self::check_actuator_types(
+      &self.attrs.scalar_cmd,
+      ButtplugActuatorFeatureMessageType::ScalarCmd,
+    )?;
+    Self::check_actuator_types(
+      &self.attrs.rotate_cmd,
+      ButtplugActuatorFeatureMessageType::RotateCmd,
+    )?;
+    Self::check_actuator_types(
+      &self.attrs.linear_cmd,
+      ButtplugActuatorFeatureMessageType::LinearCmd,
+    )?;
     self.attrs.finalize();
-    self.attrs.clone()
+    Ok(self.attrs.clone())
+  }
+
+  fn check_actuator_types(
+    attrs: &Option<Vec<ClientGenericDeviceMessageAttributesV3>>,
+    message_type: ButtplugActuatorFeatureMessageType,
+  ) -> Result<(), ButtplugDeviceError> {
+    let valid_types = message_type.valid_actuator_types();
+    if let Some(attrs) = attrs {
+      if let Some((index, attr)) = attrs
+        .iter()
+        .enumerate()
+        .find(|(_, attr)| !valid_types.contains(attr.actuator_type()))
+      {
+        return Err(ButtplugDeviceError::DeviceConfigurationError(format!(
+          "{} attribute at index {} has mismatched actuator type {}",
+          ButtplugDeviceMessageType::from(message_type),
+          index,
+          attr.actuator_type()
+        )));
+      }
+    }
+    Ok(())
   }
 }
 
@@ -460,13 +554,20 @@ impl TryFrom<DeviceFeature> for SensorDeviceMessageAttributesV3 {
   }
 }
 
-/*
-impl SensorDeviceMessageAttributes {
-  pub fn new(feature_descriptor: &str, sensor_type: SensorType) -> Self {
-    Self { feature_descriptor: feature_descriptor.to_owned(), sensor_type }
+impl SensorDeviceMessageAttributesV3 {
+  pub fn new(
+    feature_descriptor: &str,
+    sensor_type: SensorType,
+    sensor_range: &[RangeInclusive<i32>],
+  ) -> Self {
+    Self {
+      feature_descriptor: feature_descriptor.to_owned(),
+      sensor_type,
+      sensor_range: sensor_range.to_vec(),
+      index: 0,
+    }
   }
 }
- */
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Getters, Setters)]
 pub struct ClientDeviceMessageAttributesV2 {
@@ -693,3 +794,106 @@ impl From<GenericDeviceMessageAttributesV2> for GenericDeviceMessageAttributesV1
     }
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::{
+    ActuatorType,
+    ClientDeviceMessageAttributesV3,
+    ClientDeviceMessageAttributesV3Builder,
+    ClientGenericDeviceMessageAttributesV3,
+  };
+  use crate::core::errors::ButtplugDeviceError;
+
+  fn generic_attrs(actuator_type: ActuatorType) -> ClientGenericDeviceMessageAttributesV3 {
+    ClientGenericDeviceMessageAttributesV3::new("Test", 20, actuator_type)
+  }
+
+  #[test]
+  fn test_builder_accepts_matching_actuator_types() {
+    let mut builder = ClientDeviceMessageAttributesV3Builder::default();
+    // Rotate and Position features can also be driven by ScalarCmd, as plenty of devices in the
+    // device config are.
+    builder.scalar_cmd(&[
+      generic_attrs(ActuatorType::Vibrate),
+      generic_attrs(ActuatorType::Oscillate),
+      generic_attrs(ActuatorType::Rotate),
+      generic_attrs(ActuatorType::Position),
+    ]);
+    builder.rotate_cmd(&[generic_attrs(ActuatorType::Rotate)]);
+    builder.linear_cmd(&[generic_attrs(ActuatorType::Position)]);
+    let attrs = builder.finish().expect("Test, assuming infallible.");
+    assert_eq!(attrs.scalar_cmd().as_ref().expect("Test").len(), 4);
+  }
+
+  #[test]
+  fn test_builder_rejects_mismatched_actuator_types() {
+    let mut builder = ClientDeviceMessageAttributesV3Builder::default();
+    builder.rotate_cmd(&[generic_attrs(ActuatorType::Vibrate)]);
+    assert!(matches!(
+      builder.finish(),
+      Err(ButtplugDeviceError::DeviceConfigurationError(_))
+    ));
+
+    let mut builder = ClientDeviceMessageAttributesV3Builder::default();
+    builder.linear_cmd(&[generic_attrs(ActuatorType::Rotate)]);
+    assert!(builder.finish().is_err());
+
+    let mut builder = ClientDeviceMessageAttributesV3Builder::default();
+    builder.scalar_cmd(&[
+      generic_attrs(ActuatorType::Vibrate),
+      generic_attrs(ActuatorType::Unknown),
+    ]);
+    assert!(builder.finish().is_err());
+  }
+
+  fn scalar_indexes(attrs: &ClientDeviceMessageAttributesV3) -> Vec<(String, u32)> {
+    attrs
+      .scalar_cmd()
+      .as_ref()
+      .expect("Test, assuming infallible.")
+      .iter()
+      .map(|x| (x.feature_descriptor().clone(), *x.index()))
+      .collect()
+  }
+
+  #[test]
+  fn test_finalize_is_idempotent() {
+    let mut builder = ClientDeviceMessageAttributesV3Builder::default();
+    builder.scalar_cmd(&[
+      ClientGenericDeviceMessageAttributesV3::new("B", 20, ActuatorType::Vibrate),
+      ClientGenericDeviceMessageAttributesV3::new("A", 20, ActuatorType::Vibrate),
+    ]);
+    builder.rotate_cmd(&[generic_attrs(ActuatorType::Rotate)]);
+    let mut attrs = builder.finish().expect("Test, assuming infallible.");
+    let first = attrs.clone();
+    attrs.finalize();
+    assert_eq!(attrs, first);
+    assert_eq!(
+      scalar_indexes(&attrs),
+      vec![("B".to_owned(), 0), ("A".to_owned(), 1)]
+    );
+  }
+
+  #[test]
+  fn test_finalize_indexes_follow_list_order() {
+    // Index has to match the server's feature order, so reordering the list reorders the indexes
+    // with it instead of each descriptor keeping a fixed index.
+    let a = ClientGenericDeviceMessageAttributesV3::new("A", 20, ActuatorType::Vibrate);
+    let b = ClientGenericDeviceMessageAttributesV3::new("B", 20, ActuatorType::Vibrate);
+    let mut builder = ClientDeviceMessageAttributesV3Builder::default();
+    builder.scalar_cmd(&[a.clone(), b.clone()]);
+    let forward = builder.finish().expect("Test, assuming infallible.");
+    let mut builder = ClientDeviceMessageAttributesV3Builder::default();
+    builder.scalar_cmd(&[b, a]);
+    let reversed = builder.finish().expect("Test, assuming infallible.");
+    assert_eq!(
+      scalar_indexes(&forward),
+      vec![("A".to_owned(), 0), ("B".to_owned(), 1)]
+    );
+    assert_eq!(
+      scalar_indexes(&reversed),
+      vec![("B".to_owned(), 0), ("A".to_owned(), 1)]
+    );
+  }
+}
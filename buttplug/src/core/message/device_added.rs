@@ -10,31 +10,31 @@ use super::*;
 
 use getset::{CopyGetters, Getters};
 
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde::{Deserialize, Serialize};
 
 /// Notification that a device has been found and connected to the server.
 #[derive(ButtplugMessage, Clone, Debug, PartialEq, Eq, Getters, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct DeviceAddedV4 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
   // DeviceAdded is not considered a device message because it only notifies of existence and is not
   // a command (and goes from server to client), therefore we have to define the getter ourselves.
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   #[getset(get_copy = "pub")]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceName"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceName"))]
   #[getset(get = "pub")]
   device_name: String,
   #[cfg_attr(
-    feature = "serialize-json",
+    feature = "message-serde",
     serde(rename = "DeviceDisplayName", skip_serializing_if = "Option::is_none")
   )]
   #[getset(get = "pub")]
   device_display_name: Option<String>,
   #[cfg_attr(
-    feature = "serialize-json",
+    feature = "message-serde",
     serde(
       rename = "DeviceMessageTimingGap",
       skip_serializing_if = "Option::is_none"
@@ -42,7 +42,7 @@ pub struct DeviceAddedV4 {
   )]
   #[getset(get = "pub")]
   device_message_timing_gap: Option<u32>,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceFeatures"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceFeatures"))]
   #[getset(get = "pub")]
   device_features: Vec<DeviceFeature>,
 }
@@ -85,7 +85,7 @@ impl From<DeviceAddedV4> for DeviceAddedV3 {
       value.device_index(),
       &value.device_name(),
       &value.device_display_name(),
-      &None,
+      value.device_message_timing_gap(),
       &value.device_features().clone().into(),
     );
     da3.set_id(value.id);
@@ -95,26 +95,26 @@ impl From<DeviceAddedV4> for DeviceAddedV3 {
 
 /// Notification that a device has been found and connected to the server.
 #[derive(ButtplugMessage, Clone, Debug, PartialEq, Eq, Getters, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct DeviceAddedV3 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
   // DeviceAdded is not considered a device message because it only notifies of existence and is not
   // a command (and goes from server to client), therefore we have to define the getter ourselves.
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   #[getset(get_copy = "pub")]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceName"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceName"))]
   #[getset(get = "pub")]
   device_name: String,
   #[cfg_attr(
-    feature = "serialize-json",
+    feature = "message-serde",
     serde(rename = "DeviceDisplayName", skip_serializing_if = "Option::is_none")
   )]
   #[getset(get = "pub")]
   device_display_name: Option<String>,
   #[cfg_attr(
-    feature = "serialize-json",
+    feature = "message-serde",
     serde(
       rename = "DeviceMessageTimingGap",
       skip_serializing_if = "Option::is_none"
@@ -122,7 +122,7 @@ pub struct DeviceAddedV3 {
   )]
   #[getset(get = "pub")]
   device_message_timing_gap: Option<u32>,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceMessages"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceMessages"))]
   #[getset(get = "pub")]
   device_messages: ClientDeviceMessageAttributesV3,
 }
@@ -161,17 +161,17 @@ impl ButtplugMessageFinalizer for DeviceAddedV3 {
 }
 
 #[derive(ButtplugMessage, Clone, Debug, PartialEq, Eq, Getters, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct DeviceAddedV2 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   #[getset(get_copy = "pub")]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceName"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceName"))]
   #[getset(get = "pub")]
   device_name: String,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceMessages"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceMessages"))]
   #[getset(get = "pub")]
   device_messages: ClientDeviceMessageAttributesV2,
 }
@@ -201,17 +201,17 @@ impl ButtplugMessageFinalizer for DeviceAddedV2 {
 }
 
 #[derive(ButtplugMessage, Clone, Debug, PartialEq, Eq, Getters, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct DeviceAddedV1 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   #[getset(get_copy = "pub")]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceName"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceName"))]
   #[getset(get = "pub")]
   device_name: String,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceMessages"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceMessages"))]
   #[getset(get = "pub")]
   device_messages: ClientDeviceMessageAttributesV1,
 }
@@ -241,17 +241,17 @@ impl ButtplugMessageFinalizer for DeviceAddedV1 {
 }
 
 #[derive(Default, ButtplugMessage, Clone, Debug, PartialEq, Eq, Getters, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct DeviceAddedV0 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   #[getset(get_copy = "pub")]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceName"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceName"))]
   #[getset(get = "pub")]
   device_name: String,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceMessages"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceMessages"))]
   #[getset(get = "pub")]
   device_messages: Vec<ButtplugDeviceMessageType>,
 }
@@ -281,3 +281,23 @@ impl ButtplugMessageFinalizer for DeviceAddedV0 {
 }
 
 // TODO Test repeated message type in attributes in JSON
+
+#[cfg(test)]
+mod test {
+  use super::{DeviceAddedV3, DeviceAddedV4};
+
+  #[test]
+  fn test_device_added_v4_to_v3_keeps_display_info() {
+    let da4 = DeviceAddedV4::new(
+      1,
+      "LVS-Max02",
+      &Some("Max 2".to_owned()),
+      &Some(50),
+      &vec![],
+    );
+    let da3 = DeviceAddedV3::from(da4);
+    assert_eq!(da3.device_index(), 1);
+    assert_eq!(*da3.device_display_name(), Some("Max 2".to_owned()));
+    assert_eq!(*da3.device_message_timing_gap(), Some(50));
+  }
+}
@@ -30,6 +30,8 @@ pub enum FeatureType {
   Oscillate,
   Constrict,
   Inflate,
+  // Bidirectional grip, from fully open to fully gripped, usually air pressure chambers.
+  Squeeze,
   // For instances where we specify a position to move to ASAP. Usually servos, probably for the
   // OSR-2/SR-6.
   Position,
@@ -38,11 +40,10 @@ pub enum FeatureType {
   RSSI,
   Button,
   Pressure,
-  // Currently unused but possible sensor features:
-  // Temperature,
-  // Accelerometer,
-  // Gyro,
-  //
+  Temperature,
+  // Accelerometer and Gyroscope readings are reported as 3 axis (x, y, z) values.
+  Accelerometer,
+  Gyroscope,
   // Raw Feature, for when raw messages are on
   Raw,
 }
@@ -56,6 +57,7 @@ impl From<ActuatorType> for FeatureType {
       ActuatorType::Oscillate => FeatureType::Oscillate,
       ActuatorType::Constrict => FeatureType::Constrict,
       ActuatorType::Inflate => FeatureType::Inflate,
+      ActuatorType::Squeeze => FeatureType::Squeeze,
       ActuatorType::Position => FeatureType::Position,
     }
   }
@@ -69,6 +71,9 @@ impl From<SensorType> for FeatureType {
       SensorType::RSSI => FeatureType::RSSI,
       SensorType::Button => FeatureType::Button,
       SensorType::Pressure => FeatureType::Pressure,
+      SensorType::Temperature => FeatureType::Temperature,
+      SensorType::Accelerometer => FeatureType::Accelerometer,
+      SensorType::Gyroscope => FeatureType::Gyroscope,
     }
   }
 }
@@ -81,9 +86,13 @@ impl From<SensorType> for FeatureType {
 // For many messages, client and server configurations may be exactly the same. If they are not,
 // then we denote this by prefixing the type with Client/Server. Server attributes will usually be
 // hosted in the server/device/configuration module.
-#[derive(
-  Clone, Debug, Default, PartialEq, Eq, Getters, MutGetters, Setters, Serialize, Deserialize,
-)]
+/// A single actuator, sensor, or set of raw endpoints on a device.
+///
+/// Two features are equal if they describe the same capability: the same feature type, actuator
+/// step range and messages, sensor value ranges, and raw endpoints (in any order). Descriptions
+/// and user step limits are display/configuration metadata and are ignored, so features loaded
+/// from the same device config always compare equal.
+#[derive(Clone, Debug, Default, Eq, Getters, MutGetters, Setters, Serialize, Deserialize)]
 pub struct DeviceFeature {
   #[getset(get = "pub", get_mut = "pub(super)")]
   #[serde(default)]
@@ -104,6 +113,29 @@ pub struct DeviceFeature {
   raw: Option<DeviceFeatureRaw>,
 }
 
+impl PartialEq for DeviceFeature {
+  fn eq(&self, other: &Self) -> bool {
+    let actuator_eq = match (&self.actuator, &other.actuator) {
+      (Some(a), Some(b)) => a.step_range == b.step_range && a.messages == b.messages,
+      (None, None) => true,
+      _ => false,
+    };
+    let sensor_eq = match (&self.sensor, &other.sensor) {
+      (Some(a), Some(b)) => a.value_range == b.value_range,
+      (None, None) => true,
+      _ => false,
+    };
+    let raw_eq = match (&self.raw, &other.raw) {
+      (Some(a), Some(b)) => {
+        a.endpoints.iter().collect::<HashSet<_>>() == b.endpoints.iter().collect::<HashSet<_>>()
+      }
+      (None, None) => true,
+      _ => false,
+    };
+    self.feature_type == other.feature_type && actuator_eq && sensor_eq && raw_eq
+  }
+}
+
 impl DeviceFeature {
   pub fn new(
     description: &str,
@@ -180,7 +212,7 @@ pub struct DeviceFeatureActuatorSerialized {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Getters, MutGetters, Setters, Serialize, Deserialize)]
-#[serde(from = "DeviceFeatureActuatorSerialized")]
+#[serde(try_from = "DeviceFeatureActuatorSerialized")]
 pub struct DeviceFeatureActuator {
   #[getset(get = "pub")]
   #[serde(rename = "step-range")]
@@ -197,13 +229,18 @@ pub struct DeviceFeatureActuator {
   messages: HashSet<ButtplugActuatorFeatureMessageType>,
 }
 
-impl From<DeviceFeatureActuatorSerialized> for DeviceFeatureActuator {
-  fn from(value: DeviceFeatureActuatorSerialized) -> Self {
-    Self {
-      step_range: value.step_range.clone(),
-      step_limit: value.step_limit.unwrap_or(value.step_range),
-      messages: value.messages,
+impl TryFrom<DeviceFeatureActuatorSerialized> for DeviceFeatureActuator {
+  type Error = ButtplugDeviceError;
+
+  fn try_from(value: DeviceFeatureActuatorSerialized) -> Result<Self, Self::Error> {
+    let mut builder = DeviceFeatureActuatorBuilder::default();
+    builder
+      .step_range(&value.step_range)
+      .messages(&value.messages);
+    if let Some(step_limit) = &value.step_limit {
+      builder.step_limit(step_limit);
     }
+    builder.finish()
   }
 }
 
@@ -235,9 +272,77 @@ impl DeviceFeatureActuator {
   }
 }
 
+#[derive(Default)]
+pub struct DeviceFeatureActuatorBuilder {
+  step_range: Option<RangeInclusive<u32>>,
+  step_limit: Option<RangeInclusive<u32>>,
+  messages: HashSet<ButtplugActuatorFeatureMessageType>,
+}
+
+impl DeviceFeatureActuatorBuilder {
+  pub fn step_range(&mut self, step_range: &RangeInclusive<u32>) -> &mut Self {
+    self.step_range = Some(step_range.clone());
+    self
+  }
+
+  /// Defaults to the step range if not set.
+  pub fn step_limit(&mut self, step_limit: &RangeInclusive<u32>) -> &mut Self {
+    self.step_limit = Some(step_limit.clone());
+    self
+  }
+
+  pub fn message(&mut self, message: ButtplugActuatorFeatureMessageType) -> &mut Self {
+    self.messages.insert(message);
+    self
+  }
+
+  pub fn messages(&mut self, messages: &HashSet<ButtplugActuatorFeatureMessageType>) -> &mut Self {
+    self.messages.extend(messages.iter().cloned());
+    self
+  }
+
+  /// Builds the actuator, failing if the step range is missing or doesn't have at least two steps,
+  /// the step limit is out of order, or no messages were added.
+  pub fn finish(&self) -> Result<DeviceFeatureActuator, ButtplugDeviceError> {
+    let step_range = self.step_range.clone().ok_or_else(|| {
+      ButtplugDeviceError::DeviceConfigurationError("Actuator requires a step range.".to_owned())
+    })?;
+    if step_range.start() >= step_range.end() {
+      return Err(ButtplugDeviceError::DeviceConfigurationError(format!(
+        "Actuator step range {step_range:?} must have an end greater than its start."
+      )));
+    }
+    let step_limit = self.step_limit.clone().unwrap_or(step_range.clone());
+    if step_limit.is_empty() {
+      return Err(ButtplugDeviceError::DeviceConfigurationError(format!(
+        "Actuator step limit {step_limit:?} out of order, must be start <= x <= end."
+      )));
+    }
+    if self.messages.is_empty() {
+      return Err(ButtplugDeviceError::DeviceConfigurationError(
+        "Actuator requires at least one message type.".to_owned(),
+      ));
+    }
+    Ok(DeviceFeatureActuator::new(
+      &step_range,
+      &step_limit,
+      &self.messages,
+    ))
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct DeviceFeatureSensorSerialized {
+  #[serde(rename = "value-range")]
+  value_range: Vec<RangeInclusive<i32>>,
+  #[serde(rename = "messages")]
+  messages: HashSet<ButtplugSensorFeatureMessageType>,
+}
+
 #[derive(
   Clone, Debug, Default, PartialEq, Eq, Getters, MutGetters, Setters, Serialize, Deserialize,
 )]
+#[serde(try_from = "DeviceFeatureSensorSerialized")]
 pub struct DeviceFeatureSensor {
   #[getset(get = "pub", get_mut = "pub(super)")]
   #[serde(rename = "value-range")]
@@ -260,6 +365,63 @@ impl DeviceFeatureSensor {
   }
 }
 
+impl TryFrom<DeviceFeatureSensorSerialized> for DeviceFeatureSensor {
+  type Error = ButtplugDeviceError;
+
+  fn try_from(value: DeviceFeatureSensorSerialized) -> Result<Self, Self::Error> {
+    let mut builder = DeviceFeatureSensorBuilder::default();
+    for range in &value.value_range {
+      builder.value_range(range);
+    }
+    builder.messages(&value.messages).finish()
+  }
+}
+
+#[derive(Default)]
+pub struct DeviceFeatureSensorBuilder {
+  value_range: Vec<RangeInclusive<i32>>,
+  messages: HashSet<ButtplugSensorFeatureMessageType>,
+}
+
+impl DeviceFeatureSensorBuilder {
+  /// Adds the range for the next value in the sensor reading.
+  pub fn value_range(&mut self, value_range: &RangeInclusive<i32>) -> &mut Self {
+    self.value_range.push(value_range.clone());
+    self
+  }
+
+  pub fn message(&mut self, message: ButtplugSensorFeatureMessageType) -> &mut Self {
+    self.messages.insert(message);
+    self
+  }
+
+  pub fn messages(&mut self, messages: &HashSet<ButtplugSensorFeatureMessageType>) -> &mut Self {
+    self.messages.extend(messages.iter().cloned());
+    self
+  }
+
+  /// Builds the sensor, failing if no value ranges were added, any range is out of order, or no
+  /// messages were added.
+  pub fn finish(&self) -> Result<DeviceFeatureSensor, ButtplugDeviceError> {
+    if self.value_range.is_empty() {
+      return Err(ButtplugDeviceError::DeviceConfigurationError(
+        "Sensor requires at least one value range.".to_owned(),
+      ));
+    }
+    if let Some(range) = self.value_range.iter().find(|x| x.is_empty()) {
+      return Err(ButtplugDeviceError::DeviceConfigurationError(format!(
+        "Sensor value range {range:?} out of order, must be start <= x <= end."
+      )));
+    }
+    if self.messages.is_empty() {
+      return Err(ButtplugDeviceError::DeviceConfigurationError(
+        "Sensor requires at least one message type.".to_owned(),
+      ));
+    }
+    Ok(DeviceFeatureSensor::new(&self.value_range, &self.messages))
+  }
+}
+
 #[derive(
   Clone, Debug, Default, PartialEq, Eq, Getters, MutGetters, Setters, Serialize, Deserialize,
 )]
@@ -289,3 +451,107 @@ impl DeviceFeatureRaw {
     }
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::{
+    DeviceFeature,
+    DeviceFeatureActuatorBuilder,
+    DeviceFeatureSensor,
+    DeviceFeatureSensorBuilder,
+  };
+  use crate::core::{
+    errors::ButtplugDeviceError,
+    message::{
+      ButtplugActuatorFeatureMessageType,
+      ButtplugSensorFeatureMessageType,
+      Endpoint,
+      FeatureType,
+    },
+  };
+  use std::ops::RangeInclusive;
+
+  #[test]
+  fn test_actuator_builder_validation() {
+    let mut builder = DeviceFeatureActuatorBuilder::default();
+    builder
+      .step_range(&(0..=20))
+      .message(ButtplugActuatorFeatureMessageType::ScalarCmd);
+    let actuator = builder.finish().expect("Test, assuming infallible.");
+    assert_eq!(*actuator.step_limit(), 0..=20);
+
+    builder.step_limit(&RangeInclusive::new(10, 5));
+    assert!(matches!(
+      builder.finish(),
+      Err(ButtplugDeviceError::DeviceConfigurationError(_))
+    ));
+
+    let mut builder = DeviceFeatureActuatorBuilder::default();
+    builder
+      .step_range(&(5..=5))
+      .message(ButtplugActuatorFeatureMessageType::ScalarCmd);
+    assert!(builder.finish().is_err());
+
+    let mut builder = DeviceFeatureActuatorBuilder::default();
+    builder.step_range(&(0..=20));
+    assert!(builder.finish().is_err());
+  }
+
+  #[test]
+  fn test_sensor_builder_validation() {
+    let mut builder = DeviceFeatureSensorBuilder::default();
+    builder.message(ButtplugSensorFeatureMessageType::SensorReadCmd);
+    assert!(builder.finish().is_err());
+    builder.value_range(&(0..=100));
+    assert!(builder.finish().is_ok());
+    builder.value_range(&RangeInclusive::new(10, 0));
+    assert!(builder.finish().is_err());
+  }
+
+  #[test]
+  fn test_feature_equality_ignores_metadata() {
+    let actuator = |limit: RangeInclusive<u32>| {
+      let mut builder = DeviceFeatureActuatorBuilder::default();
+      builder
+        .step_range(&(0..=20))
+        .step_limit(&limit)
+        .message(ButtplugActuatorFeatureMessageType::ScalarCmd);
+      Some(builder.finish().expect("Test, assuming infallible."))
+    };
+    let feature = DeviceFeature::new("Vibrator 1", FeatureType::Vibrate, &actuator(0..=20), &None);
+    assert_eq!(
+      feature,
+      DeviceFeature::new("Other Name", FeatureType::Vibrate, &actuator(0..=10), &None)
+    );
+    assert_ne!(
+      feature,
+      DeviceFeature::new("Vibrator 1", FeatureType::Rotate, &actuator(0..=20), &None)
+    );
+    assert_eq!(
+      DeviceFeature::new_raw_feature(&[Endpoint::Tx, Endpoint::Rx]),
+      DeviceFeature::new_raw_feature(&[Endpoint::Rx, Endpoint::Tx])
+    );
+    assert_ne!(
+      DeviceFeature::new_raw_feature(&[Endpoint::Tx]),
+      DeviceFeature::new_raw_feature(&[Endpoint::Rx, Endpoint::Tx])
+    );
+  }
+
+  #[test]
+  fn test_feature_deserialization_validates() {
+    let valid = r#"{
+      "feature-type": "Vibrate",
+      "actuator": { "step-range": [0, 20], "messages": ["ScalarCmd"] }
+    }"#;
+    assert!(serde_json::from_str::<DeviceFeature>(valid).is_ok());
+    let inverted = r#"{
+      "feature-type": "Vibrate",
+      "actuator": { "step-range": [20, 0], "messages": ["ScalarCmd"] }
+    }"#;
+    let err = serde_json::from_str::<DeviceFeature>(inverted)
+      .expect_err("Inverted step range should be rejected.");
+    assert!(err.to_string().contains("step range"));
+    let no_messages = r#"{ "value-range": [[0, 100]], "messages": [] }"#;
+    assert!(serde_json::from_str::<DeviceFeatureSensor>(no_messages).is_err());
+  }
+}
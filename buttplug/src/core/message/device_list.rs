@@ -9,16 +9,16 @@ use super::device_message_info::{DeviceMessageInfoV0, DeviceMessageInfoV1, Devic
 use super::*;
 use device_message_info::DeviceMessageInfoV4;
 use getset::Getters;
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde::{Deserialize, Serialize};
 
 /// List of all devices currently connected to the server.
 #[derive(Default, Clone, Debug, PartialEq, Eq, ButtplugMessage, Getters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct DeviceListV4 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Devices"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Devices"))]
   #[getset(get = "pub")]
   devices: Vec<DeviceMessageInfoV4>,
 }
@@ -36,6 +36,7 @@ impl ButtplugMessageValidator for DeviceListV4 {
 }
 
 impl ButtplugMessageFinalizer for DeviceListV4 {
+  // V4 device info carries its features as-is, so there are no attribute indexes to fill in.
   fn finalize(&mut self) {
   }
 }
@@ -50,18 +51,20 @@ impl From<DeviceListV4> for DeviceListV3 {
 
 /// List of all devices currently connected to the server.
 #[derive(Default, Clone, Debug, PartialEq, Eq, ButtplugMessage, Getters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct DeviceListV3 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Devices"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Devices"))]
   #[getset(get = "pub")]
   devices: Vec<DeviceMessageInfoV3>,
 }
 
 impl DeviceListV3 {
   pub fn new(devices: Vec<DeviceMessageInfoV3>) -> Self {
-    Self { id: 1, devices }
+    let mut obj = Self { id: 1, devices };
+    obj.finalize();
+    obj
   }
 }
 
@@ -80,11 +83,11 @@ impl ButtplugMessageFinalizer for DeviceListV3 {
 }
 
 #[derive(Default, Clone, Debug, PartialEq, Eq, ButtplugMessage, Getters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct DeviceListV2 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Devices"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Devices"))]
   #[getset(get = "pub")]
   devices: Vec<DeviceMessageInfoV2>,
 }
@@ -112,11 +115,11 @@ impl ButtplugMessageFinalizer for DeviceListV2 {
 }
 
 #[derive(Default, Clone, Debug, PartialEq, Eq, ButtplugMessage, Getters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct DeviceListV1 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Devices"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Devices"))]
   #[getset(get = "pub")]
   devices: Vec<DeviceMessageInfoV1>,
 }
@@ -145,11 +148,11 @@ impl ButtplugMessageFinalizer for DeviceListV1 {
 }
 
 #[derive(Default, Clone, Debug, PartialEq, Eq, ButtplugMessage, Getters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct DeviceListV0 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Devices"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Devices"))]
   #[getset(get = "pub")]
   devices: Vec<DeviceMessageInfoV0>,
 }
@@ -176,3 +179,66 @@ impl ButtplugMessageValidator for DeviceListV0 {
 
 impl ButtplugMessageFinalizer for DeviceListV0 {
 }
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use std::collections::HashSet;
+
+  fn actuator_feature(
+    feature_type: FeatureType,
+    message_type: ButtplugActuatorFeatureMessageType,
+  ) -> DeviceFeature {
+    DeviceFeature::new(
+      "Test",
+      feature_type,
+      &Some(DeviceFeatureActuator::new(
+        &(0..=20),
+        &(0..=20),
+        &HashSet::from([message_type]),
+      )),
+      &None,
+    )
+  }
+
+  #[test]
+  fn test_device_list_v3_from_v4_assigns_attribute_indexes() {
+    let features = [
+      (
+        FeatureType::Vibrate,
+        ButtplugActuatorFeatureMessageType::ScalarCmd,
+      ),
+      (
+        FeatureType::Rotate,
+        ButtplugActuatorFeatureMessageType::RotateCmd,
+      ),
+      (
+        FeatureType::Position,
+        ButtplugActuatorFeatureMessageType::LinearCmd,
+      ),
+    ]
+    .iter()
+    .flat_map(|(feature_type, message_type)| {
+      vec![actuator_feature(*feature_type, *message_type); 2]
+    })
+    .collect();
+    let dl4 = DeviceListV4::new(vec![DeviceMessageInfoV4::new(
+      0,
+      "Test Device",
+      &None,
+      &None,
+      features,
+    )]);
+    let dl3: DeviceListV3 = dl4.into();
+    let attrs = dl3.devices()[0].device_messages();
+    for cmd_attrs in [attrs.scalar_cmd(), attrs.rotate_cmd(), attrs.linear_cmd()] {
+      let indexes: Vec<u32> = cmd_attrs
+        .as_ref()
+        .expect("Test, assuming infallible.")
+        .iter()
+        .map(|x| *x.index())
+        .collect();
+      assert_eq!(indexes, vec![0, 1]);
+    }
+  }
+}
@@ -8,27 +8,27 @@
 use super::*;
 use device_added::DeviceAddedV4;
 use getset::{CopyGetters, Getters, MutGetters};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde::{Deserialize, Serialize};
 
 /// Substructure of device messages, used for attribute information (name, messages supported, etc...)
 #[derive(Clone, Debug, PartialEq, Eq, MutGetters, Getters, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct DeviceMessageInfoV4 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   #[getset(get_copy = "pub")]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceName"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceName"))]
   #[getset(get = "pub")]
   device_name: String,
   #[cfg_attr(
-    feature = "serialize-json",
+    feature = "message-serde",
     serde(rename = "DeviceDisplayName", skip_serializing_if = "Option::is_none")
   )]
   #[getset(get = "pub")]
   device_display_name: Option<String>,
   #[cfg_attr(
-    feature = "serialize-json",
+    feature = "message-serde",
     serde(
       rename = "DeviceMessageTimingGap",
       skip_serializing_if = "Option::is_none"
@@ -36,7 +36,7 @@ pub struct DeviceMessageInfoV4 {
   )]
   #[getset(get = "pub")]
   device_message_timing_gap: Option<u32>,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceFeatures"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceFeatures"))]
   #[getset(get = "pub", get_mut = "pub(super)")]
   device_features: Vec<DeviceFeature>,
 }
@@ -77,7 +77,7 @@ impl From<DeviceMessageInfoV4> for DeviceMessageInfoV3 {
       value.device_index(),
       &value.device_name(),
       &value.device_display_name(),
-      &None,
+      value.device_message_timing_gap(),
       value.device_features().clone().into(),
     )
   }
@@ -85,22 +85,22 @@ impl From<DeviceMessageInfoV4> for DeviceMessageInfoV3 {
 
 /// Substructure of device messages, used for attribute information (name, messages supported, etc...)
 #[derive(Clone, Debug, PartialEq, Eq, MutGetters, Getters, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct DeviceMessageInfoV3 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   #[getset(get_copy = "pub")]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceName"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceName"))]
   #[getset(get = "pub")]
   device_name: String,
   #[cfg_attr(
-    feature = "serialize-json",
+    feature = "message-serde",
     serde(rename = "DeviceDisplayName", skip_serializing_if = "Option::is_none")
   )]
   #[getset(get = "pub")]
   device_display_name: Option<String>,
   #[cfg_attr(
-    feature = "serialize-json",
+    feature = "message-serde",
     serde(
       rename = "DeviceMessageTimingGap",
       skip_serializing_if = "Option::is_none"
@@ -108,7 +108,7 @@ pub struct DeviceMessageInfoV3 {
   )]
   #[getset(get = "pub")]
   device_message_timing_gap: Option<u32>,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceMessages"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceMessages"))]
   #[getset(get = "pub", get_mut = "pub(super)")]
   device_messages: ClientDeviceMessageAttributesV3,
 }
@@ -144,15 +144,15 @@ impl From<DeviceAddedV3> for DeviceMessageInfoV3 {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Getters, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct DeviceMessageInfoV2 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   #[getset(get_copy = "pub")]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceName"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceName"))]
   #[getset(get = "pub")]
   device_name: String,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceMessages"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceMessages"))]
   #[getset(get = "pub")]
   device_messages: ClientDeviceMessageAttributesV2,
 }
@@ -187,15 +187,15 @@ impl From<DeviceMessageInfoV3> for DeviceMessageInfoV2 {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Getters, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct DeviceMessageInfoV1 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   #[getset(get_copy = "pub")]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceName"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceName"))]
   #[getset(get = "pub")]
   device_name: String,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceMessages"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceMessages"))]
   #[getset(get = "pub")]
   device_messages: ClientDeviceMessageAttributesV1,
 }
@@ -222,15 +222,15 @@ impl From<DeviceMessageInfoV2> for DeviceMessageInfoV1 {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Getters, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct DeviceMessageInfoV0 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   #[getset(get_copy = "pub")]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceName"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceName"))]
   #[getset(get = "pub")]
   device_name: String,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceMessages"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceMessages"))]
   #[getset(get = "pub")]
   device_messages: Vec<ButtplugDeviceMessageType>,
 }
@@ -283,3 +283,124 @@ impl From<DeviceMessageInfoV1> for DeviceMessageInfoV0 {
     }
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use std::collections::HashSet;
+
+  fn v4_info(features: Vec<DeviceFeature>) -> DeviceMessageInfoV4 {
+    DeviceMessageInfoV4::new(1, "Test Device", &None, &Some(50), features)
+  }
+
+  #[test]
+  fn test_device_message_info_v4_to_v3_actuators() {
+    let vibrator = DeviceFeature::new(
+      "Vibe",
+      FeatureType::Vibrate,
+      &Some(DeviceFeatureActuator::new(
+        &(0..=20),
+        &(0..=10),
+        &HashSet::from([ButtplugActuatorFeatureMessageType::ScalarCmd]),
+      )),
+      &None,
+    );
+    let battery = DeviceFeature::new(
+      "Battery",
+      FeatureType::Battery,
+      &None,
+      &Some(DeviceFeatureSensor::new(
+        &vec![0..=100],
+        &HashSet::from([ButtplugSensorFeatureMessageType::SensorReadCmd]),
+      )),
+    );
+    let info: DeviceMessageInfoV3 = v4_info(vec![vibrator, battery]).into();
+    assert_eq!(*info.device_message_timing_gap(), Some(50));
+    let scalars = info
+      .device_messages()
+      .scalar_cmd()
+      .as_ref()
+      .expect("Test, assuming infallible.");
+    assert_eq!(scalars.len(), 1);
+    assert_eq!(*scalars[0].actuator_type(), ActuatorType::Vibrate);
+    assert_eq!(*scalars[0].step_count(), 10);
+    assert_eq!(scalars[0].feature_descriptor(), "Vibe");
+    let sensors = info
+      .device_messages()
+      .sensor_read_cmd()
+      .as_ref()
+      .expect("Test, assuming infallible.");
+    assert_eq!(*sensors[0].sensor_type(), SensorType::Battery);
+    assert!(info.device_messages().linear_cmd().is_none());
+  }
+
+  #[test]
+  fn test_device_message_info_v4_to_v3_sensor_only() {
+    let button = DeviceFeature::new(
+      "Button",
+      FeatureType::Button,
+      &None,
+      &Some(DeviceFeatureSensor::new(
+        &vec![0..=1],
+        &HashSet::from([ButtplugSensorFeatureMessageType::SensorSubscribeCmd]),
+      )),
+    );
+    let info: DeviceMessageInfoV3 = v4_info(vec![button]).into();
+    let messages = info.device_messages();
+    assert!(messages.scalar_cmd().is_none());
+    assert!(messages.rotate_cmd().is_none());
+    assert!(messages.sensor_read_cmd().is_none());
+    assert_eq!(
+      messages
+        .sensor_subscribe_cmd()
+        .as_ref()
+        .expect("Test, assuming infallible.")
+        .len(),
+      1
+    );
+  }
+
+  #[test]
+  fn test_device_message_info_v4_to_v3_raw_and_empty() {
+    let info: DeviceMessageInfoV3 = v4_info(vec![]).into();
+    assert_eq!(
+      *info.device_messages(),
+      ClientDeviceMessageAttributesV3::default()
+    );
+
+    let info: DeviceMessageInfoV3 =
+      v4_info(vec![DeviceFeature::new_raw_feature(&[Endpoint::Tx])]).into();
+    let messages = info.device_messages();
+    assert!(messages.scalar_cmd().is_none());
+    for raw in [
+      messages.raw_read_cmd(),
+      messages.raw_write_cmd(),
+      messages.raw_subscribe_cmd(),
+    ] {
+      assert_eq!(
+        raw
+          .as_ref()
+          .expect("Test, assuming infallible.")
+          .endpoints(),
+        &vec![Endpoint::Tx]
+      );
+    }
+  }
+
+  #[test]
+  fn test_device_message_info_v4_to_v3_skips_unconvertible_features() {
+    // An actuator hung off a sensor feature type can't be expressed in v3, and shouldn't panic.
+    let bogus = DeviceFeature::new(
+      "Bogus",
+      FeatureType::Battery,
+      &Some(DeviceFeatureActuator::new(
+        &(0..=20),
+        &(0..=20),
+        &HashSet::from([ButtplugActuatorFeatureMessageType::ScalarCmd]),
+      )),
+      &None,
+    );
+    let info: DeviceMessageInfoV3 = v4_info(vec![bogus]).into();
+    assert!(info.device_messages().scalar_cmd().is_none());
+  }
+}
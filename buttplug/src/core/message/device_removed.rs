@@ -8,16 +8,63 @@
 //! Notification that a device has disconnected from the server.
 
 use super::*;
-use getset::CopyGetters;
-#[cfg(feature = "serialize-json")]
+use getset::{CopyGetters, Getters};
+#[cfg(feature = "message-serde")]
 use serde::{Deserialize, Serialize};
 
+/// Notification that a device has disconnected from the server. Carries the device's names, so
+/// receivers can report which device went away without having kept track of it themselves.
+#[derive(Debug, Default, ButtplugMessage, Clone, PartialEq, Eq, Getters, CopyGetters)]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
+pub struct DeviceRemovedV4 {
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
+  id: u32,
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
+  #[getset(get_copy = "pub")]
+  device_index: u32,
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceName"))]
+  #[getset(get = "pub")]
+  device_name: String,
+  #[cfg_attr(
+    feature = "message-serde",
+    serde(rename = "DeviceDisplayName", skip_serializing_if = "Option::is_none")
+  )]
+  #[getset(get = "pub")]
+  device_display_name: Option<String>,
+}
+
+impl DeviceRemovedV4 {
+  pub fn new(device_index: u32, device_name: &str, device_display_name: &Option<String>) -> Self {
+    Self {
+      id: 0,
+      device_index,
+      device_name: device_name.to_owned(),
+      device_display_name: device_display_name.clone(),
+    }
+  }
+}
+
+impl ButtplugMessageValidator for DeviceRemovedV4 {
+  fn is_valid(&self) -> Result<(), ButtplugMessageError> {
+    self.is_system_id(self.id)
+  }
+}
+
+impl ButtplugMessageFinalizer for DeviceRemovedV4 {
+}
+
+impl From<DeviceRemovedV4> for DeviceRemovedV0 {
+  fn from(value: DeviceRemovedV4) -> Self {
+    Self::new(value.device_index)
+  }
+}
+
 #[derive(Debug, Default, ButtplugMessage, Clone, PartialEq, Eq, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct DeviceRemovedV0 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   #[getset(get_copy = "pub")]
   device_index: u32,
 }
@@ -39,3 +86,23 @@ impl ButtplugMessageValidator for DeviceRemovedV0 {
 
 impl ButtplugMessageFinalizer for DeviceRemovedV0 {
 }
+
+#[cfg(all(test, feature = "serialize-json"))]
+mod test {
+  use super::{DeviceRemovedV0, DeviceRemovedV4};
+
+  #[test]
+  fn test_device_removed_v4_json() {
+    let dr = DeviceRemovedV4::new(3, "LVS-Max02", &Some("Max 2".to_owned()));
+    assert_eq!(
+      serde_json::to_string(&dr).expect("Test, assuming infallible."),
+      r#"{"Id":0,"DeviceIndex":3,"DeviceName":"LVS-Max02","DeviceDisplayName":"Max 2"}"#
+    );
+    let dr = DeviceRemovedV4::new(3, "LVS-Max02", &None);
+    assert_eq!(
+      serde_json::to_string(&dr).expect("Test, assuming infallible."),
+      r#"{"Id":0,"DeviceIndex":3,"DeviceName":"LVS-Max02"}"#
+    );
+    assert_eq!(DeviceRemovedV0::from(dr), DeviceRemovedV0::new(3));
+  }
+}
@@ -10,6 +10,7 @@ use std::{
   str::FromStr,
   string::ToString,
 };
+use thiserror::Error;
 
 use core::hash::Hash;
 
@@ -27,7 +28,7 @@ use core::hash::Hash;
 /// context. These names are used in [Device Configuration](crate::server::device::configuration)
 /// and the [Device Configuration File](crate::util::device_configuration), and are expected to
 /// de/serialize to lowercase versions of their names.
-#[derive(EnumString, Clone, Debug, PartialEq, Eq, Hash, Display, Copy)]
+#[derive(EnumIter, Clone, Debug, PartialEq, Eq, Hash, Display, Copy)]
 #[strum(serialize_all = "lowercase")]
 pub enum Endpoint {
   /// Expect to take commands, when multiple receive endpoints may be available
@@ -125,6 +126,69 @@ pub enum Endpoint {
   Generic31,
 }
 
+/// Endpoint name {0} is not a known endpoint
+#[derive(Debug, Clone, PartialEq, Eq, Error, displaydoc::Display)]
+pub struct UnknownEndpointError(pub String);
+
+impl FromStr for Endpoint {
+  type Err = UnknownEndpointError;
+
+  /// Parses endpoint names as used in device configuration files, ignoring case, so both
+  /// `"txvibrate"` (what [Display](std::fmt::Display) produces) and `"TxVibrate"` work.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let endpoint = match s.to_ascii_lowercase().as_str() {
+      "command" => Endpoint::Command,
+      "firmware" => Endpoint::Firmware,
+      "rx" => Endpoint::Rx,
+      "rxaccel" => Endpoint::RxAccel,
+      "rxblebattery" => Endpoint::RxBLEBattery,
+      "rxblemodel" => Endpoint::RxBLEModel,
+      "rxpressure" => Endpoint::RxPressure,
+      "rxtouch" => Endpoint::RxTouch,
+      "tx" => Endpoint::Tx,
+      "txmode" => Endpoint::TxMode,
+      "txshock" => Endpoint::TxShock,
+      "txvibrate" => Endpoint::TxVibrate,
+      "txvendorcontrol" => Endpoint::TxVendorControl,
+      "whitelist" => Endpoint::Whitelist,
+      "generic0" => Endpoint::Generic0,
+      "generic1" => Endpoint::Generic1,
+      "generic2" => Endpoint::Generic2,
+      "generic3" => Endpoint::Generic3,
+      "generic4" => Endpoint::Generic4,
+      "generic5" => Endpoint::Generic5,
+      "generic6" => Endpoint::Generic6,
+      "generic7" => Endpoint::Generic7,
+      "generic8" => Endpoint::Generic8,
+      "generic9" => Endpoint::Generic9,
+      "generic10" => Endpoint::Generic10,
+      "generic11" => Endpoint::Generic11,
+      "generic12" => Endpoint::Generic12,
+      "generic13" => Endpoint::Generic13,
+      "generic14" => Endpoint::Generic14,
+      "generic15" => Endpoint::Generic15,
+      "generic16" => Endpoint::Generic16,
+      "generic17" => Endpoint::Generic17,
+      "generic18" => Endpoint::Generic18,
+      "generic19" => Endpoint::Generic19,
+      "generic20" => Endpoint::Generic20,
+      "generic21" => Endpoint::Generic21,
+      "generic22" => Endpoint::Generic22,
+      "generic23" => Endpoint::Generic23,
+      "generic24" => Endpoint::Generic24,
+      "generic25" => Endpoint::Generic25,
+      "generic26" => Endpoint::Generic26,
+      "generic27" => Endpoint::Generic27,
+      "generic28" => Endpoint::Generic28,
+      "generic29" => Endpoint::Generic29,
+      "generic30" => Endpoint::Generic30,
+      "generic31" => Endpoint::Generic31,
+      _ => return Err(UnknownEndpointError(s.to_owned())),
+    };
+    Ok(endpoint)
+  }
+}
+
 // Implement to/from string serialization for Endpoint struct
 impl Serialize for Endpoint {
   fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -160,3 +224,36 @@ impl<'de> Deserialize<'de> for Endpoint {
     deserializer.deserialize_str(EndpointVisitor)
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::{Endpoint, UnknownEndpointError};
+  use strum::IntoEnumIterator;
+
+  #[test]
+  fn test_endpoint_string_round_trip() {
+    for endpoint in Endpoint::iter() {
+      let name = endpoint.to_string();
+      assert_eq!(name.parse::<Endpoint>(), Ok(endpoint));
+      assert_eq!(name.parse::<Endpoint>().unwrap().to_string(), name);
+    }
+  }
+
+  #[test]
+  fn test_endpoint_unknown_name() {
+    assert_eq!(
+      "notanendpoint".parse::<Endpoint>(),
+      Err(UnknownEndpointError("notanendpoint".to_owned()))
+    );
+  }
+
+  #[test]
+  fn test_endpoint_parse_ignores_case() {
+    assert_eq!("TxVibrate".parse::<Endpoint>(), Ok(Endpoint::TxVibrate));
+    assert_eq!(
+      "RXBLEBATTERY".parse::<Endpoint>(),
+      Ok(Endpoint::RxBLEBattery)
+    );
+    assert_eq!("Generic31".parse::<Endpoint>(), Ok(Endpoint::Generic31));
+  }
+}
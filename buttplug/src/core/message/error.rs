@@ -10,15 +10,15 @@
 use super::*;
 use crate::core::errors::*;
 use getset::{CopyGetters, Getters};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde::{Deserialize, Serialize};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
 /// Error codes pertaining to error classes that can be represented in the
 /// Buttplug [Error] message.
 #[derive(Debug, Clone, PartialEq, Eq, Copy)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize_repr, Deserialize_repr))]
+#[cfg_attr(feature = "message-serde", derive(Serialize_repr, Deserialize_repr))]
 #[repr(u8)]
 pub enum ErrorCode {
   ErrorUnknown = 0,
@@ -42,20 +42,20 @@ pub enum ErrorCode {
   Getters,
   CopyGetters,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct ErrorV0 {
   /// Message Id, used for matching message pairs in remote connection instances.
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
   /// Specifies the class of the error.
-  #[cfg_attr(feature = "serialize-json", serde(rename = "ErrorCode"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "ErrorCode"))]
   #[getset(get_copy = "pub")]
   error_code: ErrorCode,
   /// Description of the error.
-  #[cfg_attr(feature = "serialize-json", serde(rename = "ErrorMessage"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "ErrorMessage"))]
   #[getset(get = "pub")]
   error_message: String,
-  #[cfg_attr(feature = "serialize-json", serde(skip))]
+  #[cfg_attr(feature = "message-serde", serde(skip))]
   original_error: Option<ButtplugError>,
 }
 
@@ -82,6 +82,23 @@ impl ErrorV0 {
     }
   }
 
+  /// Creates a new error object whose message is the given explanation followed by the error that
+  /// caused it, i.e. "Could not write to device: <source>". The source is written the same way the
+  /// `From<ButtplugError>` impl writes it, so [ErrorV0::original_error] can still recover it on the
+  /// other side of a connection.
+  pub fn new_with_source(
+    error_code: ErrorCode,
+    error_message: &str,
+    source: impl Into<ButtplugError>,
+  ) -> Self {
+    let source = ErrorV0::from(source.into());
+    Self::new(
+      error_code,
+      &format!("{}: {}", error_message, source.error_message),
+      source.original_error,
+    )
+  }
+
   pub fn original_error(&self) -> ButtplugError {
     if self.original_error.is_some() {
       self
@@ -95,6 +112,14 @@ impl ErrorV0 {
         if let Ok(deserialized_msg) = serde_json::from_str(&self.error_message) {
           return deserialized_msg;
         }
+        // Errors built with new_with_source have an explanation in front of the serialized error.
+        if let Some(deserialized_msg) = self
+          .error_message
+          .split_once(": ")
+          .and_then(|(_, source)| serde_json::from_str(source).ok())
+        {
+          return deserialized_msg;
+        }
       }
       ButtplugError::from(self.clone())
     }
@@ -123,7 +148,10 @@ impl From<ButtplugError> for ErrorV0 {
 #[cfg(feature = "serialize-json")]
 #[cfg(test)]
 mod test {
-  use crate::core::message::{ButtplugServerMessageCurrent, ErrorCode, ErrorV0};
+  use crate::core::{
+    errors::{ButtplugDeviceError, ButtplugError},
+    message::{ButtplugServerMessageCurrent, Endpoint, ErrorCode, ErrorV0},
+  };
 
   const ERROR_STR: &str = "{\"Error\":{\"Id\":0,\"ErrorCode\":1,\"ErrorMessage\":\"Test Error\"}}";
 
@@ -151,4 +179,27 @@ mod test {
       union
     );
   }
+
+  #[test]
+  fn test_error_new_with_source() {
+    let source = ButtplugError::from(ButtplugDeviceError::InvalidEndpoint(Endpoint::Tx));
+    let error = ErrorV0::new_with_source(
+      ErrorCode::ErrorDevice,
+      "Could not write to device",
+      source.clone(),
+    );
+    assert_eq!(error.error_code(), ErrorCode::ErrorDevice);
+    assert_eq!(
+      error.error_message(),
+      &format!(
+        "Could not write to device: {}",
+        serde_json::to_string(&source).expect("Infallible serialization.")
+      )
+    );
+    // Going over the wire drops the original error, but it can still be read back out of the
+    // message.
+    let js = serde_json::to_string(&error).expect("Infallible serialization.");
+    let deserialized: ErrorV0 = serde_json::from_str(&js).expect("Infallible deserialization");
+    assert_eq!(deserialized.original_error(), source);
+  }
 }
@@ -9,22 +9,22 @@
 
 use super::*;
 use getset::CopyGetters;
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde::{Deserialize, Serialize};
 
 #[derive(
   Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Eq, Clone, CopyGetters,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct FleshlightLaunchFW12CmdV0 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Position"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Position"))]
   #[getset(get_copy = "pub")]
   position: u8,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Speed"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Speed"))]
   #[getset(get_copy = "pub")]
   speed: u8,
 }
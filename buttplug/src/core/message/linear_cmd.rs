@@ -7,19 +7,21 @@
 
 use super::*;
 use getset::{CopyGetters, Getters};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde::{Deserialize, Serialize};
 
 /// Move device to a certain position in a certain amount of time
 #[derive(Debug, PartialEq, Clone, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 #[getset(get_copy = "pub")]
 pub struct VectorSubcommandV4 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Index"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Index"))]
   feature_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Duration"))]
+  /// Time to take to reach the position, in milliseconds. A duration of 0 is valid, and means the
+  /// device should move to the position as fast as it can.
+  #[cfg_attr(feature = "message-serde", serde(rename = "Duration"))]
   duration: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Position"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Position"))]
   position: f64,
 }
 
@@ -33,14 +35,23 @@ impl VectorSubcommandV4 {
   }
 }
 
+impl ButtplugMessageValidator for VectorSubcommandV4 {
+  fn is_valid(&self) -> Result<(), ButtplugMessageError> {
+    self.is_in_command_range_named(
+      &format!("LinearCmd index {} Position", self.feature_index),
+      self.position,
+    )
+  }
+}
+
 #[derive(Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Clone, Getters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct LinearCmdV4 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Vectors"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Vectors"))]
   #[getset(get = "pub")]
   vectors: Vec<VectorSubcommandV4>,
 }
@@ -59,28 +70,25 @@ impl ButtplugMessageValidator for LinearCmdV4 {
   fn is_valid(&self) -> Result<(), ButtplugMessageError> {
     self.is_not_system_id(self.id)?;
     for vec in &self.vectors {
-      self.is_in_command_range(
-        vec.position,
-        format!(
-          "VectorSubcommand position {} for index {} is invalid, should be between 0.0 and 1.0",
-          vec.position, vec.feature_index
-        ),
-      )?;
+      vec.is_valid()?;
     }
+    self.has_unique_indexes(self.vectors.iter().map(|x| x.feature_index), "LinearCmd")?;
     Ok(())
   }
 }
 
 /// Move device to a certain position in a certain amount of time
 #[derive(Debug, PartialEq, Clone, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 #[getset(get_copy = "pub")]
 pub struct VectorSubcommandV1 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Index"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Index"))]
   index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Duration"))]
+  /// Time to take to reach the position, in milliseconds. A duration of 0 is valid, and means the
+  /// device should move to the position as fast as it can.
+  #[cfg_attr(feature = "message-serde", serde(rename = "Duration"))]
   duration: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Position"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Position"))]
   position: f64,
 }
 
@@ -94,14 +102,23 @@ impl VectorSubcommandV1 {
   }
 }
 
+impl ButtplugMessageValidator for VectorSubcommandV1 {
+  fn is_valid(&self) -> Result<(), ButtplugMessageError> {
+    self.is_in_command_range_named(
+      &format!("LinearCmd index {} Position", self.index),
+      self.position,
+    )
+  }
+}
+
 #[derive(Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Clone, Getters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct LinearCmdV1 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Vectors"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Vectors"))]
   #[getset(get = "pub")]
   vectors: Vec<VectorSubcommandV1>,
 }
@@ -120,14 +137,40 @@ impl ButtplugMessageValidator for LinearCmdV1 {
   fn is_valid(&self) -> Result<(), ButtplugMessageError> {
     self.is_not_system_id(self.id)?;
     for vec in &self.vectors {
-      self.is_in_command_range(
-        vec.position,
-        format!(
-          "VectorSubcommand position {} for index {} is invalid, should be between 0.0 and 1.0",
-          vec.position, vec.index
-        ),
-      )?;
+      vec.is_valid()?;
     }
+    self.has_unique_indexes(self.vectors.iter().map(|x| x.index), "LinearCmd")?;
     Ok(())
   }
 }
+
+#[cfg(test)]
+mod test {
+  use crate::core::message::{
+    ButtplugMessageValidator,
+    LinearCmdV1,
+    LinearCmdV4,
+    VectorSubcommandV1,
+    VectorSubcommandV4,
+  };
+
+  #[test]
+  fn test_linear_cmd_zero_duration_is_valid() {
+    // Zero duration means "as fast as possible", not "ignore this".
+    assert!(
+      LinearCmdV4::new(0, vec![VectorSubcommandV4::new(0, 0, 0.5)])
+        .is_valid()
+        .is_ok()
+    );
+    assert!(
+      LinearCmdV1::new(0, vec![VectorSubcommandV1::new(0, 0, 0.5)])
+        .is_valid()
+        .is_ok()
+    );
+    assert!(
+      LinearCmdV4::new(0, vec![VectorSubcommandV4::new(0, 0, 1.5)])
+        .is_valid()
+        .is_err()
+    );
+  }
+}
@@ -7,21 +7,21 @@
 
 use super::*;
 use getset::{CopyGetters, Getters};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde::{Deserialize, Serialize};
 
 /// Log message received from server (Version 1 Message, Deprecated)
 #[derive(
   Debug, ButtplugMessage, ButtplugMessageFinalizer, PartialEq, Eq, Clone, Getters, CopyGetters,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct LogV0 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "LogLevel"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "LogLevel"))]
   #[getset(get_copy = "pub")]
   log_level: LogLevel,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "LogMessage"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "LogMessage"))]
   #[getset(get = "pub")]
   log_message: String,
 }
@@ -7,7 +7,7 @@
 
 use super::*;
 use getset::Getters;
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde::{Deserialize, Serialize};
 
 /// Lovense specific commands (Version 0 Message, **Deprecated**)
@@ -15,13 +15,13 @@ use serde::{Deserialize, Serialize};
 // Lovense devices even on spec v1 connections, we can put a null validator on
 // it.
 #[derive(Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Eq, Clone, Getters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct LovenseCmdV0 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Command"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Command"))]
   #[getset(get = "pub")]
   command: String,
 }
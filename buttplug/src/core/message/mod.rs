@@ -15,6 +15,8 @@
 
 mod battery_level_cmd;
 mod battery_level_reading;
+#[cfg(feature = "serialize-cbor")]
+pub mod cbor;
 mod client_device_message_attributes;
 mod device_added;
 mod device_feature;
@@ -78,8 +80,10 @@ pub use device_added::{DeviceAddedV0, DeviceAddedV1, DeviceAddedV2, DeviceAddedV
 pub use device_feature::{
   DeviceFeature,
   DeviceFeatureActuator,
+  DeviceFeatureActuatorBuilder,
   DeviceFeatureRaw,
   DeviceFeatureSensor,
+  DeviceFeatureSensorBuilder,
   FeatureType,
 };
 pub use device_list::{DeviceListV0, DeviceListV1, DeviceListV2, DeviceListV3, DeviceListV4};
@@ -90,8 +94,8 @@ pub use device_message_info::{
   DeviceMessageInfoV3,
   DeviceMessageInfoV4,
 };
-pub use device_removed::DeviceRemovedV0;
-pub use endpoint::Endpoint;
+pub use device_removed::{DeviceRemovedV0, DeviceRemovedV4};
+pub use endpoint::{Endpoint, UnknownEndpointError};
 pub use error::{ErrorCode, ErrorV0};
 pub use fleshlight_launch_fw12_cmd::FleshlightLaunchFW12CmdV0;
 pub use kiiroo_cmd::KiirooCmdV0;
@@ -128,11 +132,17 @@ pub use vibrate_cmd::{VibrateCmdV1, VibrateSubcommandV1};
 pub use vorze_a10_cyclone_cmd::VorzeA10CycloneCmdV0;
 
 use crate::core::errors::ButtplugMessageError;
+#[cfg(feature = "message-serde")]
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use std::cmp::Ordering;
+use std::collections::HashSet;
 use std::convert::TryFrom;
+use std::str::FromStr;
+use strum::IntoEnumIterator;
+use thiserror::Error;
 
 use super::errors::ButtplugError;
 
@@ -140,7 +150,7 @@ use super::errors::ButtplugError;
 /// Spec](https://buttplug-spec.docs.buttplug.io) versions.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Display)]
 #[repr(u32)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize_repr, Deserialize_repr))]
+#[cfg_attr(feature = "message-serde", derive(Serialize_repr, Deserialize_repr))]
 pub enum ButtplugMessageSpecVersion {
   Version0 = 0,
   Version1 = 1,
@@ -175,9 +185,28 @@ impl TryFrom<i32> for ButtplugMessageSpecVersion {
 /// client request.
 pub const BUTTPLUG_SERVER_EVENT_ID: u32 = 0;
 
-/// The current latest version of the spec implemented by the library.
-pub const BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION: ButtplugMessageSpecVersion =
-  ButtplugMessageSpecVersion::Version3;
+// The spec version constant and the current message aliases have to move together, so they are all
+// generated from the single version number passed in here.
+macro_rules! current_message_spec_version {
+  ($version:literal) => {
+    paste::paste! {
+      /// The current latest version of the spec implemented by the library.
+      ///
+      /// Version4 messages exist and are handled by the server, but connections at that version are
+      /// only accepted with the `allow-unstable-v4-connections` feature until the client is ported
+      /// over to the v4 message set.
+      pub const BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION: ButtplugMessageSpecVersion =
+        ButtplugMessageSpecVersion::[<Version $version>];
+
+      /// Type alias for the latest version of client-to-server messages.
+      pub type ButtplugClientMessageCurrent = [<ButtplugClientMessageV $version>];
+      /// Type alias for the latest version of server-to-client messages.
+      pub type ButtplugServerMessageCurrent = [<ButtplugServerMessageV $version>];
+    }
+  };
+}
+
+current_message_spec_version!(3);
 
 pub trait ButtplugMessageFinalizer {
   fn finalize(&mut self) {
@@ -198,6 +227,44 @@ pub trait ButtplugMessage:
   fn is_server_event(&self) -> bool {
     self.id() == BUTTPLUG_SERVER_EVENT_ID
   }
+  /// Serializes the message to a JSON string. For message enums, this is the single object form
+  /// (i.e. `{"Ok":{"Id":1}}`), not the array wrapped form used on the wire.
+  #[cfg(feature = "serialize-json")]
+  fn to_json(&self) -> Result<String, serde_json::Error>
+  where
+    Self: Serialize,
+  {
+    serde_json::to_string(self)
+  }
+  /// Deserializes a message from a JSON string produced by [ButtplugMessage::to_json]. This does
+  /// not run schema validation, use a
+  /// [ButtplugMessageSerializer](crate::core::message::serializer::ButtplugMessageSerializer) for
+  /// messages coming in from outside the library.
+  #[cfg(feature = "serialize-json")]
+  fn from_json(json: &str) -> Result<Self, serde_json::Error>
+  where
+    Self: DeserializeOwned,
+  {
+    serde_json::from_str(json)
+  }
+  /// Serializes the message to CBOR. Like [ButtplugMessage::to_json], message enums are encoded in
+  /// the single object form, but map keys are replaced with the integers from [cbor].
+  #[cfg(feature = "serialize-cbor")]
+  fn to_cbor(&self) -> Result<Vec<u8>, ciborium::ser::Error<std::io::Error>>
+  where
+    Self: Serialize,
+  {
+    cbor::to_cbor(self)
+  }
+  /// Deserializes a message from CBOR produced by [ButtplugMessage::to_cbor]. Like
+  /// [ButtplugMessage::from_json], this does not run schema validation.
+  #[cfg(feature = "serialize-cbor")]
+  fn from_cbor(bytes: &[u8]) -> Result<Self, ciborium::de::Error<std::io::Error>>
+  where
+    Self: DeserializeOwned,
+  {
+    cbor::from_cbor(bytes)
+  }
 }
 
 /// Validation function for message contents. Can be run before message is
@@ -231,6 +298,9 @@ pub trait ButtplugMessageValidator {
     }
   }
 
+  #[deprecated(
+    note = "Use is_in_command_range_named, which formats the error message consistently"
+  )]
   fn is_in_command_range(&self, value: f64, error_msg: String) -> Result<(), ButtplugMessageError> {
     if !(0.0..=1.0).contains(&value) {
       Err(ButtplugMessageError::InvalidMessageContents(error_msg))
@@ -238,6 +308,40 @@ pub trait ButtplugMessageValidator {
       Ok(())
     }
   }
+
+  /// Checks that `value` is in the 0.0-1.0 range used for actuator commands and levels. On failure,
+  /// the error names `field_name`, so it should say which message and subcommand index the value
+  /// came from (e.g. "ScalarCmd index 1 Scalar").
+  fn is_in_command_range_named(
+    &self,
+    field_name: &str,
+    value: f64,
+  ) -> Result<(), ButtplugMessageError> {
+    if !(0.0..=1.0).contains(&value) {
+      Err(ButtplugMessageError::InvalidMessageContents(format!(
+        "Field {field_name} has value {value:.4} which is not in range [0.0, 1.0]"
+      )))
+    } else {
+      Ok(())
+    }
+  }
+
+  fn has_unique_indexes(
+    &self,
+    indexes: impl IntoIterator<Item = u32>,
+    message_name: &str,
+  ) -> Result<(), ButtplugMessageError> {
+    let mut seen = HashSet::new();
+    for index in indexes {
+      if !seen.insert(index) {
+        return Err(ButtplugMessageError::InvalidMessageContents(format!(
+          "{} has multiple subcommands for index {}, each index should only be used once",
+          message_name, index
+        )));
+      }
+    }
+    Ok(())
+  }
 }
 
 /// Adds device index handling to the [ButtplugMessage] trait.
@@ -248,7 +352,7 @@ pub trait ButtplugDeviceMessage: ButtplugMessage {
 
 /// Used in [MessageAttributes][crate::core::messages::DeviceMessageAttributes] for denoting message
 /// capabilties.
-#[derive(Copy, Debug, Clone, PartialEq, Eq, Hash, Display, Serialize, Deserialize)]
+#[derive(Copy, Debug, Clone, PartialEq, Eq, Hash, Display, EnumIter, Serialize, Deserialize)]
 pub enum ButtplugDeviceMessageType {
   VibrateCmd,
   LinearCmd,
@@ -273,6 +377,33 @@ pub enum ButtplugDeviceMessageType {
   VorzeA10CycloneCmd,
 }
 
+/// Message type name {0} is not a known device message type
+#[derive(Debug, Clone, PartialEq, Eq, Error, displaydoc::Display)]
+pub struct UnknownMessageTypeError(pub String);
+
+impl FromStr for ButtplugDeviceMessageType {
+  type Err = UnknownMessageTypeError;
+
+  /// Parses message type names as produced by [Display](std::fmt::Display) (e.g. `"ScalarCmd"`).
+  /// Matching is case sensitive, see [ButtplugDeviceMessageType::from_str_case_insensitive] for
+  /// lenient parsing.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    ButtplugDeviceMessageType::iter()
+      .find(|message_type| message_type.to_string() == s)
+      .ok_or_else(|| UnknownMessageTypeError(s.to_owned()))
+  }
+}
+
+impl ButtplugDeviceMessageType {
+  /// Same as [FromStr::from_str], but ignores ASCII case, so `"scalarcmd"` also parses to
+  /// [ButtplugDeviceMessageType::ScalarCmd].
+  pub fn from_str_case_insensitive(s: &str) -> Result<Self, UnknownMessageTypeError> {
+    ButtplugDeviceMessageType::iter()
+      .find(|message_type| message_type.to_string().eq_ignore_ascii_case(s))
+      .ok_or_else(|| UnknownMessageTypeError(s.to_owned()))
+  }
+}
+
 // Ordering for ButtplugDeviceMessageType should be lexicographic, for
 // serialization reasons.
 impl PartialOrd for ButtplugDeviceMessageType {
@@ -294,6 +425,27 @@ pub enum ButtplugActuatorFeatureMessageType {
   LinearCmd,
 }
 
+impl ButtplugActuatorFeatureMessageType {
+  /// Returns the actuator types a feature can be commanded as using this message type. ScalarCmd
+  /// can carry any known actuator type, while RotateCmd only handles Rotate and LinearCmd only
+  /// handles Position.
+  pub fn valid_actuator_types(&self) -> &'static [ActuatorType] {
+    match self {
+      ButtplugActuatorFeatureMessageType::ScalarCmd => &[
+        ActuatorType::Vibrate,
+        ActuatorType::Rotate,
+        ActuatorType::Oscillate,
+        ActuatorType::Constrict,
+        ActuatorType::Inflate,
+        ActuatorType::Squeeze,
+        ActuatorType::Position,
+      ],
+      ButtplugActuatorFeatureMessageType::RotateCmd => &[ActuatorType::Rotate],
+      ButtplugActuatorFeatureMessageType::LinearCmd => &[ActuatorType::Position],
+    }
+  }
+}
+
 impl From<ButtplugActuatorFeatureMessageType> for ButtplugDeviceMessageType {
   fn from(value: ButtplugActuatorFeatureMessageType) -> Self {
     match value {
@@ -374,7 +526,8 @@ impl TryFrom<ButtplugDeviceMessageType> for ButtplugRawFeatureMessageType {
     match value {
       ButtplugDeviceMessageType::RawReadCmd => Ok(ButtplugRawFeatureMessageType::RawReadCmd),
       ButtplugDeviceMessageType::RawWriteCmd => Ok(ButtplugRawFeatureMessageType::RawWriteCmd),
-      ButtplugDeviceMessageType::RawSubscribeCmd => {
+      // Unsubscription is allowed anywhere subscription is, so it maps to the same feature type.
+      ButtplugDeviceMessageType::RawSubscribeCmd | ButtplugDeviceMessageType::RawUnsubscribeCmd => {
         Ok(ButtplugRawFeatureMessageType::RawSubscribeCmd)
       }
       _ => Err(()),
@@ -488,6 +641,23 @@ impl From<ButtplugServerMessageV4> for ButtplugServerMessageVariant {
   }
 }
 
+impl TryFrom<ButtplugServerMessageVariant> for ButtplugServerMessageV4 {
+  type Error = ButtplugMessageError;
+
+  /// Older spec messages lose information V4 needs (feature indexes, sensor ranges, etc...), so
+  /// only messages that are already V4 can be converted.
+  fn try_from(value: ButtplugServerMessageVariant) -> Result<Self, ButtplugMessageError> {
+    match value {
+      ButtplugServerMessageVariant::V4(msg) => Ok(msg),
+      _ => Err(ButtplugMessageError::MessageConversionError(format!(
+        "Cannot convert message {:?} from {} to Version4.",
+        value,
+        value.version()
+      ))),
+    }
+  }
+}
+
 /// Represents all possible messages a [ButtplugServer][crate::server::ButtplugServer] can send to a
 /// [ButtplugClient][crate::client::ButtplugClient] that denote an EVENT from a device. These are
 /// only used in notifications, so read requests will not need to be added here, only messages that
@@ -520,22 +690,11 @@ impl From<ButtplugServerDeviceMessage> for ButtplugServerMessageV4 {
   }
 }
 
-/// Type alias for the latest version of client-to-server messages.
-pub type ButtplugClientMessageCurrent = ButtplugClientMessageV3;
-/// Type alias for the latest version of server-to-client messages.
-pub type ButtplugServerMessageCurrent = ButtplugServerMessageV3;
-
 /// Represents all client-to-server messages in v3 of the Buttplug Spec
 #[derive(
-  Debug,
-  Clone,
-  PartialEq,
-  ButtplugMessage,
-  ButtplugMessageValidator,
-  ButtplugMessageFinalizer,
-  FromSpecificButtplugMessage,
+  Debug, Clone, PartialEq, ButtplugMessage, ButtplugMessageValidator, FromSpecificButtplugMessage,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub enum ButtplugClientMessageV4 {
   // Handshake messages
   RequestServerInfo(RequestServerInfoV1),
@@ -560,11 +719,19 @@ pub enum ButtplugClientMessageV4 {
   SensorUnsubscribeCmd(SensorUnsubscribeCmdV4),
 }
 
+impl ButtplugMessageFinalizer for ButtplugClientMessageV4 {
+  fn finalize(&mut self) {
+    if let ButtplugClientMessageV4::ScalarCmd(msg) = self {
+      msg.finalize();
+    }
+  }
+}
+
 /// Represents all server-to-client messages in v3 of the Buttplug Spec
 #[derive(
   Debug, Clone, PartialEq, ButtplugMessage, ButtplugMessageValidator, FromSpecificButtplugMessage,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub enum ButtplugServerMessageV4 {
   // Status messages
   Ok(OkV0),
@@ -574,7 +741,7 @@ pub enum ButtplugServerMessageV4 {
   // Device enumeration messages
   DeviceList(DeviceListV4),
   DeviceAdded(DeviceAddedV4),
-  DeviceRemoved(DeviceRemovedV0),
+  DeviceRemoved(DeviceRemovedV4),
   ScanningFinished(ScanningFinishedV0),
   // Generic commands
   RawReading(RawReadingV2),
@@ -594,15 +761,9 @@ impl ButtplugMessageFinalizer for ButtplugServerMessageV4 {
 
 /// Represents all client-to-server messages in v3 of the Buttplug Spec
 #[derive(
-  Debug,
-  Clone,
-  PartialEq,
-  ButtplugMessage,
-  ButtplugMessageValidator,
-  ButtplugMessageFinalizer,
-  FromSpecificButtplugMessage,
+  Debug, Clone, PartialEq, ButtplugMessage, ButtplugMessageValidator, FromSpecificButtplugMessage,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub enum ButtplugClientMessageV3 {
   // Handshake messages
   RequestServerInfo(RequestServerInfoV1),
@@ -628,11 +789,19 @@ pub enum ButtplugClientMessageV3 {
   SensorUnsubscribeCmd(SensorUnsubscribeCmdV3),
 }
 
+impl ButtplugMessageFinalizer for ButtplugClientMessageV3 {
+  fn finalize(&mut self) {
+    if let ButtplugClientMessageV3::ScalarCmd(msg) = self {
+      msg.finalize();
+    }
+  }
+}
+
 /// Represents all server-to-client messages in v3 of the Buttplug Spec
 #[derive(
   Debug, Clone, PartialEq, ButtplugMessage, ButtplugMessageValidator, FromSpecificButtplugMessage,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub enum ButtplugServerMessageV3 {
   // Status messages
   Ok(OkV0),
@@ -670,7 +839,7 @@ impl ButtplugMessageFinalizer for ButtplugServerMessageV3 {
   ButtplugMessageFinalizer,
   FromSpecificButtplugMessage,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub enum ButtplugClientMessageV2 {
   // Handshake messages
   RequestServerInfo(RequestServerInfoV1),
@@ -704,7 +873,7 @@ pub enum ButtplugClientMessageV2 {
   ButtplugMessageFinalizer,
   FromSpecificButtplugMessage,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub enum ButtplugServerMessageV2 {
   // Status messages
   Ok(OkV0),
@@ -733,12 +902,13 @@ pub enum ButtplugServerMessageV2 {
   ButtplugMessageFinalizer,
   FromSpecificButtplugMessage,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub enum ButtplugClientMessageV1 {
   // Handshake and server messages
   RequestServerInfo(RequestServerInfoV1),
   Ping(PingV0),
   RequestLog(RequestLogV0),
+  Test(TestV0),
   // Device enumeration messages
   StartScanning(StartScanningV0),
   StopScanning(StopScanningV0),
@@ -768,12 +938,13 @@ pub enum ButtplugClientMessageV1 {
   ButtplugMessageFinalizer,
   FromSpecificButtplugMessage,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub enum ButtplugServerMessageV1 {
   // Status messages
   Ok(OkV0),
   Error(ErrorV0),
   Log(LogV0),
+  Test(TestV0),
   // Handshake messages
   ServerInfo(ServerInfoV0),
   // Device enumeration messages
@@ -793,10 +964,11 @@ pub enum ButtplugServerMessageV1 {
   ButtplugMessageFinalizer,
   FromSpecificButtplugMessage,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub enum ButtplugClientMessageV0 {
   RequestLog(RequestLogV0),
   Ping(PingV0),
+  Test(TestV0),
   // Handshake messages
   //
   // We use RequestServerInfoV1 here, as the only difference between v0 and v1 was passing the spec
@@ -822,12 +994,13 @@ pub enum ButtplugClientMessageV0 {
 #[derive(
   Debug, Clone, PartialEq, ButtplugMessage, ButtplugMessageValidator, ButtplugMessageFinalizer,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub enum ButtplugServerMessageV0 {
   // Status messages
   Ok(OkV0),
   Error(ErrorV0),
   Log(LogV0),
+  Test(TestV0),
   // Handshake messages
   ServerInfo(ServerInfoV0),
   // Device enumeration messages
@@ -889,7 +1062,7 @@ impl TryFrom<ButtplugClientMessageV4> for ButtplugDeviceManagerMessageUnion {
   ButtplugMessageFinalizer,
   FromSpecificButtplugMessage,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub enum ButtplugDeviceCommandMessageUnion {
   StopDeviceCmd(StopDeviceCmdV0),
   LinearCmd(LinearCmdV4),
@@ -940,3 +1113,121 @@ impl TryFrom<ButtplugClientMessageV4> for ButtplugDeviceCommandMessageUnion {
     }
   }
 }
+
+// The union only carries current spec message types (older versions are upgraded before they get
+// here), so unlike the other direction, this conversion can't fail.
+impl From<ButtplugDeviceCommandMessageUnion> for ButtplugClientMessageV4 {
+  fn from(value: ButtplugDeviceCommandMessageUnion) -> Self {
+    match value {
+      ButtplugDeviceCommandMessageUnion::StopDeviceCmd(m) => {
+        ButtplugClientMessageV4::StopDeviceCmd(m)
+      }
+      ButtplugDeviceCommandMessageUnion::LinearCmd(m) => ButtplugClientMessageV4::LinearCmd(m),
+      ButtplugDeviceCommandMessageUnion::RotateCmd(m) => ButtplugClientMessageV4::RotateCmd(m),
+      ButtplugDeviceCommandMessageUnion::ScalarCmd(m) => ButtplugClientMessageV4::ScalarCmd(m),
+      ButtplugDeviceCommandMessageUnion::SensorReadCmd(m) => {
+        ButtplugClientMessageV4::SensorReadCmd(m)
+      }
+      ButtplugDeviceCommandMessageUnion::SensorSubscribeCmd(m) => {
+        ButtplugClientMessageV4::SensorSubscribeCmd(m)
+      }
+      ButtplugDeviceCommandMessageUnion::SensorUnsubscribeCmd(m) => {
+        ButtplugClientMessageV4::SensorUnsubscribeCmd(m)
+      }
+      ButtplugDeviceCommandMessageUnion::RawWriteCmd(m) => ButtplugClientMessageV4::RawWriteCmd(m),
+      ButtplugDeviceCommandMessageUnion::RawReadCmd(m) => ButtplugClientMessageV4::RawReadCmd(m),
+      ButtplugDeviceCommandMessageUnion::RawSubscribeCmd(m) => {
+        ButtplugClientMessageV4::RawSubscribeCmd(m)
+      }
+      ButtplugDeviceCommandMessageUnion::RawUnsubscribeCmd(m) => {
+        ButtplugClientMessageV4::RawUnsubscribeCmd(m)
+      }
+    }
+  }
+}
+
+impl From<&ButtplugDeviceCommandMessageUnion> for ButtplugDeviceMessageType {
+  fn from(value: &ButtplugDeviceCommandMessageUnion) -> Self {
+    match value {
+      ButtplugDeviceCommandMessageUnion::StopDeviceCmd(_) => {
+        ButtplugDeviceMessageType::StopDeviceCmd
+      }
+      ButtplugDeviceCommandMessageUnion::LinearCmd(_) => ButtplugDeviceMessageType::LinearCmd,
+      ButtplugDeviceCommandMessageUnion::RotateCmd(_) => ButtplugDeviceMessageType::RotateCmd,
+      ButtplugDeviceCommandMessageUnion::ScalarCmd(_) => ButtplugDeviceMessageType::ScalarCmd,
+      ButtplugDeviceCommandMessageUnion::SensorReadCmd(_) => {
+        ButtplugDeviceMessageType::SensorReadCmd
+      }
+      ButtplugDeviceCommandMessageUnion::SensorSubscribeCmd(_) => {
+        ButtplugDeviceMessageType::SensorSubscribeCmd
+      }
+      ButtplugDeviceCommandMessageUnion::SensorUnsubscribeCmd(_) => {
+        ButtplugDeviceMessageType::SensorUnsubscribeCmd
+      }
+      ButtplugDeviceCommandMessageUnion::RawWriteCmd(_) => ButtplugDeviceMessageType::RawWriteCmd,
+      ButtplugDeviceCommandMessageUnion::RawReadCmd(_) => ButtplugDeviceMessageType::RawReadCmd,
+      ButtplugDeviceCommandMessageUnion::RawSubscribeCmd(_) => {
+        ButtplugDeviceMessageType::RawSubscribeCmd
+      }
+      ButtplugDeviceCommandMessageUnion::RawUnsubscribeCmd(_) => {
+        ButtplugDeviceMessageType::RawUnsubscribeCmd
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{
+    ActuatorType,
+    ButtplugActuatorFeatureMessageType,
+    ButtplugDeviceMessageType,
+    UnknownMessageTypeError,
+  };
+  use strum::IntoEnumIterator;
+
+  #[test]
+  fn test_actuator_type_message_type_round_trip() {
+    for actuator_type in ActuatorType::iter().filter(|x| *x != ActuatorType::Unknown) {
+      assert!(actuator_type
+        .message_type()
+        .valid_actuator_types()
+        .contains(&actuator_type));
+      // Every known actuator type can also be sent via ScalarCmd.
+      assert!(ButtplugActuatorFeatureMessageType::ScalarCmd
+        .valid_actuator_types()
+        .contains(&actuator_type));
+    }
+    assert_eq!(
+      ActuatorType::Vibrate.message_type(),
+      ButtplugActuatorFeatureMessageType::ScalarCmd
+    );
+    assert!(!ButtplugActuatorFeatureMessageType::LinearCmd
+      .valid_actuator_types()
+      .contains(&ActuatorType::Vibrate));
+  }
+
+  #[test]
+  fn test_device_message_type_string_round_trip() {
+    for message_type in ButtplugDeviceMessageType::iter() {
+      let name = message_type.to_string();
+      assert_eq!(name.parse::<ButtplugDeviceMessageType>(), Ok(message_type));
+      assert_eq!(
+        ButtplugDeviceMessageType::from_str_case_insensitive(&name.to_lowercase()),
+        Ok(message_type)
+      );
+    }
+  }
+
+  #[test]
+  fn test_device_message_type_unknown_name() {
+    assert_eq!(
+      "scalarcmd".parse::<ButtplugDeviceMessageType>(),
+      Err(UnknownMessageTypeError("scalarcmd".to_owned()))
+    );
+    assert_eq!(
+      ButtplugDeviceMessageType::from_str_case_insensitive("NotACmd"),
+      Err(UnknownMessageTypeError("NotACmd".to_owned()))
+    );
+  }
+}
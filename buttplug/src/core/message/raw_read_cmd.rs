@@ -7,25 +7,25 @@
 
 use super::*;
 use getset::CopyGetters;
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde::{Deserialize, Serialize};
 
 #[derive(
   Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Eq, Clone, CopyGetters,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct RawReadCmdV2 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Endpoint"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Endpoint"))]
   #[getset(get_copy = "pub")]
   endpoint: Endpoint,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "ExpectedLength"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "ExpectedLength"))]
   #[getset(get_copy = "pub")]
   expected_length: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Timeout"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Timeout"))]
   #[getset(get_copy = "pub")]
   timeout: u32,
 }
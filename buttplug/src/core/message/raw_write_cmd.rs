@@ -7,25 +7,25 @@
 
 use super::*;
 use getset::{CopyGetters, Getters};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde::{Deserialize, Serialize};
 
 #[derive(
   Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Eq, Clone, Getters, CopyGetters,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct RawWriteCmdV2 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Endpoint"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Endpoint"))]
   #[getset(get_copy = "pub")]
   endpoint: Endpoint,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Data"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Data"))]
   #[getset(get = "pub")]
   data: Vec<u8>,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "WriteWithResponse"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "WriteWithResponse"))]
   #[getset(get_copy = "pub")]
   write_with_response: bool,
 }
@@ -7,26 +7,29 @@
 
 use super::*;
 use getset::{CopyGetters, Getters};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde::{Deserialize, Serialize};
 
+/// Longest client name, in bytes, that a server will accept.
+pub const MAX_CLIENT_NAME_LENGTH: usize = 1024;
+
 fn return_version0() -> ButtplugMessageSpecVersion {
   ButtplugMessageSpecVersion::Version0
 }
 #[derive(
   Debug, ButtplugMessage, ButtplugMessageFinalizer, Clone, PartialEq, Eq, Getters, CopyGetters,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct RequestServerInfoV1 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "ClientName"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "ClientName"))]
   #[getset(get = "pub")]
   client_name: String,
   // Default for this message is set to 0, as this field didn't exist in the
   // first version of the protocol.
   #[cfg_attr(
-    feature = "serialize-json",
+    feature = "message-serde",
     serde(rename = "MessageVersion"),
     serde(default = "return_version0")
   )]
@@ -46,13 +49,50 @@ impl RequestServerInfoV1 {
 
 impl ButtplugMessageValidator for RequestServerInfoV1 {
   fn is_valid(&self) -> Result<(), ButtplugMessageError> {
-    self.is_not_system_id(self.id)
+    self.is_not_system_id(self.id)?;
+    if self.client_name.len() > MAX_CLIENT_NAME_LENGTH {
+      return Err(ButtplugMessageError::InvalidMessageContents(format!(
+        "Client name is {} bytes long, but must be {} bytes or less.",
+        self.client_name.len(),
+        MAX_CLIENT_NAME_LENGTH
+      )));
+    }
+    if self.client_name.contains('\0') {
+      return Err(ButtplugMessageError::InvalidMessageContents(
+        "Client name cannot contain null bytes.".to_owned(),
+      ));
+    }
+    Ok(())
   }
 }
 
 #[cfg(test)]
 mod test {
-  use super::{ButtplugMessageSpecVersion, RequestServerInfoV1};
+  use super::{
+    ButtplugMessageSpecVersion,
+    ButtplugMessageValidator,
+    RequestServerInfoV1,
+    MAX_CLIENT_NAME_LENGTH,
+  };
+
+  #[test]
+  fn test_request_server_info_client_name_length() {
+    let version = ButtplugMessageSpecVersion::Version3;
+    let name = "a".repeat(MAX_CLIENT_NAME_LENGTH);
+    assert!(RequestServerInfoV1::new(&name, version).is_valid().is_ok());
+    let name = "a".repeat(MAX_CLIENT_NAME_LENGTH + 1);
+    assert!(RequestServerInfoV1::new(&name, version).is_valid().is_err());
+    assert!(RequestServerInfoV1::new("", version).is_valid().is_ok());
+  }
+
+  #[test]
+  fn test_request_server_info_client_name_null_bytes() {
+    let version = ButtplugMessageSpecVersion::Version3;
+    assert!(RequestServerInfoV1::new("Test\0Client", version)
+      .is_valid()
+      .is_err());
+    assert!(RequestServerInfoV1::new("\0", version).is_valid().is_err());
+  }
 
   #[cfg(feature = "serialize-json")]
   #[test]
@@ -7,18 +7,18 @@
 
 use super::*;
 pub use getset::{CopyGetters, Getters};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, PartialEq, Clone, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 #[getset(get_copy = "pub")]
 pub struct RotationSubcommandV4 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Index"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Index"))]
   feature_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Speed"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Speed"))]
   speed: f64,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Clockwise"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Clockwise"))]
   clockwise: bool,
 }
 
@@ -32,15 +32,24 @@ impl RotationSubcommandV4 {
   }
 }
 
+impl ButtplugMessageValidator for RotationSubcommandV4 {
+  fn is_valid(&self) -> Result<(), ButtplugMessageError> {
+    self.is_in_command_range_named(
+      &format!("RotateCmd index {} Speed", self.feature_index),
+      self.speed,
+    )
+  }
+}
+
 #[derive(Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Clone, Getters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct RotateCmdV4 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   device_index: u32,
   #[getset(get = "pub")]
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Rotations"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Rotations"))]
   #[getset(get = "pub")]
   rotations: Vec<RotationSubcommandV4>,
 }
@@ -59,27 +68,22 @@ impl ButtplugMessageValidator for RotateCmdV4 {
   fn is_valid(&self) -> Result<(), ButtplugMessageError> {
     self.is_not_system_id(self.id)?;
     for rotation in &self.rotations {
-      self.is_in_command_range(
-        rotation.speed,
-        format!(
-          "Speed {} for RotateCmd index {} is invalid. Speed should be a value between 0.0 and 1.0",
-          rotation.speed, rotation.feature_index
-        ),
-      )?;
+      rotation.is_valid()?;
     }
+    self.has_unique_indexes(self.rotations.iter().map(|x| x.feature_index), "RotateCmd")?;
     Ok(())
   }
 }
 
 #[derive(Debug, PartialEq, Clone, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 #[getset(get_copy = "pub")]
 pub struct RotationSubcommandV1 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Index"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Index"))]
   index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Speed"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Speed"))]
   speed: f64,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Clockwise"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Clockwise"))]
   clockwise: bool,
 }
 
@@ -93,15 +97,21 @@ impl RotationSubcommandV1 {
   }
 }
 
+impl ButtplugMessageValidator for RotationSubcommandV1 {
+  fn is_valid(&self) -> Result<(), ButtplugMessageError> {
+    self.is_in_command_range_named(&format!("RotateCmd index {} Speed", self.index), self.speed)
+  }
+}
+
 #[derive(Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Clone, Getters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct RotateCmdV1 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   device_index: u32,
   #[getset(get = "pub")]
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Rotations"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Rotations"))]
   #[getset(get = "pub")]
   rotations: Vec<RotationSubcommandV1>,
 }
@@ -120,14 +130,9 @@ impl ButtplugMessageValidator for RotateCmdV1 {
   fn is_valid(&self) -> Result<(), ButtplugMessageError> {
     self.is_not_system_id(self.id)?;
     for rotation in &self.rotations {
-      self.is_in_command_range(
-        rotation.speed,
-        format!(
-          "Speed {} for RotateCmd index {} is invalid. Speed should be a value between 0.0 and 1.0",
-          rotation.speed, rotation.index
-        ),
-      )?;
+      rotation.is_valid()?;
     }
+    self.has_unique_indexes(self.rotations.iter().map(|x| x.index), "RotateCmd")?;
     Ok(())
   }
 }
@@ -7,19 +7,20 @@
 
 use super::*;
 use getset::{CopyGetters, Getters};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 /// Generic command for setting a level (single magnitude value) of a device feature.
 #[derive(Debug, PartialEq, Clone, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 #[getset(get_copy = "pub")]
 pub struct ScalarSubcommandV4 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Index"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Index"))]
   feature_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Scalar"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Scalar"))]
   scalar: f64,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "ActuatorType"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "ActuatorType"))]
   actuator_type: ActuatorType,
 }
 
@@ -33,16 +34,14 @@ impl ScalarSubcommandV4 {
   }
 }
 
-#[derive(
-  Debug, Default, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Clone, Getters,
-)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[derive(Debug, Default, ButtplugDeviceMessage, PartialEq, Clone, Getters)]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct ScalarCmdV4 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Scalars"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Scalars"))]
   #[getset(get = "pub")]
   scalars: Vec<ScalarSubcommandV4>,
 }
@@ -61,28 +60,39 @@ impl ButtplugMessageValidator for ScalarCmdV4 {
   fn is_valid(&self) -> Result<(), ButtplugMessageError> {
     self.is_not_system_id(self.id)?;
     for level in &self.scalars {
-      self.is_in_command_range(
+      self.is_in_command_range_named(
+        &format!("ScalarCmd index {} Scalar", level.feature_index),
         level.scalar,
-        format!(
-          "Level {} for ScalarCmd feature index {} is invalid. Level should be a value between 0.0 and 1.0",
-          level.scalar, level.feature_index
-        ),
       )?;
     }
+    self.has_unique_indexes(self.scalars.iter().map(|x| x.feature_index), "ScalarCmd")?;
     Ok(())
   }
 }
 
+impl ButtplugMessageFinalizer for ScalarCmdV4 {
+  // Some clients send multiple subcommands for the same index, expecting the last one to win. Fold
+  // those down so the message will pass validation.
+  fn finalize(&mut self) {
+    let scalars: BTreeMap<u32, ScalarSubcommandV4> = self
+      .scalars
+      .drain(..)
+      .map(|x| (x.feature_index, x))
+      .collect();
+    self.scalars = scalars.into_values().collect();
+  }
+}
+
 /// Generic command for setting a level (single magnitude value) of a device feature.
 #[derive(Debug, PartialEq, Clone, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 #[getset(get_copy = "pub")]
 pub struct ScalarSubcommandV3 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Index"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Index"))]
   index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Scalar"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Scalar"))]
   scalar: f64,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "ActuatorType"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "ActuatorType"))]
   actuator_type: ActuatorType,
 }
 
@@ -96,16 +106,14 @@ impl ScalarSubcommandV3 {
   }
 }
 
-#[derive(
-  Debug, Default, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Clone, Getters,
-)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[derive(Debug, Default, ButtplugDeviceMessage, PartialEq, Clone, Getters)]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct ScalarCmdV3 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Scalars"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Scalars"))]
   #[getset(get = "pub")]
   scalars: Vec<ScalarSubcommandV3>,
 }
@@ -124,14 +132,129 @@ impl ButtplugMessageValidator for ScalarCmdV3 {
   fn is_valid(&self) -> Result<(), ButtplugMessageError> {
     self.is_not_system_id(self.id)?;
     for level in &self.scalars {
-      self.is_in_command_range(
+      self.is_in_command_range_named(
+        &format!("ScalarCmd index {} Scalar", level.index),
         level.scalar,
-        format!(
-          "Level {} for ScalarCmd index {} is invalid. Level should be a value between 0.0 and 1.0",
-          level.scalar, level.index
-        ),
       )?;
     }
+    self.has_unique_indexes(self.scalars.iter().map(|x| x.index), "ScalarCmd")?;
     Ok(())
   }
 }
+
+impl ButtplugMessageFinalizer for ScalarCmdV3 {
+  // See ScalarCmdV4::finalize
+  fn finalize(&mut self) {
+    let scalars: BTreeMap<u32, ScalarSubcommandV3> =
+      self.scalars.drain(..).map(|x| (x.index, x)).collect();
+    self.scalars = scalars.into_values().collect();
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::core::{
+    errors::ButtplugMessageError,
+    message::{
+      ActuatorType,
+      ButtplugClientMessageV4,
+      ButtplugMessageFinalizer,
+      ButtplugMessageValidator,
+      ScalarCmdV3,
+      ScalarCmdV4,
+      ScalarSubcommandV3,
+      ScalarSubcommandV4,
+    },
+  };
+
+  #[test]
+  fn test_scalar_cmd_duplicate_index() {
+    let mut msg = ScalarCmdV3::new(
+      0,
+      vec![
+        ScalarSubcommandV3::new(0, 0.5, ActuatorType::Vibrate),
+        ScalarSubcommandV3::new(1, 0.5, ActuatorType::Vibrate),
+      ],
+    );
+    assert!(msg.is_valid().is_ok());
+    msg = ScalarCmdV3::new(
+      0,
+      vec![
+        ScalarSubcommandV3::new(1, 0.5, ActuatorType::Vibrate),
+        ScalarSubcommandV3::new(1, 0.25, ActuatorType::Vibrate),
+      ],
+    );
+    let err = msg.is_valid().unwrap_err();
+    assert!(err.to_string().contains("index 1"));
+  }
+
+  #[test]
+  fn test_scalar_cmd_out_of_range_error_message() {
+    let msg = ScalarCmdV3::new(
+      0,
+      vec![
+        ScalarSubcommandV3::new(0, 0.5, ActuatorType::Vibrate),
+        ScalarSubcommandV3::new(1, 1.5, ActuatorType::Vibrate),
+      ],
+    );
+    assert_eq!(
+      msg.is_valid().unwrap_err(),
+      ButtplugMessageError::InvalidMessageContents(
+        "Field ScalarCmd index 1 Scalar has value 1.5000 which is not in range [0.0, 1.0]"
+          .to_owned()
+      )
+    );
+    let msg = ScalarCmdV4::new(
+      0,
+      vec![ScalarSubcommandV4::new(2, -0.25, ActuatorType::Vibrate)],
+    );
+    assert_eq!(
+      msg.is_valid().unwrap_err(),
+      ButtplugMessageError::InvalidMessageContents(
+        "Field ScalarCmd index 2 Scalar has value -0.2500 which is not in range [0.0, 1.0]"
+          .to_owned()
+      )
+    );
+  }
+
+  #[test]
+  fn test_scalar_cmd_finalize_dedupes_indexes() {
+    let mut msg = ScalarCmdV3::new(
+      0,
+      vec![
+        ScalarSubcommandV3::new(1, 0.5, ActuatorType::Vibrate),
+        ScalarSubcommandV3::new(0, 0.5, ActuatorType::Vibrate),
+        ScalarSubcommandV3::new(1, 0.25, ActuatorType::Vibrate),
+      ],
+    );
+    assert!(msg.is_valid().is_err());
+    msg.finalize();
+    assert!(msg.is_valid().is_ok());
+    assert_eq!(
+      *msg.scalars(),
+      vec![
+        ScalarSubcommandV3::new(0, 0.5, ActuatorType::Vibrate),
+        ScalarSubcommandV3::new(1, 0.25, ActuatorType::Vibrate),
+      ]
+    );
+
+    // Finalizing the message enum, like the serializer does, should reach the ScalarCmd too.
+    let mut msg = ButtplugClientMessageV4::from(ScalarCmdV4::new(
+      0,
+      vec![
+        ScalarSubcommandV4::new(0, 0.5, ActuatorType::Vibrate),
+        ScalarSubcommandV4::new(0, 0.75, ActuatorType::Vibrate),
+      ],
+    ));
+    assert!(msg.is_valid().is_err());
+    msg.finalize();
+    assert!(msg.is_valid().is_ok());
+    assert_eq!(
+      msg,
+      ButtplugClientMessageV4::from(ScalarCmdV4::new(
+        0,
+        vec![ScalarSubcommandV4::new(0, 0.75, ActuatorType::Vibrate)]
+      ))
+    );
+  }
+}
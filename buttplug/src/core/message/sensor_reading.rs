@@ -7,7 +7,7 @@
 
 use super::*;
 use getset::{CopyGetters, Getters};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde::{Deserialize, Serialize};
 
 // This message can have an Id of 0, as it can be emitted as part of a
@@ -23,19 +23,19 @@ use serde::{Deserialize, Serialize};
   PartialEq,
   Eq,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct SensorReadingV4 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "FeatureIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "FeatureIndex"))]
   #[getset[get_copy="pub"]]
   feature_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "SensorType"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "SensorType"))]
   #[getset[get_copy="pub"]]
   sensor_type: SensorType,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Data"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Data"))]
   #[getset[get="pub"]]
   data: Vec<i32>,
 }
@@ -70,19 +70,19 @@ impl SensorReadingV4 {
   PartialEq,
   Eq,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct SensorReadingV3 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "SensorIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "SensorIndex"))]
   #[getset[get_copy="pub"]]
   sensor_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "SensorType"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "SensorType"))]
   #[getset[get_copy="pub"]]
   sensor_type: SensorType,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Data"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Data"))]
   #[getset[get="pub"]]
   data: Vec<i32>,
 }
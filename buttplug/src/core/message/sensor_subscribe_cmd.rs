@@ -7,21 +7,21 @@
 
 use super::*;
 use getset::Getters;
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Eq, Clone, Getters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct SensorSubscribeCmdV4 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   device_index: u32,
   #[getset(get = "pub")]
-  #[cfg_attr(feature = "serialize-json", serde(rename = "FeatureIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "FeatureIndex"))]
   feature_index: u32,
   #[getset(get = "pub")]
-  #[cfg_attr(feature = "serialize-json", serde(rename = "SensorType"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "SensorType"))]
   sensor_type: SensorType,
 }
 
@@ -43,17 +43,17 @@ impl ButtplugMessageValidator for SensorSubscribeCmdV4 {
 }
 
 #[derive(Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Eq, Clone, Getters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct SensorSubscribeCmdV3 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   device_index: u32,
   #[getset(get = "pub")]
-  #[cfg_attr(feature = "serialize-json", serde(rename = "SensorIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "SensorIndex"))]
   sensor_index: u32,
   #[getset(get = "pub")]
-  #[cfg_attr(feature = "serialize-json", serde(rename = "SensorType"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "SensorType"))]
   sensor_type: SensorType,
 }
 
@@ -7,20 +7,20 @@
 
 use super::*;
 use getset::Getters;
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Eq, Clone, Getters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct SensorUnsubscribeCmdV4 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "SensorIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "SensorIndex"))]
   #[getset(get = "pub")]
   feature_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "SensorType"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "SensorType"))]
   #[getset(get = "pub")]
   sensor_type: SensorType,
 }
@@ -43,16 +43,16 @@ impl ButtplugMessageValidator for SensorUnsubscribeCmdV4 {
 }
 
 #[derive(Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Eq, Clone, Getters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct SensorUnsubscribeCmdV3 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "SensorIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "SensorIndex"))]
   #[getset(get = "pub")]
   sensor_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "SensorType"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "SensorType"))]
   #[getset(get = "pub")]
   sensor_type: SensorType,
 }
@@ -412,6 +412,44 @@ mod test {
     assert!(msg.is_err());
   }
 
+  #[test]
+  fn test_message_version_round_trip() {
+    for version in 0..=4 {
+      let spec_version = ButtplugMessageSpecVersion::try_from(version).unwrap();
+      let json = format!(
+        r#"[{{"RequestServerInfo":{{"Id":1,"ClientName":"Test Client","MessageVersion":{}}}}}]"#,
+        version
+      );
+      let serializer = ButtplugServerJSONSerializer::default();
+      let messages = serializer
+        .deserialize(&ButtplugSerializedMessage::Text(json))
+        .expect("Infallible deserialization");
+      assert_eq!(messages.len(), 1);
+      assert_eq!(messages[0].version(), spec_version);
+      let reply = match spec_version {
+        ButtplugMessageSpecVersion::Version0 => {
+          ButtplugServerMessageVariant::V0(ButtplugServerMessageV0::Ok(message::OkV0::new(1)))
+        }
+        ButtplugMessageSpecVersion::Version1 => {
+          ButtplugServerMessageVariant::V1(ButtplugServerMessageV1::Ok(message::OkV0::new(1)))
+        }
+        ButtplugMessageSpecVersion::Version2 => {
+          ButtplugServerMessageVariant::V2(ButtplugServerMessageV2::Ok(message::OkV0::new(1)))
+        }
+        ButtplugMessageSpecVersion::Version3 => {
+          ButtplugServerMessageVariant::V3(ButtplugServerMessageV3::Ok(message::OkV0::new(1)))
+        }
+        ButtplugMessageSpecVersion::Version4 => {
+          ButtplugServerMessageVariant::V4(ButtplugServerMessageV4::Ok(message::OkV0::new(1)))
+        }
+      };
+      assert_eq!(
+        serializer.serialize(&[reply]),
+        ButtplugSerializedMessage::Text("[{\"Ok\":{\"Id\":1}}]".to_owned())
+      );
+    }
+  }
+
   #[test]
   fn test_message_array() {
     let json = r#"[
@@ -543,4 +581,45 @@ mod test {
       }
     }
   }
+
+  #[test]
+  fn test_client_device_list_assigns_attribute_indexes() {
+    let json = r#"[{
+        "DeviceList": {
+          "Id": 1,
+          "Devices": [{
+            "DeviceIndex": 0,
+            "DeviceName": "Test Device",
+            "DeviceMessages": {
+              "RotateCmd": [
+                { "FeatureDescriptor": "", "ActuatorType": "Rotate", "StepCount": 20 },
+                { "FeatureDescriptor": "", "ActuatorType": "Rotate", "StepCount": 20 }
+              ],
+              "LinearCmd": [
+                { "FeatureDescriptor": "", "ActuatorType": "Position", "StepCount": 20 },
+                { "FeatureDescriptor": "", "ActuatorType": "Position", "StepCount": 20 }
+              ],
+              "StopDeviceCmd": {}
+            }
+          }]
+        }
+      }]"#;
+    let serializer = ButtplugClientJSONSerializer::default();
+    let messages = serializer
+      .deserialize(&ButtplugSerializedMessage::Text(json.to_owned()))
+      .expect("Infallible deserialization");
+    let ButtplugServerMessageCurrent::DeviceList(list) = &messages[0] else {
+      panic!("Expected a DeviceList message, got {:?}", messages[0]);
+    };
+    let attrs = list.devices()[0].device_messages();
+    for cmd_attrs in [attrs.rotate_cmd(), attrs.linear_cmd()] {
+      let indexes: Vec<u32> = cmd_attrs
+        .as_ref()
+        .expect("Test, assuming infallible.")
+        .iter()
+        .map(|x| *x.index())
+        .collect();
+      assert_eq!(indexes, vec![0, 1]);
+    }
+  }
 }
@@ -7,23 +7,23 @@
 
 use super::*;
 use getset::{CopyGetters, Getters};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde::{Deserialize, Serialize};
 
 #[derive(
   Debug, ButtplugMessage, ButtplugMessageFinalizer, PartialEq, Eq, Clone, Getters, CopyGetters,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct ServerInfoV2 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "MessageVersion"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "MessageVersion"))]
   #[getset(get_copy = "pub")]
   message_version: ButtplugMessageSpecVersion,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "MaxPingTime"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "MaxPingTime"))]
   #[getset(get_copy = "pub")]
   max_ping_time: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "ServerName"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "ServerName"))]
   #[getset(get = "pub")]
   server_name: String,
 }
@@ -52,26 +52,26 @@ impl ButtplugMessageValidator for ServerInfoV2 {
 #[derive(
   Debug, ButtplugMessage, ButtplugMessageFinalizer, PartialEq, Eq, Clone, Getters, CopyGetters,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct ServerInfoV0 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "MajorVersion"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "MajorVersion"))]
   #[getset(get_copy = "pub")]
   major_version: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "MinorVersion"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "MinorVersion"))]
   #[getset(get_copy = "pub")]
   minor_version: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "BuildVersion"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "BuildVersion"))]
   #[getset(get_copy = "pub")]
   build_version: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "MessageVersion"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "MessageVersion"))]
   #[getset(get_copy = "pub")]
   message_version: ButtplugMessageSpecVersion,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "MaxPingTime"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "MaxPingTime"))]
   #[getset(get_copy = "pub")]
   max_ping_time: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "ServerName"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "ServerName"))]
   #[getset(get = "pub")]
   server_name: String,
 }
@@ -7,17 +7,17 @@
 
 use super::*;
 use getset::CopyGetters;
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Clone, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct SingleMotorVibrateCmdV0 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Speed"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Speed"))]
   #[getset(get_copy = "pub")]
   speed: f64,
 }
@@ -35,12 +35,6 @@ impl SingleMotorVibrateCmdV0 {
 impl ButtplugMessageValidator for SingleMotorVibrateCmdV0 {
   fn is_valid(&self) -> Result<(), ButtplugMessageError> {
     self.is_not_system_id(self.id)?;
-    self.is_in_command_range(
-      self.speed,
-      format!(
-        "SingleMotorVibrateCmd Speed {} is invalid. Valid speeds are 0.0-1.0.",
-        self.speed
-      ),
-    )
+    self.is_in_command_range_named("SingleMotorVibrateCmd Speed", self.speed)
   }
 }
@@ -7,19 +7,19 @@
 
 use super::*;
 use getset::Getters;
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde::{Deserialize, Serialize};
 
 #[derive(
   Debug, Default, ButtplugMessage, ButtplugMessageFinalizer, Clone, PartialEq, Eq, Getters,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct TestV0 {
   /// Message Id, used for matching message pairs in remote connection instances.
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
   /// Test string, which will be echoed back to client when sent to server.
-  #[cfg_attr(feature = "serialize-json", serde(rename = "TestString"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "TestString"))]
   #[getset(get = "pub")]
   test_string: String,
 }
@@ -7,16 +7,16 @@
 
 use super::*;
 use getset::{CopyGetters, Getters};
-#[cfg(feature = "serialize-json")]
+#[cfg(feature = "message-serde")]
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Default, PartialEq, Clone, CopyGetters)]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 #[getset(get_copy = "pub")]
 pub struct VibrateSubcommandV1 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Index"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Index"))]
   index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Speed"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Speed"))]
   speed: f64,
 }
 
@@ -26,16 +26,25 @@ impl VibrateSubcommandV1 {
   }
 }
 
+impl ButtplugMessageValidator for VibrateSubcommandV1 {
+  fn is_valid(&self) -> Result<(), ButtplugMessageError> {
+    self.is_in_command_range_named(
+      &format!("VibrateCmd index {} Speed", self.index),
+      self.speed,
+    )
+  }
+}
+
 #[derive(
   Debug, Default, ButtplugDeviceMessage, ButtplugMessageFinalizer, PartialEq, Clone, Getters,
 )]
-#[cfg_attr(feature = "serialize-json", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "message-serde", derive(Serialize, Deserialize))]
 pub struct VibrateCmdV1 {
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Id"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Id"))]
   id: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "DeviceIndex"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "DeviceIndex"))]
   device_index: u32,
-  #[cfg_attr(feature = "serialize-json", serde(rename = "Speeds"))]
+  #[cfg_attr(feature = "message-serde", serde(rename = "Speeds"))]
   #[getset(get = "pub")]
   speeds: Vec<VibrateSubcommandV1>,
 }
@@ -54,8 +63,9 @@ impl ButtplugMessageValidator for VibrateCmdV1 {
   fn is_valid(&self) -> Result<(), ButtplugMessageError> {
     self.is_not_system_id(self.id)?;
     for speed in &self.speeds {
-      self.is_in_command_range(speed.speed, format!("Speed {} for VibrateCmd index {} is invalid. Speed should be a value between 0.0 and 1.0", speed.speed, speed.index))?;
+      speed.is_valid()?;
     }
+    self.has_unique_indexes(self.speeds.iter().map(|x| x.index), "VibrateCmd")?;
     Ok(())
   }
 }
@@ -29,7 +29,7 @@ impl BaseDeviceDefinition {
   }
 }
 
-#[derive(Serialize, Deserialize, Debug, Getters, CopyGetters, Default, Clone)]
+#[derive(Serialize, Deserialize, Debug, Getters, CopyGetters, Setters, Default, Clone)]
 pub struct UserDeviceCustomization {
   #[serde(skip_serializing_if = "Option::is_none")]
   #[serde(default)]
@@ -44,6 +44,26 @@ pub struct UserDeviceCustomization {
   deny: bool,
   #[getset(get_copy = "pub")]
   index: u32,
+  /// Reject raw messages for this device, even if the server allows raw messages.
+  #[serde(default)]
+  #[serde(rename = "deny-raw-messages")]
+  #[getset(get_copy = "pub", set = "pub")]
+  deny_raw_messages: bool,
+  /// Scaled actuator values below this are sent as 0 (off). Uses
+  /// [DEFAULT_ZERO_THRESHOLD](crate::server::device::protocol::actuator_command_manager::DEFAULT_ZERO_THRESHOLD)
+  /// if unset. A threshold of 0.0 lets tiny values map to the lowest step.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[serde(default)]
+  #[serde(rename = "zero-threshold")]
+  #[getset(get_copy = "pub", set = "pub")]
+  zero_threshold: Option<f64>,
+  /// Minimum time in milliseconds between changes to a single actuator. Changes that come in faster
+  /// are rejected instead of being queued up on the device. Unset means no limit.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[serde(default)]
+  #[serde(rename = "message-timing-gap")]
+  #[getset(get_copy = "pub", set = "pub")]
+  message_timing_gap: Option<u32>,
 }
 
 impl UserDeviceCustomization {
@@ -53,6 +73,9 @@ impl UserDeviceCustomization {
       allow,
       deny,
       index,
+      deny_raw_messages: false,
+      zero_threshold: None,
+      message_timing_gap: None,
     }
   }
 }
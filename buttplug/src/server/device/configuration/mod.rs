@@ -171,6 +171,8 @@ pub struct DeviceConfigurationManagerBuilder {
   user_communication_specifiers: DashMap<String, Vec<ProtocolCommunicationSpecifier>>,
   base_device_definitions: HashMap<BaseDeviceIdentifier, BaseDeviceDefinition>,
   user_device_definitions: DashMap<UserDeviceIdentifier, UserDeviceDefinition>,
+  allowed_address_patterns: Vec<String>,
+  denied_address_patterns: Vec<String>,
   /// Map of protocol names to their respective protocol instance factories
   protocols: Vec<(String, Arc<dyn ProtocolIdentifierFactory>)>,
 }
@@ -224,6 +226,20 @@ impl DeviceConfigurationManagerBuilder {
     self
   }
 
+  /// Only allow devices with addresses matching `pattern` (see [address_matches_pattern] for the
+  /// syntax). If any allow patterns or allowed user devices exist, all other devices are ignored.
+  pub fn allowed_address_pattern(&mut self, pattern: &str) -> &mut Self {
+    self.allowed_address_patterns.push(pattern.to_owned());
+    self
+  }
+
+  /// Ignore devices with addresses matching `pattern` (see [address_matches_pattern] for the
+  /// syntax). Denials take precedence over allows.
+  pub fn denied_address_pattern(&mut self, pattern: &str) -> &mut Self {
+    self.denied_address_patterns.push(pattern.to_owned());
+    self
+  }
+
   /// Add a protocol instance factory for a [ButtplugProtocol]
   pub fn protocol_factory<T>(&mut self, factory: T) -> &mut Self
   where
@@ -311,6 +327,8 @@ impl DeviceConfigurationManagerBuilder {
       user_communication_specifiers: self.user_communication_specifiers.clone(),
       base_device_definitions: attribute_tree_map,
       user_device_definitions: user_attribute_tree_map,
+      allowed_address_patterns: self.allowed_address_patterns.clone(),
+      denied_address_patterns: self.denied_address_patterns.clone(),
       protocol_map,
     })
   }
@@ -346,6 +364,36 @@ pub struct DeviceConfigurationManager {
   /// of session.
   #[getset(get = "pub")]
   user_device_definitions: DashMap<UserDeviceIdentifier, UserDeviceDefinition>,
+  /// Address patterns for devices allowed to connect. Loaded at session start.
+  #[getset(get = "pub")]
+  allowed_address_patterns: Vec<String>,
+  /// Address patterns for devices that should never be connected. Loaded at session start.
+  #[getset(get = "pub")]
+  denied_address_patterns: Vec<String>,
+}
+
+/// Matches a device address against a pattern, where `*` matches any run of characters (including
+/// none) at any position, i.e. `AA:BB:*` or `*:FF`. Matching is case insensitive, as platforms
+/// don't agree on the casing of hex addresses.
+pub fn address_matches_pattern(pattern: &str, address: &str) -> bool {
+  let pattern = pattern.to_lowercase();
+  let address = address.to_lowercase();
+  let mut parts: Vec<&str> = pattern.split('*').collect();
+  // Split always returns at least one part, and if there's no wildcard, only one.
+  let last = parts.pop().expect("Split always returns at least one part");
+  if parts.is_empty() {
+    return address == last;
+  }
+  let Some(mut remaining) = address.strip_prefix(parts[0]) else {
+    return false;
+  };
+  for part in &parts[1..] {
+    match remaining.find(part) {
+      Some(index) => remaining = &remaining[index + part.len()..],
+      None => return false,
+    }
+  }
+  remaining.ends_with(last)
 }
 
 impl Debug for DeviceConfigurationManager {
@@ -422,6 +470,10 @@ impl DeviceConfigurationManager {
       .user_device_definitions
       .iter()
       .any(|kv| kv.key().address() == address && kv.value().user_config().deny())
+      || self
+        .denied_address_patterns
+        .iter()
+        .any(|pattern| address_matches_pattern(pattern, address))
     {
       // If device is outright denied, deny
       info!(
@@ -429,14 +481,19 @@ impl DeviceConfigurationManager {
         address
       );
       false
-    } else if self
-      .user_device_definitions
-      .iter()
-      .any(|kv| kv.value().user_config().allow())
+    } else if (!self.allowed_address_patterns.is_empty()
+      || self
+        .user_device_definitions
+        .iter()
+        .any(|kv| kv.value().user_config().allow()))
       && !self
         .user_device_definitions
         .iter()
         .any(|kv| kv.key().address() == address && kv.value().user_config().allow())
+      && !self
+        .allowed_address_patterns
+        .iter()
+        .any(|pattern| address_matches_pattern(pattern, address))
     {
       // If device is not on allow list and allow list isn't empty, deny
       info!(
@@ -646,6 +703,45 @@ mod test {
     assert!(!config.protocol_specializers(&spec).is_empty());
   }
 
+  #[test]
+  fn test_address_matches_pattern() {
+    assert!(address_matches_pattern(
+      "AA:BB:CC:DD:EE:FF",
+      "aa:bb:cc:dd:ee:ff"
+    ));
+    assert!(!address_matches_pattern(
+      "AA:BB:CC:DD:EE:FF",
+      "AA:BB:CC:DD:EE:F0"
+    ));
+    assert!(address_matches_pattern("AA:BB:*", "AA:BB:CC:DD:EE:FF"));
+    assert!(!address_matches_pattern("AA:BB:*", "AA:BC:CC:DD:EE:FF"));
+    assert!(address_matches_pattern("*:FF", "AA:BB:CC:DD:EE:FF"));
+    assert!(address_matches_pattern("AA:*:EE:*", "AA:BB:CC:DD:EE:FF"));
+    assert!(!address_matches_pattern("AA:*:BB:*", "AA:BB:CC:DD:EE:FF"));
+    assert!(address_matches_pattern("*", "AA:BB:CC:DD:EE:FF"));
+    // Prefix and suffix can't overlap.
+    assert!(!address_matches_pattern("AA:B*B:C", "AA:B:C"));
+  }
+
+  #[test]
+  fn test_address_allowed_patterns() {
+    let dcm = DeviceConfigurationManagerBuilder::default()
+      .allowed_address_pattern("AA:BB:*")
+      .denied_address_pattern("AA:BB:CC:*")
+      .finish()
+      .unwrap();
+    assert!(dcm.address_allowed("AA:BB:00:00:00:00"));
+    assert!(!dcm.address_allowed("AA:BB:CC:00:00:00"));
+    assert!(!dcm.address_allowed("11:22:33:44:55:66"));
+
+    let dcm = DeviceConfigurationManagerBuilder::default()
+      .denied_address_pattern("*:66")
+      .finish()
+      .unwrap();
+    assert!(dcm.address_allowed("AA:BB:00:00:00:00"));
+    assert!(!dcm.address_allowed("11:22:33:44:55:66"));
+  }
+
   #[test]
   fn test_config_wildcard_equals() {
     let config = create_unit_test_dcm(false);
@@ -6,7 +6,10 @@
 // for full license information.
 
 use crate::{
-  core::{errors::ButtplugDeviceError, message::Endpoint},
+  core::{
+    errors::ButtplugDeviceError,
+    message::{Endpoint, SensorType},
+  },
   server::device::hardware::communication::HardwareSpecificError,
   server::device::{
     configuration::{BluetoothLESpecifier, ProtocolCommunicationSpecifier},
@@ -17,6 +20,7 @@ use crate::{
       HardwareInternal,
       HardwareReadCmd,
       HardwareReading,
+      HardwareSensorReadCmd,
       HardwareSpecializer,
       HardwareSubscribeCmd,
       HardwareUnsubscribeCmd,
@@ -42,6 +46,7 @@ use std::{
   fmt::{self, Debug},
   pin::Pin,
   sync::Arc,
+  time::Duration,
 };
 use tokio::sync::broadcast;
 use uuid::Uuid;
@@ -212,12 +217,12 @@ impl<T: Peripheral> HardwareSpecializer for BtleplugHardwareSpecializer<T> {
       endpoints.clone(),
       uuid_map,
     );
-    let mut hardware = Hardware::new(
+    let mut hardware = Hardware::try_new(
       &self.name,
       &format!("{:?}", address),
       &endpoints.keys().cloned().collect::<Vec<Endpoint>>(),
       Box::new(device_internal_impl),
-    );
+    )?;
 
     // Let the hardware know if we need command resends or whatever. Fucking iOS.
     if self.requires_keepalive {
@@ -420,8 +425,17 @@ impl<T: Peripheral + 'static> HardwareInternal for BtlePlugHardware<T> {
     };
     let device = self.device.clone();
     let endpoint = msg.endpoint;
+    let timeout_ms = msg.timeout_ms();
     async move {
-      match device.read(&characteristic).await {
+      let result = if timeout_ms == 0 {
+        device.read(&characteristic).await
+      } else {
+        let timeout = Duration::from_millis(timeout_ms as u64);
+        tokio::time::timeout(timeout, device.read(&characteristic))
+          .await
+          .map_err(|_| ButtplugDeviceError::CommunicationTimeout(timeout))?
+      };
+      match result {
         Ok(data) => {
           trace!("Got reading: {:?}", data);
           Ok(HardwareReading::new(endpoint, &data))
@@ -502,6 +516,38 @@ impl<T: Peripheral + 'static> HardwareInternal for BtlePlugHardware<T> {
     }
     .boxed()
   }
+
+  fn read_sensor(
+    &self,
+    msg: &HardwareSensorReadCmd,
+  ) -> BoxFuture<'static, Result<Vec<i32>, ButtplugDeviceError>> {
+    if msg.sensor_type() != SensorType::RSSI {
+      return future::ready(Err(ButtplugDeviceError::UnhandledCommand(format!(
+        "Bluetooth LE hardware does not support direct {} sensor reads",
+        msg.sensor_type()
+      ))))
+      .boxed();
+    }
+    // RSSI comes from the last advertisement/connection update btleplug saw for the peripheral, so
+    // it's only as fresh as the platform's bluetooth stack makes it.
+    let device = self.device.clone();
+    async move {
+      let properties = device.properties().await.map_err(|e| {
+        ButtplugDeviceError::DeviceSpecificError(HardwareSpecificError::BtleplugError(format!(
+          "{:?}",
+          e
+        )))
+      })?;
+      match properties.and_then(|p| p.rssi) {
+        Some(rssi) => Ok(vec![rssi as i32]),
+        None => Err(ButtplugDeviceError::DeviceCommunicationError(format!(
+          "No RSSI available for device {:?}",
+          device.id()
+        ))),
+      }
+    }
+    .boxed()
+  }
 }
 
 impl<T: Peripheral> Drop for BtlePlugHardware<T> {
@@ -7,7 +7,10 @@
 
 use super::lovense_connect_service_comm_manager::{get_local_info, LovenseServiceToyInfo};
 use crate::{
-  core::{errors::ButtplugDeviceError, message::Endpoint},
+  core::{
+    errors::ButtplugDeviceError,
+    message::{Endpoint, SensorType},
+  },
   server::device::{
     configuration::{LovenseConnectServiceSpecifier, ProtocolCommunicationSpecifier},
     hardware::{
@@ -18,6 +21,7 @@ use crate::{
       HardwareInternal,
       HardwareReadCmd,
       HardwareReading,
+      HardwareSensorReadCmd,
       HardwareSpecializer,
       HardwareSubscribeCmd,
       HardwareUnsubscribeCmd,
@@ -28,6 +32,7 @@ use crate::{
 };
 use async_trait::async_trait;
 use futures::future::{self, BoxFuture, FutureExt};
+use getset::{CopyGetters, Setters};
 use std::{
   fmt::{self, Debug},
   sync::{
@@ -38,6 +43,15 @@ use std::{
 };
 use tokio::sync::broadcast;
 
+/// Number of times a command is retried after a connection error or timeout before failing.
+const DEFAULT_MAX_RETRIES: u32 = 3;
+/// Wait before the first retry, doubled on each following retry.
+const DEFAULT_INITIAL_RETRY_BACKOFF: Duration = Duration::from_millis(50);
+/// Number of failed status checks in a row before we consider the toy disconnected. The Lovense
+/// Connect app can miss requests when it's backgrounded (especially on iOS), so a single failure
+/// doesn't mean the toy is gone.
+const MAX_STATUS_CHECK_FAILURES: u32 = 3;
+
 pub struct LovenseServiceHardwareConnector {
   http_host: String,
   toy_info: LovenseServiceToyInfo,
@@ -77,27 +91,34 @@ impl HardwareConnector for LovenseServiceHardwareConnector {
   }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, CopyGetters, Setters)]
 pub struct LovenseServiceHardware {
   event_sender: broadcast::Sender<HardwareEvent>,
   http_host: String,
   battery_level: Arc<AtomicU8>,
+  /// Number of times a command is retried after a connection error or timeout.
+  #[getset(get_copy = "pub", set = "pub")]
+  max_retries: u32,
+  /// Wait before the first command retry. Each following retry waits twice as long as the last.
+  #[getset(get_copy = "pub", set = "pub")]
+  initial_retry_backoff: Duration,
 }
 
 impl LovenseServiceHardware {
   fn new(http_host: &str, toy_id: &str) -> Self {
-    let (device_event_sender, _) = broadcast::channel(256);
-    let sender_clone = device_event_sender.clone();
+    let hardware = Self::new_without_status_checks(http_host, Arc::new(AtomicU8::new(100)));
+    let sender_clone = hardware.event_sender.clone();
     let toy_id = toy_id.to_owned();
     let host = http_host.to_owned();
-    let battery_level = Arc::new(AtomicU8::new(100));
-    let battery_level_clone = battery_level.clone();
+    let battery_level_clone = hardware.battery_level.clone();
     async_manager::spawn(async move {
+      let mut failed_checks = 0;
       loop {
         // SutekhVRC/VibeCheck patch for delay because Lovense Connect HTTP servers crash (Perma DOS)
         tokio::time::sleep(Duration::from_secs(1)).await;
         match get_local_info(&host).await {
           Some(info) => {
+            failed_checks = 0;
             for (_, toy) in info.data.iter() {
               if toy.id != toy_id {
                 continue;
@@ -112,6 +133,14 @@ impl LovenseServiceHardware {
             }
           }
           None => {
+            failed_checks += 1;
+            if failed_checks < MAX_STATUS_CHECK_FAILURES {
+              warn!(
+                "Lovense Connect status check failed ({}/{}), retrying.",
+                failed_checks, MAX_STATUS_CHECK_FAILURES
+              );
+              continue;
+            }
             let _ = sender_clone.send(HardwareEvent::Disconnected(toy_id.clone()));
             info!("Exiting lovense service device connection check loop.");
             break;
@@ -119,10 +148,19 @@ impl LovenseServiceHardware {
         }
       }
     });
+    hardware
+  }
+
+  /// Creates the hardware without the loop that polls the app for the toy's status, so nothing
+  /// updates `battery_level` or reports disconnection except the caller.
+  fn new_without_status_checks(http_host: &str, battery_level: Arc<AtomicU8>) -> Self {
+    let (event_sender, _) = broadcast::channel(256);
     Self {
-      event_sender: device_event_sender,
+      event_sender,
       http_host: http_host.to_owned(),
       battery_level,
+      max_retries: DEFAULT_MAX_RETRIES,
+      initial_retry_backoff: DEFAULT_INITIAL_RETRY_BACKOFF,
     }
   }
 }
@@ -136,21 +174,32 @@ impl HardwareInternal for LovenseServiceHardware {
     future::ready(Ok(())).boxed()
   }
 
-  // Assume the only thing we'll read is battery.
   fn read_value(
     &self,
     _msg: &HardwareReadCmd,
   ) -> BoxFuture<'static, Result<HardwareReading, ButtplugDeviceError>> {
-    let battery_level = self.battery_level.clone();
-    async move {
-      Ok(HardwareReading::new(
-        Endpoint::Rx,
-        &[battery_level.load(Ordering::SeqCst)],
-      ))
-    }
+    future::ready(Err(ButtplugDeviceError::UnhandledCommand(
+      "Lovense Connect does not support raw reads".to_owned(),
+    )))
     .boxed()
   }
 
+  // The battery level comes along with the status checks, so that's the only sensor we can report.
+  fn read_sensor(
+    &self,
+    msg: &HardwareSensorReadCmd,
+  ) -> BoxFuture<'static, Result<Vec<i32>, ButtplugDeviceError>> {
+    let result = if msg.sensor_type() == SensorType::Battery {
+      Ok(vec![self.battery_level.load(Ordering::SeqCst) as i32])
+    } else {
+      Err(ButtplugDeviceError::UnhandledCommand(format!(
+        "Lovense Connect does not support {} sensor reads",
+        msg.sensor_type()
+      )))
+    };
+    future::ready(result).boxed()
+  }
+
   fn write_value(
     &self,
     msg: &HardwareWriteCmd,
@@ -162,21 +211,44 @@ impl HardwareInternal for LovenseServiceHardware {
         .expect("We build this in the protocol then have to serialize to [u8], but it's a string.")
     );
 
+    let max_retries = self.max_retries;
+    let mut backoff = self.initial_retry_backoff;
     trace!("Sending Lovense Connect command: {}", command_url);
     async move {
-      match reqwest::get(command_url).await {
-        Ok(res) => {
-          async_manager::spawn(async move {
-            trace!(
-              "Got http response: {}",
-              res.text().await.unwrap_or(format!("no response"))
+      let mut retries = 0;
+      loop {
+        match reqwest::get(&command_url).await {
+          Ok(res) if !res.status().is_success() => {
+            error!("Got http error status: {}", res.status());
+            return Err(ButtplugDeviceError::UnhandledCommand(format!(
+              "Lovense Connect returned status {}",
+              res.status()
+            )));
+          }
+          Ok(res) => {
+            async_manager::spawn(async move {
+              trace!(
+                "Got http response: {}",
+                res.text().await.unwrap_or("no response".to_owned())
+              );
+            });
+            return Ok(());
+          }
+          // Connection failures and timeouts are usually the app dropping a request, so they're
+          // worth another try. Anything else won't get better by resending.
+          Err(err) if (err.is_connect() || err.is_timeout()) && retries < max_retries => {
+            retries += 1;
+            warn!(
+              "Got http error: {}, retrying in {:?} ({}/{})",
+              err, backoff, retries, max_retries
             );
-          });
-          Ok(())
-        }
-        Err(err) => {
-          error!("Got http error: {}", err);
-          Err(ButtplugDeviceError::UnhandledCommand(err.to_string()))
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+          }
+          Err(err) => {
+            error!("Got http error: {}", err);
+            return Err(ButtplugDeviceError::UnhandledCommand(err.to_string()));
+          }
         }
       }
     }
@@ -203,3 +275,43 @@ impl HardwareInternal for LovenseServiceHardware {
     .boxed()
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::LovenseServiceHardware;
+  use crate::{
+    core::message::{Endpoint, SensorType},
+    server::device::hardware::{HardwareInternal, HardwareReadCmd, HardwareSensorReadCmd},
+  };
+  use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Arc,
+  };
+
+  #[tokio::test]
+  async fn test_lovense_service_hardware_reads_battery_as_sensor() {
+    // Status checks are what normally update the battery level, so set it by hand instead.
+    let battery_level = Arc::new(AtomicU8::new(42));
+    let hardware = LovenseServiceHardware::new_without_status_checks(
+      "http://127.0.0.1:9",
+      battery_level.clone(),
+    );
+    let read_battery = || async {
+      hardware
+        .read_sensor(&HardwareSensorReadCmd::new(SensorType::Battery))
+        .await
+        .expect("Test, assuming infallible.")
+    };
+    assert_eq!(read_battery().await, vec![42]);
+    battery_level.store(7, Ordering::SeqCst);
+    assert_eq!(read_battery().await, vec![7]);
+    assert!(hardware
+      .read_sensor(&HardwareSensorReadCmd::new(SensorType::RSSI))
+      .await
+      .is_err());
+    assert!(hardware
+      .read_value(&HardwareReadCmd::new(Endpoint::Rx, 0, 0))
+      .await
+      .is_err());
+  }
+}
@@ -1,6 +1,15 @@
 pub mod communication;
 
-use std::{fmt::Debug, sync::Arc, time::Duration};
+use std::{
+  collections::HashSet,
+  fmt::Debug,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+    Mutex,
+  },
+  time::Duration,
+};
 
 use crate::{
   core::{
@@ -12,9 +21,11 @@ use crate::{
       RawSubscribeCmdV2,
       RawUnsubscribeCmdV2,
       RawWriteCmdV2,
+      SensorType,
     },
   },
   server::device::configuration::ProtocolCommunicationSpecifier,
+  util::async_manager,
 };
 use async_trait::async_trait;
 use futures::future::BoxFuture;
@@ -158,6 +169,24 @@ impl From<RawUnsubscribeCmdV2> for HardwareUnsubscribeCmd {
   }
 }
 
+/// Parameters for reading a sensor value that the hardware layer already knows, rather than reading
+/// it from an endpoint.
+///
+/// Only used by [Hardware](crate::device::Hardware) implementations that talk to an API instead of
+/// the device itself, like services that report battery levels alongside their device lists.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize, CopyGetters)]
+#[getset(get_copy = "pub")]
+pub struct HardwareSensorReadCmd {
+  sensor_type: SensorType,
+}
+
+impl HardwareSensorReadCmd {
+  /// Create a new HardwareSensorReadCmd instance
+  pub fn new(sensor_type: SensorType) -> Self {
+    Self { sensor_type }
+  }
+}
+
 /// Enumeration of all possible commands that can be sent to a
 /// [Hardware](crate::device::Hardware).
 #[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
@@ -254,15 +283,40 @@ pub struct Hardware {
   #[getset(get_copy = "pub")]
   requires_keepalive: bool,
   last_write_time: Arc<RwLock<Instant>>,
+  /// Set once the internal implementation emits [HardwareEvent::Disconnected]
+  disconnected: Arc<AtomicBool>,
+  /// Endpoints currently subscribed to, so repeated subscribe/unsubscribe calls don't reach the
+  /// internal implementation. Drivers disagree on how to handle those.
+  subscribed_endpoints: Arc<Mutex<HashSet<Endpoint>>>,
 }
 
 impl Hardware {
+  /// Create a new hardware instance. Hardware without any endpoints can't be used by protocols, so
+  /// callers building endpoint lists at runtime should use [Hardware::try_new] instead.
   pub fn new(
     name: &str,
     address: &str,
     endpoints: &[Endpoint],
     internal_impl: Box<dyn HardwareInternal>,
   ) -> Self {
+    debug_assert!(
+      !endpoints.is_empty(),
+      "Hardware must have at least one endpoint"
+    );
+    let disconnected = Arc::new(AtomicBool::new(false));
+    let disconnected_clone = disconnected.clone();
+    let mut event_stream = internal_impl.event_stream();
+    async_manager::spawn(async move {
+      loop {
+        match event_stream.recv().await {
+          Ok(HardwareEvent::Disconnected(_)) | Err(broadcast::error::RecvError::Closed) => {
+            disconnected_clone.store(true, Ordering::Relaxed);
+            break;
+          }
+          Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+        }
+      }
+    });
     Self {
       name: name.to_owned(),
       address: address.to_owned(),
@@ -270,7 +324,25 @@ impl Hardware {
       internal_impl,
       requires_keepalive: false,
       last_write_time: Arc::new(RwLock::new(Instant::now())),
+      disconnected,
+      subscribed_endpoints: Arc::new(Mutex::new(HashSet::new())),
+    }
+  }
+
+  /// Create a new hardware instance, returning an error if the endpoint list is empty.
+  pub fn try_new(
+    name: &str,
+    address: &str,
+    endpoints: &[Endpoint],
+    internal_impl: Box<dyn HardwareInternal>,
+  ) -> Result<Self, ButtplugDeviceError> {
+    if endpoints.is_empty() {
+      return Err(ButtplugDeviceError::DeviceConnectionError(format!(
+        "Hardware {} ({}) has no endpoints",
+        name, address
+      )));
     }
+    Ok(Self::new(name, address, endpoints, internal_impl))
   }
 
   pub async fn time_since_last_write(&self) -> Duration {
@@ -328,11 +400,23 @@ impl Hardware {
     self.internal_impl.read_value(msg)
   }
 
+  /// Read a sensor value from the hardware layer, for hardware that supports it. See
+  /// [HardwareInternal::read_sensor].
+  pub fn read_sensor(
+    &self,
+    msg: &HardwareSensorReadCmd,
+  ) -> BoxFuture<'static, Result<Vec<i32>, ButtplugDeviceError>> {
+    self.internal_impl.read_sensor(msg)
+  }
+
   /// Write a value to the device
   pub fn write_value(
     &self,
     msg: &HardwareWriteCmd,
   ) -> BoxFuture<'static, Result<(), ButtplugDeviceError>> {
+    if self.disconnected.load(Ordering::Relaxed) {
+      return futures::future::ready(Err(ButtplugDeviceError::HardwareDisconnected)).boxed();
+    }
     let write_fut = self.internal_impl.write_value(msg);
     if self.requires_keepalive {
       let last_write_time = self.last_write_time.clone();
@@ -346,20 +430,74 @@ impl Hardware {
     }
   }
 
-  /// Subscribe to a device endpoint, if it exists
+  /// Subscribe to a device endpoint, if it exists. Subscribing to an endpoint that is already
+  /// subscribed succeeds without doing anything.
   pub fn subscribe(
     &self,
     msg: &HardwareSubscribeCmd,
   ) -> BoxFuture<'static, Result<(), ButtplugDeviceError>> {
-    self.internal_impl.subscribe(msg)
+    let endpoint = msg.endpoint();
+    // Mark the endpoint before subscribing, so concurrent subscribes to it can't both get through.
+    if !self
+      .subscribed_endpoints
+      .lock()
+      .expect("Subscribed endpoints lock should never be poisoned")
+      .insert(endpoint)
+    {
+      debug!(
+        "Endpoint {} on {} already subscribed, ignoring subscribe.",
+        endpoint, self.name
+      );
+      return futures::future::ready(Ok(())).boxed();
+    }
+    let subscribe_fut = self.internal_impl.subscribe(msg);
+    let subscribed_endpoints = self.subscribed_endpoints.clone();
+    async move {
+      let result = subscribe_fut.await;
+      if result.is_err() {
+        subscribed_endpoints
+          .lock()
+          .expect("Subscribed endpoints lock should never be poisoned")
+          .remove(&endpoint);
+      }
+      result
+    }
+    .boxed()
   }
 
-  /// Unsubscribe from a device endpoint, if it exists
+  /// Unsubscribe from a device endpoint, if it exists. Unsubscribing from an endpoint that isn't
+  /// subscribed succeeds without doing anything.
   pub fn unsubscribe(
     &self,
     msg: &HardwareUnsubscribeCmd,
   ) -> BoxFuture<'static, Result<(), ButtplugDeviceError>> {
-    self.internal_impl.unsubscribe(msg)
+    let endpoint = msg.endpoint();
+    let was_subscribed = self
+      .subscribed_endpoints
+      .lock()
+      .expect("Subscribed endpoints lock should never be poisoned")
+      .remove(&endpoint);
+    // Unknown endpoints still go through, so the implementation can report them as invalid.
+    if !was_subscribed && self.endpoints.contains(&endpoint) {
+      debug!(
+        "Endpoint {} on {} not subscribed, ignoring unsubscribe.",
+        endpoint, self.name
+      );
+      return futures::future::ready(Ok(())).boxed();
+    }
+    let unsubscribe_fut = self.internal_impl.unsubscribe(msg);
+    let subscribed_endpoints = self.subscribed_endpoints.clone();
+    async move {
+      let result = unsubscribe_fut.await;
+      if was_subscribed && result.is_err() {
+        subscribed_endpoints
+          .lock()
+          .expect("Subscribed endpoints lock should never be poisoned")
+          .insert(endpoint);
+      }
+      result
+    }
+    .boxed()
   }
 }
 
@@ -394,6 +532,19 @@ pub trait HardwareInternal: Sync + Send {
     &self,
     msg: &HardwareUnsubscribeCmd,
   ) -> BoxFuture<'static, Result<(), ButtplugDeviceError>>;
+  /// Read a sensor value the hardware layer already has, returned in the same format as
+  /// [SensorReadingV4](crate::core::message::SensorReadingV4) data. Hardware that only talks to
+  /// devices through endpoints doesn't need to implement this.
+  fn read_sensor(
+    &self,
+    msg: &HardwareSensorReadCmd,
+  ) -> BoxFuture<'static, Result<Vec<i32>, ButtplugDeviceError>> {
+    futures::future::ready(Err(ButtplugDeviceError::UnhandledCommand(format!(
+      "Hardware does not support direct {} sensor reads",
+      msg.sensor_type()
+    ))))
+    .boxed()
+  }
 }
 
 #[async_trait]
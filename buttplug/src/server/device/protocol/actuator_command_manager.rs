@@ -6,44 +6,84 @@
 // for full license information.
 
 use crate::core::{
-  errors::{ButtplugDeviceError, ButtplugError},
+  errors::{ButtplugDeviceError, ButtplugError, ButtplugMessageError},
   message::{
     ActuatorType,
     ButtplugActuatorFeatureMessageType,
     ButtplugDeviceCommandMessageUnion,
+    ButtplugDeviceMessageType,
+    ButtplugMessage,
     DeviceFeature,
     DeviceFeatureActuator,
+    LinearCmdV4,
     RotateCmdV4,
     RotationSubcommandV4,
     ScalarCmdV4,
     ScalarSubcommandV4,
+    VectorSubcommandV4,
   },
 };
-use getset::Getters;
+use getset::{CopyGetters, Getters};
+#[cfg(feature = "message-serde")]
+use serde::Serialize;
 use std::{
   collections::HashSet,
-  sync::atomic::{AtomicBool, AtomicU32, Ordering::Relaxed},
+  sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering::Relaxed},
+  time::Duration,
 };
 
+/// Scaled values below this are sent as 0 (off) unless a manager is created with
+/// [ActuatorCommandManager::with_zero_threshold], e.g. for a device whose user config sets a
+/// `zero-threshold`.
+pub const DEFAULT_ZERO_THRESHOLD: f64 = 0.0001;
+
+fn now_micros() -> u64 {
+  instant::SystemTime::now()
+    .duration_since(instant::SystemTime::UNIX_EPOCH)
+    .map(|d| d.as_micros() as u64)
+    .unwrap_or(0)
+}
+
 // As of the last rewrite of the command manager, we're currently only tracking values of scalar and
 // rotation commands. We can just use the rotation (AtomicU32, AtomicBool) pair for storage, and
-// ignore the direction bool for Scalars.
+// ignore the direction bool for Scalars. A feature that accepts both ScalarCmd and RotateCmd only
+// has one speed, so both messages share the pair, and whichever was sent last is what we dedupe
+// against.
+//
+// Linear commands get their own (duration, position) pair and sent flag, so they never share dedupe
+// state with scalar or rotation commands. Protocols expect the original floating point position, so
+// that's what we store, as the bits of the f64 since there's no AtomicF64.
+//
+// If the device has a message timing gap, we also keep the last time (in microseconds since epoch)
+// we sent a new scalar or rotation value to the feature, with 0 meaning never.
 #[derive(Getters)]
 #[getset(get = "pub")]
 struct FeatureStatus {
   actuator_type: ActuatorType,
   actuator: DeviceFeatureActuator,
+  zero_threshold: f64,
   sent: AtomicBool,
   value: (AtomicU32, AtomicBool),
+  sent_linear: AtomicBool,
+  linear: (AtomicU32, AtomicU64),
+  last_sent: AtomicU64,
 }
 
 impl FeatureStatus {
-  pub fn new(actuator_type: &ActuatorType, actuator: &DeviceFeatureActuator) -> Self {
+  pub fn new(
+    actuator_type: &ActuatorType,
+    actuator: &DeviceFeatureActuator,
+    zero_threshold: f64,
+  ) -> Self {
     Self {
       actuator_type: *actuator_type,
       actuator: actuator.clone(),
+      zero_threshold,
       sent: AtomicBool::new(false),
       value: (AtomicU32::new(0), AtomicBool::new(false)),
+      sent_linear: AtomicBool::new(false),
+      linear: (AtomicU32::new(0), AtomicU64::new(0f64.to_bits())),
+      last_sent: AtomicU64::new(0),
     }
   }
 
@@ -58,12 +98,13 @@ impl FeatureStatus {
     self.actuator.messages()
   }
 
-  pub fn update(&self, value: &(f64, bool)) -> Option<(u32, bool)> {
-    let mut result = None;
+  fn step_value(&self, value: f64) -> u32 {
     let range_start = *self.actuator.step_range().start();
     let range = self.actuator.step_range().end() - range_start;
-    let scalar_modifier = value.0 * range as f64;
-    let scalar = if scalar_modifier < 0.0001 {
+    let scalar_modifier = value * range as f64;
+    // 0.0 always means off, even with no threshold, otherwise devices whose step range doesn't
+    // start at 0 could never be stopped.
+    let scalar = if scalar_modifier <= 0.0 || scalar_modifier < self.zero_threshold {
       0
     } else {
       // When calculating speeds, round up. This follows how we calculated
@@ -78,24 +119,121 @@ impl FeatureStatus {
       scalar_modifier,
       scalar
     );
+    scalar
+  }
+
+  fn update_stored(
+    sent: &AtomicBool,
+    stored: &(AtomicU32, AtomicBool),
+    value: (u32, bool),
+  ) -> Option<(u32, bool)> {
     // If we've already sent commands, we don't want to send them again,
     // because some of our communication busses are REALLY slow. Make sure
     // these values get None in our return vector.
-    let current = self.value.0.load(Relaxed);
-    let clockwise = self.value.1.load(Relaxed);
-    let sent = self.sent.load(Relaxed);
-    if !sent || scalar != current || clockwise != value.1 {
-      self.value.0.store(scalar, Relaxed);
-      self.value.1.store(value.1, Relaxed);
-      if !sent {
-        self.sent.store(true, Relaxed);
+    let current = stored.0.load(Relaxed);
+    let clockwise = stored.1.load(Relaxed);
+    let was_sent = sent.load(Relaxed);
+    if !was_sent || value.0 != current || clockwise != value.1 {
+      stored.0.store(value.0, Relaxed);
+      stored.1.store(value.1, Relaxed);
+      if !was_sent {
+        sent.store(true, Relaxed);
       }
-      result = Some((scalar, value.1));
+      Some(value)
+    } else {
+      None
     }
-    result
+  }
+
+  pub fn update(&self, value: &(f64, bool)) -> Option<(u32, bool)> {
+    Self::update_stored(&self.sent, &self.value, (self.step_value(value.0), value.1))
+  }
+
+  /// Returns true if sending `value` now would land inside the message gap since the last value
+  /// sent to this feature. Stops are never throttled, otherwise a device could be left running if a
+  /// stop lands right after a speed change, and neither are values we'd dedupe anyways.
+  pub fn throttled(&self, value: &(f64, bool), now: u64, gap: Duration) -> bool {
+    let last_sent = self.last_sent.load(Relaxed);
+    if last_sent == 0 || now.saturating_sub(last_sent) >= gap.as_micros() as u64 {
+      return false;
+    }
+    let step_value = (self.step_value(value.0), value.1);
+    step_value.0 != 0 && step_value != self.current().1
+  }
+
+  pub fn mark_sent(&self, now: u64) {
+    self.last_sent.store(now, Relaxed);
+  }
+
+  pub fn current_linear(&self) -> (u32, f64) {
+    (
+      self.linear.0.load(Relaxed),
+      f64::from_bits(self.linear.1.load(Relaxed)),
+    )
+  }
+
+  pub fn update_linear(&self, value: &(u32, f64)) -> Option<(u32, f64)> {
+    // Dedupe on the duration and the step converted position, as positions that land on the same
+    // step won't move the device any differently. A new duration is always sent though, since it
+    // changes how fast the device gets there.
+    let (duration, position) = *value;
+    let (current_duration, current_position) = self.current_linear();
+    if self.sent_linear.load(Relaxed)
+      && duration == current_duration
+      && self.step_value(position) == self.step_value(current_position)
+    {
+      return None;
+    }
+    self.linear.0.store(duration, Relaxed);
+    self.linear.1.store(position.to_bits(), Relaxed);
+    self.sent_linear.store(true, Relaxed);
+    Some(*value)
+  }
+
+  pub fn reset(&self) {
+    self.sent.store(false, Relaxed);
+    self.sent_linear.store(false, Relaxed);
+    self.value.0.store(0, Relaxed);
+    self.value.1.store(false, Relaxed);
+    self.linear.0.store(0, Relaxed);
+    self.linear.1.store(0f64.to_bits(), Relaxed);
+    self.last_sent.store(0, Relaxed);
   }
 }
 
+/// Cached state of a single device feature, as last sent by the [ActuatorCommandManager]. Values are
+/// in device steps, not the 0.0-1.0 range used in messages.
+#[derive(Debug, Clone, PartialEq, Getters, CopyGetters)]
+#[cfg_attr(feature = "message-serde", derive(Serialize))]
+pub struct FeatureStateSnapshot {
+  #[getset(get_copy = "pub")]
+  feature_index: u32,
+  #[getset(get_copy = "pub")]
+  actuator_type: ActuatorType,
+  /// Last value sent via a scalar command.
+  #[getset(get_copy = "pub")]
+  scalar: u32,
+  /// Last speed sent via a rotate command. Scalar and rotate commands share a feature's speed, so
+  /// this is always the same as [scalar](Self::scalar).
+  #[getset(get_copy = "pub")]
+  rotation_speed: u32,
+  #[getset(get_copy = "pub")]
+  rotation_clockwise: bool,
+  /// Last position sent via a linear command, in the 0.0-1.0 range.
+  #[getset(get_copy = "pub")]
+  linear_position: f64,
+}
+
+/// Snapshot of what the [ActuatorCommandManager] believes a device's features are currently set
+/// to, for diagnostics. Since the manager only knows what it has sent, this may not match the
+/// hardware if commands failed or the device lost its state.
+#[derive(Debug, Clone, PartialEq, Getters)]
+#[cfg_attr(feature = "message-serde", derive(Serialize))]
+pub struct DeviceStateSnapshot {
+  #[getset(get = "pub")]
+  features: Vec<FeatureStateSnapshot>,
+}
+
 // In order to make our lives easier, we make some assumptions about what's internally mutable in
 // the ActuatorCommandManager (ACM). Once the ACM is configured for a device, it won't change sizes,
 // because we don't support things like adding motors to devices randomly while Buttplug is running.
@@ -107,19 +245,38 @@ impl FeatureStatus {
 pub struct ActuatorCommandManager {
   feature_status: Vec<FeatureStatus>,
   stop_commands: Vec<ButtplugDeviceCommandMessageUnion>,
+  message_gap: Option<Duration>,
+  now: Box<dyn Fn() -> u64 + Send + Sync>,
 }
 
 impl ActuatorCommandManager {
-  pub fn new(features: &Vec<DeviceFeature>) -> Self {
+  /// Create a manager for a device's features. If `message_gap` is set, scalar and rotation
+  /// commands that would change a feature before the gap has elapsed since its last change are
+  /// rejected with [ButtplugDeviceError::CommandThrottled]. Pass None for devices that can take
+  /// commands as fast as we send them.
+  pub fn new(features: &[DeviceFeature], message_gap: Option<Duration>) -> Self {
+    Self::with_zero_threshold(features, message_gap, DEFAULT_ZERO_THRESHOLD)
+  }
+
+  /// Create a manager that snaps scaled values (the requested value multiplied by the size of the
+  /// feature's step range) below `zero_threshold` to 0. A threshold of 0.0 disables snapping, so
+  /// that tiny values still map to the lowest step, which matters for devices with very fine step
+  /// ranges.
+  pub fn with_zero_threshold(
+    features: &[DeviceFeature],
+    message_gap: Option<Duration>,
+    zero_threshold: f64,
+  ) -> Self {
     let mut stop_commands = vec![];
 
     let mut statuses = vec![];
     let mut scalar_subcommands = vec![];
     let mut rotate_subcommands = vec![];
+    let mut linear_subcommands = vec![];
     for (index, feature) in features.iter().enumerate() {
       if let Some(actuator) = feature.actuator() {
         let actuator_type: ActuatorType = feature.feature_type().clone().try_into().unwrap();
-        statuses.push(FeatureStatus::new(&actuator_type, actuator));
+        statuses.push(FeatureStatus::new(&actuator_type, actuator, zero_threshold));
         if actuator
           .messages()
           .contains(&crate::core::message::ButtplugActuatorFeatureMessageType::RotateCmd)
@@ -130,6 +287,15 @@ impl ActuatorCommandManager {
           .contains(&crate::core::message::ButtplugActuatorFeatureMessageType::ScalarCmd)
         {
           scalar_subcommands.push(ScalarSubcommandV4::new(index as u32, 0.0, actuator_type));
+        } else if actuator
+          .messages()
+          .contains(&crate::core::message::ButtplugActuatorFeatureMessageType::LinearCmd)
+        {
+          // Linear actuators have no real notion of stopping, so "stop" means return to position
+          // 0.0 with no duration (as fast as the device allows), instead of holding wherever the
+          // device currently is. Holding would leave the device mid-stroke and doesn't survive a
+          // reconnect, so we always send it home.
+          linear_subcommands.push(VectorSubcommandV4::new(index as u32, 0, 0.0));
         }
       }
     }
@@ -139,11 +305,40 @@ impl ActuatorCommandManager {
     if !rotate_subcommands.is_empty() {
       stop_commands.push(RotateCmdV4::new(0, rotate_subcommands).into());
     }
+    if !linear_subcommands.is_empty() {
+      stop_commands.push(LinearCmdV4::new(0, linear_subcommands).into());
+    }
 
     Self {
       feature_status: statuses,
       stop_commands,
+      message_gap,
+      now: Box::new(now_micros),
+    }
+  }
+
+  #[cfg(test)]
+  fn set_clock(&mut self, now: impl Fn() -> u64 + Send + Sync + 'static) {
+    self.now = Box::new(now);
+  }
+
+  fn check_feature_index(
+    &self,
+    cmd_type: ButtplugDeviceMessageType,
+    index: u32,
+  ) -> Result<(), ButtplugError> {
+    let feature_count = self.feature_status.len() as u32;
+    if index >= feature_count {
+      return Err(
+        ButtplugMessageError::InvalidSubcommandIndex {
+          cmd_type,
+          sent_index: index,
+          max_index: feature_count.saturating_sub(1),
+        }
+        .into(),
+      );
     }
+    Ok(())
   }
 
   fn update(
@@ -159,25 +354,44 @@ impl ActuatorCommandManager {
     let mut result: Vec<(u32, ActuatorType, (u32, bool))> = vec![];
 
     for command in commands {
-      if command.0 >= self.feature_status.len().try_into().unwrap() {
-        return Err(
-          ButtplugDeviceError::ProtocolRequirementError(format!(
-            "Command requests feature index {}, which does not exist.",
-            command.0,
-          ))
-          .into(),
-        );
+      self.check_feature_index(msg_type.into(), command.0)?;
+    }
+
+    // Check every subcommand against the message gap before updating anything, so a throttled
+    // command leaves no partial updates behind.
+    let now = (self.now)();
+    if let Some(gap) = self.message_gap {
+      if commands
+        .iter()
+        .any(|(index, _, value)| self.feature_status[*index as usize].throttled(value, now, gap))
+      {
+        return Err(ButtplugDeviceError::CommandThrottled(gap).into());
       }
     }
 
     for (index, cmd) in self.feature_status.iter().enumerate() {
       let u32_index: u32 = index.try_into().unwrap();
       if let Some((_, cmd_actuator, cmd_value)) = commands.iter().find(|x| x.0 == u32_index) {
+        let previous = cmd.current().1;
         // By this point, we should have already checked whether the feature takes the message type.
-        if let Some(updated_value) = self.feature_status[index].update(cmd_value) {
+        if let Some(updated_value) = cmd.update(cmd_value) {
+          trace!(
+            feature_index = u32_index,
+            previous = ?previous,
+            updated = ?updated_value,
+            "Feature value updated."
+          );
+          cmd.mark_sent(now);
           result.push((u32_index, *cmd_actuator, updated_value));
-        } else if match_all {
-          result.push((u32_index, *cmd.actuator_type(), cmd.current().1));
+        } else {
+          trace!(
+            feature_index = u32_index,
+            value = ?previous,
+            "Subcommand matches cached value, suppressing."
+          );
+          if match_all {
+            result.push((u32_index, *cmd.actuator_type(), previous));
+          }
         }
       } else if match_all {
         if cmd.messages().contains(&msg_type) {
@@ -189,6 +403,11 @@ impl ActuatorCommandManager {
     Ok(result)
   }
 
+  #[tracing::instrument(
+    level = "trace",
+    skip(self, msg),
+    fields(device_features_count = self.feature_status.len(), message_id = msg.id())
+  )]
   pub fn update_scalar(
     &self,
     msg: &ScalarCmdV4,
@@ -233,6 +452,11 @@ impl ActuatorCommandManager {
     Ok(final_result)
   }
 
+  #[tracing::instrument(
+    level = "trace",
+    skip(self, msg),
+    fields(device_features_count = self.feature_status.len(), message_id = msg.id())
+  )]
   pub fn update_rotation(
     &self,
     msg: &RotateCmdV4,
@@ -281,9 +505,123 @@ impl ActuatorCommandManager {
     Ok(final_result)
   }
 
+  #[tracing::instrument(
+    level = "trace",
+    skip(self, msg),
+    fields(device_features_count = self.feature_status.len(), message_id = msg.id())
+  )]
+  pub fn update_linear(
+    &self,
+    msg: &LinearCmdV4,
+    match_all: bool,
+  ) -> Result<Vec<VectorSubcommandV4>, ButtplugError> {
+    // First, make sure this is a valid command, that contains at least one
+    // command.
+    if msg.vectors().is_empty() {
+      return Err(
+        ButtplugDeviceError::ProtocolRequirementError(
+          "LinearCmd has 0 commands, will not do anything.".to_owned(),
+        )
+        .into(),
+      );
+    }
+
+    for command in msg.vectors() {
+      self.check_feature_index(
+        ButtplugDeviceMessageType::LinearCmd,
+        command.feature_index(),
+      )?;
+    }
+
+    // Unlike scalar and rotation updates, protocols take the LinearCmd itself, so rather than
+    // returning step values, we return the subcommands that should actually be sent, in feature
+    // order.
+    //
+    // Durations are passed through untouched. A duration of 0 means "move as fast as the device
+    // allows" (it's what our own stop commands use), so protocols are expected to map it to their
+    // fastest speed rather than ignoring or rejecting it.
+    let mut result = vec![];
+    for (index, status) in self.feature_status.iter().enumerate() {
+      let u32_index: u32 = index.try_into().unwrap();
+      if let Some(cmd) = msg
+        .vectors()
+        .iter()
+        .find(|x| x.feature_index() == u32_index)
+      {
+        let (_, previous) = status.current_linear();
+        if let Some((duration, position)) = status.update_linear(&(cmd.duration(), cmd.position()))
+        {
+          trace!(
+            feature_index = u32_index,
+            previous = previous,
+            updated = position,
+            "Feature value updated."
+          );
+          result.push(VectorSubcommandV4::new(u32_index, duration, position));
+        } else {
+          trace!(
+            feature_index = u32_index,
+            value = previous,
+            "Subcommand matches cached value, suppressing."
+          );
+          if match_all {
+            let (duration, position) = status.current_linear();
+            result.push(VectorSubcommandV4::new(u32_index, duration, position));
+          }
+        }
+      } else if match_all
+        && status
+          .messages()
+          .contains(&ButtplugActuatorFeatureMessageType::LinearCmd)
+      {
+        let (duration, position) = status.current_linear();
+        result.push(VectorSubcommandV4::new(u32_index, duration, position));
+      }
+    }
+    Ok(result)
+  }
+
+  /// Last position we sent to a linear feature, if the feature index exists. Used when upgrading
+  /// older messages that only describe a goal position and speed, since we need to know how far
+  /// the device is going to travel to figure out a duration.
+  pub fn current_linear_position(&self, feature_index: u32) -> Option<f64> {
+    self
+      .feature_status
+      .get(feature_index as usize)
+      .map(|x| x.current_linear().1)
+  }
+
   pub fn stop_commands(&self) -> Vec<ButtplugDeviceCommandMessageUnion> {
     self.stop_commands.clone()
   }
+
+  /// Current cached state of every feature, ordered by feature index.
+  pub fn state_snapshot(&self) -> DeviceStateSnapshot {
+    let features = self
+      .feature_status
+      .iter()
+      .enumerate()
+      .map(|(index, status)| {
+        let (actuator_type, (scalar, rotation_clockwise)) = status.current();
+        FeatureStateSnapshot {
+          feature_index: index as u32,
+          actuator_type,
+          scalar,
+          rotation_speed: scalar,
+          rotation_clockwise,
+          linear_position: status.current_linear().1,
+        }
+      })
+      .collect();
+    DeviceStateSnapshot { features }
+  }
+
+  /// Forget all cached command values, so the next command for every feature is sent regardless of
+  /// what we think the device is currently doing. Useful if the hardware may have lost its state,
+  /// i.e. after a reconnect.
+  pub fn reset(&self) {
+    self.feature_status.iter().for_each(|x| x.reset());
+  }
 }
 /*
 #[cfg(test)]
@@ -560,3 +898,694 @@ mod test {
   // TODO Write test for vibration stop generator
 }
 */
+
+#[cfg(test)]
+mod test {
+  use super::ActuatorCommandManager;
+  use crate::core::{
+    errors::{ButtplugDeviceError, ButtplugError, ButtplugMessageError},
+    message::{
+      ActuatorType,
+      ButtplugActuatorFeatureMessageType,
+      ButtplugDeviceCommandMessageUnion,
+      ButtplugDeviceMessageType,
+      DeviceFeature,
+      DeviceFeatureActuator,
+      FeatureType,
+      LinearCmdV4,
+      RotateCmdV4,
+      RotationSubcommandV4,
+      ScalarCmdV4,
+      ScalarSubcommandV4,
+      VectorSubcommandV4,
+    },
+  };
+  use proptest::prelude::*;
+  use std::{
+    collections::HashSet,
+    ops::RangeInclusive,
+    sync::{
+      atomic::{AtomicU64, Ordering},
+      Arc,
+    },
+    time::Duration,
+  };
+  use tracing_test::traced_test;
+
+  fn linear_features(count: usize) -> Vec<DeviceFeature> {
+    let actuator = DeviceFeatureActuator::new(
+      &RangeInclusive::new(0, 100),
+      &RangeInclusive::new(0, 100),
+      &HashSet::from([ButtplugActuatorFeatureMessageType::LinearCmd]),
+    );
+    vec![DeviceFeature::new("Test", FeatureType::Position, &Some(actuator), &None); count]
+  }
+
+  fn test_manager(
+    feature_type: FeatureType,
+    msg_type: ButtplugActuatorFeatureMessageType,
+  ) -> ActuatorCommandManager {
+    let actuator = DeviceFeatureActuator::new(
+      &RangeInclusive::new(0, 20),
+      &RangeInclusive::new(0, 20),
+      &HashSet::from([msg_type]),
+    );
+    let features = vec![DeviceFeature::new("Test", feature_type, &Some(actuator), &None); 2];
+    ActuatorCommandManager::new(&features, None)
+  }
+
+  fn throttled_manager(
+    feature_type: FeatureType,
+    msg_type: ButtplugActuatorFeatureMessageType,
+  ) -> (ActuatorCommandManager, Arc<AtomicU64>) {
+    let actuator = DeviceFeatureActuator::new(
+      &RangeInclusive::new(0, 20),
+      &RangeInclusive::new(0, 20),
+      &HashSet::from([msg_type]),
+    );
+    let features = vec![DeviceFeature::new("Test", feature_type, &Some(actuator), &None); 2];
+    let mut mgr = ActuatorCommandManager::new(&features, Some(Duration::from_millis(100)));
+    let clock = Arc::new(AtomicU64::new(1_000_000));
+    let clock_clone = clock.clone();
+    mgr.set_clock(move || clock_clone.load(Ordering::Relaxed));
+    (mgr, clock)
+  }
+
+  fn is_throttled<T>(result: Result<T, ButtplugError>) -> bool {
+    matches!(
+      result,
+      Err(ButtplugError::ButtplugDeviceError(
+        ButtplugDeviceError::CommandThrottled(_)
+      ))
+    )
+  }
+
+  #[test]
+  pub fn test_command_generator_scalar_throttle() {
+    let (mgr, clock) = throttled_manager(
+      FeatureType::Vibrate,
+      ButtplugActuatorFeatureMessageType::ScalarCmd,
+    );
+    let scalar_msg = |index, value| {
+      ScalarCmdV4::new(
+        0,
+        vec![ScalarSubcommandV4::new(index, value, ActuatorType::Vibrate)],
+      )
+    };
+    assert_eq!(
+      mgr
+        .update_scalar(&scalar_msg(0, 0.5), false)
+        .expect("Test, assuming infallible"),
+      vec![Some((ActuatorType::Vibrate, 10)), None]
+    );
+    // Inside the gap, a change to the same feature is rejected.
+    clock.fetch_add(50_000, Ordering::Relaxed);
+    assert!(is_throttled(mgr.update_scalar(&scalar_msg(0, 0.75), false)));
+    // The gap is tracked per feature, so the other feature can still change.
+    assert_eq!(
+      mgr
+        .update_scalar(&scalar_msg(1, 0.5), false)
+        .expect("Test, assuming infallible"),
+      vec![None, Some((ActuatorType::Vibrate, 10))]
+    );
+    // Repeating the current value isn't a change, so it's deduped rather than throttled.
+    assert_eq!(
+      mgr
+        .update_scalar(&scalar_msg(0, 0.5), false)
+        .expect("Test, assuming infallible"),
+      vec![None, None]
+    );
+    // Stops always go through, and restart the gap.
+    assert_eq!(
+      mgr
+        .update_scalar(&scalar_msg(0, 0.0), false)
+        .expect("Test, assuming infallible"),
+      vec![Some((ActuatorType::Vibrate, 0)), None]
+    );
+    clock.fetch_add(99_999, Ordering::Relaxed);
+    assert!(is_throttled(mgr.update_scalar(&scalar_msg(0, 0.75), false)));
+    clock.fetch_add(1, Ordering::Relaxed);
+    assert_eq!(
+      mgr
+        .update_scalar(&scalar_msg(0, 0.75), false)
+        .expect("Test, assuming infallible"),
+      vec![Some((ActuatorType::Vibrate, 15)), None]
+    );
+  }
+
+  #[test]
+  pub fn test_command_generator_rotation_throttle() {
+    let (mgr, clock) = throttled_manager(
+      FeatureType::Rotate,
+      ButtplugActuatorFeatureMessageType::RotateCmd,
+    );
+    let rotate_msg =
+      |speed, clockwise| RotateCmdV4::new(0, vec![RotationSubcommandV4::new(1, speed, clockwise)]);
+    assert_eq!(
+      mgr
+        .update_rotation(&rotate_msg(0.5, true), false)
+        .expect("Test, assuming infallible"),
+      vec![Some((10, true)), None]
+    );
+    // A direction change is a change too.
+    clock.fetch_add(99_999, Ordering::Relaxed);
+    assert!(is_throttled(
+      mgr.update_rotation(&rotate_msg(0.5, false), false)
+    ));
+    clock.fetch_add(1, Ordering::Relaxed);
+    assert_eq!(
+      mgr
+        .update_rotation(&rotate_msg(0.5, false), false)
+        .expect("Test, assuming infallible"),
+      vec![Some((10, false)), None]
+    );
+  }
+
+  #[test]
+  pub fn test_command_generator_no_message_gap_passthrough() {
+    let mut mgr = test_manager(
+      FeatureType::Vibrate,
+      ButtplugActuatorFeatureMessageType::ScalarCmd,
+    );
+    // The clock never moves, but without a gap nothing is throttled.
+    mgr.set_clock(|| 1_000_000);
+    for (value, step) in [(0.5, 10), (0.75, 15), (0.25, 5)] {
+      assert_eq!(
+        mgr
+          .update_scalar(
+            &ScalarCmdV4::new(
+              0,
+              vec![ScalarSubcommandV4::new(0, value, ActuatorType::Vibrate)]
+            ),
+            false
+          )
+          .expect("Test, assuming infallible"),
+        vec![Some((ActuatorType::Vibrate, step)), None]
+      );
+    }
+  }
+
+  #[test]
+  pub fn test_command_generator_state_snapshot() {
+    let actuator = |msg_type| {
+      Some(DeviceFeatureActuator::new(
+        &RangeInclusive::new(0, 20),
+        &RangeInclusive::new(0, 20),
+        &HashSet::from([msg_type]),
+      ))
+    };
+    let features = vec![
+      DeviceFeature::new(
+        "Test",
+        FeatureType::Vibrate,
+        &actuator(ButtplugActuatorFeatureMessageType::ScalarCmd),
+        &None,
+      ),
+      DeviceFeature::new(
+        "Test",
+        FeatureType::Rotate,
+        &actuator(ButtplugActuatorFeatureMessageType::RotateCmd),
+        &None,
+      ),
+      linear_features(1).remove(0),
+    ];
+    let mgr = ActuatorCommandManager::new(&features, None);
+    mgr
+      .update_scalar(
+        &ScalarCmdV4::new(
+          0,
+          vec![ScalarSubcommandV4::new(0, 0.5, ActuatorType::Vibrate)],
+        ),
+        false,
+      )
+      .expect("Test, assuming infallible");
+    mgr
+      .update_rotation(
+        &RotateCmdV4::new(0, vec![RotationSubcommandV4::new(1, 0.25, true)]),
+        false,
+      )
+      .expect("Test, assuming infallible");
+    mgr
+      .update_linear(
+        &LinearCmdV4::new(0, vec![VectorSubcommandV4::new(2, 500, 0.75)]),
+        false,
+      )
+      .expect("Test, assuming infallible");
+
+    let snapshot = mgr.state_snapshot();
+    let features = snapshot.features();
+    assert_eq!(features.len(), 3);
+    assert_eq!(features[0].actuator_type(), ActuatorType::Vibrate);
+    assert_eq!(features[0].scalar(), 10);
+    assert_eq!(features[1].actuator_type(), ActuatorType::Rotate);
+    assert_eq!(features[1].rotation_speed(), 5);
+    assert!(features[1].rotation_clockwise());
+    assert_eq!(features[2].feature_index(), 2);
+    assert_eq!(features[2].actuator_type(), ActuatorType::Position);
+    assert_eq!(features[2].linear_position(), 0.75);
+    // Linear commands keep their own state, separate from scalar values.
+    assert_eq!(features[2].scalar(), 0);
+  }
+
+  #[test]
+  #[traced_test]
+  pub fn test_command_generator_traces_suppressed_subcommands() {
+    let mgr = ActuatorCommandManager::new(&linear_features(1), None);
+    let linear_msg = LinearCmdV4::new(0, vec![VectorSubcommandV4::new(0, 500, 0.5)]);
+    mgr
+      .update_linear(&linear_msg, false)
+      .expect("Test, assuming infallible");
+    assert!(logs_contain(
+      "update_linear{match_all=false device_features_count=1 message_id=1}"
+    ));
+    assert!(logs_contain("Feature value updated."));
+    assert!(!logs_contain("suppressing"));
+    mgr
+      .update_linear(&linear_msg, false)
+      .expect("Test, assuming infallible");
+    assert!(logs_contain(
+      "Subcommand matches cached value, suppressing."
+    ));
+  }
+
+  #[test]
+  pub fn test_command_generator_invalid_subcommand_index() {
+    let mgr = test_manager(
+      FeatureType::Vibrate,
+      ButtplugActuatorFeatureMessageType::ScalarCmd,
+    );
+    let err = mgr
+      .update_scalar(
+        &ScalarCmdV4::new(
+          0,
+          vec![ScalarSubcommandV4::new(2, 0.5, ActuatorType::Vibrate)],
+        ),
+        false,
+      )
+      .unwrap_err();
+    assert_eq!(
+      err,
+      ButtplugError::from(ButtplugMessageError::InvalidSubcommandIndex {
+        cmd_type: ButtplugDeviceMessageType::ScalarCmd,
+        sent_index: 2,
+        max_index: 1,
+      })
+    );
+    assert_eq!(
+      err.to_string(),
+      "ScalarCmd subcommand index 2 is out of range, max index is 1"
+    );
+
+    let mgr = test_manager(
+      FeatureType::Rotate,
+      ButtplugActuatorFeatureMessageType::RotateCmd,
+    );
+    let err = mgr
+      .update_rotation(
+        &RotateCmdV4::new(0, vec![RotationSubcommandV4::new(5, 0.5, true)]),
+        false,
+      )
+      .unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "RotateCmd subcommand index 5 is out of range, max index is 1"
+    );
+  }
+
+  #[test]
+  pub fn test_command_generator_linear() {
+    let mgr = ActuatorCommandManager::new(&linear_features(2), None);
+    let linear_msg = LinearCmdV4::new(
+      0,
+      vec![
+        VectorSubcommandV4::new(0, 500, 0.5),
+        VectorSubcommandV4::new(1, 500, 0.5),
+      ],
+    );
+    assert_eq!(
+      mgr
+        .update_linear(&linear_msg, false)
+        .expect("Test, assuming infallible"),
+      vec![
+        VectorSubcommandV4::new(0, 500, 0.5),
+        VectorSubcommandV4::new(1, 500, 0.5)
+      ]
+    );
+    assert_eq!(
+      mgr
+        .update_linear(&linear_msg, false)
+        .expect("Test, assuming infallible"),
+      vec![]
+    );
+    let linear_msg_2 = LinearCmdV4::new(
+      0,
+      vec![
+        VectorSubcommandV4::new(0, 500, 0.5),
+        VectorSubcommandV4::new(1, 250, 0.75),
+      ],
+    );
+    assert_eq!(
+      mgr
+        .update_linear(&linear_msg_2, false)
+        .expect("Test, assuming infallible"),
+      vec![VectorSubcommandV4::new(1, 250, 0.75)]
+    );
+    // Same position, new duration, still goes out.
+    let linear_msg_3 = LinearCmdV4::new(0, vec![VectorSubcommandV4::new(1, 100, 0.75)]);
+    assert_eq!(
+      mgr
+        .update_linear(&linear_msg_3, false)
+        .expect("Test, assuming infallible"),
+      vec![VectorSubcommandV4::new(1, 100, 0.75)]
+    );
+    let linear_msg_invalid = LinearCmdV4::new(0, vec![VectorSubcommandV4::new(2, 500, 0.5)]);
+    assert!(mgr.update_linear(&linear_msg_invalid, false).is_err());
+    assert!(mgr
+      .update_linear(&LinearCmdV4::new(0, vec![]), false)
+      .is_err());
+  }
+
+  #[test]
+  pub fn test_command_generator_linear_dedupe_separate_from_scalar() {
+    let actuator = DeviceFeatureActuator::new(
+      &RangeInclusive::new(0, 100),
+      &RangeInclusive::new(0, 100),
+      &HashSet::from([
+        ButtplugActuatorFeatureMessageType::ScalarCmd,
+        ButtplugActuatorFeatureMessageType::LinearCmd,
+      ]),
+    );
+    let features = vec![DeviceFeature::new(
+      "Test",
+      FeatureType::Position,
+      &Some(actuator),
+      &None,
+    )];
+    let mgr = ActuatorCommandManager::new(&features, None);
+    let scalar_msg = ScalarCmdV4::new(
+      0,
+      vec![ScalarSubcommandV4::new(0, 0.5, ActuatorType::Position)],
+    );
+    assert_eq!(
+      mgr
+        .update_scalar(&scalar_msg, false)
+        .expect("Test, assuming infallible"),
+      vec![Some((ActuatorType::Position, 50))]
+    );
+    // A linear command to the same position isn't a repeat of the scalar command.
+    let linear_msg = LinearCmdV4::new(0, vec![VectorSubcommandV4::new(0, 500, 0.5)]);
+    assert_eq!(
+      mgr
+        .update_linear(&linear_msg, false)
+        .expect("Test, assuming infallible"),
+      vec![VectorSubcommandV4::new(0, 500, 0.5)]
+    );
+    // And it doesn't disturb what we're deduping scalar commands against.
+    assert_eq!(
+      mgr
+        .update_scalar(&scalar_msg, false)
+        .expect("Test, assuming infallible"),
+      vec![None]
+    );
+    assert_eq!(
+      mgr
+        .update_linear(&linear_msg, false)
+        .expect("Test, assuming infallible"),
+      vec![]
+    );
+  }
+
+  #[test]
+  pub fn test_command_generator_linear_match_all() {
+    let mgr = ActuatorCommandManager::new(&linear_features(2), None);
+    let linear_msg = LinearCmdV4::new(
+      0,
+      vec![
+        VectorSubcommandV4::new(0, 500, 0.5),
+        VectorSubcommandV4::new(1, 500, 0.5),
+      ],
+    );
+    assert_eq!(
+      mgr
+        .update_linear(&linear_msg, true)
+        .expect("Test, assuming infallible"),
+      vec![
+        VectorSubcommandV4::new(0, 500, 0.5),
+        VectorSubcommandV4::new(1, 500, 0.5)
+      ]
+    );
+    let linear_msg_2 = LinearCmdV4::new(0, vec![VectorSubcommandV4::new(1, 250, 0.75)]);
+    assert_eq!(
+      mgr
+        .update_linear(&linear_msg_2, true)
+        .expect("Test, assuming infallible"),
+      vec![
+        VectorSubcommandV4::new(0, 500, 0.5),
+        VectorSubcommandV4::new(1, 250, 0.75)
+      ]
+    );
+  }
+
+  #[test]
+  pub fn test_command_generator_reset() {
+    let mgr = test_manager(
+      FeatureType::Vibrate,
+      ButtplugActuatorFeatureMessageType::ScalarCmd,
+    );
+    let scalar_msg = ScalarCmdV4::new(
+      0,
+      vec![ScalarSubcommandV4::new(0, 0.5, ActuatorType::Vibrate)],
+    );
+    assert_eq!(
+      mgr
+        .update_scalar(&scalar_msg, false)
+        .expect("Test, assuming infallible"),
+      vec![Some((ActuatorType::Vibrate, 10)), None]
+    );
+    mgr.reset();
+    // Dedupe shouldn't hold back the command after a reset.
+    assert_eq!(
+      mgr
+        .update_scalar(&scalar_msg, false)
+        .expect("Test, assuming infallible"),
+      vec![Some((ActuatorType::Vibrate, 10)), None]
+    );
+
+    let linear_mgr = ActuatorCommandManager::new(&linear_features(1), None);
+    let linear_msg = LinearCmdV4::new(0, vec![VectorSubcommandV4::new(0, 500, 0.5)]);
+    assert_eq!(
+      linear_mgr
+        .update_linear(&linear_msg, false)
+        .expect("Test, assuming infallible"),
+      vec![VectorSubcommandV4::new(0, 500, 0.5)]
+    );
+    linear_mgr.reset();
+    assert_eq!(
+      linear_mgr
+        .update_linear(&linear_msg, false)
+        .expect("Test, assuming infallible"),
+      vec![VectorSubcommandV4::new(0, 500, 0.5)]
+    );
+  }
+
+  #[test]
+  pub fn test_command_generator_linear_stop_commands() {
+    let mgr = ActuatorCommandManager::new(&linear_features(2), None);
+    assert_eq!(
+      mgr.stop_commands(),
+      vec![ButtplugDeviceCommandMessageUnion::LinearCmd(
+        LinearCmdV4::new(
+          0,
+          vec![
+            VectorSubcommandV4::new(0, 0, 0.0),
+            VectorSubcommandV4::new(1, 0, 0.0)
+          ]
+        )
+      )]
+    );
+  }
+
+  #[test]
+  pub fn test_command_generator_scalar_rotation_share_feature() {
+    // A feature that takes both ScalarCmd and RotateCmd only has one speed, so whichever message
+    // set it last is what later messages of either type are deduped against.
+    let actuator = DeviceFeatureActuator::new(
+      &RangeInclusive::new(0, 20),
+      &RangeInclusive::new(0, 20),
+      &HashSet::from([
+        ButtplugActuatorFeatureMessageType::ScalarCmd,
+        ButtplugActuatorFeatureMessageType::RotateCmd,
+      ]),
+    );
+    let features = vec![DeviceFeature::new(
+      "Test",
+      FeatureType::Rotate,
+      &Some(actuator),
+      &None,
+    )];
+    let mgr = ActuatorCommandManager::new(&features, None);
+    let rotate_msg = RotateCmdV4::new(0, vec![RotationSubcommandV4::new(0, 0.75, true)]);
+    let scalar_msg = ScalarCmdV4::new(
+      0,
+      vec![ScalarSubcommandV4::new(0, 0.5, ActuatorType::Rotate)],
+    );
+    assert_eq!(
+      mgr
+        .update_scalar(&scalar_msg, false)
+        .expect("Test, assuming infallible"),
+      vec![Some((ActuatorType::Rotate, 10))]
+    );
+    // The same speed in the same direction is already set, no matter which message set it.
+    assert_eq!(
+      mgr
+        .update_rotation(
+          &RotateCmdV4::new(0, vec![RotationSubcommandV4::new(0, 0.5, false)]),
+          false
+        )
+        .expect("Test, assuming infallible"),
+      vec![None]
+    );
+    assert_eq!(
+      mgr
+        .update_rotation(&rotate_msg, false)
+        .expect("Test, assuming infallible"),
+      vec![Some((15, true))]
+    );
+    // The rotation replaced the scalar speed, so the scalar has to be sent again, and then the
+    // rotation after it.
+    assert_eq!(
+      mgr
+        .update_scalar(&scalar_msg, false)
+        .expect("Test, assuming infallible"),
+      vec![Some((ActuatorType::Rotate, 10))]
+    );
+    assert_eq!(
+      mgr
+        .update_rotation(&rotate_msg, false)
+        .expect("Test, assuming infallible"),
+      vec![Some((15, true))]
+    );
+    let snapshot = mgr.state_snapshot();
+    assert_eq!(snapshot.features()[0].scalar(), 15);
+    assert_eq!(snapshot.features()[0].rotation_speed(), 15);
+    assert!(snapshot.features()[0].rotation_clockwise());
+  }
+
+  fn scalar_features(step_ranges: &[(u32, u32)]) -> Vec<DeviceFeature> {
+    step_ranges
+      .iter()
+      .map(|(start, len)| {
+        let range = RangeInclusive::new(*start, start + len);
+        let actuator = DeviceFeatureActuator::new(
+          &range,
+          &range,
+          &HashSet::from([ButtplugActuatorFeatureMessageType::ScalarCmd]),
+        );
+        DeviceFeature::new("Test", FeatureType::Vibrate, &Some(actuator), &None)
+      })
+      .collect()
+  }
+
+  fn scalar_msg(values: &[f64]) -> ScalarCmdV4 {
+    ScalarCmdV4::new(
+      0,
+      values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| ScalarSubcommandV4::new(index as u32, *value, ActuatorType::Vibrate))
+        .collect(),
+    )
+  }
+
+  #[test]
+  fn test_zero_threshold() {
+    let features = scalar_features(&[(0, 100)]);
+    let default_mgr = ActuatorCommandManager::new(&features, None);
+    let no_snap_mgr = ActuatorCommandManager::with_zero_threshold(&features, None, 0.0);
+    // 0.0000005 * 100 steps is under the default threshold, so it's snapped to off.
+    assert_eq!(
+      default_mgr
+        .update_scalar(&scalar_msg(&[0.0000005]), false)
+        .expect("Test, assuming infallible"),
+      vec![Some((ActuatorType::Vibrate, 0))]
+    );
+    assert_eq!(
+      no_snap_mgr
+        .update_scalar(&scalar_msg(&[0.0000005]), false)
+        .expect("Test, assuming infallible"),
+      vec![Some((ActuatorType::Vibrate, 1))]
+    );
+    // Without a threshold, 0.0 still means off.
+    assert_eq!(
+      no_snap_mgr
+        .update_scalar(&scalar_msg(&[0.0]), false)
+        .expect("Test, assuming infallible"),
+      vec![Some((ActuatorType::Vibrate, 0))]
+    );
+  }
+
+  proptest! {
+    #[test]
+    fn prop_scalar_values_within_step_range(
+      step_ranges in prop::collection::vec((0u32..50, 1u32..100), 1..5),
+      values in prop::collection::vec(0.0f64..=1.0, 5),
+    ) {
+      let mgr = ActuatorCommandManager::new(&scalar_features(&step_ranges), None);
+      let values = &values[..step_ranges.len()];
+      let result = mgr.update_scalar(&scalar_msg(values), false).expect("Test, assuming infallible");
+      prop_assert_eq!(result.len(), step_ranges.len());
+      for ((start, len), cmd) in step_ranges.iter().zip(result) {
+        let (_, step) = cmd.expect("First command for a feature should always be sent");
+        // 0 is always valid, as it means "stop", regardless of where the step range starts.
+        prop_assert!(step == 0 || (*start..=start + len).contains(&step));
+      }
+    }
+
+    #[test]
+    fn prop_scalar_repeated_command_is_deduped(
+      step_ranges in prop::collection::vec((0u32..50, 1u32..100), 1..5),
+      values in prop::collection::vec(0.0f64..=1.0, 5),
+    ) {
+      let mgr = ActuatorCommandManager::new(&scalar_features(&step_ranges), None);
+      let msg = scalar_msg(&values[..step_ranges.len()]);
+      mgr.update_scalar(&msg, false).expect("Test, assuming infallible");
+      let result = mgr.update_scalar(&msg, false).expect("Test, assuming infallible");
+      prop_assert!(result.iter().all(|x| x.is_none()));
+    }
+
+    #[test]
+    fn prop_scalar_match_all_covers_all_features(
+      step_ranges in prop::collection::vec((0u32..50, 1u32..100), 1..5),
+      values in prop::collection::vec(0.0f64..=1.0, 5),
+      subcommand_count in 1usize..5,
+    ) {
+      let mgr = ActuatorCommandManager::new(&scalar_features(&step_ranges), None);
+      let count = subcommand_count.min(step_ranges.len());
+      for _ in 0..2 {
+        let result = mgr
+          .update_scalar(&scalar_msg(&values[..count]), true)
+          .expect("Test, assuming infallible");
+        prop_assert_eq!(result.len(), step_ranges.len());
+        prop_assert!(result.iter().all(|x| x.is_some()));
+      }
+    }
+
+    #[test]
+    fn prop_scalar_stop_commands_zero_all_features(
+      step_ranges in prop::collection::vec((0u32..50, 1u32..100), 1..5),
+      values in prop::collection::vec(0.0f64..=1.0, 5),
+    ) {
+      let mgr = ActuatorCommandManager::new(&scalar_features(&step_ranges), None);
+      mgr
+        .update_scalar(&scalar_msg(&values[..step_ranges.len()]), false)
+        .expect("Test, assuming infallible");
+      let stop_commands = mgr.stop_commands();
+      prop_assert_eq!(stop_commands.len(), 1);
+      let ButtplugDeviceCommandMessageUnion::ScalarCmd(stop_msg) = &stop_commands[0] else {
+        panic!("Scalar only device should only have a ScalarCmd stop command");
+      };
+      let result = mgr.update_scalar(stop_msg, true).expect("Test, assuming infallible");
+      prop_assert!(result.iter().all(|x| *x == Some((ActuatorType::Vibrate, 0))));
+    }
+  }
+}
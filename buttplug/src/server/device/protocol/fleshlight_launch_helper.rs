@@ -35,9 +35,11 @@ pub fn calculate_speed(mut distance: f64, duration: u32) -> f64 {
     distance = 1f64;
   }
 
+  // A duration of 0 means "as fast as the device can go", and very short durations can work out to
+  // more than the device can do, so cap at full speed rather than overflowing the speed byte.
   let scalar = ((duration as f64 * 90f64) / (distance * 100f64)).powf(-1.05);
 
-  250f64 * scalar
+  (250f64 * scalar).min(1f64)
 }
 
 pub fn calculate_duration(mut distance: f64, mut speed: f64) -> u32 {
@@ -26,17 +26,28 @@ impl ProtocolHandler for LiboShark {
     super::ProtocolKeepaliveStrategy::RepeatLastPacketStrategy
   }
 
+  // Both motors are packed into the same byte, so we always need both values.
+  fn needs_full_command_set(&self) -> bool {
+    true
+  }
+
   fn handle_scalar_cmd(
     &self,
     cmds: &[Option<(ActuatorType, u32)>],
   ) -> Result<Vec<HardwareCommand>, ButtplugDeviceError> {
-    // Store off result before the match, so we drop the lock ASAP.
+    if cmds.is_empty() {
+      return Err(ButtplugDeviceError::ProtocolRequirementError(
+        "Libo Shark requires at least one vibrator command.".to_owned(),
+      ));
+    }
+    // First motor goes in the high nibble, second in the low nibble. If the device only has one
+    // motor configured, the low nibble just stays empty.
     let mut data = 0u8;
-    if let Some((_, speed)) = cmds[0] {
-      data |= (speed as u8) << 4;
+    if let Some(Some((_, speed))) = cmds.first() {
+      data |= (*speed as u8) << 4;
     }
-    if let Some((_, speed)) = cmds[1] {
-      data |= speed as u8;
+    if let Some(Some((_, speed))) = cmds.get(1) {
+      data |= *speed as u8;
     }
     Ok(vec![
       HardwareWriteCmd::new(Endpoint::Tx, vec![data], false).into()
@@ -8,11 +8,19 @@
 use crate::{
   core::{
     errors::ButtplugDeviceError,
-    message::{self, ActuatorType, ButtplugDeviceMessage, Endpoint, FeatureType, SensorReadingV4},
+    message::{
+      self,
+      ActuatorType,
+      ButtplugDeviceMessage,
+      Endpoint,
+      FeatureType,
+      SensorReadingV4,
+      SensorType,
+    },
   },
   server::device::{
     configuration::{ProtocolCommunicationSpecifier, UserDeviceDefinition, UserDeviceIdentifier},
-    hardware::{Hardware, HardwareCommand, HardwareReadCmd, HardwareWriteCmd},
+    hardware::{Hardware, HardwareCommand, HardwareSensorReadCmd, HardwareWriteCmd},
     protocol::{
       generic_protocol_initializer_setup,
       ProtocolHandler,
@@ -295,18 +303,18 @@ impl ProtocolHandler for LovenseConnectService {
     msg: message::SensorReadCmdV4,
   ) -> BoxFuture<Result<SensorReadingV4, ButtplugDeviceError>> {
     async move {
-      // This is a dummy read. We just store the battery level in the device
-      // implementation and it's the only thing read will return.
-      let reading = device
-        .read_value(&HardwareReadCmd::new(Endpoint::Rx, 0, 0))
+      // The hardware keeps track of the battery level reported by the Lovense Connect app, so we
+      // can get it straight from there.
+      let data = device
+        .read_sensor(&HardwareSensorReadCmd::new(SensorType::Battery))
         .await?;
-      debug!("Battery level: {}", reading.data()[0]);
+      debug!("Battery level: {:?}", data);
       Ok(
         message::SensorReadingV4::new(
           msg.device_index(),
           *msg.feature_index(),
           *msg.sensor_type(),
-          vec![reading.data()[0] as i32],
+          data,
         )
         .into(),
       )
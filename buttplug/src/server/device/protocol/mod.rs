@@ -140,7 +140,7 @@ use crate::{
   },
   server::device::{
     configuration::{ProtocolCommunicationSpecifier, UserDeviceDefinition, UserDeviceIdentifier},
-    hardware::{Hardware, HardwareCommand, HardwareReadCmd},
+    hardware::{Hardware, HardwareCommand, HardwareReadCmd, HardwareSensorReadCmd},
   },
 };
 use async_trait::async_trait;
@@ -718,6 +718,7 @@ pub trait ProtocolHandler: Sync + Send {
         &mut (match *actuator {
           ActuatorType::Constrict => self.handle_scalar_constrict_cmd(index as u32, *scalar)?,
           ActuatorType::Inflate => self.handle_scalar_inflate_cmd(index as u32, *scalar)?,
+          ActuatorType::Squeeze => self.handle_scalar_squeeze_cmd(index as u32, *scalar)?,
           ActuatorType::Oscillate => self.handle_scalar_oscillate_cmd(index as u32, *scalar)?,
           ActuatorType::Rotate => self.handle_scalar_rotate_cmd(index as u32, *scalar)?,
           ActuatorType::Vibrate => self.handle_scalar_vibrate_cmd(index as u32, *scalar)?,
@@ -763,6 +764,14 @@ pub trait ProtocolHandler: Sync + Send {
     self.command_unimplemented("ScalarCmd (Inflate Actuator)")
   }
 
+  fn handle_scalar_squeeze_cmd(
+    &self,
+    _index: u32,
+    _scalar: u32,
+  ) -> Result<Vec<HardwareCommand>, ButtplugDeviceError> {
+    self.command_unimplemented("ScalarCmd (Squeeze Actuator)")
+  }
+
   fn handle_scalar_constrict_cmd(
     &self,
     _index: u32,
@@ -843,6 +852,22 @@ pub trait ProtocolHandler: Sync + Send {
   ) -> BoxFuture<Result<SensorReadingV4, ButtplugDeviceError>> {
     match message.sensor_type() {
       SensorType::Battery => self.handle_battery_level_cmd(device, message.clone()),
+      // RSSI is measured by whatever we're talking to the device through, not the device itself, so
+      // it always comes from the hardware layer.
+      SensorType::RSSI => {
+        let fut = device.read_sensor(&HardwareSensorReadCmd::new(SensorType::RSSI));
+        let message = message.clone();
+        async move {
+          let data = fut.await?;
+          Ok(message::SensorReadingV4::new(
+            message.device_index(),
+            *message.feature_index(),
+            *message.sensor_type(),
+            data,
+          ))
+        }
+        .boxed()
+      }
       _ => future::ready(Err(ButtplugDeviceError::UnhandledCommand(
         "Command not implemented for this protocol: SensorReadCmd".to_string(),
       )))
@@ -38,7 +38,10 @@
 
 use std::{
   fmt::{self, Debug},
-  sync::Arc,
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+  },
   time::Duration,
 };
 
@@ -48,12 +51,14 @@ use crate::{
     message::{
       self,
       ButtplugDeviceCommandMessageUnion,
+      ButtplugDeviceMessage,
       ButtplugDeviceMessageType,
       ButtplugMessage,
       ButtplugServerDeviceMessage,
       ButtplugServerMessageV4,
       Endpoint,
       FeatureType,
+      LinearCmdV4,
       RawReadingV2,
       RawSubscribeCmdV2,
       ScalarCmdV4,
@@ -63,7 +68,7 @@ use crate::{
   },
   server::{
     device::{
-      configuration::DeviceConfigurationManager,
+      configuration::{DeviceConfigurationManager, ProtocolCommunicationSpecifier},
       hardware::{Hardware, HardwareCommand, HardwareConnector, HardwareEvent},
       protocol::ProtocolHandler,
     },
@@ -82,7 +87,11 @@ use super::{
   configuration::{UserDeviceDefinition, UserDeviceIdentifier},
   hardware::HardwareWriteCmd,
   protocol::{
-    actuator_command_manager::ActuatorCommandManager,
+    actuator_command_manager::{
+      ActuatorCommandManager,
+      DeviceStateSnapshot,
+      DEFAULT_ZERO_THRESHOLD,
+    },
     ProtocolKeepaliveStrategy,
     ProtocolSpecializer,
   },
@@ -101,12 +110,18 @@ pub struct ServerDevice {
   handler: Arc<dyn ProtocolHandler>,
   #[getset(get = "pub")]
   definition: UserDeviceDefinition,
-  actuator_command_manager: ActuatorCommandManager,
+  actuator_command_manager: Arc<ActuatorCommandManager>,
   /// Unique identifier for the device
   #[getset(get = "pub")]
   identifier: UserDeviceIdentifier,
+  /// How the device was connected to (Bluetooth LE, USB, network, etc).
+  #[getset(get = "pub")]
+  communication_specifier: ProtocolCommunicationSpecifier,
   raw_subscribed_endpoints: Arc<DashSet<Endpoint>>,
   keepalive_packet: Arc<RwLock<Option<HardwareWriteCmd>>>,
+  /// Incremented on every StopDeviceCmd, so command series that started before the stop know to
+  /// abandon their remaining writes.
+  stop_generation: Arc<AtomicU64>,
 }
 impl Debug for ServerDevice {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -203,7 +218,13 @@ impl ServerDevice {
     let strategy = handler.keepalive_strategy();
 
     // We now have fully initialized hardware, return a server device.
-    let device = Self::new(identifier, handler, hardware, &attrs);
+    let device = Self::new(
+      identifier,
+      hardware_connector.specifier(),
+      handler,
+      hardware,
+      &attrs,
+    );
 
     // If we need a keepalive with a packet replay, set this up via stopping the device on connect.
     if requires_keepalive
@@ -226,12 +247,23 @@ impl ServerDevice {
   /// Given a protocol and a device impl, create a new ButtplugDevice instance
   fn new(
     identifier: UserDeviceIdentifier,
+    communication_specifier: ProtocolCommunicationSpecifier,
     handler: Arc<dyn ProtocolHandler>,
     hardware: Arc<Hardware>,
     definition: &UserDeviceDefinition,
   ) -> Self {
     let keepalive_packet = Arc::new(RwLock::new(None));
-    let acm = ActuatorCommandManager::new(definition.features());
+    let acm = Arc::new(ActuatorCommandManager::with_zero_threshold(
+      definition.features(),
+      definition
+        .user_config()
+        .message_timing_gap()
+        .map(|gap| Duration::from_millis(gap.into())),
+      definition
+        .user_config()
+        .zero_threshold()
+        .unwrap_or(DEFAULT_ZERO_THRESHOLD),
+    ));
     // If we've gotten here, we know our hardware is connected. This means we can start the keepalive if it's required.
     if hardware.requires_keepalive()
       && !matches!(
@@ -279,12 +311,14 @@ impl ServerDevice {
 
     Self {
       identifier,
+      communication_specifier,
       actuator_command_manager: acm,
       handler,
       hardware,
       keepalive_packet,
       definition: definition.clone(),
       raw_subscribed_endpoints: Arc::new(DashSet::new()),
+      stop_generation: Arc::new(AtomicU64::new(0)),
     }
   }
 
@@ -311,6 +345,20 @@ impl ServerDevice {
     }
   }
 
+  /// Get the last position sent to a linear feature on this device, as tracked by the actuator
+  /// command manager. Returns None if the feature index doesn't exist.
+  pub(crate) fn current_linear_position(&self, feature_index: u32) -> Option<f64> {
+    self
+      .actuator_command_manager
+      .current_linear_position(feature_index)
+  }
+
+  /// Get what the actuator command manager believes each feature of this device is currently set
+  /// to. See [DeviceStateSnapshot] for caveats.
+  pub fn state_snapshot(&self) -> DeviceStateSnapshot {
+    self.actuator_command_manager.state_snapshot()
+  }
+
   /// Disconnect from the device, if it's connected.
   pub fn disconnect(&self) -> ButtplugResultFuture {
     let fut = self.hardware.disconnect();
@@ -324,11 +372,17 @@ impl ServerDevice {
   pub fn event_stream(&self) -> impl futures::Stream<Item = ServerDeviceEvent> + Send {
     let identifier = self.identifier.clone();
     let raw_endpoints = self.raw_subscribed_endpoints.clone();
+    let acm = self.actuator_command_manager.clone();
     let hardware_stream = convert_broadcast_receiver_to_stream(self.hardware.event_stream())
       .filter_map(move |hardware_event| {
         let id = identifier.clone();
         match hardware_event {
-          HardwareEvent::Disconnected(_) => Some(ServerDeviceEvent::Disconnected(id)),
+          HardwareEvent::Disconnected(_) => {
+            // Whatever we last sent is gone with the connection, so don't let it dedupe the first
+            // commands sent if the hardware comes back.
+            acm.reset();
+            Some(ServerDeviceEvent::Disconnected(id))
+          }
           HardwareEvent::Notification(_address, endpoint, data) => {
             // TODO Figure out how we're going to parse raw data into something sendable to the client.
             if raw_endpoints.contains(&endpoint) {
@@ -363,22 +417,31 @@ impl ServerDevice {
         .then_some(())
         .ok_or(ButtplugDeviceError::MessageNotSupported(msg_type))
     };
+    // Raw messages can be turned off per device in the user config, regardless of whether the
+    // server allows them.
+    let check_raw_msg = |msg_type| {
+      if self.definition.user_config().deny_raw_messages() {
+        Err(ButtplugDeviceError::RawMessagesDenied)
+      } else {
+        check_msg(msg_type)
+      }
+    };
 
     match message {
       ButtplugDeviceCommandMessageUnion::LinearCmd(_) => {
         check_msg(ButtplugDeviceMessageType::LinearCmd)
       }
       ButtplugDeviceCommandMessageUnion::RawReadCmd(_) => {
-        check_msg(ButtplugDeviceMessageType::RawReadCmd)
+        check_raw_msg(ButtplugDeviceMessageType::RawReadCmd)
       }
       ButtplugDeviceCommandMessageUnion::RawSubscribeCmd(_) => {
-        check_msg(ButtplugDeviceMessageType::RawSubscribeCmd)
+        check_raw_msg(ButtplugDeviceMessageType::RawSubscribeCmd)
       }
       ButtplugDeviceCommandMessageUnion::RawUnsubscribeCmd(_) => {
-        check_msg(ButtplugDeviceMessageType::RawUnsubscribeCmd)
+        check_raw_msg(ButtplugDeviceMessageType::RawUnsubscribeCmd)
       }
       ButtplugDeviceCommandMessageUnion::RawWriteCmd(_) => {
-        check_msg(ButtplugDeviceMessageType::RawWriteCmd)
+        check_raw_msg(ButtplugDeviceMessageType::RawWriteCmd)
       }
       ButtplugDeviceCommandMessageUnion::RotateCmd(_) => {
         check_msg(ButtplugDeviceMessageType::RotateCmd)
@@ -450,7 +513,24 @@ impl ServerDevice {
         self.handle_generic_command_result(self.handler.handle_rotate_cmd(&commands))
       }
       ButtplugDeviceCommandMessageUnion::LinearCmd(msg) => {
-        self.handle_generic_command_result(self.handler.handle_linear_cmd(msg))
+        let vectors = match self
+          .actuator_command_manager
+          .update_linear(&msg, self.handler.needs_full_command_set())
+        {
+          Ok(values) => values,
+          Err(err) => return future::ready(Err(err)).boxed(),
+        };
+        if vectors.is_empty() {
+          trace!(
+            "No commands generated for incoming device packet, skipping and returning success."
+          );
+          return future::ready(Ok(message::OkV0::default().into())).boxed();
+        }
+        self.handle_generic_command_result(
+          self
+            .handler
+            .handle_linear_cmd(LinearCmdV4::new(msg.device_index(), vectors)),
+        )
       }
       // Other generic messages
       ButtplugDeviceCommandMessageUnion::StopDeviceCmd(_) => self.handle_stop_device_cmd(),
@@ -514,6 +594,8 @@ impl ServerDevice {
     let hardware = self.hardware.clone();
     let keepalive_type = self.handler.keepalive_strategy();
     let keepalive_packet = self.keepalive_packet.clone();
+    let stop_generation = self.stop_generation.clone();
+    let generation = stop_generation.load(Ordering::SeqCst);
     async move {
       // Run commands in order, otherwise we may end up sending out of order. This may take a while,
       // but it's what 99% of protocols expect. If they want something else, they can implement it
@@ -522,6 +604,15 @@ impl ServerDevice {
       // If anything errors out, just bail on the command series. This most likely means the device
       // disconnected.
       for command in commands {
+        // If the device was stopped while we were working through the series, don't send anything
+        // else, otherwise we'd start the device back up after the stop went out.
+        if stop_generation.load(Ordering::SeqCst) != generation {
+          debug!(
+            "Device {} stopped while command series was in progress, dropping remaining commands.",
+            hardware.name()
+          );
+          break;
+        }
         hardware.parse_message(&command).await?;
         if hardware.requires_keepalive()
           && matches!(
@@ -552,6 +643,9 @@ impl ServerDevice {
   }
 
   fn handle_stop_device_cmd(&self) -> ButtplugServerResultFuture {
+    // Preempt any command series still in flight. This has to happen before we build the stop
+    // futures, so they pick up the new generation.
+    self.stop_generation.fetch_add(1, Ordering::SeqCst);
     let commands = self.actuator_command_manager.stop_commands();
     let mut fut_vec = vec![];
     commands
@@ -571,13 +665,18 @@ impl ServerDevice {
     feature_index: &u32,
     sensor_type: &SensorType,
   ) -> Result<(), ButtplugDeviceError> {
-    if *feature_index > self.definition.features().len() as u32 {
+    let Some(feature) = self
+      .definition
+      .features()
+      .get(*feature_index as usize)
+      .filter(|feature| feature.sensor().is_some())
+    else {
       return Err(ButtplugDeviceError::DeviceSensorIndexError(
         self.definition.features().len() as u32,
         *feature_index,
       ));
-    }
-    let feature_type = self.definition.features()[*feature_index as usize].feature_type();
+    };
+    let feature_type = feature.feature_type();
     if *feature_type != FeatureType::from(*sensor_type) {
       Err(ButtplugDeviceError::DeviceSensorTypeMismatch(
         *feature_index,
@@ -30,15 +30,16 @@ use crate::{
         HardwareCommunicationManager,
         HardwareCommunicationManagerBuilder,
       },
+      protocol::actuator_command_manager::DeviceStateSnapshot,
       server_device_manager_event_loop::ServerDeviceManagerEventLoop,
       ServerDevice,
     },
     ButtplugServerError,
     ButtplugServerResultFuture,
   },
-  util::{async_manager, stream::convert_broadcast_receiver_to_stream},
+  util::{async_manager, sleep, stream::convert_broadcast_receiver_to_stream},
 };
-use dashmap::DashMap;
+use dashmap::{DashMap, DashSet};
 use futures::{
   future::{self, FutureExt},
   Stream,
@@ -47,19 +48,49 @@ use getset::Getters;
 use std::{
   convert::TryFrom,
   sync::{
-    atomic::{AtomicBool, Ordering},
+    atomic::{AtomicBool, AtomicUsize, Ordering},
     Arc,
   },
+  time::Duration,
 };
-use tokio::sync::{broadcast, mpsc};
+use tokio::sync::{broadcast, mpsc, watch};
 use tokio_util::sync::CancellationToken;
 
+/// Stored as the device limit when there isn't one. No device count can ever reach it.
+pub(super) const NO_DEVICE_LIMIT: usize = usize::MAX;
+
 #[derive(Debug)]
 pub(super) enum DeviceManagerCommand {
   StartScanning,
   StopScanning,
 }
 
+// Held by every device command future handed out by the manager until it resolves or is dropped,
+// so shutdown can tell when everything that was already in flight has finished.
+struct InFlightCommandGuard(Arc<watch::Sender<usize>>);
+
+impl InFlightCommandGuard {
+  fn new(in_flight_commands: &Arc<watch::Sender<usize>>) -> Self {
+    in_flight_commands.send_modify(|count| *count += 1);
+    Self(in_flight_commands.clone())
+  }
+}
+
+impl Drop for InFlightCommandGuard {
+  fn drop(&mut self) {
+    self.0.send_modify(|count| *count -= 1);
+  }
+}
+
+// Marks a device as busy until the actuator command holding this resolves or is dropped.
+struct BusyDeviceGuard(Arc<DashSet<u32>>, u32);
+
+impl Drop for BusyDeviceGuard {
+  fn drop(&mut self) {
+    self.0.remove(&self.1);
+  }
+}
+
 #[derive(Debug, Getters)]
 #[getset(get = "pub")]
 pub struct ServerDeviceInfo {
@@ -70,6 +101,8 @@ pub struct ServerDeviceInfo {
 pub struct ServerDeviceManagerBuilder {
   device_configuration_manager: Arc<DeviceConfigurationManager>,
   comm_managers: Vec<Box<dyn HardwareCommunicationManagerBuilder>>,
+  max_devices: Option<usize>,
+  reject_commands_while_busy: bool,
 }
 
 impl ServerDeviceManagerBuilder {
@@ -77,6 +110,8 @@ impl ServerDeviceManagerBuilder {
     Self {
       device_configuration_manager: Arc::new(device_configuration_manager),
       comm_managers: vec![],
+      max_devices: None,
+      reject_commands_while_busy: false,
     }
   }
 
@@ -86,6 +121,8 @@ impl ServerDeviceManagerBuilder {
     Self {
       device_configuration_manager,
       comm_managers: vec![],
+      max_devices: None,
+      reject_commands_while_busy: false,
     }
   }
 
@@ -97,6 +134,23 @@ impl ServerDeviceManagerBuilder {
     self
   }
 
+  /// Set the maximum number of devices that can be connected at once. Once the limit is reached,
+  /// newly found devices are ignored until something disconnects. If this is not called, there is
+  /// no limit.
+  pub fn max_devices(&mut self, max_devices: usize) -> &mut Self {
+    self.max_devices = Some(max_devices);
+    self
+  }
+
+  /// If true, an actuator command (ScalarCmd, RotateCmd or LinearCmd) sent to a device that is still
+  /// working through a previous actuator command fails with [ButtplugDeviceError::DeviceBusy]
+  /// instead of waiting its turn. Useful with slow-response hardware, where queued up commands can
+  /// arrive long after they were sent. Stop commands are never rejected. Defaults to false.
+  pub fn reject_commands_while_busy(&mut self, reject: bool) -> &mut Self {
+    self.reject_commands_while_busy = reject;
+    self
+  }
+
   pub fn finish(&mut self) -> Result<ServerDeviceManager, ButtplugServerError> {
     let (device_command_sender, device_command_receiver) = mpsc::channel(256);
     let (device_event_sender, device_event_receiver) = mpsc::channel(256);
@@ -145,6 +199,12 @@ impl ServerDeviceManagerBuilder {
 
     let output_sender = broadcast::channel(255).0;
 
+    // Shared with the event loop so the limit can still be changed once the manager is built (see
+    // ButtplugServerBuilder::max_devices).
+    let max_devices = Arc::new(AtomicUsize::new(
+      self.max_devices.unwrap_or(NO_DEVICE_LIMIT),
+    ));
+
     let mut event_loop = ServerDeviceManagerEventLoop::new(
       comm_managers,
       self.device_configuration_manager.clone(),
@@ -153,6 +213,7 @@ impl ServerDeviceManagerBuilder {
       output_sender.clone(),
       device_event_receiver,
       device_command_receiver,
+      max_devices.clone(),
     );
     async_manager::spawn(async move {
       event_loop.run().await;
@@ -164,6 +225,11 @@ impl ServerDeviceManagerBuilder {
       loop_cancellation_token,
       running: Arc::new(AtomicBool::new(true)),
       output_sender,
+      in_flight_commands: Arc::new(watch::channel(0).0),
+      command_cancellation_token: CancellationToken::new(),
+      reject_commands_while_busy: self.reject_commands_while_busy,
+      busy_devices: Arc::new(DashSet::new()),
+      max_devices,
     })
   }
 }
@@ -178,6 +244,17 @@ pub struct ServerDeviceManager {
   loop_cancellation_token: CancellationToken,
   running: Arc<AtomicBool>,
   output_sender: broadcast::Sender<ButtplugServerMessageV4>,
+  /// Number of device command futures that have been handed out but not yet resolved.
+  in_flight_commands: Arc<watch::Sender<usize>>,
+  /// Cancelled on shutdown if in flight commands don't finish within the drain timeout.
+  command_cancellation_token: CancellationToken,
+  /// If true, actuator commands for devices in busy_devices are rejected.
+  reject_commands_while_busy: bool,
+  /// Indexes of devices that currently have an actuator command in flight.
+  busy_devices: Arc<DashSet<u32>>,
+  /// Maximum number of devices that can be connected at once, shared with the event loop.
+  /// [NO_DEVICE_LIMIT] if there is no limit.
+  max_devices: Arc<AtomicUsize>,
 }
 
 impl ServerDeviceManager {
@@ -187,6 +264,13 @@ impl ServerDeviceManager {
     convert_broadcast_receiver_to_stream(self.output_sender.subscribe())
   }
 
+  /// Set the maximum number of devices that can be connected at once. Devices that are already
+  /// connected stay connected, but newly found devices are ignored until the count drops below the
+  /// limit.
+  pub(crate) fn set_max_devices(&self, max_devices: usize) {
+    self.max_devices.store(max_devices, Ordering::Relaxed);
+  }
+
   fn start_scanning(&self) -> ButtplugServerResultFuture {
     let command_sender = self.device_command_sender.clone();
     async move {
@@ -240,9 +324,34 @@ impl ServerDeviceManager {
   ) -> ButtplugServerResultFuture {
     match self.devices.get(&device_msg.device_index()) {
       Some(device) => {
+        let device_index = device_msg.device_index();
+        let busy_guard = if self.reject_commands_while_busy
+          && matches!(
+            device_msg,
+            ButtplugDeviceCommandMessageUnion::ScalarCmd(_)
+              | ButtplugDeviceCommandMessageUnion::RotateCmd(_)
+              | ButtplugDeviceCommandMessageUnion::LinearCmd(_)
+          ) {
+          if !self.busy_devices.insert(device_index) {
+            return ButtplugDeviceError::DeviceBusy(device_index).into();
+          }
+          Some(BusyDeviceGuard(self.busy_devices.clone(), device_index))
+        } else {
+          None
+        };
         let fut = device.parse_message(device_msg);
+        let guard = InFlightCommandGuard::new(&self.in_flight_commands);
+        let token = self.command_cancellation_token.clone();
         // Create a future to run the message through the device, then handle adding the id to the result.
-        async move { fut.await }.boxed()
+        async move {
+          let _guard = guard;
+          let _busy_guard = busy_guard;
+          tokio::select! {
+            result = fut => result,
+            _ = token.cancelled() => Err(ButtplugUnknownError::DeviceManagerNotRunning.into()),
+          }
+        }
+        .boxed()
       }
       None => ButtplugDeviceError::DeviceNotAvailable(device_msg.device_index()).into(),
     }
@@ -263,7 +372,7 @@ impl ServerDeviceManager {
               *device.key(),
               &dev.name(),
               &dev.definition().user_config().display_name(),
-              &None,
+              &dev.definition().user_config().message_timing_gap(),
               dev.definition().features().clone(),
             )
           })
@@ -305,24 +414,60 @@ impl ServerDeviceManager {
     })
   }
 
+  /// Returns the cached actuator state of the device at `index`, for diagnostics. Returns None if
+  /// no device is connected at that index.
+  pub fn device_state_snapshot(&self, index: u32) -> Option<DeviceStateSnapshot> {
+    self
+      .devices
+      .get(&index)
+      .map(|device| device.value().state_snapshot())
+  }
+
+  /// Returns the number of devices currently connected. Cheaper than building a device list, so
+  /// this is what health checks and metrics exporters should poll.
+  pub fn connected_device_count(&self) -> u32 {
+    self.devices.len() as u32
+  }
+
   // Only a ButtplugServer should be able to call this. We don't want to expose this capability to
   // the outside world. Note that this could cause issues for lifetimes if someone holds this longer
   // than the lifetime of the server that originally created it. Ideally we should lock the Server
   // Device Manager lifetime to the owning ButtplugServer lifetime to ensure that doesn't happen,
   // but that's going to be complicated.
-  pub(crate) fn shutdown(&self) -> ButtplugServerResultFuture {
-    let devices = self.devices.clone();
+  //
+  // Any device commands still in flight get up to drain_timeout to finish (along with the stop
+  // commands we send to every device) before they're cancelled and the hardware is dropped. Calling
+  // this again after shutdown has started is a no-op.
+  pub(crate) fn shutdown(&self, drain_timeout: Duration) -> ButtplugServerResultFuture {
     // Make sure that, once our owning server shuts us down, no one outside can use this manager
     // again. Otherwise we can have all sorts of ownership weirdness.
-    self.running.store(false, Ordering::SeqCst);
+    if !self.running.swap(false, Ordering::SeqCst) {
+      return future::ready(Ok(message::OkV0::default().into())).boxed();
+    }
+    let devices = self.devices.clone();
     let stop_scanning = self.stop_scanning();
     let stop_devices = self.stop_all_devices();
+    let mut in_flight_commands = self.in_flight_commands.subscribe();
+    let command_token = self.command_cancellation_token.clone();
     let token = self.loop_cancellation_token.clone();
     async move {
       // Force stop scanning, otherwise we can disconnect and instantly try to reconnect while
       // cleaning up if we're still scanning.
       let _ = stop_scanning.await;
-      let _ = stop_devices.await;
+      let drain = async move {
+        let _ = stop_devices.await;
+        let _ = in_flight_commands.wait_for(|count| *count == 0).await;
+      };
+      tokio::select! {
+        _ = drain => {},
+        _ = sleep(drain_timeout) => {
+          warn!(
+            "Device commands still running after {:?} during shutdown, cancelling them.",
+            drain_timeout
+          );
+          command_token.cancel();
+        }
+      }
       for device in devices.iter() {
         device.value().disconnect().await?;
       }
@@ -6,9 +6,16 @@
 // for full license information.
 
 use crate::{
-  core::message::{ButtplugServerMessageV4, DeviceAddedV4, DeviceRemovedV0, ScanningFinishedV0},
+  core::message::{
+    ButtplugDeviceMessage,
+    ButtplugServerDeviceMessage,
+    ButtplugServerMessageV4,
+    DeviceAddedV4,
+    DeviceRemovedV4,
+    ScanningFinishedV0,
+  },
   server::device::{
-    configuration::DeviceConfigurationManager,
+    configuration::{DeviceConfigurationManager, UserDeviceIdentifier},
     hardware::communication::{HardwareCommunicationManager, HardwareCommunicationManagerEvent},
     ServerDevice,
     ServerDeviceEvent,
@@ -17,13 +24,16 @@ use crate::{
 };
 use dashmap::{DashMap, DashSet};
 use futures::{future, FutureExt, StreamExt};
-use std::sync::Arc;
+use std::sync::{
+  atomic::{AtomicUsize, Ordering},
+  Arc,
+};
 use tokio::sync::{broadcast, mpsc};
 use tokio_util::sync::CancellationToken;
 use tracing;
 use tracing_futures::Instrument;
 
-use super::server_device_manager::DeviceManagerCommand;
+use super::server_device_manager::{DeviceManagerCommand, NO_DEVICE_LIMIT};
 
 pub(super) struct ServerDeviceManagerEventLoop {
   comm_managers: Vec<Box<dyn HardwareCommunicationManager>>,
@@ -48,11 +58,15 @@ pub(super) struct ServerDeviceManagerEventLoop {
   scanning_started: bool,
   /// Devices currently trying to connect.
   connecting_devices: Arc<DashSet<String>>,
+  /// Maximum number of devices that can be connected at once, shared with the device manager.
+  /// [NO_DEVICE_LIMIT] if there is no limit.
+  max_devices: Arc<AtomicUsize>,
   /// Cancellation token for the event loop
   loop_cancellation_token: CancellationToken,
 }
 
 impl ServerDeviceManagerEventLoop {
+  #[allow(clippy::too_many_arguments)]
   pub fn new(
     comm_managers: Vec<Box<dyn HardwareCommunicationManager>>,
     device_config_manager: Arc<DeviceConfigurationManager>,
@@ -61,6 +75,7 @@ impl ServerDeviceManagerEventLoop {
     server_sender: broadcast::Sender<ButtplugServerMessageV4>,
     device_comm_receiver: mpsc::Receiver<HardwareCommunicationManagerEvent>,
     device_command_receiver: mpsc::Receiver<DeviceManagerCommand>,
+    max_devices: Arc<AtomicUsize>,
   ) -> Self {
     let (device_event_sender, device_event_receiver) = mpsc::channel(256);
     Self {
@@ -76,9 +91,28 @@ impl ServerDeviceManagerEventLoop {
       scanning_started: false,
       connecting_devices: Arc::new(DashSet::new()),
       loop_cancellation_token,
+      max_devices,
     }
   }
 
+  fn max_devices(&self) -> Option<usize> {
+    Some(self.max_devices.load(Ordering::Relaxed)).filter(|max| *max != NO_DEVICE_LIMIT)
+  }
+
+  fn at_device_limit(&self) -> bool {
+    self
+      .max_devices()
+      .is_some_and(|max| self.device_map.len() >= max)
+  }
+
+  fn device_index_for(&self, identifier: &UserDeviceIdentifier) -> Option<u32> {
+    self
+      .device_map
+      .iter()
+      .find(|device_pair| device_pair.value().identifier() == identifier)
+      .map(|device_pair| *device_pair.key())
+  }
+
   fn scanning_status(&self) -> bool {
     if self.comm_managers.iter().any(|x| x.scanning_status()) {
       debug!("At least one manager still scanning, continuing event loop.");
@@ -168,6 +202,16 @@ impl ServerDeviceManagerEventLoop {
           return;
         }
 
+        if self.at_device_limit() {
+          warn!(
+            "Device limit of {} reached, ignoring device {} ({}).",
+            self.max_devices().expect("Only at limit if we have one"),
+            name,
+            address
+          );
+          return;
+        }
+
         // First off, we need to see if we even have a configuration available for the device we're
         // trying to create. If we don't, exit, because this isn't actually an error. However, if we
         // actually *do* have a configuration but something goes wrong after this, then it's an
@@ -264,6 +308,19 @@ impl ServerDeviceManagerEventLoop {
           }
         } else {
           info!("Device map does not contain key {}.", device_index);
+          // Multiple devices may have been connecting when we hit the limit, so check again now
+          // that this one is actually up.
+          if self.at_device_limit() {
+            warn!(
+              "Device limit of {} reached, disconnecting {}.",
+              self.max_devices().expect("Only at limit if we have one"),
+              device.name()
+            );
+            if let Err(err) = device.disconnect().await {
+              error!("Error disconnecting device over limit: {:?}", err);
+            }
+            return;
+          }
         }
 
         // Create event loop for forwarding device events into our selector.
@@ -285,7 +342,7 @@ impl ServerDeviceManagerEventLoop {
           device_index,
           &device.name(),
           &device.definition().user_config().display_name(),
-          &None,
+          &device.definition().user_config().message_timing_gap(),
           &device.definition().features().clone(),
         );
         self.device_map.insert(device_index, device);
@@ -300,28 +357,36 @@ impl ServerDeviceManagerEventLoop {
         }
       }
       ServerDeviceEvent::Disconnected(identifier) => {
-        let mut device_index = None;
-        for device_pair in self.device_map.iter() {
-          if *device_pair.value().identifier() == identifier {
-            device_index = Some(*device_pair.key());
-            break;
-          }
-        }
-        if let Some(device_index) = device_index {
-          self
+        if let Some(device_index) = self.device_index_for(&identifier) {
+          let (_, device) = self
             .device_map
             .remove(&device_index)
             .expect("Remove will always work.");
+          info!("Device disconnected: [{}] {}", device_index, device.name());
+          let device_removed_message = DeviceRemovedV4::new(
+            device_index,
+            &device.name(),
+            device.definition().user_config().display_name(),
+          );
           if self
             .server_sender
-            .send(DeviceRemovedV0::new(device_index).into())
+            .send(device_removed_message.into())
             .is_err()
           {
             debug!("Server not currently available, dropping Device Removed event.");
           }
         }
       }
-      ServerDeviceEvent::Notification(_, message) => {
+      ServerDeviceEvent::Notification(identifier, mut message) => {
+        // Raw readings are built by the device, which doesn't know the index it was assigned, so
+        // fill that in before the reading goes out.
+        if let ButtplugServerDeviceMessage::RawReading(reading) = &mut message {
+          let Some(device_index) = self.device_index_for(&identifier) else {
+            debug!("Raw reading from unknown device {identifier:?}, dropping.");
+            return;
+          };
+          reading.set_device_index(device_index);
+        }
         if self.server_sender.send(message.into()).is_err() {
           debug!("Server not currently available, dropping Device Added event.");
         }
@@ -0,0 +1,135 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2024 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Audit logging of device commands sent to a [ButtplugServer](super::ButtplugServer).
+
+use crate::core::message::ButtplugDeviceMessageType;
+use getset::{CopyGetters, Getters};
+use instant::SystemTime;
+use std::{
+  collections::VecDeque,
+  sync::{Arc, Mutex},
+};
+
+/// Record of a device command being dispatched by the server.
+///
+/// Only the message type is kept, not the message contents, so audit logs don't end up storing
+/// things like the patterns a user ran on their device.
+#[derive(Debug, Clone, PartialEq, Getters, CopyGetters)]
+pub struct AuditEvent {
+  /// Time the command was dispatched. Uses [instant::SystemTime], as std's panics on wasm.
+  #[getset(get_copy = "pub")]
+  timestamp: SystemTime,
+  /// Name the client gave during the handshake, if a client was connected.
+  #[getset(get = "pub")]
+  client_name: Option<String>,
+  /// Index of the device the command was sent to.
+  #[getset(get_copy = "pub")]
+  device_index: u32,
+  /// Type of command sent.
+  #[getset(get_copy = "pub")]
+  message_type: ButtplugDeviceMessageType,
+}
+
+impl AuditEvent {
+  pub fn new(
+    timestamp: SystemTime,
+    client_name: Option<String>,
+    device_index: u32,
+    message_type: ButtplugDeviceMessageType,
+  ) -> Self {
+    Self {
+      timestamp,
+      client_name,
+      device_index,
+      message_type,
+    }
+  }
+}
+
+/// Destination for [AuditEvent]s, set via
+/// [ButtplugServerBuilder::event_log](super::ButtplugServerBuilder::event_log).
+///
+/// Called inline while the server dispatches messages, so implementations that persist events
+/// somewhere slow should hand them off rather than blocking.
+pub trait EventLogSink: Send + Sync {
+  fn record(&self, event: AuditEvent);
+}
+
+impl<T: EventLogSink + ?Sized> EventLogSink for Arc<T> {
+  fn record(&self, event: AuditEvent) {
+    (**self).record(event)
+  }
+}
+
+/// [EventLogSink] that keeps the most recent events in memory. Once full, the oldest events are
+/// dropped to make room for new ones.
+pub struct InMemoryEventLog {
+  capacity: usize,
+  events: Mutex<VecDeque<AuditEvent>>,
+}
+
+impl InMemoryEventLog {
+  pub fn new(capacity: usize) -> Self {
+    Self {
+      capacity,
+      events: Mutex::new(VecDeque::with_capacity(capacity)),
+    }
+  }
+
+  /// Removes and returns all events currently stored, oldest first.
+  pub fn drain(&self) -> Vec<AuditEvent> {
+    self
+      .events
+      .lock()
+      .expect("Event log lock should never be poisoned")
+      .drain(..)
+      .collect()
+  }
+}
+
+impl EventLogSink for InMemoryEventLog {
+  fn record(&self, event: AuditEvent) {
+    if self.capacity == 0 {
+      return;
+    }
+    let mut events = self
+      .events
+      .lock()
+      .expect("Event log lock should never be poisoned");
+    if events.len() == self.capacity {
+      events.pop_front();
+    }
+    events.push_back(event);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{AuditEvent, EventLogSink, InMemoryEventLog};
+  use crate::core::message::ButtplugDeviceMessageType;
+  use instant::SystemTime;
+
+  #[test]
+  fn test_in_memory_event_log_drops_oldest() {
+    let log = InMemoryEventLog::new(2);
+    for index in 0..3 {
+      log.record(AuditEvent::new(
+        SystemTime::now(),
+        None,
+        index,
+        ButtplugDeviceMessageType::ScalarCmd,
+      ));
+    }
+    let events = log.drain();
+    assert_eq!(
+      events.iter().map(|x| x.device_index()).collect::<Vec<_>>(),
+      vec![1, 2]
+    );
+    assert!(log.drain().is_empty());
+  }
+}
@@ -46,12 +46,15 @@
 //!     of the [DeviceManager] teardown.
 
 pub mod device;
+mod event_log;
 mod ping_timer;
+mod rate_limiter;
 mod server;
 mod server_builder;
 mod server_downgrade_wrapper;
 mod server_message_conversion;
 
+pub use event_log::{AuditEvent, EventLogSink, InMemoryEventLog};
 pub use server::ButtplugServer;
 pub use server_builder::ButtplugServerBuilder;
 pub use server_downgrade_wrapper::ButtplugServerDowngradeWrapper;
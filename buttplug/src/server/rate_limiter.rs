@@ -0,0 +1,178 @@
+// Buttplug Rust Source Code File - See https://buttplug.io for more info.
+//
+// Copyright 2016-2024 Nonpolynomial Labs LLC. All rights reserved.
+//
+// Licensed under the BSD 3-Clause license. See LICENSE file in the project root
+// for full license information.
+
+//! Per-device limits on how fast clients can send commands to a
+//! [ButtplugServer](super::ButtplugServer).
+
+use super::device::configuration::ProtocolCommunicationSpecifier;
+use dashmap::DashMap;
+use instant::Instant;
+
+/// Default command rate for Bluetooth LE devices, which tend to choke if their transmit queue fills.
+const DEFAULT_BLUETOOTH_LE_COMMANDS_PER_SECOND: f64 = 50.0;
+/// Default command rate for devices connected over USB, HID, serial or XInput.
+const DEFAULT_WIRED_COMMANDS_PER_SECOND: f64 = 200.0;
+/// Default command rate for devices we talk to over the network, like Lovense Connect or
+/// websocket devices.
+const DEFAULT_NETWORK_COMMANDS_PER_SECOND: f64 = 20.0;
+
+/// Returns the default number of commands per second a device connected via `specifier` will accept.
+pub fn default_commands_per_second(specifier: &ProtocolCommunicationSpecifier) -> f64 {
+  match specifier {
+    ProtocolCommunicationSpecifier::BluetoothLE(_) => DEFAULT_BLUETOOTH_LE_COMMANDS_PER_SECOND,
+    ProtocolCommunicationSpecifier::HID(_)
+    | ProtocolCommunicationSpecifier::USB(_)
+    | ProtocolCommunicationSpecifier::Serial(_)
+    | ProtocolCommunicationSpecifier::XInput(_) => DEFAULT_WIRED_COMMANDS_PER_SECOND,
+    ProtocolCommunicationSpecifier::LovenseConnectService(_)
+    | ProtocolCommunicationSpecifier::Websocket(_) => DEFAULT_NETWORK_COMMANDS_PER_SECOND,
+  }
+}
+
+/// Token bucket that holds up to one second worth of commands, refilling at `rate` tokens per
+/// second.
+#[derive(Debug)]
+struct TokenBucket {
+  rate: f64,
+  capacity: f64,
+  tokens: f64,
+  last_refill: Instant,
+}
+
+impl TokenBucket {
+  fn new(rate: f64, now: Instant) -> Self {
+    // Always allow at least one command through, otherwise very low rates would never let
+    // anything past.
+    let capacity = rate.max(1.0);
+    Self {
+      rate,
+      capacity,
+      tokens: capacity,
+      last_refill: now,
+    }
+  }
+
+  fn try_acquire(&mut self, now: Instant) -> bool {
+    let elapsed = now
+      .saturating_duration_since(self.last_refill)
+      .as_secs_f64();
+    self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+    self.last_refill = now;
+    if self.tokens >= 1.0 {
+      self.tokens -= 1.0;
+      true
+    } else {
+      false
+    }
+  }
+}
+
+/// Keeps a [TokenBucket] for each device index the server has sent commands to.
+#[derive(Debug)]
+pub(super) struct RateLimiter {
+  /// If set, used for every device instead of the per transport defaults.
+  max_commands_per_second: Option<f64>,
+  buckets: DashMap<u32, TokenBucket>,
+}
+
+impl RateLimiter {
+  pub fn new(max_commands_per_second: Option<f64>) -> Self {
+    Self {
+      max_commands_per_second,
+      buckets: DashMap::new(),
+    }
+  }
+
+  /// Takes a token from the bucket for `device_index`, creating the bucket if this is the first
+  /// command for the device. Returns false if the bucket is empty and the command should be
+  /// rejected.
+  pub fn try_acquire(&self, device_index: u32, specifier: &ProtocolCommunicationSpecifier) -> bool {
+    self.try_acquire_at(device_index, specifier, Instant::now())
+  }
+
+  fn try_acquire_at(
+    &self,
+    device_index: u32,
+    specifier: &ProtocolCommunicationSpecifier,
+    now: Instant,
+  ) -> bool {
+    self
+      .buckets
+      .entry(device_index)
+      .or_insert_with(|| {
+        let rate = self
+          .max_commands_per_second
+          .unwrap_or_else(|| default_commands_per_second(specifier));
+        TokenBucket::new(rate, now)
+      })
+      .try_acquire(now)
+  }
+
+  /// Drops the bucket for `device_index`, so a device that's later given the same index starts
+  /// fresh with its own transport's rate.
+  pub fn remove(&self, device_index: u32) {
+    self.buckets.remove(&device_index);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::RateLimiter;
+  use crate::server::device::configuration::{
+    ProtocolCommunicationSpecifier,
+    SerialSpecifier,
+    WebsocketSpecifier,
+  };
+  use instant::Instant;
+  use std::time::Duration;
+
+  #[test]
+  fn test_rate_limiter_refills_at_configured_rate() {
+    let specifier = ProtocolCommunicationSpecifier::Websocket(WebsocketSpecifier::new("test"));
+    let limiter = RateLimiter::new(Some(10.0));
+    let start = Instant::now();
+    for _ in 0..10 {
+      assert!(limiter.try_acquire_at(0, &specifier, start));
+    }
+    assert!(!limiter.try_acquire_at(0, &specifier, start));
+    // Other devices have their own buckets.
+    assert!(limiter.try_acquire_at(1, &specifier, start));
+    // 10 per second means one token every 100ms.
+    let later = start + Duration::from_millis(100);
+    assert!(limiter.try_acquire_at(0, &specifier, later));
+    assert!(!limiter.try_acquire_at(0, &specifier, later));
+  }
+
+  #[test]
+  fn test_rate_limiter_uses_transport_defaults() {
+    let specifier = ProtocolCommunicationSpecifier::Websocket(WebsocketSpecifier::new("test"));
+    let limiter = RateLimiter::new(None);
+    let start = Instant::now();
+    for _ in 0..20 {
+      assert!(limiter.try_acquire_at(0, &specifier, start));
+    }
+    assert!(!limiter.try_acquire_at(0, &specifier, start));
+  }
+
+  #[test]
+  fn test_rate_limiter_remove_resets_device() {
+    let websocket = ProtocolCommunicationSpecifier::Websocket(WebsocketSpecifier::new("test"));
+    let limiter = RateLimiter::new(None);
+    let start = Instant::now();
+    for _ in 0..20 {
+      assert!(limiter.try_acquire_at(0, &websocket, start));
+    }
+    assert!(!limiter.try_acquire_at(0, &websocket, start));
+    limiter.remove(0);
+    // A new device at the same index gets a full bucket at its own transport's rate.
+    let serial = ProtocolCommunicationSpecifier::Serial(SerialSpecifier::new_from_name("test"));
+    for _ in 0..200 {
+      assert!(limiter.try_acquire_at(0, &serial, start));
+    }
+    assert!(!limiter.try_acquire_at(0, &serial, start));
+  }
+}
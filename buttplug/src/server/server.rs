@@ -5,7 +5,13 @@
 // Licensed under the BSD 3-Clause license. See LICENSE file in the project root
 // for full license information.
 
-use super::{device::ServerDeviceManager, ping_timer::PingTimer, ButtplugServerResultFuture};
+use super::{
+  device::ServerDeviceManager,
+  event_log::{AuditEvent, EventLogSink},
+  ping_timer::PingTimer,
+  rate_limiter::RateLimiter,
+  ButtplugServerResultFuture,
+};
 use crate::{
   core::{
     errors::*,
@@ -14,8 +20,11 @@ use crate::{
       ButtplugClientMessageV4,
       ButtplugDeviceCommandMessageUnion,
       ButtplugDeviceManagerMessageUnion,
+      ButtplugDeviceMessage,
       ButtplugMessage,
+      ButtplugMessageValidator,
       ButtplugServerMessageV4,
+      ErrorCode,
       StopAllDevicesV0,
       StopScanningV0,
       BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION,
@@ -32,9 +41,11 @@ use std::{
   sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
+    RwLock,
   },
+  time::Duration,
 };
-use tokio::sync::{broadcast, RwLock};
+use tokio::sync::broadcast;
 use tokio_stream::StreamExt;
 use tracing_futures::Instrument;
 
@@ -61,8 +72,15 @@ pub struct ButtplugServer {
   /// Broadcaster for server events. Receivers for this are handed out through the
   /// [ButtplugServer::event_stream()] method.
   output_sender: broadcast::Sender<ButtplugServerMessageV4>,
-  /// Name of the connected client, assuming there is one.
+  /// Name of the connected client, assuming there is one. Never held across an await, so a std lock
+  /// lets it be read from anywhere without failing.
   client_name: Arc<RwLock<Option<String>>>,
+  /// Maximum time [ButtplugServer::shutdown] waits for in flight device commands.
+  shutdown_drain_timeout: Duration,
+  /// Sink for audit events about dispatched device commands, if one was configured.
+  event_log: Option<Arc<dyn EventLogSink>>,
+  /// Per device command rate limits.
+  rate_limiter: Arc<RateLimiter>,
 }
 
 impl std::fmt::Debug for ButtplugServer {
@@ -76,6 +94,7 @@ impl std::fmt::Debug for ButtplugServer {
 }
 
 impl ButtplugServer {
+  #[allow(clippy::too_many_arguments)]
   pub(super) fn new(
     server_name: &str,
     max_ping_time: u32,
@@ -83,6 +102,9 @@ impl ButtplugServer {
     device_manager: Arc<ServerDeviceManager>,
     connected: Arc<AtomicBool>,
     output_sender: broadcast::Sender<ButtplugServerMessageV4>,
+    shutdown_drain_timeout: Duration,
+    event_log: Option<Arc<dyn EventLogSink>>,
+    rate_limiter: Arc<RateLimiter>,
   ) -> Self {
     ButtplugServer {
       server_name: server_name.to_owned(),
@@ -92,14 +114,17 @@ impl ButtplugServer {
       connected,
       output_sender,
       client_name: Arc::new(RwLock::new(None)),
+      shutdown_drain_timeout,
+      event_log,
+      rate_limiter,
     }
   }
 
   pub fn client_name(&self) -> Option<String> {
     self
       .client_name
-      .try_read()
-      .expect("We should never conflict on name access")
+      .read()
+      .expect("Client name lock should never be poisoned")
       .clone()
   }
 
@@ -137,11 +162,10 @@ impl ButtplugServer {
       StopAllDevicesV0::default(),
     ));
     let connected = self.connected.clone();
-    let mut name = self
+    *self
       .client_name
-      .try_write()
-      .expect("We should never conflict on name access");
-    *name = None;
+      .write()
+      .expect("Client name lock should never be poisoned") = None;
     async move {
       connected.store(false, Ordering::SeqCst);
       ping_timer.stop_ping_timer().await;
@@ -155,10 +179,30 @@ impl ButtplugServer {
     .boxed()
   }
 
+  /// Shuts down the server and its device manager. Scanning is stopped, every device is sent a stop
+  /// command, and device commands that are already in flight are given up to the configured drain
+  /// timeout (see [ButtplugServerBuilder::shutdown_drain_timeout]) to finish before being cancelled.
+  /// Hardware is then disconnected, and any connected client is disconnected.
+  ///
+  /// Calling this more than once is safe, later calls will do nothing.
+  ///
+  /// [ButtplugServerBuilder::shutdown_drain_timeout]: super::ButtplugServerBuilder::shutdown_drain_timeout
   pub fn shutdown(&self) -> ButtplugServerResultFuture {
     let device_manager = self.device_manager.clone();
-    //let disconnect_future = self.disconnect();
-    async move { device_manager.shutdown().await }.boxed()
+    let drain_timeout = self.shutdown_drain_timeout;
+    let ping_timer = self.ping_timer.clone();
+    let connected = self.connected.clone();
+    let client_name = self.client_name.clone();
+    async move {
+      ping_timer.stop_ping_timer().await;
+      let result = device_manager.shutdown(drain_timeout).await;
+      connected.store(false, Ordering::SeqCst);
+      *client_name
+        .write()
+        .expect("Client name lock should never be poisoned") = None;
+      result
+    }
+    .boxed()
   }
 
   pub fn parse_message(
@@ -192,13 +236,41 @@ impl ButtplugServer {
       }
       // If we haven't pinged out and we got an RSI message, fall thru.
     }
+    // Clients are supposed to validate before sending, but anything coming in over the wire may be
+    // out of range, and we don't want bogus values making it down to the device protocols.
+    if let Err(e) = msg.is_valid() {
+      let mut error =
+        message::ErrorV0::new_with_source(ErrorCode::ErrorMessage, "Message failed validation", e);
+      error.set_id(id);
+      return future::ready(Err(error)).boxed();
+    }
     // Produce whatever future is needed to reply to the message, this may be a
     // device command future, or something the server handles. All futures will
     // return Result<ButtplugServerMessage, ButtplugError>, and we'll handle
     // tagging the result with the message id in the future we put out as the
     // return value from this method.
+    let device_command = ButtplugDeviceCommandMessageUnion::try_from(msg.clone()).ok();
+    if let Some(command) = &device_command {
+      if let Err(e) = self.check_rate_limit(command) {
+        let mut error = message::ErrorV0::new_with_source(
+          ErrorCode::ErrorDevice,
+          "Device command was rate limited",
+          e,
+        );
+        error.set_id(id);
+        return future::ready(Err(error)).boxed();
+      }
+    }
+    if let (Some(event_log), Some(command)) = (&self.event_log, &device_command) {
+      event_log.record(AuditEvent::new(
+        instant::SystemTime::now(),
+        self.client_name(),
+        command.device_index(),
+        command.into(),
+      ));
+    }
     let out_fut = if ButtplugDeviceManagerMessageUnion::try_from(msg.clone()).is_ok()
-      || ButtplugDeviceCommandMessageUnion::try_from(msg.clone()).is_ok()
+      || device_command.is_some()
     {
       self.device_manager.parse_message(msg.clone())
     } else {
@@ -227,6 +299,30 @@ impl ButtplugServer {
     .boxed()
   }
 
+  /// Takes a token from the rate limiter for the device `command` is addressed to. Stop commands
+  /// always go through, as do commands for devices that aren't connected (the device manager will
+  /// reject those).
+  fn check_rate_limit(
+    &self,
+    command: &ButtplugDeviceCommandMessageUnion,
+  ) -> Result<(), ButtplugDeviceError> {
+    if matches!(command, ButtplugDeviceCommandMessageUnion::StopDeviceCmd(_)) {
+      return Ok(());
+    }
+    let device_index = command.device_index();
+    let Some(device) = self.device_manager.devices().get(&device_index) else {
+      return Ok(());
+    };
+    if self
+      .rate_limiter
+      .try_acquire(device_index, device.communication_specifier())
+    {
+      Ok(())
+    } else {
+      Err(ButtplugDeviceError::RateLimitExceeded(device_index))
+    }
+  }
+
   /// Performs the [RequestServerInfo]([ServerInfo](crate::core::message::RequestServerInfo) /
   /// [ServerInfo](crate::core::message::ServerInfo) handshake, as specified in the [Buttplug
   /// Protocol Spec](https://buttplug-spec.docs.buttplug.io). This is the first thing that must
@@ -236,9 +332,15 @@ impl ButtplugServer {
     if self.connected() {
       return ButtplugHandshakeError::HandshakeAlreadyHappened.into();
     }
+    // Client names end up in logs and UIs, so don't let control characters through.
+    let client_name: String = msg
+      .client_name()
+      .chars()
+      .filter(|c| !c.is_control())
+      .collect();
     info!(
       "Performing server handshake check with client {} at message version {}.",
-      msg.client_name(),
+      client_name,
       msg.message_version()
     );
 
@@ -255,11 +357,10 @@ impl ButtplugServer {
     let out_msg =
       message::ServerInfoV2::new(&self.server_name, msg.message_version(), self.max_ping_time);
     let connected = self.connected.clone();
-    let mut name = self
+    *self
       .client_name
-      .try_write()
-      .expect("We should never conflict on name access");
-    *name = Some(msg.client_name().clone());
+      .write()
+      .expect("Client name lock should never be poisoned") = Some(client_name);
     async move {
       ping_timer.start_ping_timer().await;
       connected.store(true, Ordering::SeqCst);
@@ -312,4 +413,17 @@ mod test {
       reply
     );
   }
+  #[tokio::test]
+  async fn test_server_client_name_handling() {
+    let server = ButtplugServerBuilder::default().finish().unwrap();
+    let msg =
+      message::RequestServerInfoV1::new("Test\0Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION);
+    assert!(server.parse_message(msg.into()).await.is_err());
+    assert!(!server.connected());
+
+    let msg =
+      message::RequestServerInfoV1::new("Test\tClient\u{7}", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION);
+    assert!(server.parse_message(msg.into()).await.is_ok());
+    assert_eq!(server.client_name(), Some("TestClient".to_owned()));
+  }
 }
@@ -11,7 +11,9 @@ use super::{
     ServerDeviceManager,
     ServerDeviceManagerBuilder,
   },
+  event_log::EventLogSink,
   ping_timer::PingTimer,
+  rate_limiter::RateLimiter,
   server::ButtplugServer,
   ButtplugServerError,
 };
@@ -22,13 +24,20 @@ use crate::{
   },
   util::async_manager,
 };
-use std::sync::{
-  atomic::{AtomicBool, Ordering},
-  Arc,
+use futures::{pin_mut, StreamExt};
+use std::{
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  time::Duration,
 };
 use tokio::sync::broadcast;
 use tracing_futures::Instrument;
 
+/// How long [ButtplugServer::shutdown] waits for in flight device commands by default.
+const DEFAULT_SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Configures and creates [ButtplugServer] instances.
 pub struct ButtplugServerBuilder {
   /// Name of the server, will be sent to the client as part of the [initial connection
@@ -39,6 +48,17 @@ pub struct ButtplugServerBuilder {
   max_ping_time: Option<u32>,
   /// Device manager builder for the server
   device_manager: Arc<ServerDeviceManager>,
+  /// Maximum time shutdown will wait for in flight device commands to finish before cancelling
+  /// them.
+  shutdown_drain_timeout: Duration,
+  /// Where to record audit events for device commands, if anywhere.
+  event_log: Option<Arc<dyn EventLogSink>>,
+  /// Command rate every device is limited to. If None, defaults depend on how the device is
+  /// connected.
+  max_commands_per_second: Option<f64>,
+  /// Maximum number of devices that can be connected at once. If None, whatever the device manager
+  /// was built with is kept.
+  max_devices: Option<usize>,
 }
 
 impl Default for ButtplugServerBuilder {
@@ -55,6 +75,10 @@ impl Default for ButtplugServerBuilder {
         .finish()
         .unwrap(),
       ),
+      shutdown_drain_timeout: DEFAULT_SHUTDOWN_DRAIN_TIMEOUT,
+      event_log: None,
+      max_commands_per_second: None,
+      max_devices: None,
     }
   }
 }
@@ -65,6 +89,10 @@ impl ButtplugServerBuilder {
       name: "Buttplug Server".to_owned(),
       max_ping_time: None,
       device_manager: Arc::new(device_manager),
+      shutdown_drain_timeout: DEFAULT_SHUTDOWN_DRAIN_TIMEOUT,
+      event_log: None,
+      max_commands_per_second: None,
+      max_devices: None,
     }
   }
 
@@ -73,6 +101,10 @@ impl ButtplugServerBuilder {
       name: "Buttplug Server".to_owned(),
       max_ping_time: None,
       device_manager: device_manager,
+      shutdown_drain_timeout: DEFAULT_SHUTDOWN_DRAIN_TIMEOUT,
+      event_log: None,
+      max_commands_per_second: None,
+      max_devices: None,
     }
   }
 
@@ -95,6 +127,45 @@ impl ButtplugServerBuilder {
     self
   }
 
+  /// Set how long [ButtplugServer::shutdown] will wait for device commands that are already in
+  /// flight (and the stop commands it sends to every device) to finish. Anything still running
+  /// once this elapses is cancelled and the hardware is dropped. Defaults to 5 seconds.
+  pub fn shutdown_drain_timeout(&mut self, timeout: Duration) -> &mut Self {
+    self.shutdown_drain_timeout = timeout;
+    self
+  }
+
+  /// Record an [AuditEvent](super::AuditEvent) to the given sink for every device command the
+  /// server dispatches. Events include the client name, device index and message type, but not the
+  /// message contents. If this is not called, no events are recorded.
+  pub fn event_log(&mut self, sink: impl EventLogSink + 'static) -> &mut Self {
+    self.event_log = Some(Arc::new(sink));
+    self
+  }
+
+  /// Limit every device to `max_commands_per_second` commands. Once a device has used up its
+  /// budget (up to one second worth of commands can be sent in a burst), further commands are
+  /// rejected with a [ButtplugDeviceError::RateLimitExceeded] error until it refills, rather than
+  /// being queued up on the hardware. StopDeviceCmd and StopAllDevices are never limited.
+  ///
+  /// If this is not called, Bluetooth LE devices are limited to 50 commands per second, USB, HID,
+  /// serial and XInput devices to 200, and network devices (Lovense Connect, websocket devices) to
+  /// 20.
+  pub fn max_commands_per_second(&mut self, max_commands_per_second: f64) -> &mut Self {
+    self.max_commands_per_second = Some(max_commands_per_second);
+    self
+  }
+
+  /// Set the maximum number of devices that can be connected at once. Once the limit is reached,
+  /// newly found devices are ignored until something disconnects. This overrides
+  /// [ServerDeviceManagerBuilder::max_devices], and as the limit lives on the device manager, it
+  /// also applies to any other server sharing it (see
+  /// [ButtplugServerBuilder::with_shared_device_manager]).
+  pub fn max_devices(&mut self, max_devices: usize) -> &mut Self {
+    self.max_devices = Some(max_devices);
+    self
+  }
+
   /// Try to build a [ButtplugServer] using the parameters given.
   pub fn finish(&self) -> Result<ButtplugServer, ButtplugServerError> {
     // Create the server
@@ -141,6 +212,24 @@ impl ButtplugServerBuilder {
       );
     }
 
+    if let Some(max_devices) = self.max_devices {
+      self.device_manager.set_max_devices(max_devices);
+    }
+
+    // Buckets are keyed by device index, so forget a device's bucket once it's gone, or whatever
+    // device gets its index next would inherit it.
+    let rate_limiter = Arc::new(RateLimiter::new(self.max_commands_per_second));
+    let rate_limiter_clone = rate_limiter.clone();
+    let device_event_stream = self.device_manager.event_stream();
+    async_manager::spawn(async move {
+      pin_mut!(device_event_stream);
+      while let Some(msg) = device_event_stream.next().await {
+        if let ButtplugServerMessageV4::DeviceRemoved(removed) = msg {
+          rate_limiter_clone.remove(removed.device_index());
+        }
+      }
+    });
+
     // Assuming everything passed, return the server.
     Ok(ButtplugServer::new(
       &self.name,
@@ -149,6 +238,9 @@ impl ButtplugServerBuilder {
       self.device_manager.clone(),
       connected,
       output_sender,
+      self.shutdown_drain_timeout,
+      self.event_log.clone(),
+      rate_limiter,
     ))
   }
 }
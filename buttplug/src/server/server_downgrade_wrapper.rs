@@ -11,8 +11,13 @@ use crate::core::{
   errors::{ButtplugError, ButtplugMessageError},
   message::{
     self,
+    ButtplugClientMessageV0,
+    ButtplugClientMessageV1,
     ButtplugClientMessageVariant,
+    ButtplugMessage,
     ButtplugMessageSpecVersion,
+    ButtplugServerMessageV0,
+    ButtplugServerMessageV1,
     ButtplugServerMessageV4,
     ButtplugServerMessageVariant,
     ErrorV0,
@@ -78,7 +83,8 @@ impl ButtplugServerDowngradeWrapper {
 
   pub fn client_version_event_stream(&self) -> impl Stream<Item = ButtplugServerMessageVariant> {
     let spec_version = self.spec_version.clone();
-    self.server.event_stream().map(move |m| {
+    let device_manager = self.server.device_manager();
+    self.server.event_stream().filter_map(move |m| {
       let converter = ButtplugServerMessageConverter::new(None);
       // If we get an event and don't have a spec version yet, just throw out the latest.
       converter
@@ -87,8 +93,10 @@ impl ButtplugServerDowngradeWrapper {
           spec_version
             .get()
             .unwrap_or(&ButtplugMessageSpecVersion::Version4),
+          &device_manager,
         )
-        .unwrap()
+        .map_err(|e| error!("Cannot convert event {:?} for client: {}", m, e))
+        .ok()
     })
   }
 
@@ -129,8 +137,17 @@ impl ButtplugServerDowngradeWrapper {
           .boxed()
         }
       }
+      // Test only exists in spec v0/v1 and is answered by echoing it back, so there's nothing for the
+      // server itself to do with it.
+      ButtplugClientMessageVariant::V0(ButtplugClientMessageV0::Test(msg)) => {
+        future::ready(Ok(ButtplugServerMessageV0::Test(msg).into())).boxed()
+      }
+      ButtplugClientMessageVariant::V1(ButtplugClientMessageV1::Test(msg)) => {
+        future::ready(Ok(ButtplugServerMessageV1::Test(msg).into())).boxed()
+      }
       msg => {
         let v = msg.version();
+        let id = msg.id();
         let converter = ButtplugServerMessageConverter::new(Some(msg));
         let spec_version = *self.spec_version.get_or_init(|| {
           info!(
@@ -139,37 +156,42 @@ impl ButtplugServerDowngradeWrapper {
           );
           v
         });
-        match converter.convert_incoming(&self.server.device_manager()) {
+        let device_manager = self.server.device_manager();
+        match converter.convert_incoming(&device_manager) {
           Ok(converted_msg) => {
             let fut = self.server.parse_message(converted_msg);
             async move {
               let result = fut.await.map_err(|e| {
                 converter
-                  .convert_outgoing(&e.into(), &spec_version)
+                  .convert_outgoing(&e.into(), &spec_version, &device_manager)
                   .unwrap()
               })?;
               converter
-                .convert_outgoing(&result, &spec_version)
+                .convert_outgoing(&result, &spec_version, &device_manager)
                 .map_err(|e| {
                   converter
                     .convert_outgoing(
                       &&ButtplugServerMessageV4::from(ErrorV0::from(e)),
                       &spec_version,
+                      &device_manager,
                     )
                     .unwrap()
                 })
             }
             .boxed()
           }
-          Err(e) => future::ready(Err(
-            converter
-              .convert_outgoing(
-                &ButtplugServerMessageV4::from(ErrorV0::from(e)),
-                &spec_version,
-              )
-              .unwrap(),
-          ))
-          .boxed(),
+          Err(e) => {
+            // Conversion failures never make it to the server, so nothing else will set the id on
+            // the error. Without it, the client can't match the error to its request.
+            let mut error = ButtplugServerMessageV4::from(ErrorV0::from(e));
+            error.set_id(id);
+            future::ready(Err(
+              converter
+                .convert_outgoing(&error, &spec_version, &device_manager)
+                .unwrap(),
+            ))
+            .boxed()
+          }
         }
       }
     }
@@ -188,9 +210,18 @@ impl ButtplugServerDowngradeWrapper {
 mod test {
   use crate::{
     core::message::{
+      ButtplugClientMessageV0,
+      ButtplugClientMessageV1,
       ButtplugClientMessageV4,
       ButtplugClientMessageVariant,
+      ButtplugServerMessageV0,
+      ButtplugServerMessageV1,
+      ButtplugServerMessageV3,
+      ButtplugServerMessageV4,
+      ButtplugServerMessageVariant,
+      OkV0,
       RequestServerInfoV1,
+      TestV0,
       BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION,
     },
     server::{ButtplugServerBuilder, ButtplugServerDowngradeWrapper},
@@ -201,15 +232,26 @@ mod test {
   async fn test_downgrader_v4_block() {
     let wrapper =
       ButtplugServerDowngradeWrapper::new(ButtplugServerBuilder::default().finish().unwrap());
-    assert!(wrapper
+    let err = wrapper
       .parse_message(ButtplugClientMessageVariant::V4(
         ButtplugClientMessageV4::RequestServerInfo(RequestServerInfoV1::new(
           "TestClient",
-          BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION
-        ))
+          BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION,
+        )),
       ))
       .await
-      .is_err());
+      .unwrap_err();
+    // The rejection is still sent in the client's spec version.
+    assert!(matches!(
+      ButtplugServerMessageV4::try_from(err),
+      Ok(ButtplugServerMessageV4::Error(_))
+    ));
+    assert!(
+      ButtplugServerMessageV4::try_from(ButtplugServerMessageVariant::V3(
+        ButtplugServerMessageV3::Ok(OkV0::new(1))
+      ))
+      .is_err()
+    );
   }
 
   #[cfg_attr(not(feature = "allow-unstable-v4-connections"), ignore)]
@@ -228,4 +270,27 @@ mod test {
     println!("{:?}", result);
     assert!(result.is_ok());
   }
+
+  #[tokio::test]
+  async fn test_downgrader_echoes_test_message() {
+    let wrapper =
+      ButtplugServerDowngradeWrapper::new(ButtplugServerBuilder::default().finish().unwrap());
+    let test_msg = TestV0::new("Echo");
+    let result = wrapper
+      .parse_message(ButtplugClientMessageV0::Test(test_msg.clone()).into())
+      .await
+      .expect("Test, assuming infallible.");
+    assert_eq!(
+      result,
+      ButtplugServerMessageVariant::V0(ButtplugServerMessageV0::Test(test_msg.clone()))
+    );
+    let result = wrapper
+      .parse_message(ButtplugClientMessageV1::Test(test_msg.clone()).into())
+      .await
+      .expect("Test, assuming infallible.");
+    assert_eq!(
+      result,
+      ButtplugServerMessageVariant::V1(ButtplugServerMessageV1::Test(test_msg))
+    );
+  }
 }
@@ -18,7 +18,7 @@
 
 use std::fmt::Debug;
 
-use super::device::ServerDeviceManager;
+use super::device::{protocol::fleshlight_launch_helper, ServerDeviceManager};
 use crate::core::{
   errors::{ButtplugDeviceError, ButtplugError, ButtplugMessageError},
   message::{
@@ -35,6 +35,7 @@ use crate::core::{
     ButtplugDeviceMessage,
     ButtplugMessage,
     ButtplugMessageSpecVersion,
+    ButtplugMessageValidator,
     ButtplugServerMessageV0,
     ButtplugServerMessageV1,
     ButtplugServerMessageV2,
@@ -44,6 +45,8 @@ use crate::core::{
     DeviceFeature,
     ErrorV0,
     FeatureType,
+    FleshlightLaunchFW12CmdV0,
+    KiirooCmdV0,
     LinearCmdV1,
     LinearCmdV4,
     RSSILevelCmdV2,
@@ -57,6 +60,7 @@ use crate::core::{
     SensorReadCmdV3,
     SensorReadCmdV4,
     SensorReadingV3,
+    SensorReadingV4,
     SensorSubscribeCmdV3,
     SensorSubscribeCmdV4,
     SensorType,
@@ -196,7 +200,9 @@ impl TryFrom<ButtplugClientMessageV1> for ButtplugClientMessageV2 {
       ButtplugClientMessageV1::RotateCmd(m) => Ok(ButtplugClientMessageV2::RotateCmd(m.clone())),
       ButtplugClientMessageV1::FleshlightLaunchFW12Cmd(_) => {
         // Direct access to FleshlightLaunchFW12Cmd could cause some devices to break via rapid
-        // changes of position/speed. Yes, some Kiiroo devices really *are* that fragile.
+        // changes of position/speed. Yes, some Kiiroo devices really *are* that fragile. The server
+        // upgrades this to LinearCmd in ButtplugServerMessageConverter, as that requires knowing
+        // where the device currently is, but there's no stateless equivalent in v2.
         Err(ButtplugMessageError::MessageConversionError("FleshlightLaunchFW12Cmd is not implemented. Please update the client software to use a newer command".to_owned()).into())
       }
       ButtplugClientMessageV1::RequestLog(_) => {
@@ -211,7 +217,8 @@ impl TryFrom<ButtplugClientMessageV1> for ButtplugClientMessageV2 {
       }
       ButtplugClientMessageV1::KiirooCmd(_) => {
         // No device protocol implementation ever worked with KiirooCmd, so no one ever should've
-        // used it. We'll just return an error if we ever see it.
+        // used it. The server upgrades it to ScalarCmd in ButtplugServerMessageConverter, since it
+        // needs device features to do so, but there's no v2 equivalent.
         Err(ButtplugMessageError::MessageConversionError("KiirooCmd is not implemented. Please update the client software to use a newer command".to_owned()).into())
       }
       ButtplugClientMessageV1::LovenseCmd(_) => {
@@ -248,6 +255,7 @@ impl From<ButtplugClientMessageV0> for ButtplugClientMessageV1 {
       ButtplugClientMessageV0::KiirooCmd(m) => ButtplugClientMessageV1::KiirooCmd(m),
       ButtplugClientMessageV0::LovenseCmd(m) => ButtplugClientMessageV1::LovenseCmd(m),
       ButtplugClientMessageV0::RequestLog(m) => ButtplugClientMessageV1::RequestLog(m),
+      ButtplugClientMessageV0::Test(m) => ButtplugClientMessageV1::Test(m),
       ButtplugClientMessageV0::SingleMotorVibrateCmd(m) => {
         ButtplugClientMessageV1::SingleMotorVibrateCmd(m)
       }
@@ -269,7 +277,9 @@ impl TryFrom<ButtplugServerMessageV4> for ButtplugServerMessageV3 {
       ButtplugServerMessageV4::Ok(m) => Ok(ButtplugServerMessageV3::Ok(m)),
       ButtplugServerMessageV4::Error(m) => Ok(ButtplugServerMessageV3::Error(m)),
       ButtplugServerMessageV4::ServerInfo(m) => Ok(ButtplugServerMessageV3::ServerInfo(m)),
-      ButtplugServerMessageV4::DeviceRemoved(m) => Ok(ButtplugServerMessageV3::DeviceRemoved(m)),
+      ButtplugServerMessageV4::DeviceRemoved(m) => {
+        Ok(ButtplugServerMessageV3::DeviceRemoved(m.into()))
+      }
       ButtplugServerMessageV4::ScanningFinished(m) => {
         Ok(ButtplugServerMessageV3::ScanningFinished(m))
       }
@@ -343,6 +353,7 @@ impl From<ButtplugServerMessageV1> for ButtplugServerMessageV0 {
     match value {
       ButtplugServerMessageV1::Ok(m) => ButtplugServerMessageV0::Ok(m),
       ButtplugServerMessageV1::Error(m) => ButtplugServerMessageV0::Error(m),
+      ButtplugServerMessageV1::Test(m) => ButtplugServerMessageV0::Test(m),
       ButtplugServerMessageV1::ServerInfo(m) => ButtplugServerMessageV0::ServerInfo(m.into()),
       ButtplugServerMessageV1::DeviceRemoved(m) => ButtplugServerMessageV0::DeviceRemoved(m),
       ButtplugServerMessageV1::ScanningFinished(m) => ButtplugServerMessageV0::ScanningFinished(m),
@@ -373,6 +384,9 @@ impl ButtplugServerMessageConverter {
     device_manager: &ServerDeviceManager,
   ) -> Result<ButtplugClientMessageV4, ButtplugError> {
     if let Some(msg) = &self.original_message {
+      // Validate before converting, as conversion may need to look up device attributes, and we'd
+      // rather tell the client its message is malformed than that the device doesn't exist.
+      msg.is_valid()?;
       let mut outgoing_msg = match msg {
         ButtplugClientMessageVariant::V0(m) => self.convert_incoming_v0(m, device_manager)?,
         ButtplugClientMessageVariant::V1(m) => self.convert_incoming_v1(m, device_manager)?,
@@ -418,6 +432,16 @@ impl ButtplugServerMessageConverter {
         // SingleMotorVibrate is a ScalarCmd w/ Vibrate type for all vibrate functionality.
         self.convert_singlemotorvibratecmdv0_to_scalarcmdv4(m, device_manager)
       }
+      ButtplugClientMessageV1::FleshlightLaunchFW12Cmd(m) => {
+        // FleshlightLaunchFW12Cmd is a LinearCmd that describes speed instead of duration. We go
+        // through LinearCmd so protocols get the same handling as any other linear command.
+        self.convert_fleshlightlaunchfw12cmdv0_to_linearcmdv4(m, device_manager)
+      }
+      ButtplugClientMessageV1::KiirooCmd(m) => {
+        // KiirooCmd was a 0-4 vibration level sent as a string, so treat it as a ScalarCmd w/
+        // Vibrate type for all vibrate functionality.
+        self.convert_kiiroocmdv0_to_scalarcmdv4(m, device_manager)
+      }
       _ => self.convert_incoming_v2(&msg_v1.clone().try_into()?, device_manager),
     }
   }
@@ -543,6 +567,80 @@ impl ButtplugServerMessageConverter {
     Ok(ScalarCmdV4::new(message.device_index(), cmds).into())
   }
 
+  fn convert_fleshlightlaunchfw12cmdv0_to_linearcmdv4(
+    &self,
+    message: &FleshlightLaunchFW12CmdV0,
+    device_manager: &ServerDeviceManager,
+  ) -> Result<ButtplugClientMessageV4, ButtplugError> {
+    let linear_features: Vec<usize> =
+      self.find_device_features(message, device_manager, |(_, x)| {
+        x.actuator().as_ref().is_some_and(|y| {
+          y.messages()
+            .contains(&message::ButtplugActuatorFeatureMessageType::LinearCmd)
+        })
+      })?;
+    let device = device_manager
+      .devices()
+      .get(&message.device_index())
+      .ok_or(ButtplugDeviceError::DeviceNotAvailable(
+        message.device_index(),
+      ))?;
+
+    // Position and speed are both 0-99 in the v0 spec. Duration depends on how far we're moving,
+    // so work that out from the last position we sent to each feature.
+    let goal_position = message.position() as f64 / 99f64;
+    let speed = message.speed() as f64 / 99f64;
+    let cmds: Vec<VectorSubcommandV4> = linear_features
+      .iter()
+      .map(|x| {
+        let previous_position = device.current_linear_position(*x as u32).unwrap_or(0f64);
+        let distance = (goal_position - previous_position).abs();
+        VectorSubcommandV4::new(
+          *x as u32,
+          fleshlight_launch_helper::calculate_duration(distance, speed),
+          goal_position,
+        )
+      })
+      .collect();
+
+    Ok(LinearCmdV4::new(message.device_index(), cmds).into())
+  }
+
+  fn convert_kiiroocmdv0_to_scalarcmdv4(
+    &self,
+    message: &KiirooCmdV0,
+    device_manager: &ServerDeviceManager,
+  ) -> Result<ButtplugClientMessageV4, ButtplugError> {
+    let level = message
+      .command()
+      .trim()
+      .parse::<u8>()
+      .ok()
+      .filter(|x| *x <= 4)
+      .ok_or_else(|| {
+        ButtplugMessageError::MessageConversionError(format!(
+          "KiirooCmd command {} invalid, should be a number between 0 and 4",
+          message.command()
+        ))
+      })?;
+
+    let vibrate_features: Vec<usize> =
+      self.find_device_features(message, device_manager, |(_, x)| {
+        *x.feature_type() == FeatureType::Vibrate
+          && x.actuator().as_ref().is_some_and(|y| {
+            y.messages()
+              .contains(&message::ButtplugActuatorFeatureMessageType::ScalarCmd)
+          })
+      })?;
+
+    let cmds: Vec<ScalarSubcommandV4> = vibrate_features
+      .iter()
+      .map(|x| ScalarSubcommandV4::new(*x as u32, level as f64 / 4f64, ActuatorType::Vibrate))
+      .collect();
+
+    Ok(ScalarCmdV4::new(message.device_index(), cmds).into())
+  }
+
   fn convert_vorzea10cyclonecmdv0_to_rotatecmdv4(
     &self,
     message: &VorzeA10CycloneCmdV0,
@@ -729,6 +827,17 @@ impl ButtplugServerMessageConverter {
     Ok(LinearCmdV4::new(message.device_index(), cmds).into())
   }
 
+  /// Maps a v3 sensor index, which indexes only the device's sensors that take a certain message,
+  /// to the index of the matching device feature.
+  fn sensor_feature_index(features: &[usize], sensor_index: u32) -> Result<u32, ButtplugError> {
+    features
+      .get(sensor_index as usize)
+      .map(|index| *index as u32)
+      .ok_or(
+        ButtplugDeviceError::DeviceSensorIndexError(features.len() as u32, sensor_index).into(),
+      )
+  }
+
   fn convert_sensorreadv3_to_sensorreadv4(
     &self,
     message: &SensorReadCmdV3,
@@ -741,7 +850,7 @@ impl ButtplugServerMessageConverter {
       })
     })?;
 
-    let sensor_feature_index = features[*message.sensor_index() as usize] as u32;
+    let sensor_feature_index = Self::sensor_feature_index(&features, *message.sensor_index())?;
 
     Ok(SensorReadCmdV4::new(
       message.device_index(),
@@ -762,7 +871,7 @@ impl ButtplugServerMessageConverter {
       })
     })?;
 
-    let sensor_feature_index = features[*message.sensor_index() as usize] as u32;
+    let sensor_feature_index = Self::sensor_feature_index(&features, *message.sensor_index())?;
 
     Ok(SensorSubscribeCmdV4::new(
       message.device_index(),
@@ -783,7 +892,7 @@ impl ButtplugServerMessageConverter {
       })
     })?;
 
-    let sensor_feature_index = features[*message.sensor_index() as usize] as u32;
+    let sensor_feature_index = Self::sensor_feature_index(&features, *message.sensor_index())?;
 
     Ok(SensorUnsubscribeCmdV4::new(
       message.device_index(),
@@ -800,19 +909,20 @@ impl ButtplugServerMessageConverter {
     &self,
     msg: &ButtplugServerMessageV4,
     version: &ButtplugMessageSpecVersion,
+    device_manager: &ServerDeviceManager,
   ) -> Result<ButtplugServerMessageVariant, ButtplugError> {
     let mut outgoing_msg = match version {
       ButtplugMessageSpecVersion::Version0 => ButtplugServerMessageVariant::V0(
-        self.convert_servermessagev4_to_servermessagev0(msg)?,
+        self.convert_servermessagev4_to_servermessagev0(msg, device_manager)?,
       ),
       ButtplugMessageSpecVersion::Version1 => ButtplugServerMessageVariant::V1(
-        self.convert_servermessagev4_to_servermessagev1(msg)?,
+        self.convert_servermessagev4_to_servermessagev1(msg, device_manager)?,
       ),
       ButtplugMessageSpecVersion::Version2 => ButtplugServerMessageVariant::V2(
-        self.convert_servermessagev4_to_servermessagev2(msg)?,
+        self.convert_servermessagev4_to_servermessagev2(msg, device_manager)?,
       ),
       ButtplugMessageSpecVersion::Version3 => ButtplugServerMessageVariant::V3(
-        self.convert_servermessagev4_to_servermessagev3(msg)?,
+        self.convert_servermessagev4_to_servermessagev3(msg, device_manager)?,
       ),
       ButtplugMessageSpecVersion::Version4 => ButtplugServerMessageVariant::V4(msg.clone())
     };
@@ -824,13 +934,11 @@ impl ButtplugServerMessageConverter {
   fn convert_servermessagev4_to_servermessagev3(
     &self,
     msg: &ButtplugServerMessageV4,
+    device_manager: &ServerDeviceManager,
   ) -> Result<ButtplugServerMessageV3, ButtplugError> {
     match msg {
-      ButtplugServerMessageV4::SensorReading(m) => {
-        let original_msg = self.original_message.as_ref().unwrap();
-        if let ButtplugClientMessageVariant::V3(ButtplugClientMessageV3::SensorReadCmd(msg)) =
-          &original_msg
-        {
+      ButtplugServerMessageV4::SensorReading(m) => match &self.original_message {
+        Some(ButtplugClientMessageVariant::V3(ButtplugClientMessageV3::SensorReadCmd(msg))) => {
           let msg_out = SensorReadingV3::new(
               msg.device_index(),
               *msg.sensor_index(),
@@ -838,10 +946,24 @@ impl ButtplugServerMessageConverter {
               m.data().clone(),
             );
           Ok(msg_out.into())
-        } else {
-          Err(ButtplugMessageError::UnexpectedMessageType("SensorReading".to_owned()).into())
         }
-      }
+        // Subscription events have no original message to pull the sensor index from, so we have to
+        // work it out from the device's subscribable sensors.
+        None => self.convert_sensorreadingv4_to_sensorreadingv3(
+          m,
+          device_manager,
+          message::ButtplugSensorFeatureMessageType::SensorSubscribeCmd,
+        ),
+        // v2 BatteryLevelCmd and RSSILevelCmd are upgraded to SensorReadCmd, so their replies are
+        // indexed against the device's readable sensors.
+        Some(ButtplugClientMessageVariant::V2(_)) => self
+          .convert_sensorreadingv4_to_sensorreadingv3(
+            m,
+            device_manager,
+            message::ButtplugSensorFeatureMessageType::SensorReadCmd,
+          ),
+        _ => Err(ButtplugMessageError::UnexpectedMessageType("SensorReading".to_owned()).into()),
+      },
       _ => Ok(msg.clone().try_into()?),
     }
   }
@@ -849,19 +971,21 @@ impl ButtplugServerMessageConverter {
   fn convert_servermessagev4_to_servermessagev2(
     &self,
     msg: &ButtplugServerMessageV4,
+    device_manager: &ServerDeviceManager,
   ) -> Result<ButtplugServerMessageV2, ButtplugError> {
-    let msg_v3 = self.convert_servermessagev4_to_servermessagev3(msg)?;
+    let msg_v3 = self.convert_servermessagev4_to_servermessagev3(msg, device_manager)?;
     match msg_v3 {
       ButtplugServerMessageV3::SensorReading(m) => {
-        let original_msg = self.original_message.as_ref().unwrap();
         // Sensor Reading didn't exist in v2, we only had Battery or RSSI. Therefore we need to
         // context of the original message to make sure this conversion happens correctly.
-        if let ButtplugClientMessageVariant::V2(ButtplugClientMessageV2::BatteryLevelCmd(msg)) =
-          &original_msg
+        if let Some(ButtplugClientMessageVariant::V2(ButtplugClientMessageV2::BatteryLevelCmd(
+          msg,
+        ))) = &self.original_message
         {
           Ok(BatteryLevelReadingV2::new(msg.device_index(), m.data()[0] as f64 / 100f64).into())
-        } else if let ButtplugClientMessageVariant::V2(ButtplugClientMessageV2::RSSILevelCmd(msg)) =
-          &original_msg
+        } else if let Some(ButtplugClientMessageVariant::V2(
+          ButtplugClientMessageV2::RSSILevelCmd(msg),
+        )) = &self.original_message
         {
           Ok(RSSILevelReadingV2::new(msg.device_index(), m.data()[0]).into())
         } else {
@@ -875,16 +999,180 @@ impl ButtplugServerMessageConverter {
   fn convert_servermessagev4_to_servermessagev1(
     &self,
     msg: &ButtplugServerMessageV4,
+    device_manager: &ServerDeviceManager,
   ) -> Result<ButtplugServerMessageV1, ButtplugError> {
-    Ok(self.convert_servermessagev4_to_servermessagev2(msg)?.into())
+    Ok(
+      self
+        .convert_servermessagev4_to_servermessagev2(msg, device_manager)?
+        .into(),
+    )
   }
 
   fn convert_servermessagev4_to_servermessagev0(
     &self,
     msg: &ButtplugServerMessageV4,
+    device_manager: &ServerDeviceManager,
   ) -> Result<ButtplugServerMessageV0, ButtplugError> {
-    Ok(self.convert_servermessagev4_to_servermessagev1(msg)?.into())
+    Ok(
+      self
+        .convert_servermessagev4_to_servermessagev1(msg, device_manager)?
+        .into(),
+    )
   }
 
   // Outgoing Conversion Utility Methods
+
+  /// Inverse of [sensor_feature_index](Self::sensor_feature_index). Finds the v3 sensor index of a
+  /// device feature, i.e. its position among the device's sensors that take `message_type`.
+  fn sensor_index(
+    features: &[DeviceFeature],
+    feature_index: u32,
+    message_type: message::ButtplugSensorFeatureMessageType,
+  ) -> Option<u32> {
+    features
+      .iter()
+      .enumerate()
+      .filter(|(_, x)| {
+        x.sensor()
+          .as_ref()
+          .is_some_and(|y| y.messages().contains(&message_type))
+      })
+      .position(|(index, _)| index as u32 == feature_index)
+      .map(|index| index as u32)
+  }
+
+  fn convert_sensorreadingv4_to_sensorreadingv3(
+    &self,
+    message: &SensorReadingV4,
+    device_manager: &ServerDeviceManager,
+    message_type: message::ButtplugSensorFeatureMessageType,
+  ) -> Result<ButtplugServerMessageV3, ButtplugError> {
+    let device = device_manager
+      .devices()
+      .get(&message.device_index())
+      .ok_or(ButtplugDeviceError::DeviceNotAvailable(
+        message.device_index(),
+      ))?;
+    let sensor_index = Self::sensor_index(
+      device.definition().features(),
+      message.feature_index(),
+      message_type,
+    )
+    .ok_or_else(|| {
+      ButtplugDeviceError::ProtocolRequirementError(format!(
+        "Device {} feature {} is not a sensor that takes {:?}",
+        message.device_index(),
+        message.feature_index(),
+        message_type
+      ))
+    })?;
+
+    Ok(
+      SensorReadingV3::new(
+        message.device_index(),
+        sensor_index,
+        message.sensor_type(),
+        message.data().clone(),
+      )
+      .into(),
+    )
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::ButtplugServerMessageConverter;
+  use crate::core::message::{
+    ButtplugActuatorFeatureMessageType,
+    ButtplugClientMessageV1,
+    ButtplugClientMessageV2,
+    ButtplugClientMessageV3,
+    ButtplugClientMessageV4,
+    ButtplugSensorFeatureMessageType,
+    DeviceFeature,
+    DeviceFeatureActuator,
+    DeviceFeatureSensor,
+    FeatureType,
+    StopDeviceCmdV0,
+    VibrateCmdV1,
+    VibrateSubcommandV1,
+  };
+  use std::{collections::HashSet, ops::RangeInclusive};
+
+  #[test]
+  fn test_client_message_upgrade_chain() {
+    let msg_v1 = ButtplugClientMessageV1::StopDeviceCmd(StopDeviceCmdV0::new(0));
+    let msg_v2 = ButtplugClientMessageV2::try_from(msg_v1).expect("Test, assuming infallible.");
+    let msg_v3 = ButtplugClientMessageV3::try_from(msg_v2).expect("Test, assuming infallible.");
+    assert_eq!(
+      ButtplugClientMessageV4::try_from(msg_v3).expect("Test, assuming infallible."),
+      ButtplugClientMessageV4::StopDeviceCmd(StopDeviceCmdV0::new(0))
+    );
+    // VibrateCmd survives until v3, but needs device context to become a v4 ScalarCmd.
+    let vibrate_v2 = ButtplugClientMessageV2::VibrateCmd(VibrateCmdV1::new(
+      0,
+      vec![VibrateSubcommandV1::new(0, 0.5)],
+    ));
+    let vibrate_v3 =
+      ButtplugClientMessageV3::try_from(vibrate_v2).expect("Test, assuming infallible.");
+    assert!(ButtplugClientMessageV4::try_from(vibrate_v3).is_err());
+  }
+
+  #[test]
+  fn test_sensor_index_from_feature_index() {
+    let sensor = |messages: &[ButtplugSensorFeatureMessageType]| {
+      Some(DeviceFeatureSensor::new(
+        &vec![RangeInclusive::new(0, 100)],
+        &HashSet::from_iter(messages.iter().cloned()),
+      ))
+    };
+    let vibrate = DeviceFeatureActuator::new(
+      &RangeInclusive::new(0, 20),
+      &RangeInclusive::new(0, 20),
+      &HashSet::from([ButtplugActuatorFeatureMessageType::ScalarCmd]),
+    );
+    // Actuators come first, so v3 sensor indexes and v4 feature indexes differ.
+    let features = vec![
+      DeviceFeature::new("", FeatureType::Vibrate, &Some(vibrate.clone()), &None),
+      DeviceFeature::new("", FeatureType::Vibrate, &Some(vibrate), &None),
+      DeviceFeature::new(
+        "",
+        FeatureType::Battery,
+        &None,
+        &sensor(&[ButtplugSensorFeatureMessageType::SensorReadCmd]),
+      ),
+      DeviceFeature::new(
+        "",
+        FeatureType::Pressure,
+        &None,
+        &sensor(&[
+          ButtplugSensorFeatureMessageType::SensorReadCmd,
+          ButtplugSensorFeatureMessageType::SensorSubscribeCmd,
+        ]),
+      ),
+    ];
+    let index = |feature_index, message_type| {
+      ButtplugServerMessageConverter::sensor_index(&features, feature_index, message_type)
+    };
+    assert_eq!(
+      index(2, ButtplugSensorFeatureMessageType::SensorReadCmd),
+      Some(0)
+    );
+    assert_eq!(
+      index(3, ButtplugSensorFeatureMessageType::SensorReadCmd),
+      Some(1)
+    );
+    assert_eq!(
+      index(3, ButtplugSensorFeatureMessageType::SensorSubscribeCmd),
+      Some(0)
+    );
+    assert_eq!(
+      index(2, ButtplugSensorFeatureMessageType::SensorSubscribeCmd),
+      None
+    );
+    assert_eq!(
+      index(0, ButtplugSensorFeatureMessageType::SensorReadCmd),
+      None
+    );
+  }
 }
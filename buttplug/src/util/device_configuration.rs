@@ -96,6 +96,21 @@ struct UserConfigDefinition {
   protocols: Option<DashMap<String, ProtocolDefinition>>,
   #[serde(rename = "devices", default, skip_serializing_if = "Option::is_none")]
   user_device_configs: Option<Vec<UserDeviceConfigPair>>,
+  #[serde(
+    rename = "allow-list",
+    default,
+    skip_serializing_if = "Option::is_none"
+  )]
+  allow_list: Option<Vec<AddressPatternDefinition>>,
+  #[serde(rename = "deny-list", default, skip_serializing_if = "Option::is_none")]
+  deny_list: Option<Vec<AddressPatternDefinition>>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Getters)]
+#[getset(get = "pub")]
+struct AddressPatternDefinition {
+  #[serde(rename = "address-pattern")]
+  address_pattern: String,
 }
 
 impl From<ProtocolDefinition> for ProtocolDeviceConfiguration {
@@ -358,6 +373,14 @@ fn load_user_config(
     );
   }
 
+  for allowed in user_config.allow_list.unwrap_or_default() {
+    dcm_builder.allowed_address_pattern(allowed.address_pattern());
+  }
+
+  for denied in user_config.deny_list.unwrap_or_default() {
+    dcm_builder.denied_address_pattern(denied.address_pattern());
+  }
+
   Ok(())
 }
 
@@ -397,9 +420,25 @@ pub fn save_user_config(dcm: &DeviceConfigurationManager) -> Result<String, Butt
       },
     );
   }
+  let pattern_list = |patterns: &Vec<String>| {
+    if patterns.is_empty() {
+      None
+    } else {
+      Some(
+        patterns
+          .iter()
+          .map(|pattern| AddressPatternDefinition {
+            address_pattern: pattern.clone(),
+          })
+          .collect(),
+      )
+    }
+  };
   let user_config_definition = UserConfigDefinition {
     protocols: Some(user_protos.clone()),
     user_device_configs: Some(user_definitions_vec),
+    allow_list: pattern_list(dcm.allowed_address_patterns()),
+    deny_list: pattern_list(dcm.denied_address_patterns()),
   };
   let mut user_config_file = UserConfigFile::new(3, 0);
   user_config_file.user_configs = Some(user_config_definition);
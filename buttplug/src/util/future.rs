@@ -117,6 +117,11 @@ impl<T> ButtplugFutureStateShared<T> {
   pub fn set_reply(&self, reply: T) {
     self.lock().set_reply(reply);
   }
+
+  /// Returns true if both handles point at the state of the same future.
+  pub fn is_same_state(&self, other: &Self) -> bool {
+    Arc::ptr_eq(&self.state, &other.state)
+  }
 }
 
 impl<T> Default for ButtplugFutureStateShared<T> {
@@ -23,6 +23,28 @@ where
   }
 }
 
+/// Like [convert_broadcast_receiver_to_stream], but if the sender overwrites values we haven't read
+/// yet, the missed values are skipped with a warning instead of ending the stream.
+pub fn convert_broadcast_receiver_to_lossy_stream<T>(
+  receiver: broadcast::Receiver<T>,
+) -> impl Stream<Item = T>
+where
+  T: Unpin + Clone,
+{
+  stream! {
+    pin_mut!(receiver);
+    loop {
+      match receiver.recv().await {
+        Ok(val) => yield val,
+        Err(broadcast::error::RecvError::Lagged(count)) => {
+          warn!("Stream receiver lagged, {} values dropped.", count);
+        }
+        Err(broadcast::error::RecvError::Closed) => break,
+      }
+    }
+  }
+}
+
 pub fn recv_now<T>(receiver: &mut mpsc::Receiver<T>) -> Option<Option<T>> {
   receiver.recv().now_or_never()
 }
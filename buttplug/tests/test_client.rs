@@ -6,12 +6,30 @@
 // for full license information.
 
 mod util;
-use util::{test_client, test_client_with_delayed_device_manager, test_client_with_device};
+use util::{
+  test_client,
+  test_client_with_delayed_device_manager,
+  test_client_with_device,
+  test_device_manager::{check_test_recv_value, TestHardwareEvent},
+  test_server,
+  test_server_v4_with_device,
+  TestDeviceChannelHost,
+};
 extern crate buttplug;
 extern crate tracing;
 
 use buttplug::{
-  client::{ButtplugClient, ButtplugClientError, ButtplugClientEvent},
+  client::{
+    playback,
+    ButtplugClient,
+    ButtplugClientBuilder,
+    ButtplugClientDevice,
+    ButtplugClientError,
+    ButtplugClientEvent,
+    RecordedMessage,
+    ScalarValueCommand,
+    VecMessageRecorder,
+  },
   core::{
     connector::{
       ButtplugConnector,
@@ -20,13 +38,37 @@ use buttplug::{
       ButtplugInProcessClientConnectorBuilder,
     },
     errors::{ButtplugDeviceError, ButtplugError},
-    message::{ButtplugClientMessageCurrent, ButtplugServerMessageCurrent},
+    message::{
+      ButtplugClientMessageCurrent,
+      ButtplugMessage,
+      ButtplugServerMessageCurrent,
+      DeviceListV3,
+      Endpoint,
+      ErrorCode,
+      ErrorV0,
+      ServerInfoV2,
+      BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION,
+    },
+  },
+  server::{
+    device::hardware::{HardwareCommand, HardwareWriteCmd},
+    ButtplugServerBuilder,
   },
-  server::ButtplugServerBuilder,
 };
 
-use futures::{future::BoxFuture, StreamExt};
-use std::time::Duration;
+use futures::{
+  future::{self, BoxFuture},
+  FutureExt,
+  StreamExt,
+};
+use std::{
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+    Mutex,
+  },
+  time::Duration,
+};
 use tokio::{sync::mpsc::Sender, time::sleep};
 
 #[derive(Default)]
@@ -51,6 +93,163 @@ impl ButtplugConnector<ButtplugClientMessageCurrent, ButtplugServerMessageCurren
   }
 }
 
+/// Connector that can either hang while connecting, or connect and then never answer.
+#[derive(Default)]
+struct ButtplugHangingConnector {
+  hang_on_connect: bool,
+  disconnected: Arc<AtomicBool>,
+  // Held so the client event loop doesn't see the server go away.
+  server_sender: Option<Sender<ButtplugServerMessageCurrent>>,
+}
+
+impl ButtplugConnector<ButtplugClientMessageCurrent, ButtplugServerMessageCurrent>
+  for ButtplugHangingConnector
+{
+  fn connect(
+    &mut self,
+    server_sender: Sender<ButtplugServerMessageCurrent>,
+  ) -> BoxFuture<'static, Result<(), ButtplugConnectorError>> {
+    self.server_sender = Some(server_sender);
+    if self.hang_on_connect {
+      future::pending().boxed()
+    } else {
+      future::ready(Ok(())).boxed()
+    }
+  }
+
+  fn disconnect(&self) -> ButtplugConnectorResultFuture {
+    self.disconnected.store(true, Ordering::SeqCst);
+    future::ready(Ok(())).boxed()
+  }
+
+  fn send(&self, _msg: ButtplugClientMessageCurrent) -> ButtplugConnectorResultFuture {
+    future::ready(Ok(())).boxed()
+  }
+}
+
+/// Connector that answers the handshake, then never replies to anything else.
+#[derive(Default)]
+struct ButtplugUnresponsiveConnector {
+  server_sender: Arc<Mutex<Option<Sender<ButtplugServerMessageCurrent>>>>,
+  // Id of the last message left unanswered.
+  last_ignored_id: Arc<Mutex<Option<u32>>>,
+}
+
+impl ButtplugConnector<ButtplugClientMessageCurrent, ButtplugServerMessageCurrent>
+  for ButtplugUnresponsiveConnector
+{
+  fn connect(
+    &mut self,
+    server_sender: Sender<ButtplugServerMessageCurrent>,
+  ) -> BoxFuture<'static, Result<(), ButtplugConnectorError>> {
+    *self.server_sender.lock().unwrap() = Some(server_sender);
+    future::ready(Ok(())).boxed()
+  }
+
+  fn disconnect(&self) -> ButtplugConnectorResultFuture {
+    future::ready(Ok(())).boxed()
+  }
+
+  fn send(&self, msg: ButtplugClientMessageCurrent) -> ButtplugConnectorResultFuture {
+    let mut reply: ButtplugServerMessageCurrent = match msg {
+      ButtplugClientMessageCurrent::RequestServerInfo(_) => ServerInfoV2::new(
+        "Unresponsive Server",
+        BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION,
+        0,
+      )
+      .into(),
+      ButtplugClientMessageCurrent::RequestDeviceList(_) => DeviceListV3::new(vec![]).into(),
+      _ => {
+        *self.last_ignored_id.lock().unwrap() = Some(msg.id());
+        return future::ready(Ok(())).boxed();
+      }
+    };
+    reply.set_id(msg.id());
+    let sender = self.server_sender.lock().unwrap().clone().unwrap();
+    async move {
+      sender.send(reply).await.unwrap();
+      Ok(())
+    }
+    .boxed()
+  }
+}
+
+#[tokio::test]
+async fn test_client_default_message_timeout() {
+  let client = ButtplugClientBuilder::new("Test Client")
+    .default_message_timeout(Duration::from_millis(50))
+    .finish();
+  let connector = ButtplugUnresponsiveConnector::default();
+  let server_sender = connector.server_sender.clone();
+  let last_ignored_id = connector.last_ignored_id.clone();
+  client
+    .connect(connector)
+    .await
+    .expect("Test, assuming infallible.");
+  let mut events = client.event_stream();
+  assert!(matches!(
+    client.start_scanning().await,
+    Err(ButtplugClientError::CommandTimeout(_))
+  ));
+  assert!(client.connected());
+
+  // Once the event loop has processed the cancellation, the timed out message should no longer be
+  // waiting on a reply, so a late reply with its id is handled as an event instead.
+  sleep(Duration::from_millis(50)).await;
+  let id = last_ignored_id
+    .lock()
+    .unwrap()
+    .expect("Test, assuming infallible.");
+  let mut late_reply = ErrorV0::new(ErrorCode::ErrorUnknown, "Late reply", None);
+  late_reply.set_id(id);
+  let sender = server_sender.lock().unwrap().clone().unwrap();
+  sender
+    .send(late_reply.into())
+    .await
+    .expect("Test, assuming infallible.");
+  assert!(matches!(
+    events.next().await,
+    Some(ButtplugClientEvent::Error(_))
+  ));
+}
+
+#[tokio::test]
+async fn test_client_connect_timeout_while_connecting() {
+  let client = ButtplugClient::new("Test Client");
+  let connector = ButtplugHangingConnector {
+    hang_on_connect: true,
+    ..Default::default()
+  };
+  assert!(matches!(
+    client
+      .connect_with_timeout(connector, Duration::from_millis(50))
+      .await,
+    Err(ButtplugClientError::ConnectionTimeout(_))
+  ));
+  assert!(!client.connected());
+}
+
+#[tokio::test]
+async fn test_client_connect_timeout_during_handshake() {
+  let client = ButtplugClientBuilder::new("Test Client")
+    .connection_timeout(Duration::from_millis(50))
+    .finish();
+  let disconnected = Arc::new(AtomicBool::new(false));
+  let connector = ButtplugHangingConnector {
+    hang_on_connect: false,
+    disconnected: disconnected.clone(),
+    ..Default::default()
+  };
+  assert!(matches!(
+    client.connect(connector).await,
+    Err(ButtplugClientError::ConnectionTimeout(_))
+  ));
+  assert!(!client.connected());
+  // The event loop owned the connector, and should shut it down.
+  sleep(Duration::from_millis(50)).await;
+  assert!(disconnected.load(Ordering::SeqCst));
+}
+
 #[cfg(feature = "server")]
 #[tokio::test]
 async fn test_failing_connection() {
@@ -148,6 +347,91 @@ async fn test_client_scanning_finished() {
   ));
 }
 
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_scan_for() {
+  let (client, _device) = test_client_with_device().await;
+  let devices = client
+    .scan_for(Duration::from_millis(500))
+    .await
+    .expect("Test, assuming infallible.");
+  assert_eq!(devices.len(), 1);
+  assert_eq!(devices[0].index(), 0);
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_scan_for_consumes_scanning_finished() {
+  // The delayed device manager only reports scanning finished once it's told to stop.
+  let client = test_client_with_delayed_device_manager().await;
+  let devices = client
+    .scan_for(Duration::from_millis(100))
+    .await
+    .expect("Test, assuming infallible.");
+  assert!(devices.is_empty());
+  let mut recv = client.event_stream();
+  assert!(
+    tokio::time::timeout(Duration::from_millis(200), recv.next())
+      .await
+      .is_err()
+  );
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_scan_for_when_already_scanning() {
+  let client = test_client_with_delayed_device_manager().await;
+  assert!(client.start_scanning().await.is_ok());
+  let should_be_err = client.scan_for(Duration::from_millis(100)).await;
+  assert!(matches!(
+    should_be_err,
+    Err(ButtplugClientError::ButtplugError(
+      ButtplugError::ButtplugDeviceError(ButtplugDeviceError::DeviceScanningAlreadyStarted)
+    ))
+  ));
+  assert!(client.stop_scanning().await.is_ok());
+  assert!(client.scan_for(Duration::from_millis(100)).await.is_ok());
+  // Calls made at the same time can't both start a scan.
+  let (first, second) = futures::join!(
+    client.scan_for(Duration::from_millis(100)),
+    client.scan_for(Duration::from_millis(100))
+  );
+  assert!(first.is_ok());
+  assert!(matches!(
+    second,
+    Err(ButtplugClientError::ButtplugError(
+      ButtplugError::ButtplugDeviceError(ButtplugDeviceError::DeviceScanningAlreadyStarted)
+    ))
+  ));
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_scan_for_dropped() {
+  let client = test_client_with_delayed_device_manager().await;
+  // A scan_for future that's never polled doesn't start a scan, or block later ones.
+  drop(client.scan_for(Duration::from_millis(100)));
+  assert!(client.scan_for(Duration::from_millis(100)).await.is_ok());
+  // Dropping the future mid-scan stops the scan. The delayed device manager only reports scanning
+  // finished once it's told to stop.
+  let mut recv = client.event_stream();
+  assert!(tokio::time::timeout(
+    Duration::from_millis(100),
+    client.scan_for(Duration::from_secs(60))
+  )
+  .await
+  .is_err());
+  assert!(matches!(
+    tokio::time::timeout(Duration::from_secs(1), recv.next())
+      .await
+      .expect("Test, assuming infallible."),
+    Some(ButtplugClientEvent::ScanningFinished)
+  ));
+  // The scan is released once the stop has been acknowledged.
+  sleep(Duration::from_millis(50)).await;
+  assert!(client.scan_for(Duration::from_millis(100)).await.is_ok());
+}
+
 #[cfg(feature = "server")]
 #[tokio::test]
 async fn test_client_ping() {
@@ -163,10 +447,240 @@ async fn test_client_ping() {
     .connect(connector)
     .await
     .expect("Test, assuming infallible.");
+  let mut recv = client.event_stream();
   assert!(client.ping().await.is_ok());
   sleep(Duration::from_millis(800)).await;
-  // TODO Watch for ping events
   assert!(client.ping().await.is_err());
+  assert!(matches!(
+    recv.next().await.expect("Test, assuming infallible."),
+    ButtplugClientEvent::PingTimeout
+  ));
+  assert!(matches!(
+    recv.next().await.expect("Test, assuming infallible."),
+    ButtplugClientEvent::Error(ButtplugError::ButtplugPingError(_))
+  ));
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_reconnect() {
+  let (client, _device) = test_client_with_device().await;
+  let mut recv = client.event_stream();
+  assert!(client.start_scanning().await.is_ok());
+  let old_device = loop {
+    if let ButtplugClientEvent::DeviceAdded(dev) =
+      recv.next().await.expect("Test, assuming infallible.")
+    {
+      break dev;
+    }
+  };
+  assert!(old_device
+    .vibrate(&ScalarValueCommand::ScalarValue(0.5))
+    .await
+    .is_ok());
+
+  let (server, _new_device) = test_server_v4_with_device("Massage Demo", false);
+  let connector = ButtplugInProcessClientConnectorBuilder::default()
+    .server(server)
+    .finish();
+  client
+    .reconnect(connector)
+    .await
+    .expect("Test, assuming infallible.");
+  assert!(client.connected());
+  // Devices from the old connection are gone, and their handles no longer work.
+  assert!(client.devices().is_empty());
+  assert!(old_device
+    .vibrate(&ScalarValueCommand::ScalarValue(0.5))
+    .await
+    .is_err());
+
+  assert!(client.start_scanning().await.is_ok());
+  let new_device = loop {
+    if let ButtplugClientEvent::DeviceAdded(dev) =
+      recv.next().await.expect("Test, assuming infallible.")
+    {
+      break dev;
+    }
+  };
+  assert!(new_device
+    .vibrate(&ScalarValueCommand::ScalarValue(0.5))
+    .await
+    .is_ok());
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_multiple_event_streams() {
+  let (client, device) = test_client_with_device().await;
+  let mut first = client.event_stream();
+  let mut second = client.event_stream();
+  assert!(client.start_scanning().await.is_ok());
+  for recv in [&mut first, &mut second] {
+    loop {
+      if let ButtplugClientEvent::DeviceAdded(_) =
+        recv.next().await.expect("Test, assuming infallible.")
+      {
+        break;
+      }
+    }
+  }
+  device
+    .sender
+    .send(TestHardwareEvent::Disconnect)
+    .await
+    .expect("Test, assuming infallible.");
+  for recv in [&mut first, &mut second] {
+    loop {
+      if let ButtplugClientEvent::DeviceRemoved(_) =
+        recv.next().await.expect("Test, assuming infallible.")
+      {
+        break;
+      }
+    }
+  }
+}
+
+async fn connect_to_massage_demo(
+  client: &ButtplugClient,
+) -> (TestDeviceChannelHost, Arc<ButtplugClientDevice>) {
+  let (server, device) = test_server_v4_with_device("Massage Demo", false);
+  let connector = ButtplugInProcessClientConnectorBuilder::default()
+    .server(server)
+    .finish();
+  client
+    .connect(connector)
+    .await
+    .expect("Test, assuming infallible.");
+  let mut event_stream = client.event_stream();
+  client
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  while let Some(event) = event_stream.next().await {
+    if let ButtplugClientEvent::DeviceAdded(client_device) = event {
+      return (device, client_device);
+    }
+  }
+  panic!("Should've gotten a DeviceAdded event");
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_record_and_playback_messages() {
+  let recorder = Arc::new(VecMessageRecorder::default());
+  let client = ButtplugClientBuilder::new("Test Client")
+    .record_messages(recorder.clone())
+    .finish();
+  let (mut device, client_device) = connect_to_massage_demo(&client).await;
+  client_device
+    .vibrate(&ScalarValueCommand::ScalarValue(0.5))
+    .await
+    .expect("Test, assuming infallible.");
+  let expected_writes = [
+    HardwareWriteCmd::new(Endpoint::Tx, vec![0xF1, 64], false),
+    HardwareWriteCmd::new(Endpoint::Tx, vec![0xF2, 64], false),
+  ];
+  for write in &expected_writes {
+    check_test_recv_value(&mut device, HardwareCommand::Write(write.clone()));
+  }
+
+  let recorded = recorder.messages();
+  assert!(matches!(
+    recorded[0].message(),
+    RecordedMessage::Sent(ButtplugClientMessageCurrent::RequestServerInfo(_))
+  ));
+  assert!(matches!(
+    recorded[1].message(),
+    RecordedMessage::Received(ButtplugServerMessageCurrent::ServerInfo(_))
+  ));
+  assert!(recorded.iter().any(|x| matches!(
+    x.message(),
+    RecordedMessage::Sent(ButtplugClientMessageCurrent::ScalarCmd(_))
+  )));
+  assert!(recorded
+    .windows(2)
+    .all(|pair| pair[0].timestamp() <= pair[1].timestamp()));
+
+  // Replaying against a fresh server with the same device should drive it the same way.
+  let replay_client = ButtplugClient::new("Replay Client");
+  let (mut replay_device, _) = connect_to_massage_demo(&replay_client).await;
+  let replies = playback(&recorded, &replay_client).await;
+  assert!(!replies.is_empty());
+  assert!(replies.iter().all(|reply| reply.is_ok()));
+  for write in &expected_writes {
+    check_test_recv_value(&mut replay_device, HardwareCommand::Write(write.clone()));
+  }
+
+  // Without the device, the vibrate command fails, but playback still sends everything.
+  let empty_client = ButtplugClient::new("Empty Client");
+  empty_client
+    .connect(
+      ButtplugInProcessClientConnectorBuilder::default()
+        .server(test_server(false))
+        .finish(),
+    )
+    .await
+    .expect("Test, assuming infallible.");
+  let replies = playback(&recorded, &empty_client).await;
+  // Everything but the handshake is replayed.
+  let sent_count = recorded
+    .iter()
+    .filter(|x| matches!(x.message(), RecordedMessage::Sent(_)))
+    .count();
+  assert_eq!(replies.len(), sent_count - 1);
+  assert!(replies.last().expect("Test, assuming infallible.").is_err());
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_message_ids_are_per_client() {
+  let recorders = [
+    Arc::new(VecMessageRecorder::default()),
+    Arc::new(VecMessageRecorder::default()),
+  ];
+  let mut clients = vec![];
+  for recorder in &recorders {
+    let client = ButtplugClientBuilder::new("Test Client")
+      .record_messages(recorder.clone())
+      .finish();
+    let connector = ButtplugInProcessClientConnectorBuilder::default()
+      .server(test_server(false))
+      .finish();
+    client
+      .connect(connector)
+      .await
+      .expect("Test, assuming infallible.");
+    clients.push(client);
+  }
+  // Interleave messages from both clients. If the id counter were shared, each client would see
+  // gaps in its own ids.
+  for _ in 0..5 {
+    let (first, second) =
+      futures::join!(clients[0].stop_all_devices(), clients[1].stop_all_devices());
+    first.expect("Test, assuming infallible.");
+    second.expect("Test, assuming infallible.");
+  }
+  for recorder in &recorders {
+    let ids: Vec<u32> = recorder
+      .messages()
+      .iter()
+      .filter_map(|x| match x.message() {
+        RecordedMessage::Sent(msg) => Some(msg.id()),
+        RecordedMessage::Received(_) => None,
+      })
+      .collect();
+    assert_eq!(ids, (1..=ids.len() as u32).collect::<Vec<_>>());
+  }
+}
+
+#[tokio::test]
+async fn test_client_builder_event_channel_capacity() {
+  // Zero capacity channels aren't valid, so the builder should clamp rather than panic.
+  let client = ButtplugClientBuilder::new("Test Client")
+    .event_channel_capacity(0)
+    .finish();
+  assert!(!client.connected());
 }
 /*
 // Tests both the stop all devices functionality, as well as both ends of the
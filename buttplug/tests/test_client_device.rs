@@ -8,21 +8,48 @@
 mod util;
 use buttplug::{
   client::{
+    ButtplugClient,
     ButtplugClientDeviceEvent,
     ButtplugClientError,
     ButtplugClientEvent,
     ScalarValueCommand,
   },
+  core::connector::ButtplugInProcessClientConnectorBuilder,
   core::{
     errors::{ButtplugDeviceError, ButtplugError, ButtplugMessageError},
-    message::{self, ClientDeviceMessageAttributesV3},
+    message::{self, ActuatorType, ClientDeviceMessageAttributesV3, Endpoint},
+  },
+  server::device::hardware::{
+    HardwareCommand,
+    HardwareSubscribeCmd,
+    HardwareUnsubscribeCmd,
+    HardwareWriteCmd,
   },
   util::async_manager,
 };
 use futures::StreamExt;
-use std::{sync::Arc, time::Duration};
+use std::{
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  time::Duration,
+};
 use tokio::time::sleep;
-use util::{test_client_with_device, test_device_manager::TestHardwareEvent};
+use tokio_util::sync::CancellationToken;
+use util::{
+  test_client_with_device,
+  test_client_with_device_allow_raw,
+  test_device_manager::{
+    check_test_recv_value,
+    TestDeviceCommunicationManagerBuilder,
+    TestDeviceIdentifier,
+    TestHardwareEvent,
+    TestHardwareNotification,
+  },
+  test_server_v4_with_device,
+  test_server_with_comm_manager,
+};
 
 #[cfg(feature = "server")]
 #[tokio::test]
@@ -126,9 +153,8 @@ async fn test_client_device_connected_no_event_listener() {
 
 #[cfg(feature = "server")]
 #[tokio::test]
-async fn test_client_device_invalid_command() {
-  tracing_subscriber::fmt::init();
-  let (client, _) = test_client_with_device().await;
+async fn test_client_device_max_queue_depth() {
+  let (client, _device) = test_client_with_device().await;
 
   let mut event_stream = client.event_stream();
   client
@@ -143,169 +169,1728 @@ async fn test_client_device_invalid_command() {
     }
   }
   let test_device = client_device.expect("Test, assuming infallible.");
+  assert_eq!(test_device.pending_commands(), 0);
+  test_device.set_max_queue_depth(1);
+  let first_cmd = test_device.vibrate(&ScalarValueCommand::ScalarValue(0.5));
+  assert_eq!(test_device.pending_commands(), 1);
   assert!(matches!(
     test_device
-      .vibrate(&ScalarValueCommand::ScalarValue(2.0))
-      .await
-      .unwrap_err(),
-    ButtplugClientError::ButtplugError(ButtplugError::ButtplugMessageError(
-      ButtplugMessageError::InvalidMessageContents(..)
-    ))
-  ));
-  assert!(matches!(
-    test_device
-      .vibrate(&ScalarValueCommand::ScalarValueVec(vec!(0.5, 0.5, 0.5)))
-      .await
-      .unwrap_err(),
-    ButtplugClientError::ButtplugError(ButtplugError::ButtplugDeviceError(
-      ButtplugDeviceError::DeviceFeatureCountMismatch(..)
-    ))
-  ));
-  assert!(matches!(
-    test_device
-      .vibrate(&ScalarValueCommand::ScalarValueVec(vec!()))
-      .await
-      .unwrap_err(),
-    ButtplugClientError::ButtplugError(ButtplugError::ButtplugDeviceError(
-      ButtplugDeviceError::ProtocolRequirementError(..)
-    ))
+      .vibrate(&ScalarValueCommand::ScalarValue(0.25))
+      .await,
+    Err(ButtplugClientError::DeviceBusy(_))
   ));
+  first_cmd.await.expect("Test, assuming infallible.");
+  assert_eq!(test_device.pending_commands(), 0);
+  // Dropping a command without waiting on it frees up its slot too.
+  drop(test_device.vibrate(&ScalarValueCommand::ScalarValue(0.75)));
+  test_device
+    .vibrate(&ScalarValueCommand::ScalarValue(0.75))
+    .await
+    .expect("Test, assuming infallible.");
+  test_device.set_max_queue_depth(0);
+  let _first_cmd = test_device.vibrate(&ScalarValueCommand::ScalarValue(0.5));
+  test_device
+    .vibrate(&ScalarValueCommand::ScalarValue(0.25))
+    .await
+    .expect("Test, assuming infallible.");
 }
 
 #[cfg(feature = "server")]
 #[tokio::test]
-async fn test_client_repeated_deviceadded_message() {
-  use buttplug::core::message::{
-    ButtplugClientMessageV3,
-    ButtplugClientMessageVariant,
-    ButtplugServerMessageVariant,
+async fn test_client_device_serialize_commands() {
+  // Each write takes a while to complete, so the server holds off replying to a command for long
+  // enough that a second command would reach the device early if it wasn't waiting its turn.
+  let mut builder = TestDeviceCommunicationManagerBuilder::default();
+  let mut device = builder.add_slow_test_device(
+    &TestDeviceIdentifier::new("Massage Demo", None),
+    Duration::from_millis(100),
+  );
+  let connector = ButtplugInProcessClientConnectorBuilder::default()
+    .server(test_server_with_comm_manager(builder, false))
+    .finish();
+  let client = ButtplugClient::new("Test Client");
+  client
+    .connect(connector)
+    .await
+    .expect("Test, assuming infallible.");
+
+  let mut event_stream = client.event_stream();
+  client
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  let mut client_device = None;
+  while let Some(msg) = event_stream.next().await {
+    if let ButtplugClientEvent::DeviceAdded(da) = msg {
+      client_device = Some(da);
+      break;
+    }
+  }
+  let test_device = client_device.expect("Test, assuming infallible.");
+  // Clear out anything sent during device initialization.
+  while device.receiver.try_recv().is_ok() {}
+  test_device.serialize_commands(true);
+  let first_replied = Arc::new(AtomicBool::new(false));
+  let first = {
+    let first_replied = first_replied.clone();
+    let fut = test_device.vibrate(&ScalarValueCommand::ScalarValue(0.5));
+    async move {
+      fut.await.expect("Test, assuming infallible.");
+      first_replied.store(true, Ordering::SeqCst);
+    }
+  };
+  let second = test_device.vibrate(&ScalarValueCommand::ScalarValue(1.0));
+  let check_writes = async {
+    for data in [[0xF1, 64], [0xF2, 64], [0xF1, 127], [0xF2, 127]] {
+      let cmd = device
+        .receiver
+        .recv()
+        .await
+        .expect("Test, assuming infallible.");
+      assert_eq!(
+        cmd,
+        HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, data.to_vec(), false))
+      );
+      // Writes are only recorded once their delay is up, so if the second command had been sent
+      // before the first was answered, its writes would land before that answer did.
+      if data[1] == 127 {
+        assert!(first_replied.load(Ordering::SeqCst));
+      }
+    }
   };
+  let (_, second, _) = futures::join!(first, second, check_writes);
+  second.expect("Test, assuming infallible.");
+}
 
-  let helper = Arc::new(util::channel_transport::ChannelClientTestHelper::new());
-  helper.simulate_successful_connect().await;
-  let helper_clone = helper.clone();
-  let mut event_stream = helper.client().event_stream();
-  async_manager::spawn(async move {
-    assert!(matches!(
-      helper_clone.next_client_message().await,
-      ButtplugClientMessageVariant::V3(ButtplugClientMessageV3::StartScanning(..))
-    ));
-    helper_clone
-      .send_client_incoming(ButtplugServerMessageVariant::V3(
-        message::OkV0::new(3).into(),
-      ))
-      .await;
-    let device_added = message::DeviceAddedV3::new(
-      1,
-      "Test Device",
-      &None,
-      &None,
-      &ClientDeviceMessageAttributesV3::default(),
-    );
-    helper_clone
-      .send_client_incoming(ButtplugServerMessageVariant::V3(
-        device_added.clone().into(),
-      ))
-      .await;
-    helper_clone
-      .send_client_incoming(ButtplugServerMessageVariant::V3(device_added.into()))
-      .await;
-  });
-  helper
-    .client()
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_device_set_scalar() {
+  let (client, mut device) = test_client_with_device().await;
+
+  let mut event_stream = client.event_stream();
+  client
     .start_scanning()
     .await
     .expect("Test, assuming infallible.");
+  let mut client_device = None;
+  while let Some(msg) = event_stream.next().await {
+    if let ButtplugClientEvent::DeviceAdded(da) = msg {
+      client_device = Some(da);
+      break;
+    }
+  }
+  let test_device = client_device.expect("Test, assuming infallible.");
+  // Clear out anything sent during device initialization.
+  while device.receiver.try_recv().is_ok() {}
+  test_device
+    .set_vibration(1, 0.5)
+    .await
+    .expect("Test, assuming infallible.");
+  // Only the second motor should be touched.
+  check_test_recv_value(
+    &mut device,
+    HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, vec![0xF2, 64], false)),
+  );
+  assert!(device.receiver.try_recv().is_err());
+  test_device
+    .set_scalar(0, 1.0, ActuatorType::Vibrate)
+    .await
+    .expect("Test, assuming infallible.");
+  check_test_recv_value(
+    &mut device,
+    HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, vec![0xF1, 127], false)),
+  );
   assert!(matches!(
-    event_stream
-      .next()
-      .await
-      .expect("Test, assuming infallible."),
-    ButtplugClientEvent::DeviceAdded(..)
-  ));
-  assert!(matches!(
-    event_stream
-      .next()
-      .await
-      .expect("Test, assuming infallible."),
-    ButtplugClientEvent::Error(..)
+    test_device.set_vibration(2, 0.5).await,
+    Err(ButtplugClientError::ButtplugError(
+      ButtplugError::ButtplugDeviceError(ButtplugDeviceError::DeviceFeatureIndexError(2, 2))
+    ))
   ));
 }
 
 #[cfg(feature = "server")]
 #[tokio::test]
-async fn test_client_repeated_deviceremoved_message() {
-  use buttplug::core::message::{
-    ButtplugClientMessageV3,
-    ButtplugClientMessageVariant,
-    ButtplugServerMessageVariant,
-  };
+async fn test_client_device_raw_subscription_stream() {
+  let (client, device) = test_client_with_device_allow_raw(true).await;
 
-  let helper = Arc::new(util::channel_transport::ChannelClientTestHelper::new());
-  helper.simulate_successful_connect().await;
-  let helper_clone = helper.clone();
-  let mut event_stream = helper.client().event_stream();
-  async_manager::spawn(async move {
-    assert!(matches!(
-      helper_clone.next_client_message().await,
-      ButtplugClientMessageVariant::V3(ButtplugClientMessageV3::StartScanning(..))
-    ));
-    helper_clone
-      .send_client_incoming(ButtplugServerMessageVariant::V3(
-        message::OkV0::new(3).into(),
-      ))
-      .await;
-    let device_added = message::DeviceAddedV3::new(
-      1,
-      "Test Device",
-      &None,
-      &None,
-      &ClientDeviceMessageAttributesV3::default(),
-    );
-    let device_removed = message::DeviceRemovedV0::new(1);
-    helper_clone
-      .send_client_incoming(ButtplugServerMessageVariant::V3(device_added.into()))
-      .await;
-    helper_clone
-      .send_client_incoming(ButtplugServerMessageVariant::V3(
-        device_removed.clone().into(),
-      ))
-      .await;
-    helper_clone
-      .send_client_incoming(ButtplugServerMessageVariant::V3(device_removed.into()))
-      .await;
-  });
-  helper
-    .client()
+  let mut event_stream = client.event_stream();
+  client
     .start_scanning()
     .await
     .expect("Test, assuming infallible.");
-  assert!(matches!(
-    event_stream
-      .next()
-      .await
-      .expect("Test, assuming infallible."),
-    ButtplugClientEvent::DeviceAdded(..)
-  ));
-  assert!(matches!(
-    event_stream
-      .next()
+  let mut client_device = None;
+  while let Some(msg) = event_stream.next().await {
+    if let ButtplugClientEvent::DeviceAdded(da) = msg {
+      client_device = Some(da);
+      break;
+    }
+  }
+  let test_device = client_device.expect("Test, assuming infallible.");
+  let mut raw_stream = test_device
+    .raw_subscription_stream(message::Endpoint::Tx)
+    .await
+    .expect("Test, assuming infallible.");
+  device
+    .sender
+    .send(TestHardwareEvent::Notifications(vec![
+      TestHardwareNotification::new(message::Endpoint::Tx, &[0x01, 0x02]),
+    ]))
+    .await
+    .expect("Test, assuming infallible.");
+  let data = tokio::time::timeout(Duration::from_secs(1), raw_stream.next())
+    .await
+    .expect("Test, assuming infallible.")
+    .expect("Test, assuming infallible.");
+  assert_eq!(data, vec![0x01, 0x02]);
+  drop(raw_stream);
+  // Once the stream is dropped, we should have unsubscribed, so further notifications shouldn't
+  // show up on the device event stream.
+  sleep(Duration::from_millis(100)).await;
+  let mut device_event_stream = test_device.event_stream();
+  device
+    .sender
+    .send(TestHardwareEvent::Notifications(vec![
+      TestHardwareNotification::new(message::Endpoint::Tx, &[0x03]),
+    ]))
+    .await
+    .expect("Test, assuming infallible.");
+  assert!(
+    tokio::time::timeout(Duration::from_millis(100), device_event_stream.next())
       .await
-      .expect("Test, assuming infallible."),
-    ButtplugClientEvent::DeviceRemoved(..)
-  ));
-  assert!(matches!(
-    event_stream
-      .next()
+      .is_err()
+  );
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_device_raw_subscription_stream_buffers_before_poll() {
+  let (client, device) = test_client_with_device_allow_raw(true).await;
+
+  let mut event_stream = client.event_stream();
+  client
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  let mut client_device = None;
+  while let Some(msg) = event_stream.next().await {
+    if let ButtplugClientEvent::DeviceAdded(da) = msg {
+      client_device = Some(da);
+      break;
+    }
+  }
+  let test_device = client_device.expect("Test, assuming infallible.");
+  let mut raw_stream = test_device
+    .raw_subscription_stream(message::Endpoint::Tx)
+    .await
+    .expect("Test, assuming infallible.");
+  // Notifications that show up before the caller gets around to polling the stream (like a
+  // handshake byte sent right after subscription) are held for it, in order.
+  device
+    .sender
+    .send(TestHardwareEvent::Notifications(vec![
+      TestHardwareNotification::new(message::Endpoint::Tx, &[0x01]),
+      TestHardwareNotification::new(message::Endpoint::Tx, &[0x02]),
+      TestHardwareNotification::new(message::Endpoint::Tx, &[0x03]),
+    ]))
+    .await
+    .expect("Test, assuming infallible.");
+  sleep(Duration::from_millis(100)).await;
+  for expected in [[0x01], [0x02], [0x03]] {
+    let data = tokio::time::timeout(Duration::from_secs(1), raw_stream.next())
       .await
-      .expect("Test, assuming infallible."),
-    ButtplugClientEvent::Error(..)
-  ));
+      .expect("Test, assuming infallible.")
+      .expect("Test, assuming infallible.");
+    assert_eq!(data, expected.to_vec());
+  }
 }
 
-// TODO Test invalid messages to device
-// TODO Test invalid parameters in message
-// TODO Test device invalidation across client connections (i.e. a device shouldn't be allowed to reconnect even if index is the same)
-// TODO Test DeviceList being sent followed by repeat DeviceAdded
-// TODO Test DeviceList being sent multiple times
-// TODO Test sending device return for device that doesn't exist (in client)
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_device_raw_subscription_streams_share_subscription() {
+  let (client, mut device) = test_client_with_device_allow_raw(true).await;
+
+  let mut event_stream = client.event_stream();
+  client
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  let mut client_device = None;
+  while let Some(msg) = event_stream.next().await {
+    if let ButtplugClientEvent::DeviceAdded(da) = msg {
+      client_device = Some(da);
+      break;
+    }
+  }
+  let test_device = client_device.expect("Test, assuming infallible.");
+  // Clear out anything sent during device initialization.
+  while device.receiver.try_recv().is_ok() {}
+  let first_stream = test_device
+    .raw_subscription_stream(Endpoint::Tx)
+    .await
+    .expect("Test, assuming infallible.");
+  let mut second_stream = test_device
+    .raw_subscription_stream(Endpoint::Tx)
+    .await
+    .expect("Test, assuming infallible.");
+  check_test_recv_value(
+    &mut device,
+    HardwareCommand::Subscribe(HardwareSubscribeCmd::new(Endpoint::Tx)),
+  );
+
+  // Dropping one stream leaves the subscription in place for the other.
+  drop(first_stream);
+  sleep(Duration::from_millis(100)).await;
+  assert!(device.receiver.try_recv().is_err());
+  device
+    .sender
+    .send(TestHardwareEvent::Notifications(vec![
+      TestHardwareNotification::new(Endpoint::Tx, &[0x01]),
+    ]))
+    .await
+    .expect("Test, assuming infallible.");
+  let data = tokio::time::timeout(Duration::from_secs(1), second_stream.next())
+    .await
+    .expect("Test, assuming infallible.")
+    .expect("Test, assuming infallible.");
+  assert_eq!(data, vec![0x01]);
+
+  // A direct subscription holds the endpoint too, so dropping the last stream doesn't end it.
+  test_device
+    .raw_subscribe(Endpoint::Tx)
+    .await
+    .expect("Test, assuming infallible.");
+  drop(second_stream);
+  sleep(Duration::from_millis(100)).await;
+  assert!(device.receiver.try_recv().is_err());
+
+  // Once nothing holds the subscription, we unsubscribe.
+  test_device
+    .raw_unsubscribe(Endpoint::Tx)
+    .await
+    .expect("Test, assuming infallible.");
+  check_test_recv_value(
+    &mut device,
+    HardwareCommand::Unsubscribe(HardwareUnsubscribeCmd::new(Endpoint::Tx)),
+  );
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_device_raw_reading_routed_to_device_endpoint() {
+  let mut builder = TestDeviceCommunicationManagerBuilder::default();
+  let hardware = [
+    builder.add_test_device(&TestDeviceIdentifier::new(
+      "Massage Demo",
+      Some("first".to_owned()),
+    )),
+    builder.add_test_device(&TestDeviceIdentifier::new(
+      "Massage Demo",
+      Some("second".to_owned()),
+    )),
+  ];
+  let connector = ButtplugInProcessClientConnectorBuilder::default()
+    .server(test_server_with_comm_manager(builder, true))
+    .finish();
+  let client = ButtplugClient::new("Test Client");
+  client
+    .connect(connector)
+    .await
+    .expect("Test, assuming infallible.");
+  let mut event_stream = client.event_stream();
+  client
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  let mut client_devices = vec![];
+  while let Some(msg) = event_stream.next().await {
+    if let ButtplugClientEvent::DeviceAdded(da) = msg {
+      client_devices.push(da);
+      if client_devices.len() == 2 {
+        break;
+      }
+    }
+  }
+  // Each device has its own reading channels, but the endpoints themselves are the same.
+  assert_eq!(
+    client_devices[0].raw_endpoints(),
+    client_devices[1].raw_endpoints()
+  );
+  let mut raw_streams = vec![];
+  for client_device in &client_devices {
+    raw_streams.push(
+      client_device
+        .raw_subscription_stream(message::Endpoint::Tx)
+        .await
+        .expect("Test, assuming infallible."),
+    );
+  }
+  // We don't know which client device index each piece of hardware ended up with, so send a
+  // notification from each and make sure it only shows up on one stream, and a different one each
+  // time.
+  let mut receiving_streams = vec![];
+  for (hardware, data) in hardware.iter().zip([0x01u8, 0x02]) {
+    hardware
+      .sender
+      .send(TestHardwareEvent::Notifications(vec![
+        TestHardwareNotification::new(message::Endpoint::Tx, &[data]),
+      ]))
+      .await
+      .expect("Test, assuming infallible.");
+    let mut received = vec![];
+    for (index, raw_stream) in raw_streams.iter_mut().enumerate() {
+      if let Ok(reading) = tokio::time::timeout(Duration::from_millis(100), raw_stream.next()).await
+      {
+        assert_eq!(reading, Some(vec![data]));
+        received.push(index);
+      }
+    }
+    assert_eq!(received.len(), 1);
+    receiving_streams.push(received[0]);
+    // Every reading also shows up on the client event stream, tagged with its device.
+    loop {
+      let event = tokio::time::timeout(Duration::from_millis(100), event_stream.next())
+        .await
+        .expect("Test, assuming infallible.");
+      if let Some(ButtplugClientEvent::RawReading {
+        device_index,
+        endpoint,
+        data: reading,
+      }) = event
+      {
+        assert_eq!(device_index, client_devices[received[0]].index());
+        assert_eq!(endpoint, message::Endpoint::Tx);
+        assert_eq!(reading, vec![data]);
+        break;
+      }
+    }
+  }
+  assert_ne!(receiving_streams[0], receiving_streams[1]);
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_device_vibrate_binaural() {
+  let (client, mut device) = test_client_with_device().await;
+
+  let mut event_stream = client.event_stream();
+  client
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  let mut client_device = None;
+  while let Some(msg) = event_stream.next().await {
+    if let ButtplugClientEvent::DeviceAdded(da) = msg {
+      client_device = Some(da);
+      break;
+    }
+  }
+  let test_device = client_device.expect("Test, assuming infallible.");
+  // Clear out anything sent during device initialization.
+  while device.receiver.try_recv().is_ok() {}
+  let start = std::time::Instant::now();
+  test_device
+    .vibrate_binaural(2.0, 0.5, 1.0, Duration::from_millis(200))
+    .await
+    .expect("Test, assuming infallible.");
+  // 4 samples, 50ms apart, each followed by a sleep before the stop goes out.
+  assert!(start.elapsed() >= Duration::from_millis(200));
+  // Motor 1 runs at 2hz, motor 2 at 2.5hz. The server dedupes motor 1's last sample, since it
+  // matches the one before it, then both motors are stopped.
+  for (motor, value) in [
+    (0xF1, 64),
+    (0xF2, 64),
+    (0xF1, 101),
+    (0xF2, 109),
+    (0xF1, 124),
+    (0xF2, 127),
+    (0xF2, 109),
+    (0xF1, 0),
+    (0xF2, 0),
+  ] {
+    check_test_recv_value(
+      &mut device,
+      HardwareCommand::Write(HardwareWriteCmd::new(
+        Endpoint::Tx,
+        vec![motor, value],
+        false,
+      )),
+    );
+  }
+  assert!(device.receiver.try_recv().is_err());
+  assert!(matches!(
+    test_device
+      .vibrate_binaural(20.0, 0.5, 1.0, Duration::from_millis(200))
+      .await
+      .unwrap_err(),
+    ButtplugClientError::ButtplugError(ButtplugError::ButtplugMessageError(
+      ButtplugMessageError::InvalidMessageContents(..)
+    ))
+  ));
+  assert!(matches!(
+    test_device
+      .vibrate_binaural(2.0, 0.5, 1.5, Duration::from_millis(200))
+      .await
+      .unwrap_err(),
+    ButtplugClientError::ButtplugError(ButtplugError::ButtplugMessageError(
+      ButtplugMessageError::InvalidMessageContents(..)
+    ))
+  ));
+  // Binaural samples count against the queue depth like any other actuator command.
+  test_device.set_max_queue_depth(1);
+  let held_cmd = test_device.vibrate(&ScalarValueCommand::ScalarValue(0.5));
+  assert!(matches!(
+    test_device
+      .vibrate_binaural(2.0, 0.5, 1.0, Duration::from_millis(200))
+      .await,
+    Err(ButtplugClientError::DeviceBusy(_))
+  ));
+  held_cmd.await.expect("Test, assuming infallible.");
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_device_vibrate_binaural_stops_on_error() {
+  let (client, mut device) = test_client_with_device().await;
+
+  let mut event_stream = client.event_stream();
+  client
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  let mut client_device = None;
+  while let Some(msg) = event_stream.next().await {
+    if let ButtplugClientEvent::DeviceAdded(da) = msg {
+      client_device = Some(da);
+      break;
+    }
+  }
+  let test_device = client_device.expect("Test, assuming infallible.");
+  while device.receiver.try_recv().is_ok() {}
+  let binaural_task =
+    tokio::spawn(test_device.vibrate_binaural(2.0, 0.5, 1.0, Duration::from_secs(60)));
+  // Wait for the first sample to be acknowledged, then fill the queue so the next sample fails.
+  while device.receiver.try_recv().is_err() {
+    sleep(Duration::from_millis(1)).await;
+  }
+  while test_device.pending_commands() != 0 {
+    sleep(Duration::from_millis(1)).await;
+  }
+  test_device.set_max_queue_depth(1);
+  let held_cmd = test_device.vibrate(&ScalarValueCommand::ScalarValue(0.5));
+  assert!(matches!(
+    binaural_task.await.expect("Test, assuming infallible."),
+    Err(ButtplugClientError::DeviceBusy(_))
+  ));
+  drop(held_cmd);
+  // The stop has reached the device by the time the error is returned.
+  let mut writes = vec![];
+  while let Ok(cmd) = device.receiver.try_recv() {
+    writes.push(cmd);
+  }
+  assert_eq!(
+    writes[writes.len() - 2..],
+    [0xF1, 0xF2].map(|motor| HardwareCommand::Write(HardwareWriteCmd::new(
+      Endpoint::Tx,
+      vec![motor, 0],
+      false,
+    )))
+  );
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_device_emergency_stop() {
+  let (client, mut device) = test_client_with_device().await;
+
+  let mut event_stream = client.event_stream();
+  client
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  let mut client_device = None;
+  while let Some(msg) = event_stream.next().await {
+    if let ButtplugClientEvent::DeviceAdded(da) = msg {
+      client_device = Some(da);
+      break;
+    }
+  }
+  let test_device = client_device.expect("Test, assuming infallible.");
+  test_device
+    .vibrate(&ScalarValueCommand::ScalarValue(0.5))
+    .await
+    .expect("Test, assuming infallible.");
+  while device.receiver.try_recv().is_ok() {}
+  test_device
+    .emergency_stop()
+    .await
+    .expect("Test, assuming infallible.");
+  // The stop must have been written to hardware by the time the future resolves.
+  assert!(device.receiver.try_recv().is_ok());
+  device
+    .sender
+    .send(TestHardwareEvent::Disconnect)
+    .await
+    .expect("Test, assuming infallible.");
+  while test_device.connected() {
+    sleep(Duration::from_millis(10)).await;
+  }
+  assert!(matches!(
+    test_device.emergency_stop().await.unwrap_err(),
+    ButtplugClientError::ButtplugError(ButtplugError::ButtplugDeviceError(
+      ButtplugDeviceError::DeviceNotConnected(..)
+    ))
+  ));
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_device_invalid_command() {
+  tracing_subscriber::fmt::init();
+  let (client, _) = test_client_with_device().await;
+
+  let mut event_stream = client.event_stream();
+  client
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  let mut client_device = None;
+  while let Some(msg) = event_stream.next().await {
+    if let ButtplugClientEvent::DeviceAdded(da) = msg {
+      client_device = Some(da);
+      break;
+    }
+  }
+  let test_device = client_device.expect("Test, assuming infallible.");
+  assert!(matches!(
+    test_device
+      .vibrate(&ScalarValueCommand::ScalarValue(2.0))
+      .await
+      .unwrap_err(),
+    ButtplugClientError::ButtplugError(ButtplugError::ButtplugMessageError(
+      ButtplugMessageError::InvalidMessageContents(..)
+    ))
+  ));
+  assert!(matches!(
+    test_device
+      .vibrate(&ScalarValueCommand::ScalarValueVec(vec!(0.5, 0.5, 0.5)))
+      .await
+      .unwrap_err(),
+    ButtplugClientError::ButtplugError(ButtplugError::ButtplugDeviceError(
+      ButtplugDeviceError::DeviceFeatureCountMismatch(..)
+    ))
+  ));
+  assert!(matches!(
+    test_device
+      .vibrate(&ScalarValueCommand::ScalarValueVec(vec!()))
+      .await
+      .unwrap_err(),
+    ButtplugClientError::ButtplugError(ButtplugError::ButtplugDeviceError(
+      ButtplugDeviceError::ProtocolRequirementError(..)
+    ))
+  ));
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_repeated_deviceadded_message() {
+  use buttplug::core::message::{
+    ButtplugClientMessageV3,
+    ButtplugClientMessageVariant,
+    ButtplugServerMessageVariant,
+  };
+
+  let helper = Arc::new(util::channel_transport::ChannelClientTestHelper::new());
+  helper.simulate_successful_connect().await;
+  let helper_clone = helper.clone();
+  let mut event_stream = helper.client().event_stream();
+  async_manager::spawn(async move {
+    assert!(matches!(
+      helper_clone.next_client_message().await,
+      ButtplugClientMessageVariant::V3(ButtplugClientMessageV3::StartScanning(..))
+    ));
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(
+        message::OkV0::new(3).into(),
+      ))
+      .await;
+    let device_added = message::DeviceAddedV3::new(
+      1,
+      "Test Device",
+      &None,
+      &None,
+      &ClientDeviceMessageAttributesV3::default(),
+    );
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(
+        device_added.clone().into(),
+      ))
+      .await;
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(device_added.into()))
+      .await;
+  });
+  helper
+    .client()
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  assert!(matches!(
+    event_stream
+      .next()
+      .await
+      .expect("Test, assuming infallible."),
+    ButtplugClientEvent::DeviceAdded(..)
+  ));
+  assert!(matches!(
+    event_stream
+      .next()
+      .await
+      .expect("Test, assuming infallible."),
+    ButtplugClientEvent::Error(..)
+  ));
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_repeated_deviceremoved_message() {
+  use buttplug::core::message::{
+    ButtplugClientMessageV3,
+    ButtplugClientMessageVariant,
+    ButtplugServerMessageVariant,
+  };
+
+  let helper = Arc::new(util::channel_transport::ChannelClientTestHelper::new());
+  helper.simulate_successful_connect().await;
+  let helper_clone = helper.clone();
+  let mut event_stream = helper.client().event_stream();
+  async_manager::spawn(async move {
+    assert!(matches!(
+      helper_clone.next_client_message().await,
+      ButtplugClientMessageVariant::V3(ButtplugClientMessageV3::StartScanning(..))
+    ));
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(
+        message::OkV0::new(3).into(),
+      ))
+      .await;
+    let device_added = message::DeviceAddedV3::new(
+      1,
+      "Test Device",
+      &None,
+      &None,
+      &ClientDeviceMessageAttributesV3::default(),
+    );
+    let device_removed = message::DeviceRemovedV0::new(1);
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(device_added.into()))
+      .await;
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(
+        device_removed.clone().into(),
+      ))
+      .await;
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(device_removed.into()))
+      .await;
+  });
+  helper
+    .client()
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  assert!(matches!(
+    event_stream
+      .next()
+      .await
+      .expect("Test, assuming infallible."),
+    ButtplugClientEvent::DeviceAdded(..)
+  ));
+  assert!(matches!(
+    event_stream
+      .next()
+      .await
+      .expect("Test, assuming infallible."),
+    ButtplugClientEvent::DeviceRemoved(..)
+  ));
+  assert!(matches!(
+    event_stream
+      .next()
+      .await
+      .expect("Test, assuming infallible."),
+    ButtplugClientEvent::Error(..)
+  ));
+}
+
+#[tokio::test]
+async fn test_client_device_sensors_merge_read_and_subscribe() {
+  use buttplug::core::message::{
+    ButtplugClientMessageV3,
+    ButtplugClientMessageVariant,
+    ButtplugServerMessageVariant,
+    ClientDeviceMessageAttributesV3Builder,
+    SensorDeviceMessageAttributesV3,
+    SensorType,
+  };
+
+  let helper = Arc::new(util::channel_transport::ChannelClientTestHelper::new());
+  helper.simulate_successful_connect().await;
+  let helper_clone = helper.clone();
+  let mut event_stream = helper.client().event_stream();
+  async_manager::spawn(async move {
+    assert!(matches!(
+      helper_clone.next_client_message().await,
+      ButtplugClientMessageVariant::V3(ButtplugClientMessageV3::StartScanning(..))
+    ));
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(
+        message::OkV0::new(3).into(),
+      ))
+      .await;
+    let battery = SensorDeviceMessageAttributesV3::new("Battery", SensorType::Battery, &[0..=100]);
+    let pressure =
+      SensorDeviceMessageAttributesV3::new("Pressure", SensorType::Pressure, &[0..=1000]);
+    let button = SensorDeviceMessageAttributesV3::new("Button", SensorType::Button, &[0..=1]);
+    let mut builder = ClientDeviceMessageAttributesV3Builder::default();
+    builder.sensor_read_cmd(&[battery, pressure.clone()]);
+    builder.sensor_subscribe_cmd(&[pressure, button]);
+    let attrs = builder.finish().expect("Test, assuming infallible.");
+    let device_added = message::DeviceAddedV3::new(1, "Test Device", &None, &None, &attrs);
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(device_added.into()))
+      .await;
+  });
+  helper
+    .client()
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  let Some(ButtplugClientEvent::DeviceAdded(device)) = event_stream.next().await else {
+    panic!("Expected DeviceAdded event");
+  };
+  let sensors: Vec<_> = device
+    .sensors()
+    .iter()
+    .map(|x| (x.sensor_type(), x.read_index(), x.subscribe_index()))
+    .collect();
+  // Pressure is listed under both messages, at different indexes, but is still one sensor.
+  assert_eq!(
+    sensors,
+    vec![
+      (SensorType::Battery, Some(0), None),
+      (SensorType::Pressure, Some(1), Some(0)),
+      (SensorType::Button, None, Some(1)),
+    ]
+  );
+  assert!(device.sensors()[1].readable() && device.sensors()[1].subscribable());
+  assert!(!device.sensors()[2].readable());
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_device_sensor_subscription_stream() {
+  use buttplug::core::message::{
+    ButtplugClientMessageV3,
+    ButtplugClientMessageVariant,
+    ButtplugMessage,
+    ButtplugServerMessageVariant,
+    ClientDeviceMessageAttributesV3Builder,
+    SensorDeviceMessageAttributesV3,
+    SensorType,
+  };
+
+  let helper = Arc::new(util::channel_transport::ChannelClientTestHelper::new());
+  helper.simulate_successful_connect().await;
+  let helper_clone = helper.clone();
+  let mut event_stream = helper.client().event_stream();
+  async_manager::spawn(async move {
+    assert!(matches!(
+      helper_clone.next_client_message().await,
+      ButtplugClientMessageVariant::V3(ButtplugClientMessageV3::StartScanning(..))
+    ));
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(
+        message::OkV0::new(3).into(),
+      ))
+      .await;
+    let mut builder = ClientDeviceMessageAttributesV3Builder::default();
+    builder.sensor_subscribe_cmd(&[SensorDeviceMessageAttributesV3::new(
+      "Pressure",
+      SensorType::Pressure,
+      &[0..=100],
+    )]);
+    let attrs = builder.finish().expect("Test, assuming infallible.");
+    let device_added = message::DeviceAddedV3::new(1, "Test Device", &None, &None, &attrs);
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(device_added.into()))
+      .await;
+    let subscribe_msg = helper_clone.next_client_message().await;
+    assert!(matches!(
+      subscribe_msg,
+      ButtplugClientMessageVariant::V3(ButtplugClientMessageV3::SensorSubscribeCmd(..))
+    ));
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(
+        message::OkV0::new(subscribe_msg.id()).into(),
+      ))
+      .await;
+    // Readings for other sensors shouldn't show up on the stream.
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(
+        message::SensorReadingV3::new(1, 1, SensorType::Pressure, vec![10]).into(),
+      ))
+      .await;
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(
+        message::SensorReadingV3::new(1, 0, SensorType::Pressure, vec![50]).into(),
+      ))
+      .await;
+    let unsubscribe_msg = helper_clone.next_client_message().await;
+    assert!(matches!(
+      unsubscribe_msg,
+      ButtplugClientMessageVariant::V3(ButtplugClientMessageV3::SensorUnsubscribeCmd(..))
+    ));
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(
+        message::OkV0::new(unsubscribe_msg.id()).into(),
+      ))
+      .await;
+  });
+  helper
+    .client()
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  let device = if let ButtplugClientEvent::DeviceAdded(device) = event_stream
+    .next()
+    .await
+    .expect("Test, assuming infallible.")
+  {
+    device
+  } else {
+    panic!("Expected DeviceAdded event");
+  };
+  assert!(device.supports_sensor_subscribe(SensorType::Pressure));
+  assert!(!device.supports_sensor_subscribe(SensorType::Battery));
+  assert!(!device.supports_sensor_read(SensorType::Pressure));
+  let mut sensor_stream = device
+    .sensor_subscription_stream(0, SensorType::Pressure)
+    .await
+    .expect("Test, assuming infallible.");
+  assert_eq!(
+    sensor_stream
+      .next()
+      .await
+      .expect("Test, assuming infallible."),
+    vec![50]
+  );
+  // Every reading also shows up on the client event stream, regardless of sensor.
+  for (sensor_index, value) in [(1, 10), (0, 50)] {
+    let event = event_stream
+      .next()
+      .await
+      .expect("Test, assuming infallible.");
+    if let ButtplugClientEvent::SensorReading {
+      device_index,
+      sensor_index: event_sensor_index,
+      sensor_type,
+      data,
+    } = event
+    {
+      assert_eq!(device_index, 1);
+      assert_eq!(event_sensor_index, sensor_index);
+      assert_eq!(sensor_type, SensorType::Pressure);
+      assert_eq!(data, vec![value]);
+    } else {
+      panic!("Expected SensorReading event, got {:?}", event);
+    }
+  }
+  drop(sensor_stream);
+  sleep(Duration::from_millis(100)).await;
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_device_sensor_subscription_streams_share_subscription() {
+  use buttplug::core::message::{
+    ButtplugClientMessageV3,
+    ButtplugClientMessageVariant,
+    ButtplugMessage,
+    ButtplugServerMessageVariant,
+    ClientDeviceMessageAttributesV3Builder,
+    SensorDeviceMessageAttributesV3,
+    SensorType,
+  };
+
+  let helper = Arc::new(util::channel_transport::ChannelClientTestHelper::new());
+  helper.simulate_successful_connect().await;
+  let helper_clone = helper.clone();
+  let mut event_stream = helper.client().event_stream();
+  let server_task = tokio::spawn(async move {
+    assert!(matches!(
+      helper_clone.next_client_message().await,
+      ButtplugClientMessageVariant::V3(ButtplugClientMessageV3::StartScanning(..))
+    ));
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(
+        message::OkV0::new(3).into(),
+      ))
+      .await;
+    let mut builder = ClientDeviceMessageAttributesV3Builder::default();
+    builder.sensor_subscribe_cmd(&[SensorDeviceMessageAttributesV3::new(
+      "Pressure",
+      SensorType::Pressure,
+      &[0..=100],
+    )]);
+    let attrs = builder.finish().expect("Test, assuming infallible.");
+    let device_added = message::DeviceAddedV3::new(1, "Test Device", &None, &None, &attrs);
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(device_added.into()))
+      .await;
+    // Each stream asks to subscribe. The server treats the second as a no-op.
+    for _ in 0..2 {
+      let subscribe_msg = helper_clone.next_client_message().await;
+      assert!(matches!(
+        subscribe_msg,
+        ButtplugClientMessageVariant::V3(ButtplugClientMessageV3::SensorSubscribeCmd(..))
+      ));
+      helper_clone
+        .send_client_incoming(ButtplugServerMessageVariant::V3(
+          message::OkV0::new(subscribe_msg.id()).into(),
+        ))
+        .await;
+    }
+    // The first stream is dropped before the stop is sent, so if dropping it unsubscribed, the
+    // unsubscribe would show up here instead.
+    let stop_msg = helper_clone.next_client_message().await;
+    assert!(matches!(
+      stop_msg,
+      ButtplugClientMessageVariant::V3(ButtplugClientMessageV3::StopDeviceCmd(..))
+    ));
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(
+        message::OkV0::new(stop_msg.id()).into(),
+      ))
+      .await;
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(
+        message::SensorReadingV3::new(1, 0, SensorType::Pressure, vec![50]).into(),
+      ))
+      .await;
+    let unsubscribe_msg = helper_clone.next_client_message().await;
+    assert!(matches!(
+      unsubscribe_msg,
+      ButtplugClientMessageVariant::V3(ButtplugClientMessageV3::SensorUnsubscribeCmd(..))
+    ));
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(
+        message::OkV0::new(unsubscribe_msg.id()).into(),
+      ))
+      .await;
+  });
+  helper
+    .client()
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  let device = if let ButtplugClientEvent::DeviceAdded(device) = event_stream
+    .next()
+    .await
+    .expect("Test, assuming infallible.")
+  {
+    device
+  } else {
+    panic!("Expected DeviceAdded event");
+  };
+  let first_stream = device
+    .sensor_subscription_stream(0, SensorType::Pressure)
+    .await
+    .expect("Test, assuming infallible.");
+  let mut second_stream = device
+    .sensor_subscription_stream(0, SensorType::Pressure)
+    .await
+    .expect("Test, assuming infallible.");
+  drop(first_stream);
+  sleep(Duration::from_millis(100)).await;
+  tokio::time::timeout(Duration::from_secs(1), device.stop())
+    .await
+    .expect("Test, assuming infallible.")
+    .expect("Test, assuming infallible.");
+  // The remaining stream still gets readings.
+  assert_eq!(
+    tokio::time::timeout(Duration::from_secs(1), second_stream.next())
+      .await
+      .expect("Test, assuming infallible.")
+      .expect("Test, assuming infallible."),
+    vec![50]
+  );
+  drop(second_stream);
+  tokio::time::timeout(Duration::from_secs(1), server_task)
+    .await
+    .expect("Test, assuming infallible.")
+    .expect("Test, assuming infallible.");
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_device_read_sensor() {
+  use buttplug::core::message::{
+    ButtplugClientMessageV3,
+    ButtplugClientMessageVariant,
+    ButtplugMessage,
+    ButtplugServerMessageVariant,
+    ClientDeviceMessageAttributesV3Builder,
+    SensorDeviceMessageAttributesV3,
+    SensorType,
+  };
+
+  let helper = Arc::new(util::channel_transport::ChannelClientTestHelper::new());
+  helper.simulate_successful_connect().await;
+  let helper_clone = helper.clone();
+  let mut event_stream = helper.client().event_stream();
+  async_manager::spawn(async move {
+    assert!(matches!(
+      helper_clone.next_client_message().await,
+      ButtplugClientMessageVariant::V3(ButtplugClientMessageV3::StartScanning(..))
+    ));
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(
+        message::OkV0::new(3).into(),
+      ))
+      .await;
+    let mut builder = ClientDeviceMessageAttributesV3Builder::default();
+    builder.sensor_read_cmd(&[
+      SensorDeviceMessageAttributesV3::new("Battery", SensorType::Battery, &[0..=100]),
+      SensorDeviceMessageAttributesV3::new("Pressure", SensorType::Pressure, &[0..=1000]),
+      SensorDeviceMessageAttributesV3::new("Temperature", SensorType::Temperature, &[0..=100]),
+    ]);
+    let attrs = builder.finish().expect("Test, assuming infallible.");
+    let device_added = message::DeviceAddedV3::new(1, "Test Device", &None, &None, &attrs);
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(device_added.into()))
+      .await;
+    let read_msg = helper_clone.next_client_message().await;
+    if let ButtplugClientMessageVariant::V3(ButtplugClientMessageV3::SensorReadCmd(cmd)) = &read_msg
+    {
+      assert_eq!(*cmd.sensor_index(), 1);
+      assert_eq!(*cmd.sensor_type(), SensorType::Pressure);
+    } else {
+      panic!("Expected SensorReadCmd");
+    }
+    let mut reading = message::SensorReadingV3::new(1, 1, SensorType::Pressure, vec![512]);
+    reading.set_id(read_msg.id());
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(reading.into()))
+      .await;
+    // A reading with no data is valid to send, but there's no temperature in it.
+    let read_msg = helper_clone.next_client_message().await;
+    let mut reading = message::SensorReadingV3::new(1, 2, SensorType::Temperature, vec![]);
+    reading.set_id(read_msg.id());
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(reading.into()))
+      .await;
+  });
+  helper
+    .client()
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  let device = if let ButtplugClientEvent::DeviceAdded(device) = event_stream
+    .next()
+    .await
+    .expect("Test, assuming infallible.")
+  {
+    device
+  } else {
+    panic!("Expected DeviceAdded event");
+  };
+  // Index and type have to match what the device reported.
+  assert!(device.read_sensor(0, SensorType::Pressure).await.is_err());
+  assert!(device.read_sensor(2, SensorType::Pressure).await.is_err());
+  assert_eq!(
+    device
+      .read_sensor(1, SensorType::Pressure)
+      .await
+      .expect("Test, assuming infallible."),
+    vec![512]
+  );
+  assert!(matches!(
+    device.temperature().await,
+    Err(ButtplugClientError::ButtplugError(
+      ButtplugError::ButtplugMessageError(ButtplugMessageError::InvalidMessageContents(_))
+    ))
+  ));
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_device_accelerometer_stream() {
+  use buttplug::core::message::{
+    ButtplugClientMessageV3,
+    ButtplugClientMessageVariant,
+    ButtplugMessage,
+    ButtplugServerMessageVariant,
+    ClientDeviceMessageAttributesV3Builder,
+    SensorDeviceMessageAttributesV3,
+    SensorType,
+  };
+
+  let helper = Arc::new(util::channel_transport::ChannelClientTestHelper::new());
+  helper.simulate_successful_connect().await;
+  let helper_clone = helper.clone();
+  let mut event_stream = helper.client().event_stream();
+  async_manager::spawn(async move {
+    assert!(matches!(
+      helper_clone.next_client_message().await,
+      ButtplugClientMessageVariant::V3(ButtplugClientMessageV3::StartScanning(..))
+    ));
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(
+        message::OkV0::new(3).into(),
+      ))
+      .await;
+    let mut builder = ClientDeviceMessageAttributesV3Builder::default();
+    builder.sensor_subscribe_cmd(&[
+      SensorDeviceMessageAttributesV3::new("Pressure", SensorType::Pressure, &[0..=100]),
+      SensorDeviceMessageAttributesV3::new(
+        "Accelerometer",
+        SensorType::Accelerometer,
+        &[-512..=512, -512..=512, -512..=512],
+      ),
+    ]);
+    let attrs = builder.finish().expect("Test, assuming infallible.");
+    let device_added = message::DeviceAddedV3::new(1, "Test Device", &None, &None, &attrs);
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(device_added.into()))
+      .await;
+    let subscribe_msg = helper_clone.next_client_message().await;
+    if let ButtplugClientMessageVariant::V3(ButtplugClientMessageV3::SensorSubscribeCmd(cmd)) =
+      &subscribe_msg
+    {
+      assert_eq!(*cmd.sensor_index(), 1);
+      assert_eq!(*cmd.sensor_type(), SensorType::Accelerometer);
+    } else {
+      panic!("Expected SensorSubscribeCmd");
+    }
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(
+        message::OkV0::new(subscribe_msg.id()).into(),
+      ))
+      .await;
+    // Malformed readings are reported as errors.
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(
+        message::SensorReadingV3::new(1, 1, SensorType::Accelerometer, vec![10]).into(),
+      ))
+      .await;
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(
+        message::SensorReadingV3::new(1, 1, SensorType::Accelerometer, vec![-3, 0, 256]).into(),
+      ))
+      .await;
+    let unsubscribe_msg = helper_clone.next_client_message().await;
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(
+        message::OkV0::new(unsubscribe_msg.id()).into(),
+      ))
+      .await;
+  });
+  helper
+    .client()
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  let device = if let ButtplugClientEvent::DeviceAdded(device) = event_stream
+    .next()
+    .await
+    .expect("Test, assuming infallible.")
+  {
+    device
+  } else {
+    panic!("Expected DeviceAdded event");
+  };
+  assert!(device.gyroscope_stream().await.is_err());
+  let mut accel_stream = device
+    .accelerometer_stream()
+    .await
+    .expect("Test, assuming infallible.");
+  assert!(matches!(
+    accel_stream.next().await,
+    Some(Err(ButtplugError::ButtplugMessageError(
+      ButtplugMessageError::InvalidMessageContents(_)
+    )))
+  ));
+  assert_eq!(
+    accel_stream
+      .next()
+      .await
+      .expect("Test, assuming infallible.")
+      .expect("Test, assuming infallible."),
+    [-3, 0, 256]
+  );
+  drop(accel_stream);
+  sleep(Duration::from_millis(100)).await;
+}
+
+// TODO Test invalid messages to device
+// TODO Test invalid parameters in message
+// TODO Test device invalidation across client connections (i.e. a device shouldn't be allowed to reconnect even if index is the same)
+// TODO Test DeviceList being sent followed by repeat DeviceAdded
+// TODO Test DeviceList being sent multiple times
+// TODO Test sending device return for device that doesn't exist (in client)
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_device_supports() {
+  for allow_raw in [false, true] {
+    let (client, _device) = test_client_with_device_allow_raw(allow_raw).await;
+    let mut event_stream = client.event_stream();
+    client
+      .start_scanning()
+      .await
+      .expect("Test, assuming infallible.");
+    let mut client_device = None;
+    while let Some(msg) = event_stream.next().await {
+      if let ButtplugClientEvent::DeviceAdded(da) = msg {
+        client_device = Some(da);
+        break;
+      }
+    }
+    let test_device = client_device.expect("Test, assuming infallible.");
+    // Massage Demo is an Aneros Vivi, which only has two vibrators.
+    assert!(test_device.supports_scalar());
+    assert!(!test_device.supports_rotation());
+    assert!(!test_device.supports_linear());
+    assert!(!test_device.supports_sensor());
+    assert_eq!(test_device.supports_raw(), allow_raw);
+    assert_eq!(test_device.raw_endpoints().is_empty(), !allow_raw);
+    if allow_raw {
+      let tx = test_device
+        .raw_endpoint(Endpoint::Tx)
+        .expect("Test, assuming infallible.");
+      assert!(tx.readable() && tx.writable() && tx.subscribable());
+      assert!(test_device.raw_endpoint(Endpoint::RxBLEModel).is_none());
+    } else {
+      assert!(test_device.raw_endpoint(Endpoint::Tx).is_none());
+    }
+    assert!(test_device.supports_actuator_type(ActuatorType::Vibrate));
+    assert!(!test_device.supports_actuator_type(ActuatorType::Rotate));
+    assert!(!test_device.supports_actuator_type(ActuatorType::Position));
+  }
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_device_linear_ramp() {
+  let (server, mut device) = test_server_v4_with_device("Titan1.1", false);
+  let connector = ButtplugInProcessClientConnectorBuilder::default()
+    .server(server)
+    .finish();
+  let client = ButtplugClient::new("Test Client");
+  client
+    .connect(connector)
+    .await
+    .expect("Test, assuming infallible.");
+  let mut event_stream = client.event_stream();
+  client
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  let mut client_device = None;
+  while let Some(msg) = event_stream.next().await {
+    if let ButtplugClientEvent::DeviceAdded(da) = msg {
+      client_device = Some(da);
+      break;
+    }
+  }
+  let test_device = client_device.expect("Test, assuming infallible.");
+  assert!(test_device.supports_linear());
+  while device.receiver.try_recv().is_ok() {}
+
+  test_device
+    .linear_ramp(
+      0.0,
+      1.0,
+      Duration::from_millis(100),
+      4,
+      CancellationToken::new(),
+    )
+    .await
+    .expect("Test, assuming infallible.");
+  let mut writes = 0;
+  while device.receiver.try_recv().is_ok() {
+    writes += 1;
+  }
+  assert_eq!(writes, 4);
+
+  // A ramp that is already cancelled never sends anything.
+  let cancel_token = CancellationToken::new();
+  cancel_token.cancel();
+  test_device
+    .linear_oscillate(0.0, 1.0, Duration::from_millis(100), 4, 2, cancel_token)
+    .await
+    .expect("Test, assuming infallible.");
+  assert!(device.receiver.try_recv().is_err());
+
+  assert!(matches!(
+    test_device
+      .linear_ramp(
+        0.0,
+        1.0,
+        Duration::from_millis(100),
+        0,
+        CancellationToken::new()
+      )
+      .await
+      .unwrap_err(),
+    ButtplugClientError::ButtplugError(ButtplugError::ButtplugMessageError(
+      ButtplugMessageError::InvalidMessageContents(..)
+    ))
+  ));
+  assert!(matches!(
+    test_device
+      .linear_ramp(0.0, 1.0, Duration::ZERO, 4, CancellationToken::new())
+      .await
+      .unwrap_err(),
+    ButtplugClientError::ButtplugError(ButtplugError::ButtplugMessageError(
+      ButtplugMessageError::InvalidMessageContents(..)
+    ))
+  ));
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_device_stop_sends_stop_commands() {
+  let (client, mut device) = test_client_with_device().await;
+
+  let mut event_stream = client.event_stream();
+  client
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  let mut client_device = None;
+  while let Some(msg) = event_stream.next().await {
+    if let ButtplugClientEvent::DeviceAdded(da) = msg {
+      client_device = Some(da);
+      break;
+    }
+  }
+  let test_device = client_device.expect("Test, assuming infallible.");
+  // Clear out anything sent during device initialization.
+  while device.receiver.try_recv().is_ok() {}
+  test_device
+    .vibrate(&ScalarValueCommand::ScalarValueVec(vec![0.5, 1.0]))
+    .await
+    .expect("Test, assuming infallible.");
+  check_test_recv_value(
+    &mut device,
+    HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, vec![0xF1, 64], false)),
+  );
+  check_test_recv_value(
+    &mut device,
+    HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, vec![0xF2, 127], false)),
+  );
+  test_device
+    .stop()
+    .await
+    .expect("Test, assuming infallible.");
+  // Both motors get zeroed by the server's stop commands, and nothing else goes out.
+  check_test_recv_value(
+    &mut device,
+    HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, vec![0xF1, 0], false)),
+  );
+  check_test_recv_value(
+    &mut device,
+    HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, vec![0xF2, 0], false)),
+  );
+  assert!(device.receiver.try_recv().is_err());
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_device_reconnect_keeps_handle() {
+  use buttplug::core::message::{
+    ButtplugClientMessageV3,
+    ButtplugClientMessageVariant,
+    ButtplugServerMessageVariant,
+  };
+
+  let helper = Arc::new(util::channel_transport::ChannelClientTestHelper::new());
+  helper.simulate_successful_connect().await;
+  let helper_clone = helper.clone();
+  let mut event_stream = helper.client().event_stream();
+  let (reconnect_checked_sender, reconnect_checked_receiver) = tokio::sync::oneshot::channel();
+  async_manager::spawn(async move {
+    assert!(matches!(
+      helper_clone.next_client_message().await,
+      ButtplugClientMessageVariant::V3(ButtplugClientMessageV3::StartScanning(..))
+    ));
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(
+        message::OkV0::new(3).into(),
+      ))
+      .await;
+    let device_added = message::DeviceAddedV3::new(
+      1,
+      "Test Device",
+      &None,
+      &None,
+      &ClientDeviceMessageAttributesV3::default(),
+    );
+    let device_removed = message::DeviceRemovedV0::new(1);
+    let other_device_added = message::DeviceAddedV3::new(
+      1,
+      "Other Test Device",
+      &None,
+      &None,
+      &ClientDeviceMessageAttributesV3::default(),
+    );
+    for msg in [
+      device_added.clone().into(),
+      device_removed.clone().into(),
+      device_added.into(),
+    ] {
+      helper_clone
+        .send_client_incoming(ButtplugServerMessageVariant::V3(msg))
+        .await;
+    }
+    reconnect_checked_receiver
+      .await
+      .expect("Test, assuming infallible.");
+    for msg in [device_removed.into(), other_device_added.into()] {
+      helper_clone
+        .send_client_incoming(ButtplugServerMessageVariant::V3(msg))
+        .await;
+    }
+  });
+  helper
+    .client()
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  let mut added = vec![];
+  let mut reconnect_checked_sender = Some(reconnect_checked_sender);
+  while added.len() < 3 {
+    if let ButtplugClientEvent::DeviceAdded(device) = event_stream
+      .next()
+      .await
+      .expect("Test, assuming infallible.")
+    {
+      added.push(device);
+    }
+    if added.len() == 2 {
+      if let Some(sender) = reconnect_checked_sender.take() {
+        // The same device coming back at the same index reuses the handle.
+        assert!(Arc::ptr_eq(&added[0], &added[1]));
+        assert!(added[0].connected());
+        sender.send(()).expect("Test, assuming infallible.");
+      }
+    }
+  }
+  // A different device at a reused index gets a new one.
+  assert!(!added[0].connected());
+  assert!(!Arc::ptr_eq(&added[0], &added[2]));
+  assert_eq!(added[2].name(), "Other Test Device");
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_device_removed_dropped_after_scan() {
+  use buttplug::core::message::{
+    ButtplugClientMessageV3,
+    ButtplugClientMessageVariant,
+    ButtplugServerMessageVariant,
+  };
+
+  let helper = Arc::new(util::channel_transport::ChannelClientTestHelper::new());
+  helper.simulate_successful_connect().await;
+  let helper_clone = helper.clone();
+  let mut event_stream = helper.client().event_stream();
+  async_manager::spawn(async move {
+    assert!(matches!(
+      helper_clone.next_client_message().await,
+      ButtplugClientMessageVariant::V3(ButtplugClientMessageV3::StartScanning(..))
+    ));
+    helper_clone
+      .send_client_incoming(ButtplugServerMessageVariant::V3(
+        message::OkV0::new(3).into(),
+      ))
+      .await;
+    let device_added = message::DeviceAddedV3::new(
+      1,
+      "Test Device",
+      &None,
+      &None,
+      &ClientDeviceMessageAttributesV3::default(),
+    );
+    for msg in [
+      device_added.clone().into(),
+      message::DeviceRemovedV0::new(1).into(),
+      message::ScanningFinishedV0::default().into(),
+      device_added.into(),
+    ] {
+      helper_clone
+        .send_client_incoming(ButtplugServerMessageVariant::V3(msg))
+        .await;
+    }
+  });
+  helper
+    .client()
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  let mut added = vec![];
+  while added.len() < 2 {
+    if let ButtplugClientEvent::DeviceAdded(device) = event_stream
+      .next()
+      .await
+      .expect("Test, assuming infallible.")
+    {
+      added.push(device);
+    }
+  }
+  // The scan finished before the device came back, so the old handle was let go.
+  assert!(!added[0].connected());
+  assert!(added[1].connected());
+  assert!(!Arc::ptr_eq(&added[0], &added[1]));
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_device_display() {
+  let (client, _device) = test_client_with_device().await;
+
+  let mut event_stream = client.event_stream();
+  client
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  let mut client_device = None;
+  while let Some(msg) = event_stream.next().await {
+    if let ButtplugClientEvent::DeviceAdded(da) = msg {
+      client_device = Some(da);
+      break;
+    }
+  }
+  let test_device = client_device.expect("Test, assuming infallible.");
+  assert!(test_device.display_name().is_none());
+  assert_eq!(
+    test_device.to_string(),
+    format!("[{}] {}", test_device.index(), test_device.name())
+  );
+  assert_eq!(test_device.to_short_string(), *test_device.name());
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_device_vibrate_for() {
+  let (client, mut device) = test_client_with_device().await;
+
+  let mut event_stream = client.event_stream();
+  client
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  let mut client_device = None;
+  while let Some(msg) = event_stream.next().await {
+    if let ButtplugClientEvent::DeviceAdded(da) = msg {
+      client_device = Some(da);
+      break;
+    }
+  }
+  let test_device = client_device.expect("Test, assuming infallible.");
+  // Clear out anything sent during device initialization.
+  while device.receiver.try_recv().is_ok() {}
+  test_device
+    .vibrate_for(0.5, Duration::from_millis(50))
+    .await
+    .expect("Test, assuming infallible.");
+  for command in [vec![0xF1, 64], vec![0xF2, 64], vec![0xF1, 0], vec![0xF2, 0]] {
+    check_test_recv_value(
+      &mut device,
+      HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, command, false)),
+    );
+  }
+  assert!(device.receiver.try_recv().is_err());
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_device_vibrate_for_stops_when_dropped() {
+  let (client, mut device) = test_client_with_device().await;
+
+  let mut event_stream = client.event_stream();
+  client
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  let mut client_device = None;
+  while let Some(msg) = event_stream.next().await {
+    if let ButtplugClientEvent::DeviceAdded(da) = msg {
+      client_device = Some(da);
+      break;
+    }
+  }
+  let test_device = client_device.expect("Test, assuming infallible.");
+  // Clear out anything sent during device initialization.
+  while device.receiver.try_recv().is_ok() {}
+  // Give up on the command long before its duration is up.
+  assert!(tokio::time::timeout(
+    Duration::from_millis(200),
+    test_device.vibrate_for(0.5, Duration::from_secs(60))
+  )
+  .await
+  .is_err());
+  sleep(Duration::from_millis(200)).await;
+  for command in [vec![0xF1, 64], vec![0xF2, 64], vec![0xF1, 0], vec![0xF2, 0]] {
+    check_test_recv_value(
+      &mut device,
+      HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, command, false)),
+    );
+  }
+  assert!(device.receiver.try_recv().is_err());
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_client_device_repeated_raw_subscribe() {
+  let (client, mut device) = test_client_with_device_allow_raw(true).await;
+
+  let mut event_stream = client.event_stream();
+  client
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  let mut client_device = None;
+  while let Some(msg) = event_stream.next().await {
+    if let ButtplugClientEvent::DeviceAdded(da) = msg {
+      client_device = Some(da);
+      break;
+    }
+  }
+  let test_device = client_device.expect("Test, assuming infallible.");
+  // Clear out anything sent during device initialization.
+  while device.receiver.try_recv().is_ok() {}
+  // Only the first subscribe and unsubscribe should make it to the hardware.
+  for _ in 0..2 {
+    test_device
+      .raw_subscribe(Endpoint::Tx)
+      .await
+      .expect("Test, assuming infallible.");
+  }
+  check_test_recv_value(
+    &mut device,
+    HardwareCommand::Subscribe(HardwareSubscribeCmd::new(Endpoint::Tx)),
+  );
+  assert!(device.receiver.try_recv().is_err());
+  for _ in 0..2 {
+    test_device
+      .raw_unsubscribe(Endpoint::Tx)
+      .await
+      .expect("Test, assuming infallible.");
+  }
+  check_test_recv_value(
+    &mut device,
+    HardwareCommand::Unsubscribe(HardwareUnsubscribeCmd::new(Endpoint::Tx)),
+  );
+  assert!(device.receiver.try_recv().is_err());
+}
@@ -8,7 +8,7 @@
 mod util;
 extern crate buttplug;
 
-use buttplug::util::device_configuration::load_protocol_configs;
+use buttplug::util::device_configuration::{load_protocol_configs, save_user_config};
 use tokio_test::assert_ok;
 
 const BASE_CONFIG_JSON: &str = r#"
@@ -195,6 +195,36 @@ async fn test_valid_user_config() {
   .is_err());
 }
 
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_user_config_address_pattern_lists() {
+  let user_config_json = r#"
+  {
+    "version": {
+      "major": 3,
+      "minor": 0
+    },
+    "user-configs": {
+      "allow-list": [
+        { "address-pattern": "AA:BB:*" }
+      ],
+      "deny-list": [
+        { "address-pattern": "AA:BB:CC:*" }
+      ]
+    }
+  }"#;
+  let dcm = load_protocol_configs(&None, &Some(user_config_json.to_owned()), false)
+    .expect("Test, assuming infallible")
+    .finish()
+    .expect("Test, assuming infallible");
+  assert!(dcm.address_allowed("aa:bb:00:00:00:00"));
+  assert!(!dcm.address_allowed("AA:BB:CC:00:00:00"));
+  assert!(!dcm.address_allowed("11:22:33:44:55:66"));
+  let saved = save_user_config(&dcm).expect("Test, assuming infallible");
+  assert!(saved.contains(r#""allow-list":[{"address-pattern":"AA:BB:*"}]"#));
+  assert!(saved.contains(r#""deny-list":[{"address-pattern":"AA:BB:CC:*"}]"#));
+}
+
 #[cfg(feature = "server")]
 #[tokio::test]
 async fn test_invalid_step_range_device_config_wrong_range_length() {
@@ -307,6 +337,257 @@ async fn test_server_builder_user_device_config_invalid_json() {
   assert!(load_protocol_configs(&None, &Some("{\"Not Valid JSON\"}".to_owned()), false).is_err())
 }
 
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_device_index_persists_through_user_config() {
+  use buttplug::{
+    server::device::configuration::UserDeviceIdentifier,
+    util::device_configuration::save_user_config,
+  };
+
+  let first = UserDeviceIdentifier::new("FirstAddress", "aneros", &None);
+  let second = UserDeviceIdentifier::new("SecondAddress", "aneros", &None);
+  let dcm = load_protocol_configs(&None, &None, false)
+    .expect("Test, assuming infallible.")
+    .finish()
+    .expect("Test, assuming infallible.");
+  let index = |dcm: &buttplug::server::device::configuration::DeviceConfigurationManager,
+               identifier: &UserDeviceIdentifier| {
+    dcm
+      .device_definition(identifier, &[])
+      .expect("Test, assuming infallible.")
+      .user_config()
+      .index()
+  };
+  assert_eq!(index(&dcm, &first), 0);
+  assert_eq!(index(&dcm, &second), 1);
+  // Rediscovering a device, i.e. on a later scan, should hand back the same index.
+  assert_eq!(index(&dcm, &first), 0);
+
+  // Saving and reloading the user config should keep indexes across server restarts.
+  let user_config = save_user_config(&dcm).expect("Test, assuming infallible.");
+  let reloaded_dcm = load_protocol_configs(&None, &Some(user_config), false)
+    .expect("Test, assuming infallible.")
+    .finish()
+    .expect("Test, assuming infallible.");
+  let third = UserDeviceIdentifier::new("ThirdAddress", "aneros", &None);
+  assert_eq!(index(&reloaded_dcm, &second), 1);
+  assert_eq!(index(&reloaded_dcm, &third), 2);
+  assert_eq!(index(&reloaded_dcm, &first), 0);
+}
+
+// User config for a single feature Aneros device, with the given fields added to its
+// "user-config" block.
+#[cfg(feature = "server")]
+fn aneros_user_config(address: &str, user_config_fields: &str) -> String {
+  r#"
+  {
+    "version": {
+      "major": 3,
+      "minor": 0
+    },
+    "user-configs": {
+      "devices": [
+        {
+          "identifier": {
+            "address": "ADDRESS",
+            "protocol": "aneros",
+            "identifier": "Massage Demo"
+          },
+          "config": {
+            "name": "Aneros Vivi",
+            "features": [
+              {
+                "feature-type": "Vibrate",
+                "description": "Perineum Vibrator",
+                "actuator": {
+                  "step-range": [
+                    0,
+                    127
+                  ],
+                  "step-limit": [
+                    0,
+                    127
+                  ],
+                  "messages": [
+                    "ScalarCmd"
+                  ]
+                }
+              }
+            ],
+            "user-config": {
+              "allow": false,
+              "deny": false,
+              "index": 0,
+              USER_CONFIG_FIELDS
+            }
+          }
+        }
+      ]
+    }
+  }"#
+    .replace("ADDRESS", address)
+    .replace("USER_CONFIG_FIELDS", user_config_fields)
+}
+
+// Connects a client to a server using the given device config, and returns the client device for
+// a test device at the given address. The client is returned too, as dropping it disconnects.
+#[cfg(feature = "server")]
+async fn connect_test_device(
+  dcm: buttplug::server::device::configuration::DeviceConfigurationManager,
+  address: &str,
+) -> (
+  buttplug::client::ButtplugClient,
+  std::sync::Arc<buttplug::client::ButtplugClientDevice>,
+  util::test_device_manager::TestDeviceChannelHost,
+) {
+  use buttplug::{
+    client::{ButtplugClient, ButtplugClientEvent},
+    core::connector::ButtplugInProcessClientConnectorBuilder,
+    server::{device::ServerDeviceManagerBuilder, ButtplugServerBuilder},
+  };
+  use futures::StreamExt;
+  use util::test_device_manager::{TestDeviceCommunicationManagerBuilder, TestDeviceIdentifier};
+
+  let mut builder = TestDeviceCommunicationManagerBuilder::default();
+  let device = builder.add_test_device(&TestDeviceIdentifier::new(
+    "Massage Demo",
+    Some(address.to_owned()),
+  ));
+  let mut dm_builder = ServerDeviceManagerBuilder::new(dcm);
+  dm_builder.comm_manager(builder);
+  let server = ButtplugServerBuilder::new(dm_builder.finish().expect("Test, assuming infallible."))
+    .finish()
+    .expect("Test, assuming infallible.");
+  let connector = ButtplugInProcessClientConnectorBuilder::default()
+    .server(server)
+    .finish();
+  let client = ButtplugClient::new("Test Client");
+  let mut event_stream = client.event_stream();
+  client
+    .connect(connector)
+    .await
+    .expect("Test, assuming infallible.");
+  client
+    .start_scanning()
+    .await
+    .expect("Test, assuming infallible.");
+  while let Some(msg) = event_stream.next().await {
+    if let ButtplugClientEvent::DeviceAdded(da) = msg {
+      return (client, da, device);
+    }
+  }
+  panic!("Test device was never added.");
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_user_config_deny_raw_messages() {
+  use buttplug::{
+    client::{ButtplugClientError, ScalarValueCommand},
+    core::{
+      errors::{ButtplugDeviceError, ButtplugError},
+      message::Endpoint,
+    },
+  };
+
+  let user_config_json = aneros_user_config("DenyRawTest", r#""deny-raw-messages": true"#);
+  let dcm = load_protocol_configs(&None, &Some(user_config_json), false)
+    .expect("Test, assuming infallible.")
+    .allow_raw_messages(true)
+    .finish()
+    .expect("Test, assuming infallible.");
+  let (_client, client_device, _device) = connect_test_device(dcm, "DenyRawTest").await;
+
+  let is_denied = |result: Result<_, ButtplugClientError>| {
+    matches!(
+      result,
+      Err(ButtplugClientError::ButtplugError(
+        ButtplugError::ButtplugDeviceError(ButtplugDeviceError::RawMessagesDenied)
+      ))
+    )
+  };
+  assert!(is_denied(
+    client_device.raw_write(Endpoint::Tx, &[0x01], false).await
+  ));
+  assert!(is_denied(
+    client_device.raw_read(Endpoint::Tx, 1, 0).await.map(|_| ())
+  ));
+  assert!(is_denied(client_device.raw_subscribe(Endpoint::Tx).await));
+  assert!(is_denied(client_device.raw_unsubscribe(Endpoint::Tx).await));
+  // Non-raw commands still go through.
+  assert!(client_device
+    .vibrate(&ScalarValueCommand::ScalarValue(0.5))
+    .await
+    .is_ok());
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_user_config_zero_threshold() {
+  use buttplug::{
+    client::ScalarValueCommand,
+    core::message::Endpoint,
+    server::device::hardware::{HardwareCommand, HardwareWriteCmd},
+  };
+  use util::test_device_manager::check_test_recv_value;
+
+  let user_config_json = aneros_user_config("ZeroThresholdTest", r#""zero-threshold": 0.0"#);
+  let dcm = load_protocol_configs(&None, &Some(user_config_json), false)
+    .expect("Test, assuming infallible.")
+    .finish()
+    .expect("Test, assuming infallible.");
+  let (_client, client_device, mut device) = connect_test_device(dcm, "ZeroThresholdTest").await;
+
+  // 0.0000005 * 127 is below the default zero threshold, so this would be sent as 0 without the user
+  // config. With a threshold of 0.0 it maps to the lowest step.
+  client_device
+    .vibrate(&ScalarValueCommand::ScalarValue(0.0000005))
+    .await
+    .expect("Test, assuming infallible.");
+  check_test_recv_value(
+    &mut device,
+    HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, vec![0xF1, 1], false)),
+  );
+}
+
+#[cfg(feature = "server")]
+#[tokio::test]
+async fn test_user_config_message_timing_gap() {
+  use buttplug::{
+    client::{ButtplugClientError, ScalarValueCommand},
+    core::errors::{ButtplugDeviceError, ButtplugError},
+  };
+
+  // Long enough that the second command can't make it outside the gap.
+  let user_config_json = aneros_user_config("MessageGapTest", r#""message-timing-gap": 60000"#);
+  let dcm = load_protocol_configs(&None, &Some(user_config_json), false)
+    .expect("Test, assuming infallible.")
+    .finish()
+    .expect("Test, assuming infallible.");
+  let (_client, client_device, _device) = connect_test_device(dcm, "MessageGapTest").await;
+
+  // The gap is reported to clients along with the device.
+  assert_eq!(client_device.message_timing_gap(), Some(60000));
+  client_device
+    .vibrate(&ScalarValueCommand::ScalarValue(0.5))
+    .await
+    .expect("Test, assuming infallible.");
+  assert!(matches!(
+    client_device
+      .vibrate(&ScalarValueCommand::ScalarValue(0.75))
+      .await,
+    Err(ButtplugClientError::ButtplugError(
+      ButtplugError::ButtplugDeviceError(ButtplugDeviceError::CommandThrottled(_))
+    ))
+  ));
+  // Stops are never held back.
+  client_device
+    .stop()
+    .await
+    .expect("Test, assuming infallible.");
+}
+
 /*
     #[tokio::test]
     fn test_user_config_loading() {
@@ -19,14 +19,18 @@ async fn load_test_case(test_file: &str) -> DeviceTestCase {
 }
 
 #[test_case("test_aneros_protocol.yaml" ; "Aneros Protocol")]
+#[test_case("test_aneros_rssi.yaml" ; "Aneros Protocol - RSSI")]
 #[test_case("test_ankni_protocol.yaml" ; "Ankni Protocol")]
 #[test_case("test_ankni_protocol_no_handshake.yaml" ; "Ankni Protocol - No Handshake")]
 #[test_case("test_cachito_protocol.yaml" ; "Cachito Protocol")]
+#[test_case("test_device_disconnect.yaml" ; "Device Disconnect")]
 #[test_case("test_fredorch_protocol.yaml" ; "Fredorch Protocol")]
 #[test_case("test_hismith_auxfun_box.yaml" ; "Hismith Mini Protocol - Auxfun Box")]
 #[test_case("test_hismith_sinloli.yaml" ; "Hismith Mini Protocol - Sinloli")]
 #[test_case("test_hismith_thrusting_cup.yaml" ; "Hismith Protocol - Thrusting Cup")]
 #[test_case("test_hismith_wildolo.yaml" ; "Hismith Protocol - Wildolo")]
+#[test_case("test_libo_shark.yaml" ; "Libo Shark Protocol")]
+#[test_case("test_libo_shark_single_motor.yaml" ; "Libo Shark Protocol - Single Motor")]
 #[test_case("test_lovense_single_vibrator.yaml" ; "Lovense Protocol - Single Vibrator Device")]
 #[test_case("test_lovense_max.yaml" ; "Lovense Protocol - Lovense Max (Vibrate/Constrict)")]
 #[test_case("test_lovense_nora.yaml" ; "Lovense Protocol - Lovense Nora (Vibrate/Rotate)")]
@@ -45,6 +49,7 @@ async fn load_test_case(test_file: &str) -> DeviceTestCase {
 #[test_case("test_mizzzee_protocol.yaml" ; "Mizz Zee Protocol")]
 #[test_case("test_mizzzee_v2_protocol.yaml" ; "Mizz Zee v2 Protocol")]
 #[test_case("test_mizzzee_v3_protocol.yaml" ; "Mizz Zee v3 Protocol")]
+#[test_case("test_multiple_devices.yaml" ; "Multiple Devices")]
 #[test_case("test_vorze_ufo.yaml" ; "Vorze Protocol - UFO")]
 #[test_case("test_vorze_ufo_tw.yaml" ; "Vorze Protocol - UFO TW")]
 #[test_case("test_vorze_cyclone.yaml" ; "Vorze Protocol - Cyclone")]
@@ -115,14 +120,18 @@ async fn test_device_protocols_embedded_v3(test_file: &str) {
 }
 
 #[test_case("test_aneros_protocol.yaml" ; "Aneros Protocol")]
+#[test_case("test_aneros_rssi.yaml" ; "Aneros Protocol - RSSI")]
 #[test_case("test_ankni_protocol.yaml" ; "Ankni Protocol")]
 #[test_case("test_ankni_protocol_no_handshake.yaml" ; "Ankni Protocol - No Handshake")]
 #[test_case("test_cachito_protocol.yaml" ; "Cachito Protocol")]
+#[test_case("test_device_disconnect.yaml" ; "Device Disconnect")]
 #[test_case("test_fredorch_protocol.yaml" ; "Fredorch Protocol")]
 #[test_case("test_hismith_auxfun_box.yaml" ; "Hismith Mini Protocol - Auxfun Box")]
 #[test_case("test_hismith_sinloli.yaml" ; "Hismith Mini Protocol - Sinloli")]
 #[test_case("test_hismith_thrusting_cup.yaml" ; "Hismith Protocol - Thrusting Cup")]
 #[test_case("test_hismith_wildolo.yaml" ; "Hismith Protocol - Wildolo")]
+#[test_case("test_libo_shark.yaml" ; "Libo Shark Protocol")]
+#[test_case("test_libo_shark_single_motor.yaml" ; "Libo Shark Protocol - Single Motor")]
 #[test_case("test_lovense_single_vibrator.yaml" ; "Lovense Protocol - Single Vibrator Device")]
 #[test_case("test_lovense_max.yaml" ; "Lovense Protocol - Lovense Max (Vibrate/Constrict)")]
 #[test_case("test_lovense_nora.yaml" ; "Lovense Protocol - Lovense Nora (Vibrate/Rotate)")]
@@ -142,6 +151,7 @@ async fn test_device_protocols_embedded_v3(test_file: &str) {
 #[test_case("test_mizzzee_protocol.yaml" ; "Mizz Zee Protocol")]
 #[test_case("test_mizzzee_v2_protocol.yaml" ; "Mizz Zee v2 Protocol")]
 #[test_case("test_mizzzee_v3_protocol.yaml" ; "Mizz Zee v3 Protocol")]
+#[test_case("test_multiple_devices.yaml" ; "Multiple Devices")]
 #[test_case("test_vorze_ufo.yaml" ; "Vorze Protocol - UFO")]
 #[test_case("test_vorze_ufo_tw.yaml" ; "Vorze Protocol - UFO TW")]
 #[test_case("test_vorze_cyclone.yaml" ; "Vorze Protocol - Cyclone")]
@@ -209,11 +219,15 @@ async fn test_device_protocols_json_v3(test_file: &str) {
 }
 
 #[test_case("test_aneros_protocol.yaml" ; "Aneros Protocol")]
+#[test_case("test_aneros_rssi.yaml" ; "Aneros Protocol - RSSI")]
 #[test_case("test_ankni_protocol.yaml" ; "Ankni Protocol")]
 #[test_case("test_ankni_protocol_no_handshake.yaml" ; "Ankni Protocol - No Handshake")]
 #[test_case("test_cachito_protocol.yaml" ; "Cachito Protocol")]
+#[test_case("test_device_disconnect.yaml" ; "Device Disconnect")]
 #[test_case("test_fredorch_protocol.yaml" ; "Fredorch Protocol")]
 #[test_case("test_hismith_wildolo.yaml" ; "Hismith Protocol - Wildolo")]
+#[test_case("test_libo_shark.yaml" ; "Libo Shark Protocol")]
+#[test_case("test_libo_shark_single_motor.yaml" ; "Libo Shark Protocol - Single Motor")]
 #[test_case("test_lovense_single_vibrator.yaml" ; "Lovense Protocol - Single Vibrator Device")]
 #[test_case("test_lovense_nora.yaml" ; "Lovense Protocol - Lovense Nora (Vibrate/Rotate)")]
 #[test_case("test_lovense_battery.yaml" ; "Lovense Protocol - Lovense Battery (Default Devices)")]
@@ -228,6 +242,7 @@ async fn test_device_protocols_json_v3(test_file: &str) {
 #[test_case("test_mizzzee_protocol.yaml" ; "Mizz Zee Protocol")]
 #[test_case("test_mizzzee_v2_protocol.yaml" ; "Mizz Zee v2 Protocol")]
 #[test_case("test_mizzzee_v3_protocol.yaml" ; "Mizz Zee v3 Protocol")]
+#[test_case("test_multiple_devices.yaml" ; "Multiple Devices")]
 #[test_case("test_vorze_ufo.yaml" ; "Vorze Protocol - UFO")]
 #[test_case("test_vorze_ufo_tw.yaml" ; "Vorze Protocol - UFO TW")]
 #[test_case("test_vorze_cyclone.yaml" ; "Vorze Protocol - Cyclone")]
@@ -279,11 +294,15 @@ async fn test_device_protocols_embedded_v2(test_file: &str) {
 }
 
 #[test_case("test_aneros_protocol.yaml" ; "Aneros Protocol")]
+#[test_case("test_aneros_rssi.yaml" ; "Aneros Protocol - RSSI")]
 #[test_case("test_ankni_protocol.yaml" ; "Ankni Protocol")]
 #[test_case("test_ankni_protocol_no_handshake.yaml" ; "Ankni Protocol - No Handshake")]
 #[test_case("test_cachito_protocol.yaml" ; "Cachito Protocol")]
+#[test_case("test_device_disconnect.yaml" ; "Device Disconnect")]
 #[test_case("test_fredorch_protocol.yaml" ; "Fredorch Protocol")]
 #[test_case("test_hismith_wildolo.yaml" ; "Hismith Protocol - Wildolo")]
+#[test_case("test_libo_shark.yaml" ; "Libo Shark Protocol")]
+#[test_case("test_libo_shark_single_motor.yaml" ; "Libo Shark Protocol - Single Motor")]
 #[test_case("test_lovense_single_vibrator.yaml" ; "Lovense Protocol - Single Vibrator Device")]
 #[test_case("test_lovense_nora.yaml" ; "Lovense Protocol - Lovense Nora (Vibrate/Rotate)")]
 #[test_case("test_lovense_battery.yaml" ; "Lovense Protocol - Lovense Battery (Default Devices)")]
@@ -297,6 +316,7 @@ async fn test_device_protocols_embedded_v2(test_file: &str) {
 #[test_case("test_mizzzee_protocol.yaml" ; "Mizz Zee Protocol")]
 #[test_case("test_mizzzee_v2_protocol.yaml" ; "Mizz Zee v2 Protocol")]
 #[test_case("test_mizzzee_v3_protocol.yaml" ; "Mizz Zee v3 Protocol")]
+#[test_case("test_multiple_devices.yaml" ; "Multiple Devices")]
 #[test_case("test_vorze_ufo.yaml" ; "Vorze Protocol - UFO")]
 #[test_case("test_vorze_ufo_tw.yaml" ; "Vorze Protocol - UFO TW")]
 #[test_case("test_vorze_cyclone.yaml" ; "Vorze Protocol - Cyclone")]
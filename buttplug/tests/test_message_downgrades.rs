@@ -17,6 +17,7 @@ use buttplug::{
       ButtplugSerializedMessage,
       ButtplugServerJSONSerializer,
     },
+    ButtplugMessage,
     Endpoint,
   },
   server::{
@@ -69,6 +70,35 @@ async fn test_version2_connection() {
       );
 }
 
+#[tokio::test]
+async fn test_version2_conversion_error_keeps_id() {
+  let server =
+    ButtplugServerDowngradeWrapper::new(ButtplugServerBuilder::default().finish().unwrap());
+  let serializer = ButtplugServerJSONSerializer::default();
+  let rsi =
+    r#"[{"RequestServerInfo":{"Id": 1, "ClientName": "Test Client", "MessageVersion": 2}}]"#;
+  let output = serializer
+    .deserialize(&rsi.to_owned().into())
+    .expect("Test, assuming infallible.");
+  server
+    .parse_message(output[0].clone())
+    .await
+    .expect("Test, assuming infallible.");
+  // Upgrading VibrateCmd needs the device's features, so this fails before reaching the server.
+  let output = serializer
+    .deserialize(
+      &r#"[{"VibrateCmd":{"Id": 2, "DeviceIndex": 5, "Speeds": [{"Index": 0, "Speed": 0.5}]}}]"#
+        .to_owned()
+        .into(),
+    )
+    .expect("Test, assuming infallible.");
+  let error = server
+    .parse_message(output[0].clone())
+    .await
+    .expect_err("Command to a missing device should fail");
+  assert_eq!(error.id(), 2);
+}
+
 #[tokio::test]
 async fn test_version0_device_added_device_list() {
   let (server, _) = test_server_with_device("Massage Demo", false);
@@ -340,3 +370,118 @@ async fn test_version1_oscilatoronly() {
     smsg
   );
 }
+
+#[tokio::test]
+async fn test_version1_fleshlightlaunchfw12cmd() {
+  let (server, mut device) = test_server_with_device("Titan1.1", false);
+  let recv = server.client_version_event_stream();
+  pin_mut!(recv);
+  let serializer = ButtplugServerJSONSerializer::default();
+  let rsi =
+    r#"[{"RequestServerInfo":{"Id": 1, "ClientName": "Test Client", "MessageVersion": 1}}]"#;
+  server
+    .parse_message(
+      serializer
+        .deserialize(&rsi.to_owned().into())
+        .expect("Test, assuming infallible.")[0]
+        .clone(),
+    )
+    .await
+    .expect("Test, assuming infallible.");
+  let reply = server
+    .parse_message(message::ButtplugClientMessageVariant::V1(
+      message::StartScanningV0::default().into(),
+    ))
+    .await;
+  assert!(reply.is_ok(), "Should get back ok: {:?}", reply);
+  // Wait for ScanningFinished and DeviceAdded, in whatever order they show up.
+  recv.next().await.expect("Test, assuming infallible.");
+  recv.next().await.expect("Test, assuming infallible.");
+  let output = server
+    .parse_message(
+      serializer
+        .deserialize(
+          &r#"[{"FleshlightLaunchFW12Cmd": { "Id": 2, "DeviceIndex": 0, "Position": 99, "Speed": 50 }}]"#
+            .to_owned()
+            .into(),
+        )
+        .expect("Test, assuming infallible.")[0]
+        .clone(),
+    )
+    .await
+    .expect("Test, assuming infallible.");
+  assert_eq!(
+    serializer.serialize(&[output]),
+    r#"[{"Ok":{"Id":2}}]"#.to_owned().into()
+  );
+  // The command goes through LinearCmd, so the protocol converts back to speed/position itself.
+  check_test_recv_value(
+    &mut device,
+    HardwareCommand::Write(HardwareWriteCmd::new(
+      Endpoint::Tx,
+      vec![0x03, 0x00, 50, 99],
+      false,
+    )),
+  );
+}
+
+#[tokio::test]
+async fn test_version0_kiiroocmd() {
+  let (server, mut device) = test_server_with_device("Titan1.1", false);
+  let recv = server.client_version_event_stream();
+  pin_mut!(recv);
+  let serializer = ButtplugServerJSONSerializer::default();
+  let rsi = r#"[{"RequestServerInfo":{"Id": 1, "ClientName": "Test Client"}}]"#;
+  server
+    .parse_message(
+      serializer
+        .deserialize(&rsi.to_owned().into())
+        .expect("Test, assuming infallible.")[0]
+        .clone(),
+    )
+    .await
+    .expect("Test, assuming infallible.");
+  let reply = server
+    .parse_message(message::ButtplugClientMessageVariant::V0(
+      message::StartScanningV0::default().into(),
+    ))
+    .await;
+  assert!(reply.is_ok(), "Should get back ok: {:?}", reply);
+  recv.next().await.expect("Test, assuming infallible.");
+  recv.next().await.expect("Test, assuming infallible.");
+  let output = server
+    .parse_message(
+      serializer
+        .deserialize(
+          &r#"[{"KiirooCmd": { "Id": 2, "DeviceIndex": 0, "Command": "2" }}]"#
+            .to_owned()
+            .into(),
+        )
+        .expect("Test, assuming infallible.")[0]
+        .clone(),
+    )
+    .await
+    .expect("Test, assuming infallible.");
+  assert_eq!(
+    serializer.serialize(&[output]),
+    r#"[{"Ok":{"Id":2}}]"#.to_owned().into()
+  );
+  check_test_recv_value(
+    &mut device,
+    HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, vec![0x01, 50], false)),
+  );
+  // Anything outside of 0-4 isn't something a Kiiroo device ever understood.
+  let output = server
+    .parse_message(
+      serializer
+        .deserialize(
+          &r#"[{"KiirooCmd": { "Id": 3, "DeviceIndex": 0, "Command": "5" }}]"#
+            .to_owned()
+            .into(),
+        )
+        .expect("Test, assuming infallible.")[0]
+        .clone(),
+    )
+    .await;
+  assert!(output.is_err());
+}
@@ -98,3 +98,117 @@ async fn test_serialized_error_relay() {
 // TODO Test deserialization of concatenated messages
 // TODO Test message with negative message id
 // TODO Test device message with negative device id
+
+fn client_message_v3_samples() -> Vec<ButtplugClientMessageV3> {
+  vec![
+    message::RequestServerInfoV1::new(
+      "test client",
+      message::BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION,
+    )
+    .into(),
+    message::PingV0::default().into(),
+    message::StartScanningV0::default().into(),
+    message::StopScanningV0::default().into(),
+    message::RequestDeviceListV0::default().into(),
+    message::StopAllDevicesV0::default().into(),
+    message::VibrateCmdV1::new(0, vec![message::VibrateSubcommandV1::new(0, 0.5)]).into(),
+    message::LinearCmdV1::new(0, vec![message::VectorSubcommandV1::new(0, 500, 0.5)]).into(),
+    message::RotateCmdV1::new(0, vec![message::RotationSubcommandV1::new(0, 0.5, true)]).into(),
+    message::RawWriteCmdV2::new(0, message::Endpoint::Tx, &[0x0, 0x1], false).into(),
+    message::RawReadCmdV2::new(0, message::Endpoint::Rx, 2, 100).into(),
+    message::StopDeviceCmdV0::new(0).into(),
+    message::RawSubscribeCmdV2::new(0, message::Endpoint::Rx).into(),
+    message::RawUnsubscribeCmdV2::new(0, message::Endpoint::Rx).into(),
+    message::ScalarCmdV3::new(
+      0,
+      vec![message::ScalarSubcommandV3::new(
+        0,
+        0.5,
+        message::ActuatorType::Vibrate,
+      )],
+    )
+    .into(),
+    message::SensorReadCmdV3::new(0, 0, message::SensorType::Battery).into(),
+    message::SensorSubscribeCmdV3::new(0, 0, message::SensorType::Pressure).into(),
+    message::SensorUnsubscribeCmdV3::new(0, 0, message::SensorType::Pressure).into(),
+  ]
+}
+
+fn server_message_v3_samples() -> Vec<ButtplugServerMessageV3> {
+  let mut builder = message::ClientDeviceMessageAttributesV3Builder::default();
+  builder.scalar_cmd(&[message::ClientGenericDeviceMessageAttributesV3::new(
+    "Vibrator",
+    20,
+    message::ActuatorType::Vibrate,
+  )]);
+  let attrs = builder.finish().expect("Test, assuming infallible.");
+  vec![
+    message::OkV0::new(1).into(),
+    message::ErrorV0::new(message::ErrorCode::ErrorDevice, "test error", None).into(),
+    message::ServerInfoV2::new(
+      "test server",
+      message::BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION,
+      0,
+    )
+    .into(),
+    message::DeviceListV3::new(vec![message::DeviceMessageInfoV3::new(
+      0,
+      "Test Device",
+      &None,
+      &None,
+      attrs.clone(),
+    )])
+    .into(),
+    message::DeviceAddedV3::new(0, "Test Device", &None, &Some(50), &attrs).into(),
+    message::DeviceRemovedV0::new(0).into(),
+    message::ScanningFinishedV0::default().into(),
+    message::RawReadingV2::new(0, message::Endpoint::Rx, vec![0x0, 0x1]).into(),
+    message::SensorReadingV3::new(0, 0, message::SensorType::Battery, vec![50]).into(),
+  ]
+}
+
+#[test]
+fn test_client_message_v3_json_round_trip() {
+  for msg in client_message_v3_samples() {
+    let json = msg.to_json().expect("Test, assuming infallible.");
+    assert_eq!(
+      ButtplugClientMessageV3::from_json(&json).expect("Test, assuming infallible."),
+      msg
+    );
+  }
+}
+
+#[test]
+fn test_server_message_v3_json_round_trip() {
+  for msg in server_message_v3_samples() {
+    let json = msg.to_json().expect("Test, assuming infallible.");
+    assert_eq!(
+      ButtplugServerMessageV3::from_json(&json).expect("Test, assuming infallible."),
+      msg
+    );
+  }
+}
+
+#[cfg(feature = "serialize-cbor")]
+#[test]
+fn test_client_message_v3_cbor_round_trip() {
+  for msg in client_message_v3_samples() {
+    let cbor = msg.to_cbor().expect("Test, assuming infallible.");
+    assert_eq!(
+      ButtplugClientMessageV3::from_cbor(&cbor).expect("Test, assuming infallible."),
+      msg
+    );
+  }
+}
+
+#[cfg(feature = "serialize-cbor")]
+#[test]
+fn test_server_message_v3_cbor_round_trip() {
+  for msg in server_message_v3_samples() {
+    let cbor = msg.to_cbor().expect("Test, assuming infallible.");
+    assert_eq!(
+      ButtplugServerMessageV3::from_cbor(&cbor).expect("Test, assuming infallible."),
+      msg
+    );
+  }
+}
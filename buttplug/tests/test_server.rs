@@ -13,9 +13,12 @@ pub use util::{
     check_test_recv_value,
     TestDeviceCommunicationManagerBuilder,
     TestDeviceIdentifier,
+    TestHardwareEvent,
   },
+  test_server_v4_with_device,
   test_server_with_comm_manager,
   test_server_with_device,
+  TestDeviceChannelHost,
 };
 
 use buttplug::{
@@ -23,6 +26,7 @@ use buttplug::{
     errors::{ButtplugDeviceError, ButtplugError, ButtplugHandshakeError},
     message::{
       self,
+      ButtplugDeviceMessageType,
       ButtplugMessageSpecVersion,
       ButtplugServerMessageV2,
       ButtplugServerMessageV3,
@@ -37,12 +41,17 @@ use buttplug::{
       hardware::{HardwareCommand, HardwareWriteCmd},
       ServerDeviceManagerBuilder,
     },
+    ButtplugServer,
     ButtplugServerBuilder,
     ButtplugServerDowngradeWrapper,
+    InMemoryEventLog,
   },
 };
 use futures::{pin_mut, Stream, StreamExt};
-use std::time::Duration;
+use std::{
+  sync::Arc,
+  time::{Duration, Instant},
+};
 use tokio::time::sleep;
 
 async fn setup_test_server(
@@ -128,6 +137,54 @@ async fn test_client_version_older_than_server() {
   }
 }
 
+#[tokio::test]
+async fn test_v2_client_vibrate_cmd() {
+  let (server, mut device) = test_server_with_device("Massage Demo", false);
+  let recv = server.client_version_event_stream();
+  pin_mut!(recv);
+  let v2_msg = message::ButtplugClientMessageVariant::V2;
+  server
+    .parse_message(v2_msg(
+      message::RequestServerInfoV1::new("Test Client", ButtplugMessageSpecVersion::Version2).into(),
+    ))
+    .await
+    .expect("Test, assuming infallible.");
+  server
+    .parse_message(v2_msg(message::StartScanningV0::default().into()))
+    .await
+    .expect("Test, assuming infallible.");
+  let mut device_index = 100;
+  while let Some(msg) = recv.next().await {
+    if let ButtplugServerMessageVariant::V2(ButtplugServerMessageV2::DeviceAdded(da)) = msg {
+      device_index = da.device_index();
+      break;
+    }
+  }
+  // VibrateCmd doesn't exist past spec v3, so the server has to turn it into a ScalarCmd using
+  // the device's vibrator features.
+  server
+    .parse_message(v2_msg(
+      message::VibrateCmdV1::new(
+        device_index,
+        vec![
+          message::VibrateSubcommandV1::new(0, 0.5),
+          message::VibrateSubcommandV1::new(1, 1.0),
+        ],
+      )
+      .into(),
+    ))
+    .await
+    .expect("Test, assuming infallible.");
+  check_test_recv_value(
+    &mut device,
+    HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, vec![0xF1, 64], false)),
+  );
+  check_test_recv_value(
+    &mut device,
+    HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, vec![0xF2, 127], false)),
+  );
+}
+
 #[tokio::test]
 #[ignore = "Needs to be rewritten to send in via the JSON parser, otherwise we're type bound due to the enum and can't fail"]
 async fn test_server_version_older_than_client() {
@@ -257,6 +314,75 @@ async fn test_device_stop_on_ping_timeout() {
    */
 }
 
+#[tokio::test]
+async fn test_device_duplicate_subcommand_index() {
+  let mut builder = TestDeviceCommunicationManagerBuilder::default();
+  let _device = builder.add_test_device(&TestDeviceIdentifier::new("Massage Demo", None));
+
+  let dm_builder = ServerDeviceManagerBuilder::new(create_test_dcm(false))
+    .comm_manager(builder)
+    .finish()
+    .unwrap();
+  let server = ButtplugServerBuilder::new(dm_builder).finish().unwrap();
+
+  let recv = server.event_stream();
+  pin_mut!(recv);
+
+  let msg = message::RequestServerInfoV1::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION);
+  assert!(server
+    .parse_message(message::ButtplugClientMessageV4::from(msg))
+    .await
+    .is_ok());
+  assert!(server
+    .parse_message(message::ButtplugClientMessageV4::from(
+      message::StartScanningV0::default(),
+    ))
+    .await
+    .is_ok());
+  let mut device_index = 100;
+  while let Some(msg) = recv.next().await {
+    if let ButtplugServerMessageV4::DeviceAdded(da) = msg {
+      device_index = da.device_index();
+      break;
+    }
+  }
+  let err = server
+    .parse_message(message::ButtplugClientMessageV4::from(
+      message::ScalarCmdV4::new(
+        device_index,
+        vec![
+          message::ScalarSubcommandV4::new(0, 0.5, message::ActuatorType::Vibrate),
+          message::ScalarSubcommandV4::new(0, 0.25, message::ActuatorType::Vibrate),
+        ],
+      ),
+    ))
+    .await
+    .unwrap_err();
+  assert_eq!(err.error_code(), message::ErrorCode::ErrorMessage);
+}
+
+#[tokio::test]
+async fn test_device_subcommand_out_of_range() {
+  let msg = message::RequestServerInfoV1::new("Test Client", ButtplugMessageSpecVersion::Version3);
+  let (server, _) = setup_test_server(msg.into()).await;
+  let out_of_range_msgs: Vec<message::ButtplugClientMessageV3> = vec![
+    message::VibrateCmdV1::new(0, vec![message::VibrateSubcommandV1::new(0, 2.0)]).into(),
+    message::RotateCmdV1::new(0, vec![message::RotationSubcommandV1::new(0, -0.5, true)]).into(),
+    message::LinearCmdV1::new(0, vec![message::VectorSubcommandV1::new(0, 500, 1.5)]).into(),
+  ];
+  for msg in out_of_range_msgs {
+    let err = server
+      .parse_message(message::ButtplugClientMessageVariant::V3(msg))
+      .await
+      .unwrap_err();
+    if let ButtplugServerMessageVariant::V3(ButtplugServerMessageV3::Error(e)) = err {
+      assert_eq!(e.error_code(), message::ErrorCode::ErrorMessage);
+    } else {
+      panic!("Should've gotten error")
+    }
+  }
+}
+
 #[tokio::test]
 async fn test_repeated_handshake() {
   let msg = message::RequestServerInfoV1::new("Test Client", ButtplugMessageSpecVersion::Version3);
@@ -343,6 +469,86 @@ async fn test_device_index_generation() {
   }
 }
 
+#[tokio::test]
+async fn test_server_device_removed_includes_name() {
+  let (server, device) = test_server_v4_with_device("Massage Demo", false);
+  let recv = server.event_stream();
+  pin_mut!(recv);
+  assert!(server
+    .parse_message(
+      message::RequestServerInfoV1::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+        .into()
+    )
+    .await
+    .is_ok());
+  assert!(server
+    .parse_message(message::StartScanningV0::default().into())
+    .await
+    .is_ok());
+  let mut device_index = None;
+  while let Some(msg) = recv.next().await {
+    match msg {
+      ButtplugServerMessageV4::DeviceAdded(da) => {
+        device_index = Some(da.device_index());
+        assert_eq!(server.device_manager().connected_device_count(), 1);
+        device
+          .sender
+          .send(TestHardwareEvent::Disconnect)
+          .await
+          .expect("Test, assuming infallible.");
+      }
+      ButtplugServerMessageV4::DeviceRemoved(dr) => {
+        assert_eq!(Some(dr.device_index()), device_index);
+        assert_eq!(dr.device_name(), "Aneros Vivi");
+        assert_eq!(server.device_manager().connected_device_count(), 0);
+        return;
+      }
+      _ => {}
+    }
+  }
+  panic!("Server event stream ended before DeviceRemoved was received");
+}
+
+#[tokio::test]
+async fn test_server_max_devices() {
+  let mut builder = TestDeviceCommunicationManagerBuilder::default();
+  let _devices: Vec<_> = (0..17)
+    .map(|_| builder.add_test_device(&TestDeviceIdentifier::new("Massage Demo", None)))
+    .collect();
+
+  let dm = ServerDeviceManagerBuilder::new(create_test_dcm(false))
+    .comm_manager(builder)
+    .finish()
+    .unwrap();
+  let server = ButtplugServerBuilder::new(dm)
+    .max_devices(16)
+    .finish()
+    .unwrap();
+
+  let recv = server.event_stream();
+  pin_mut!(recv);
+  assert!(server
+    .parse_message(
+      message::RequestServerInfoV1::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+        .into()
+    )
+    .await
+    .is_ok());
+  assert!(server
+    .parse_message(message::StartScanningV0::default().into())
+    .await
+    .is_ok());
+  let mut added = 0u32;
+  // Give all 17 devices plenty of time to show up, then make sure the 17th didn't.
+  while let Ok(Some(msg)) = tokio::time::timeout(Duration::from_millis(500), recv.next()).await {
+    if let ButtplugServerMessageV4::DeviceAdded(_) = msg {
+      added += 1;
+    }
+  }
+  assert_eq!(added, 16);
+  assert_eq!(server.device_manager().connected_device_count(), 16);
+}
+
 #[tokio::test]
 async fn test_server_scanning_finished() {
   let mut builder = TestDeviceCommunicationManagerBuilder::default();
@@ -381,6 +587,344 @@ async fn test_server_scanning_finished() {
   assert!(finish_received);
 }
 
+async fn setup_shutdown_test_server(
+  drain_timeout: Duration,
+  write_delay: Duration,
+) -> (ButtplugServer, TestDeviceChannelHost, u32) {
+  let mut builder = TestDeviceCommunicationManagerBuilder::default();
+  let device = builder.add_slow_test_device(
+    &TestDeviceIdentifier::new("Massage Demo", None),
+    write_delay,
+  );
+
+  let dm = ServerDeviceManagerBuilder::new(create_test_dcm(false))
+    .comm_manager(builder)
+    .finish()
+    .unwrap();
+  let server = ButtplugServerBuilder::new(dm)
+    .shutdown_drain_timeout(drain_timeout)
+    .finish()
+    .unwrap();
+
+  let recv = server.event_stream();
+  pin_mut!(recv);
+  assert!(server
+    .parse_message(
+      message::RequestServerInfoV1::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+        .into()
+    )
+    .await
+    .is_ok());
+  assert!(server
+    .parse_message(message::StartScanningV0::default().into())
+    .await
+    .is_ok());
+  let mut device_index = 100;
+  while let Some(msg) = recv.next().await {
+    if let ButtplugServerMessageV4::DeviceAdded(da) = msg {
+      device_index = da.device_index();
+      break;
+    }
+  }
+  (server, device, device_index)
+}
+
+fn vibrate_msg(device_index: u32) -> message::ButtplugClientMessageV4 {
+  message::ScalarCmdV4::new(
+    device_index,
+    vec![message::ScalarSubcommandV4::new(
+      0,
+      0.5,
+      message::ActuatorType::Vibrate,
+    )],
+  )
+  .into()
+}
+
+#[tokio::test]
+async fn test_server_shutdown_drains_in_flight_commands() {
+  let (server, mut device, device_index) =
+    setup_shutdown_test_server(Duration::from_secs(5), Duration::from_millis(200)).await;
+
+  let command = tokio::spawn(server.parse_message(vibrate_msg(device_index)));
+  sleep(Duration::from_millis(20)).await;
+  server.shutdown().await.expect("Test, assuming infallible.");
+  // The command was already in flight, so shutdown should have waited for it to land.
+  assert!(command.await.unwrap().is_ok());
+  check_test_recv_value(
+    &mut device,
+    HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, vec![0xF1, 64], false)),
+  );
+  assert!(!server.connected());
+
+  // Shutting down again is a no-op.
+  server.shutdown().await.expect("Test, assuming infallible.");
+  assert!(server
+    .parse_message(vibrate_msg(device_index))
+    .await
+    .is_err());
+}
+
+#[tokio::test]
+async fn test_server_shutdown_cancels_commands_after_drain_timeout() {
+  let (server, _device, device_index) =
+    setup_shutdown_test_server(Duration::from_millis(100), Duration::from_secs(10)).await;
+
+  let command = tokio::spawn(server.parse_message(vibrate_msg(device_index)));
+  sleep(Duration::from_millis(20)).await;
+  let start = Instant::now();
+  server.shutdown().await.expect("Test, assuming infallible.");
+  assert!(start.elapsed() < Duration::from_secs(5));
+  // The command never finished, so it should have been cancelled.
+  let err = command.await.unwrap().unwrap_err();
+  assert_eq!(err.error_code(), message::ErrorCode::ErrorUnknown);
+}
+
+#[tokio::test]
+async fn test_server_slow_device_delays_each_command() {
+  let (server, mut device, device_index) =
+    setup_shutdown_test_server(Duration::from_secs(5), Duration::from_millis(100)).await;
+
+  let msg = message::ScalarCmdV4::new(
+    device_index,
+    vec![
+      message::ScalarSubcommandV4::new(0, 0.5, message::ActuatorType::Vibrate),
+      message::ScalarSubcommandV4::new(1, 0.5, message::ActuatorType::Vibrate),
+    ],
+  );
+  let start = Instant::now();
+  assert!(server.parse_message(msg.into()).await.is_ok());
+  // One write per motor, each paying the latency.
+  assert!(start.elapsed() >= Duration::from_millis(200));
+  check_test_recv_value(
+    &mut device,
+    HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, vec![0xF1, 64], false)),
+  );
+  check_test_recv_value(
+    &mut device,
+    HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, vec![0xF2, 64], false)),
+  );
+}
+
+#[tokio::test]
+async fn test_server_stop_preempts_in_flight_scalar_cmd() {
+  let (server, mut device, device_index) =
+    setup_shutdown_test_server(Duration::from_secs(5), Duration::from_millis(200)).await;
+
+  let msg = message::ScalarCmdV4::new(
+    device_index,
+    vec![
+      message::ScalarSubcommandV4::new(0, 0.5, message::ActuatorType::Vibrate),
+      message::ScalarSubcommandV4::new(1, 0.5, message::ActuatorType::Vibrate),
+    ],
+  );
+  let command = tokio::spawn(server.parse_message(msg.into()));
+  sleep(Duration::from_millis(50)).await;
+  assert!(server
+    .parse_message(message::StopDeviceCmdV0::new(device_index).into())
+    .await
+    .is_ok());
+  assert!(command.await.unwrap().is_ok());
+  // The first motor write was already on its way out, but the second should have been dropped in
+  // favor of the stop.
+  for expected in [vec![0xF1, 64], vec![0xF1, 0], vec![0xF2, 0]] {
+    check_test_recv_value(
+      &mut device,
+      HardwareCommand::Write(HardwareWriteCmd::new(Endpoint::Tx, expected, false)),
+    );
+  }
+  assert!(device.receiver.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn test_server_reject_commands_while_busy() {
+  let mut builder = TestDeviceCommunicationManagerBuilder::default();
+  let _device = builder.add_slow_test_device(
+    &TestDeviceIdentifier::new("Massage Demo", None),
+    Duration::from_millis(200),
+  );
+  let mut dm_builder = ServerDeviceManagerBuilder::new(create_test_dcm(false));
+  dm_builder
+    .comm_manager(builder)
+    .reject_commands_while_busy(true);
+  let server = ButtplugServerBuilder::new(dm_builder.finish().unwrap())
+    .finish()
+    .unwrap();
+
+  let recv = server.event_stream();
+  pin_mut!(recv);
+  assert!(server
+    .parse_message(
+      message::RequestServerInfoV1::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+        .into()
+    )
+    .await
+    .is_ok());
+  assert!(server
+    .parse_message(message::StartScanningV0::default().into())
+    .await
+    .is_ok());
+  let mut device_index = 100;
+  while let Some(msg) = recv.next().await {
+    if let ButtplugServerMessageV4::DeviceAdded(da) = msg {
+      device_index = da.device_index();
+      break;
+    }
+  }
+
+  let first_cmd = tokio::spawn(server.parse_message(vibrate_msg(device_index)));
+  sleep(Duration::from_millis(20)).await;
+  let err = server
+    .parse_message(vibrate_msg(device_index))
+    .await
+    .unwrap_err();
+  assert_eq!(err.error_code(), message::ErrorCode::ErrorDevice);
+  assert_eq!(
+    err.original_error(),
+    ButtplugError::from(ButtplugDeviceError::DeviceBusy(device_index))
+  );
+  // Stops always get through, busy or not.
+  assert!(server
+    .parse_message(message::StopDeviceCmdV0::new(device_index).into())
+    .await
+    .is_ok());
+  assert!(first_cmd.await.unwrap().is_ok());
+  // Once the first command is done, the device takes commands again.
+  assert!(server
+    .parse_message(vibrate_msg(device_index))
+    .await
+    .is_ok());
+}
+
+#[tokio::test]
+async fn test_server_event_log_records_device_commands() {
+  let mut builder = TestDeviceCommunicationManagerBuilder::default();
+  let _device = builder.add_test_device(&TestDeviceIdentifier::new("Massage Demo", None));
+  let dm = ServerDeviceManagerBuilder::new(create_test_dcm(false))
+    .comm_manager(builder)
+    .finish()
+    .unwrap();
+  let event_log = Arc::new(InMemoryEventLog::new(16));
+  let server = ButtplugServerBuilder::new(dm)
+    .event_log(event_log.clone())
+    .finish()
+    .unwrap();
+
+  let recv = server.event_stream();
+  pin_mut!(recv);
+  assert!(server
+    .parse_message(
+      message::RequestServerInfoV1::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+        .into()
+    )
+    .await
+    .is_ok());
+  assert!(server
+    .parse_message(message::StartScanningV0::default().into())
+    .await
+    .is_ok());
+  let mut device_index = 100;
+  while let Some(msg) = recv.next().await {
+    if let ButtplugServerMessageV4::DeviceAdded(da) = msg {
+      device_index = da.device_index();
+      break;
+    }
+  }
+  // Only device commands are audited.
+  assert!(event_log.drain().is_empty());
+
+  for speed in [0.25, 0.5, 0.5, 0.0] {
+    assert!(server
+      .parse_message(
+        message::ScalarCmdV4::new(
+          device_index,
+          vec![message::ScalarSubcommandV4::new(
+            0,
+            speed,
+            message::ActuatorType::Vibrate
+          )],
+        )
+        .into()
+      )
+      .await
+      .is_ok());
+  }
+  let events = event_log.drain();
+  assert_eq!(events.len(), 4);
+  for event in events {
+    assert_eq!(event.device_index(), device_index);
+    assert_eq!(event.client_name(), &Some("Test Client".to_owned()));
+    assert_eq!(event.message_type(), ButtplugDeviceMessageType::ScalarCmd);
+  }
+}
+
+#[tokio::test]
+async fn test_server_rate_limits_device_commands() {
+  let mut builder = TestDeviceCommunicationManagerBuilder::default();
+  let _device = builder.add_test_device(&TestDeviceIdentifier::new("Massage Demo", None));
+  let dm = ServerDeviceManagerBuilder::new(create_test_dcm(false))
+    .comm_manager(builder)
+    .finish()
+    .unwrap();
+  let server = ButtplugServerBuilder::new(dm)
+    .max_commands_per_second(2.0)
+    .finish()
+    .unwrap();
+
+  let recv = server.event_stream();
+  pin_mut!(recv);
+  assert!(server
+    .parse_message(
+      message::RequestServerInfoV1::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+        .into()
+    )
+    .await
+    .is_ok());
+  assert!(server
+    .parse_message(message::StartScanningV0::default().into())
+    .await
+    .is_ok());
+  let mut device_index = 100;
+  while let Some(msg) = recv.next().await {
+    if let ButtplugServerMessageV4::DeviceAdded(da) = msg {
+      device_index = da.device_index();
+      break;
+    }
+  }
+
+  // The bucket starts with a full second worth of commands.
+  for _ in 0..2 {
+    assert!(server
+      .parse_message(vibrate_msg(device_index))
+      .await
+      .is_ok());
+  }
+  let err = server
+    .parse_message(vibrate_msg(device_index))
+    .await
+    .unwrap_err();
+  assert_eq!(err.error_code(), message::ErrorCode::ErrorDevice);
+  assert!(err
+    .original_error()
+    .to_string()
+    .contains("Rate limit exceeded"));
+  assert_eq!(
+    err.original_error(),
+    ButtplugError::from(ButtplugDeviceError::RateLimitExceeded(device_index))
+  );
+  // Stops always get through.
+  assert!(server
+    .parse_message(message::StopDeviceCmdV0::new(device_index).into())
+    .await
+    .is_ok());
+  // At 2 commands per second, a token is back after 500ms.
+  sleep(Duration::from_millis(550)).await;
+  assert!(server
+    .parse_message(vibrate_msg(device_index))
+    .await
+    .is_ok());
+}
+
 // TODO Test sending system message (Id 0)
 // TODO Test sending system message (Ok but Id > 0)
 // TODO Test scan with no comm managers
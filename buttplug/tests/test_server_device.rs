@@ -19,10 +19,20 @@ use buttplug::core::{
     BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION,
   },
 };
+use buttplug::server::device::hardware::{Hardware, HardwareWriteCmd};
 use futures::{pin_mut, StreamExt};
-use std::matches;
+use std::{
+  collections::{BTreeMap, HashSet},
+  matches,
+  time::Duration,
+};
 pub use util::test_device_manager::TestDeviceCommunicationManagerBuilder;
-use util::{test_server_v4_with_device, test_server_with_device};
+use util::{
+  test_device_manager::{new_device_channel, TestDevice, TestDeviceIdentifier, TestHardwareEvent},
+  test_server_v4_with_device,
+  test_server_with_comm_manager,
+  test_server_with_device,
+};
 
 // Test devices that have protocols that support movements not all devices do.
 // For instance, the Onyx+ is part of a protocol that supports vibration, but
@@ -274,3 +284,230 @@ async fn test_repeated_address_additions() {
     }
 }
 */
+
+#[tokio::test]
+async fn test_device_index_persists_across_scans() {
+  let mut builder = TestDeviceCommunicationManagerBuilder::default();
+  let first = TestDeviceIdentifier::new("Massage Demo", Some("FirstAddress".to_owned()));
+  let second = TestDeviceIdentifier::new("Onyx+", Some("SecondAddress".to_owned()));
+  let first_device = builder.add_test_device(&first);
+  let second_device = builder.add_test_device(&second);
+  // Found again on the second scan, second device first, so they'd likely swap indexes if they
+  // were handed out fresh.
+  let _second_device_again = builder.add_reconnecting_test_device(&second);
+  let _first_device_again = builder.add_reconnecting_test_device(&first);
+  let server = test_server_with_comm_manager(builder, false);
+  let recv = server.event_stream();
+  pin_mut!(recv);
+  assert!(server
+    .parse_message(ButtplugClientMessageV4::from(
+      message::RequestServerInfoV1::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+    ))
+    .await
+    .is_ok());
+
+  let mut indexes = vec![];
+  for scan in 0..2 {
+    assert!(server
+      .parse_message(ButtplugClientMessageV4::from(
+        message::StartScanningV0::default()
+      ))
+      .await
+      .is_ok());
+    // Devices can finish connecting in any order, so key indexes by name.
+    let mut added = BTreeMap::new();
+    while added.len() < 2 {
+      match recv.next().await.expect("Test, assuming infallible.") {
+        ButtplugServerMessageV4::DeviceAdded(da) => {
+          added.insert(da.device_name().clone(), da.device_index());
+        }
+        ButtplugServerMessageV4::ScanningFinished(_) => continue,
+        msg => panic!("Unexpected message on scan {scan}: {msg:?}"),
+      }
+    }
+    assert!(server
+      .parse_message(ButtplugClientMessageV4::from(
+        message::StopScanningV0::default()
+      ))
+      .await
+      .is_ok());
+    indexes.push(added);
+    if scan == 0 {
+      for device in [&first_device, &second_device] {
+        device
+          .sender
+          .send(TestHardwareEvent::Disconnect)
+          .await
+          .expect("Test, assuming infallible.");
+      }
+      let mut removed = 0;
+      while removed < 2 {
+        if let ButtplugServerMessageV4::DeviceRemoved(_) =
+          recv.next().await.expect("Test, assuming infallible.")
+        {
+          removed += 1;
+        }
+      }
+    }
+  }
+  assert_eq!(indexes[0].values().collect::<HashSet<_>>().len(), 2);
+  assert_eq!(indexes[0], indexes[1]);
+}
+
+#[tokio::test]
+async fn test_sensor_commands_check_feature_index() {
+  let (server, _device) = test_server_v4_with_device("Krush", false);
+  let recv = server.event_stream();
+  pin_mut!(recv);
+  assert!(server
+    .parse_message(ButtplugClientMessageV4::from(
+      message::RequestServerInfoV1::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+    ))
+    .await
+    .is_ok());
+  assert!(server
+    .parse_message(ButtplugClientMessageV4::from(
+      message::StartScanningV0::default()
+    ))
+    .await
+    .is_ok());
+  while let Some(msg) = recv.next().await {
+    if let ButtplugServerMessageV4::DeviceAdded(da) = msg {
+      let index = da.device_index();
+      // The Krush has a vibrator at feature 0 and a battery at feature 1, so index 0 is not a
+      // sensor and indexes 2 and up are past the end of the feature list.
+      for feature_index in [0, 2, 9999] {
+        let err = server
+          .parse_message(
+            message::SensorReadCmdV4::new(index, feature_index, message::SensorType::Battery)
+              .into(),
+          )
+          .await
+          .unwrap_err();
+        assert!(matches!(
+          err.original_error(),
+          ButtplugError::ButtplugDeviceError(ButtplugDeviceError::DeviceSensorIndexError(..))
+        ));
+        assert_eq!(err.error_code(), message::ErrorCode::ErrorDevice);
+      }
+      let err = server
+        .parse_message(message::SensorReadCmdV4::new(index, 1, message::SensorType::RSSI).into())
+        .await
+        .unwrap_err();
+      assert!(matches!(
+        err.original_error(),
+        ButtplugError::ButtplugDeviceError(ButtplugDeviceError::DeviceSensorTypeMismatch(..))
+      ));
+      return;
+    }
+  }
+  panic!("Never got DeviceAdded message");
+}
+
+#[tokio::test]
+async fn test_v3_sensor_commands_check_sensor_index() {
+  let (server, _) = test_server_with_device("Krush", false);
+  let recv = server.client_version_event_stream();
+  pin_mut!(recv);
+  assert!(server
+    .parse_message(ButtplugClientMessageVariant::V3(
+      message::RequestServerInfoV1::new(
+        "Test Client",
+        message::ButtplugMessageSpecVersion::Version3
+      )
+      .into()
+    ))
+    .await
+    .is_ok());
+  assert!(server
+    .parse_message(ButtplugClientMessageVariant::V3(
+      message::StartScanningV0::default().into()
+    ))
+    .await
+    .is_ok());
+  while let Some(msg) = recv.next().await {
+    if let ButtplugServerMessageVariant::V3(ButtplugServerMessageV3::DeviceAdded(da)) = msg {
+      let err = server
+        .parse_message(ButtplugClientMessageVariant::V3(
+          message::SensorReadCmdV3::new(da.device_index(), 9999, message::SensorType::Battery)
+            .into(),
+        ))
+        .await
+        .unwrap_err();
+      if let ButtplugServerMessageVariant::V3(ButtplugServerMessageV3::Error(e)) = err {
+        assert_eq!(e.error_code(), message::ErrorCode::ErrorDevice);
+      } else {
+        panic!("Should've gotten error")
+      }
+      return;
+    }
+  }
+  panic!("Never got DeviceAdded message");
+}
+
+#[tokio::test]
+async fn test_raw_read_communication_timeout() {
+  let (server, _device) = test_server_v4_with_device("Massage Demo", true);
+  let recv = server.event_stream();
+  pin_mut!(recv);
+  assert!(server
+    .parse_message(ButtplugClientMessageV4::from(
+      message::RequestServerInfoV1::new("Test Client", BUTTPLUG_CURRENT_MESSAGE_SPEC_VERSION)
+    ))
+    .await
+    .is_ok());
+  assert!(server
+    .parse_message(ButtplugClientMessageV4::from(
+      message::StartScanningV0::default()
+    ))
+    .await
+    .is_ok());
+  while let Some(msg) = recv.next().await {
+    if let ButtplugServerMessageV4::DeviceAdded(da) = msg {
+      // Nothing queued on the test device, so the read never completes.
+      let err = server
+        .parse_message(ButtplugClientMessageV4::from(message::RawReadCmdV2::new(
+          da.device_index(),
+          Endpoint::Tx,
+          0,
+          20,
+        )))
+        .await
+        .unwrap_err();
+      assert_eq!(
+        err.original_error(),
+        ButtplugError::ButtplugDeviceError(ButtplugDeviceError::CommunicationTimeout(
+          Duration::from_millis(20)
+        ))
+      );
+      return;
+    }
+  }
+  panic!("Never got DeviceAdded message");
+}
+
+#[tokio::test]
+async fn test_write_after_hardware_disconnect() {
+  let (host, device_channel) = new_device_channel();
+  let mut device = TestDevice::new("Disconnect Test", "disconnect-test", device_channel);
+  device.add_endpoint(&Endpoint::Tx);
+  let hardware = Hardware::new(
+    "Disconnect Test",
+    "disconnect-test",
+    &[Endpoint::Tx],
+    Box::new(device),
+  );
+  let write = HardwareWriteCmd::new(Endpoint::Tx, vec![0x0], false);
+  assert!(hardware.write_value(&write).await.is_ok());
+  host
+    .sender
+    .send(TestHardwareEvent::Disconnect)
+    .await
+    .expect("Test, assuming infallible");
+  // Give the hardware a moment to see the disconnect event.
+  tokio::time::sleep(Duration::from_millis(50)).await;
+  assert_eq!(
+    hardware.write_value(&write).await,
+    Err(ButtplugDeviceError::HardwareDisconnected)
+  );
+}
@@ -2,6 +2,7 @@ use crate::util::{
   device_test::connector::build_channel_connector,
   ButtplugTestServer,
   TestDeviceChannelHost,
+  TestHardwareEvent,
 };
 use buttplug::{
   client::{
@@ -21,6 +22,7 @@ use tokio::sync::Notify;
 
 use super::super::{
   super::TestDeviceCommunicationManagerBuilder,
+  DeviceSlots,
   DeviceTestCase,
   TestClientCommand,
   TestCommand,
@@ -90,46 +92,26 @@ async fn run_test_client_command(command: &TestClientCommand, device: &Arc<Buttp
         assert_eq!(device.battery_level().await.unwrap(), *expected_power);
       }
     }
-    _ => {
-      panic!(
-        "Tried to run unhandled TestClientCommand type {:?}",
-        command
+    RSSI {
+      expected_rssi_min,
+      expected_rssi_max,
+    } => {
+      let rssi = device.rssi_level().await.unwrap();
+      assert!(
+        (*expected_rssi_min..=*expected_rssi_max).contains(&rssi),
+        "RSSI {} outside of expected range {}..={}",
+        rssi,
+        expected_rssi_min,
+        expected_rssi_max
       );
     }
   }
 }
 
-fn build_server(test_case: &DeviceTestCase) -> (ButtplugServer, Vec<TestDeviceChannelHost>) {
-  let base_cfg = if let Some(device_config_file) = &test_case.device_config_file {
-    let config_file_path = std::path::Path::new(
-      &std::env::var("CARGO_MANIFEST_DIR").expect("Should have manifest path"),
-    )
-    .join("tests")
-    .join("util")
-    .join("device_test")
-    .join("device_test_case")
-    .join("config")
-    .join(device_config_file);
-
-    Some(std::fs::read_to_string(config_file_path).expect("Should be able to load config"))
-  } else {
-    None
-  };
-  let user_cfg = if let Some(user_device_config_file) = &test_case.user_device_config_file {
-    let config_file_path = std::path::Path::new(
-      &std::env::var("CARGO_MANIFEST_DIR").expect("Should have manifest path"),
-    )
-    .join("tests")
-    .join("util")
-    .join("device_test")
-    .join("device_test_case")
-    .join("config")
-    .join(user_device_config_file);
-    Some(std::fs::read_to_string(config_file_path).expect("Should be able to load config"))
-  } else {
-    None
-  };
-
+fn build_server(
+  test_case: &DeviceTestCase,
+) -> (ButtplugServer, Vec<TestDeviceChannelHost>, DeviceSlots) {
+  let (base_cfg, user_cfg) = test_case.load_config_files();
   let dcm = load_protocol_configs(&base_cfg, &user_cfg, false)
     .unwrap()
     .finish()
@@ -141,21 +123,27 @@ fn build_server(test_case: &DeviceTestCase) -> (ButtplugServer, Vec<TestDeviceCh
     info!("identifier: {:?}", device.identifier);
     device_channels.push(builder.add_test_device(&device.identifier));
   }
+  let device_addresses = (0..test_case.devices.len())
+    .map(|slot| {
+      builder
+        .device_address(slot)
+        .expect("Every test case device was just added")
+    })
+    .collect();
   let dm = ServerDeviceManagerBuilder::new(dcm)
     .comm_manager(builder)
     .finish()
     .unwrap();
+  let server = ButtplugServerBuilder::new(dm)
+    .finish()
+    .expect("Should always build");
+  let device_slots = DeviceSlots::new(server.device_manager(), device_addresses);
 
-  (
-    ButtplugServerBuilder::new(dm)
-      .finish()
-      .expect("Should always build"),
-    device_channels,
-  )
+  (server, device_channels, device_slots)
 }
 
 pub async fn run_embedded_test_case(test_case: &DeviceTestCase) {
-  let (server, device_channels) = build_server(test_case);
+  let (server, device_channels, device_slots) = build_server(test_case);
   // Connect client
   let client = ButtplugClient::new("Test Client");
   let mut in_process_connector_builder = ButtplugInProcessClientConnectorBuilder::default();
@@ -164,7 +152,7 @@ pub async fn run_embedded_test_case(test_case: &DeviceTestCase) {
     .connect(in_process_connector_builder.finish())
     .await
     .expect("Test client couldn't connect to embedded process");
-  run_test_case(client, device_channels, test_case).await;
+  run_test_case(client, device_channels, device_slots, test_case).await;
 }
 
 pub async fn run_json_test_case(test_case: &DeviceTestCase) {
@@ -172,7 +160,7 @@ pub async fn run_json_test_case(test_case: &DeviceTestCase) {
 
   let (client_connector, server_connector) = build_channel_connector(&notify);
 
-  let (server, device_channels) = build_server(test_case);
+  let (server, device_channels, device_slots) = build_server(test_case);
   let remote_server = ButtplugTestServer::new(server);
   async_manager::spawn(async move {
     remote_server
@@ -187,12 +175,13 @@ pub async fn run_json_test_case(test_case: &DeviceTestCase) {
     .connect(client_connector)
     .await
     .expect("Test client couldn't connect to embedded process");
-  run_test_case(client, device_channels, test_case).await;
+  run_test_case(client, device_channels, device_slots, test_case).await;
 }
 
 pub async fn run_test_case(
   client: ButtplugClient,
   mut device_channels: Vec<TestDeviceChannelHost>,
+  mut device_slots: DeviceSlots,
   test_case: &DeviceTestCase,
 ) {
   let mut event_stream = client.event_stream();
@@ -236,6 +225,7 @@ pub async fn run_test_case(
         TestCommand::Events {
           device_index,
           events,
+          ..
         } => {
           let device_sender = &device_channels[*device_index as usize].sender;
           for event in events {
@@ -246,8 +236,9 @@ pub async fn run_test_case(
     }
   }
 
-  // Scan for devices, wait 'til we get all of the ones we're expecting. Also check names at this
-  // point.
+  // Scan for devices, wait 'til every slot has connected. Checking the client's device count isn't
+  // enough, as the client may already know about devices we haven't seen DeviceAdded for yet. Also
+  // check names at this point.
   loop {
     tokio::select! {
       _ = tokio::time::sleep(Duration::from_millis(300)) => {
@@ -255,14 +246,17 @@ pub async fn run_test_case(
       }
       event = event_stream.next() => {
         if let Some(ButtplugClientEvent::DeviceAdded(device_added)) = event {
+          let expected_device = &test_case.devices[device_slots.device_added(device_added.index())];
           // Compare expected device name
-          if let Some(expected_name) = &test_case.devices[device_added.index() as usize].expected_name {
+          if let Some(expected_name) = &expected_device.expected_name {
             assert_eq!(*expected_name, *device_added.name());
           }
-          if let Some(expected_display_name) = &test_case.devices[device_added.index() as usize].expected_display_name {
+          if let Some(expected_display_name) = &expected_device.expected_display_name {
             assert_eq!(Some(expected_display_name.clone()), *device_added.display_name());
+            assert_eq!(*expected_display_name, device_added.to_short_string());
+            assert!(device_added.to_string().ends_with(&format!(" ({expected_display_name})")));
           }
-          if client.devices().len() == test_case.devices.len() {
+          if device_slots.all_connected() {
             break;
           }
         } else if event.is_none() {
@@ -281,9 +275,14 @@ pub async fn run_test_case(
         device_index,
         messages,
       } => {
-        let device = &client.devices()[*device_index as usize];
+        let index = device_slots.device_index(*device_index);
+        let device = client
+          .devices()
+          .into_iter()
+          .find(|device| device.index() == index)
+          .expect("Device should still be connected");
         for message in messages {
-          run_test_client_command(message, device).await;
+          run_test_client_command(message, &device).await;
         }
       }
       TestCommand::Commands {
@@ -309,10 +308,34 @@ pub async fn run_test_case(
       TestCommand::Events {
         device_index,
         events,
+        timeout,
       } => {
         let device_sender = &device_channels[*device_index as usize].sender;
         for event in events {
           device_sender.send(event.clone()).await.unwrap();
+          if !matches!(event, TestHardwareEvent::Disconnect) {
+            continue;
+          }
+          // Make sure the client knows the device is gone before running anything else.
+          let wait = tokio::time::sleep(Duration::from_millis(timeout.unwrap_or(500)));
+          tokio::pin!(wait);
+          loop {
+            tokio::select! {
+              _ = &mut wait => {
+                panic!("Timeout while waiting for device removal!")
+              }
+              event = event_stream.next() => {
+                if let Some(ButtplugClientEvent::DeviceRemoved(device_removed)) = event {
+                  assert_eq!(device_removed.index(), device_slots.device_index(*device_index));
+                  break;
+                } else if event.is_none() {
+                  panic!("Should not have dropped event stream!");
+                } else {
+                  debug!("Ignoring client message while waiting for device removal: {:?}", event);
+                }
+              }
+            }
+          }
         }
       }
     }
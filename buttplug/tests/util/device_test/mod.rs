@@ -10,9 +10,10 @@ use buttplug::{
     VectorSubcommandV1,
     VibrateSubcommandV1,
   },
-  server::device::hardware::HardwareCommand,
+  server::device::{hardware::HardwareCommand, ServerDeviceManager},
 };
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
 #[derive(Serialize, Deserialize)]
 struct TestDevice {
@@ -21,6 +22,8 @@ struct TestDevice {
   expected_display_name: Option<String>,
 }
 
+// device_index is the device's slot, i.e. its position in the test case's devices list, not the
+// index the server assigns it.
 #[derive(Serialize, Deserialize)]
 enum TestCommand {
   Messages {
@@ -34,6 +37,9 @@ enum TestCommand {
   Events {
     device_index: u32,
     events: Vec<TestHardwareEvent>,
+    // Milliseconds to wait for the client to see the device removed, if a Disconnect event is sent.
+    #[serde(default)]
+    timeout: Option<u64>,
   },
 }
 
@@ -48,7 +54,11 @@ enum TestClientCommand {
     run_async: bool,
   },
   Stop,
-  RSSI,
+  // RSSI fluctuates on real hardware, so tests give the range of values they'll accept.
+  RSSI {
+    expected_rssi_min: i32,
+    expected_rssi_max: i32,
+  },
 }
 
 #[derive(Serialize, Deserialize)]
@@ -59,3 +69,73 @@ pub struct DeviceTestCase {
   device_init: Option<Vec<TestCommand>>,
   device_commands: Vec<TestCommand>,
 }
+
+impl DeviceTestCase {
+  /// Reads the base and user device configuration files named by the test case, if any. Paths are
+  /// relative to `tests/util/device_test/device_test_case/config`.
+  fn load_config_files(&self) -> (Option<String>, Option<String>) {
+    let load = |file: &Option<String>| {
+      file.as_ref().map(|file| {
+        let config_file_path = std::path::Path::new(
+          &std::env::var("CARGO_MANIFEST_DIR").expect("Should have manifest path"),
+        )
+        .join("tests")
+        .join("util")
+        .join("device_test")
+        .join("device_test_case")
+        .join("config")
+        .join(file);
+        std::fs::read_to_string(config_file_path).expect("Should be able to load config")
+      })
+    };
+    (
+      load(&self.device_config_file),
+      load(&self.user_device_config_file),
+    )
+  }
+}
+
+/// Maps device slots (positions in [DeviceTestCase::devices]) to the device indexes the server
+/// assigned them. Devices can finish connecting in any order, so this is filled in as DeviceAdded
+/// events arrive, by matching the address of the connected device to the address of each slot.
+pub struct DeviceSlots {
+  device_manager: Arc<ServerDeviceManager>,
+  addresses: Vec<String>,
+  indexes: Vec<Option<u32>>,
+}
+
+impl DeviceSlots {
+  /// `addresses` holds the address each slot's device connects with, in slot order.
+  fn new(device_manager: Arc<ServerDeviceManager>, addresses: Vec<String>) -> Self {
+    let indexes = vec![None; addresses.len()];
+    Self {
+      device_manager,
+      addresses,
+      indexes,
+    }
+  }
+
+  /// Records that the server added a device at `device_index`, returning the slot it belongs to.
+  fn device_added(&mut self, device_index: u32) -> usize {
+    let info = self
+      .device_manager
+      .device_info(device_index)
+      .expect("Server should know about devices it just added");
+    let slot = self
+      .addresses
+      .iter()
+      .position(|address| address == info.identifier().address())
+      .expect("Added device should match one of the test case devices");
+    self.indexes[slot] = Some(device_index);
+    slot
+  }
+
+  fn all_connected(&self) -> bool {
+    self.indexes.iter().all(|index| index.is_some())
+  }
+
+  /// Server device index for the device in `slot`.
+  fn device_index(&self, slot: u32) -> u32 {
+    self.indexes[slot as usize].expect("Device in slot should have connected")
+  }
+}
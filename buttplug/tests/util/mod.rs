@@ -77,10 +77,17 @@ pub async fn test_client() -> ButtplugClient {
 
 #[allow(dead_code)]
 pub async fn test_client_with_device() -> (ButtplugClient, TestDeviceChannelHost) {
+  test_client_with_device_allow_raw(false).await
+}
+
+#[allow(dead_code)]
+pub async fn test_client_with_device_allow_raw(
+  allow_raw_messages: bool,
+) -> (ButtplugClient, TestDeviceChannelHost) {
   let mut builder = TestDeviceCommunicationManagerBuilder::default();
   let device = builder.add_test_device(&TestDeviceIdentifier::new("Massage Demo", None));
 
-  let mut dm_builder = ServerDeviceManagerBuilder::new(create_test_dcm(false));
+  let mut dm_builder = ServerDeviceManagerBuilder::new(create_test_dcm(allow_raw_messages));
   dm_builder.comm_manager(builder);
 
   let server_builder = ButtplugServerBuilder::new(dm_builder.finish().unwrap());
@@ -14,6 +14,8 @@ use buttplug::{
   util::stream::{iffy_is_empty_check, recv_now},
 };
 use std::sync::{Arc, Mutex};
+#[allow(unused_imports)]
+pub use test_device::{new_device_channel, TestHardwareNotification};
 pub use test_device::{TestDevice, TestDeviceChannelHost, TestHardwareEvent};
 #[cfg(feature = "server")]
 pub use test_device_comm_manager::{
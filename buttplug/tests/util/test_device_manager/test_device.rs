@@ -6,7 +6,10 @@
 // for full license information.
 
 use buttplug::{
-  core::{errors::ButtplugDeviceError, message::Endpoint},
+  core::{
+    errors::ButtplugDeviceError,
+    message::{Endpoint, SensorType},
+  },
   server::device::{
     configuration::ProtocolCommunicationSpecifier,
     hardware::{
@@ -17,6 +20,7 @@ use buttplug::{
       HardwareInternal,
       HardwareReadCmd,
       HardwareReading,
+      HardwareSensorReadCmd,
       HardwareSpecializer,
       HardwareSubscribeCmd,
       HardwareUnsubscribeCmd,
@@ -33,7 +37,11 @@ use serde::{Deserialize, Serialize};
 use std::{
   collections::{HashSet, VecDeque},
   fmt::{self, Debug},
-  sync::Arc,
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+  },
+  time::Duration,
 };
 use tokio::sync::{broadcast, mpsc, Mutex};
 
@@ -43,6 +51,16 @@ pub struct TestHardwareNotification {
   data: Vec<u8>,
 }
 
+impl TestHardwareNotification {
+  #[allow(dead_code)]
+  pub fn new(endpoint: Endpoint, data: &[u8]) -> Self {
+    Self {
+      endpoint,
+      data: data.to_vec(),
+    }
+  }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum TestHardwareEvent {
   // Values to be emitted from subscriptions
@@ -50,6 +68,13 @@ pub enum TestHardwareEvent {
   // Values to be emitted when calls to ReadValue happen
   Reads(Vec<TestHardwareNotification>),
   Disconnect,
+  // Time each write should take before it's sent, to simulate slow hardware
+  WriteDelay(Duration),
+  // Value returned when the hardware layer is asked to read a sensor, like RSSI
+  SensorReading {
+    sensor_type: SensorType,
+    data: Vec<i32>,
+  },
 }
 
 pub struct TestHardwareConnector {
@@ -119,13 +144,12 @@ impl HardwareSpecializer for TestHardwareSpecializer {
         }
       }
     }
-    let hardware = Hardware::new(
+    Hardware::try_new(
       &device.name(),
       &device.address(),
       &endpoints,
       Box::new(device),
-    );
-    Ok(hardware)
+    )
   }
 }
 
@@ -139,6 +163,7 @@ pub struct TestDeviceChannelDevice {
   pub receiver: mpsc::Receiver<TestHardwareEvent>,
 }
 
+#[allow(dead_code)]
 pub fn new_device_channel() -> (TestDeviceChannelHost, TestDeviceChannelDevice) {
   let (host_sender, device_receiver) = mpsc::channel(256);
   let (device_sender, host_receiver) = mpsc::channel(256);
@@ -154,6 +179,9 @@ pub fn new_device_channel() -> (TestDeviceChannelHost, TestDeviceChannelDevice)
   )
 }
 
+/// Latest sensor reading set through [TestHardwareEvent::SensorReading], per sensor type.
+type SensorData = (SensorType, Vec<i32>);
+
 pub struct TestDevice {
   name: String,
   address: String,
@@ -162,6 +190,8 @@ pub struct TestDevice {
   event_sender: broadcast::Sender<HardwareEvent>,
   subscribed_endpoints: Arc<DashSet<Endpoint>>,
   read_data: Arc<Mutex<VecDeque<HardwareReading>>>,
+  sensor_data: Arc<Mutex<Vec<SensorData>>>,
+  write_delay_ms: Arc<AtomicU64>,
 }
 
 impl TestDevice {
@@ -176,6 +206,10 @@ impl TestDevice {
     let subscribed_endpoints_clone = subscribed_endpoints.clone();
     let read_data = Arc::new(Mutex::new(VecDeque::new()));
     let read_data_clone = read_data.clone();
+    let sensor_data = Arc::new(Mutex::new(vec![]));
+    let sensor_data_clone = sensor_data.clone();
+    let write_delay_ms = Arc::new(AtomicU64::new(0));
+    let write_delay_ms_clone = write_delay_ms.clone();
     async_manager::spawn(async move {
       while let Some(event) = receiver.recv().await {
         match event {
@@ -203,6 +237,14 @@ impl TestDevice {
               guard.push_front(HardwareReading::new(read.endpoint, &read.data));
             }
           }
+          TestHardwareEvent::WriteDelay(delay) => {
+            write_delay_ms_clone.store(delay.as_millis() as u64, Ordering::Relaxed);
+          }
+          TestHardwareEvent::SensorReading { sensor_type, data } => {
+            let mut guard = sensor_data_clone.lock().await;
+            guard.retain(|(stored_type, _)| *stored_type != sensor_type);
+            guard.push((sensor_type, data));
+          }
         }
       }
     });
@@ -215,9 +257,17 @@ impl TestDevice {
       event_sender,
       subscribed_endpoints,
       read_data,
+      sensor_data,
+      write_delay_ms,
     }
   }
 
+  pub fn set_write_delay(&self, delay: Duration) {
+    self
+      .write_delay_ms
+      .store(delay.as_millis() as u64, Ordering::Relaxed);
+  }
+
   pub fn add_endpoint(&mut self, endpoint: &Endpoint) {
     self.endpoints.insert(*endpoint);
   }
@@ -266,19 +316,24 @@ impl HardwareInternal for TestDevice {
   ) -> BoxFuture<'static, Result<HardwareReading, ButtplugDeviceError>> {
     let reads = self.read_data.clone();
     let msg = *msg;
+    // Protocols that don't specify a read timeout still shouldn't hang tests forever.
+    let timeout = std::time::Duration::from_millis(if msg.timeout_ms() == 0 {
+      50
+    } else {
+      msg.timeout_ms() as u64
+    });
     async move {
-      let mut count = 0;
+      let start = std::time::Instant::now();
       loop {
-        if count == 5 {
-          panic!("Not getting expected read in time!");
-        }
         {
           if reads.lock().await.len() > 0 {
             break;
           }
         }
+        if start.elapsed() >= timeout {
+          return Err(ButtplugDeviceError::CommunicationTimeout(timeout));
+        }
         tokio::time::sleep(std::time::Duration::from_millis(10)).await;
-        count += 1;
       }
       let read_msg = reads.lock().await.pop_back().unwrap();
       if *read_msg.endpoint() != msg.endpoint() {
@@ -294,6 +349,34 @@ impl HardwareInternal for TestDevice {
     .boxed()
   }
 
+  fn read_sensor(
+    &self,
+    msg: &HardwareSensorReadCmd,
+  ) -> BoxFuture<'static, Result<Vec<i32>, ButtplugDeviceError>> {
+    let sensor_data = self.sensor_data.clone();
+    let sensor_type = msg.sensor_type();
+    // Same as reads, give the test a moment to send the value, but don't hang forever.
+    let timeout = std::time::Duration::from_millis(50);
+    async move {
+      let start = std::time::Instant::now();
+      loop {
+        if let Some((_, data)) = sensor_data
+          .lock()
+          .await
+          .iter()
+          .find(|(stored_type, _)| *stored_type == sensor_type)
+        {
+          return Ok(data.clone());
+        }
+        if start.elapsed() >= timeout {
+          return Err(ButtplugDeviceError::CommunicationTimeout(timeout));
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+      }
+    }
+    .boxed()
+  }
+
   fn write_value(
     &self,
     msg: &HardwareWriteCmd,
@@ -301,7 +384,15 @@ impl HardwareInternal for TestDevice {
     if !self.endpoints.contains(&msg.endpoint()) {
       return future::ready(Err(ButtplugDeviceError::InvalidEndpoint(msg.endpoint()))).boxed();
     }
-    self.send_command(msg.clone().into())
+    let delay = Duration::from_millis(self.write_delay_ms.load(Ordering::Relaxed));
+    let send_fut = self.send_command(msg.clone().into());
+    async move {
+      if !delay.is_zero() {
+        tokio::time::sleep(delay).await;
+      }
+      send_fut.await
+    }
+    .boxed()
   }
 
   fn subscribe(
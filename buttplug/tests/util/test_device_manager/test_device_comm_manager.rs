@@ -26,12 +26,12 @@ use buttplug::{
 use futures::future::{self, FutureExt};
 use serde::{Deserialize, Serialize};
 use std::{
-  collections::HashMap,
+  collections::{HashMap, HashSet},
   sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
   },
-  time::{SystemTime, UNIX_EPOCH},
+  time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use tokio::sync::mpsc::Sender;
 use tracing::*;
@@ -66,8 +66,13 @@ impl TestDeviceIdentifier {
   }
 }
 
+type TestDeviceEntry = (TestDeviceIdentifier, TestDeviceChannelDevice, Duration);
+
+/// Devices are kept in the order they were added, and each one's position in that order is its
+/// slot. Slots stay stable no matter what order the devices finish connecting in, and devices with
+/// identical identifiers (i.e. two of the same toy) still get their own slot.
 pub struct TestDeviceCommunicationManagerBuilder {
-  devices: Option<Vec<(TestDeviceIdentifier, TestDeviceChannelDevice)>>,
+  devices: Option<Vec<TestDeviceEntry>>,
 }
 
 impl Default for TestDeviceCommunicationManagerBuilder {
@@ -80,14 +85,61 @@ impl Default for TestDeviceCommunicationManagerBuilder {
 
 impl TestDeviceCommunicationManagerBuilder {
   pub fn add_test_device(&mut self, device: &TestDeviceIdentifier) -> TestDeviceChannelHost {
+    self.add_slow_test_device(device, Duration::ZERO)
+  }
+
+  /// Adds a test device that waits `write_latency` before completing each write command, to
+  /// simulate a slow bus. Latency can still be changed later via [TestHardwareEvent::WriteDelay].
+  ///
+  /// [TestHardwareEvent::WriteDelay]: super::TestHardwareEvent::WriteDelay
+  pub fn add_slow_test_device(
+    &mut self,
+    device: &TestDeviceIdentifier,
+    write_latency: Duration,
+  ) -> TestDeviceChannelHost {
+    let (host_channel, device_channel) = new_device_channel();
+    let devices = self
+      .devices
+      .as_mut()
+      .expect("Devices vec does not exist, is this running twice?");
+    // The server tells devices apart by address, so if this is a copy of a device we already have,
+    // give it an address of its own or the two will be treated as the same device.
+    let mut device = device.clone();
+    if devices
+      .iter()
+      .any(|(identifier, _, _)| identifier.address == device.address)
+    {
+      device.address = format!("{}-{}", device.address, devices.len());
+    }
+    devices.push((device, device_channel, write_latency));
+    host_channel
+  }
+
+  /// Adds a device with the same address as one added earlier, as if the same hardware was found
+  /// again after disconnecting. Each scan only finds one device per address, so this shows up on
+  /// the scan after the earlier device was found.
+  pub fn add_reconnecting_test_device(
+    &mut self,
+    device: &TestDeviceIdentifier,
+  ) -> TestDeviceChannelHost {
     let (host_channel, device_channel) = new_device_channel();
     self
       .devices
       .as_mut()
       .expect("Devices vec does not exist, is this running twice?")
-      .push((device.clone(), device_channel));
+      .push((device.clone(), device_channel, Duration::ZERO));
     host_channel
   }
+
+  /// Returns the address the device in `slot` will connect with, which may differ from the address
+  /// it was added with if another device already used that address.
+  pub fn device_address(&self, slot: usize) -> Option<String> {
+    self
+      .devices
+      .as_ref()
+      .and_then(|devices| devices.get(slot))
+      .map(|(identifier, _, _)| identifier.address.clone())
+  }
 }
 
 impl HardwareCommunicationManagerBuilder for TestDeviceCommunicationManagerBuilder {
@@ -108,25 +160,27 @@ impl HardwareCommunicationManagerBuilder for TestDeviceCommunicationManagerBuild
 fn new_uninitialized_ble_test_device(
   identifier: &TestDeviceIdentifier,
   device_channel: TestDeviceChannelDevice,
+  write_latency: Duration,
 ) -> TestHardwareConnector {
   let address = identifier.address.clone();
   let specifier = ProtocolCommunicationSpecifier::BluetoothLE(
     BluetoothLESpecifier::new_from_device(&identifier.name, &HashMap::new(), &[]),
   );
   let hardware = TestDevice::new(&identifier.name, &address, device_channel);
+  hardware.set_write_delay(write_latency);
   TestHardwareConnector::new(specifier, hardware)
 }
 
 pub struct TestDeviceCommunicationManager {
   device_sender: Sender<HardwareCommunicationManagerEvent>,
-  devices: Vec<(TestDeviceIdentifier, TestDeviceChannelDevice)>,
+  devices: Vec<TestDeviceEntry>,
   is_scanning: Arc<AtomicBool>,
 }
 
 impl TestDeviceCommunicationManager {
   pub fn new(
     device_sender: Sender<HardwareCommunicationManagerEvent>,
-    devices: Vec<(TestDeviceIdentifier, TestDeviceChannelDevice)>,
+    devices: Vec<TestDeviceEntry>,
   ) -> Self {
     Self {
       device_sender,
@@ -147,9 +201,16 @@ impl HardwareCommunicationManager for TestDeviceCommunicationManager {
     }
 
     let mut events = vec![];
+    let mut found_addresses = HashSet::new();
+    let mut later_devices = vec![];
 
-    while let Some((device, test_channel)) = self.devices.pop() {
-      let device_creator = new_uninitialized_ble_test_device(&device, test_channel);
+    for (device, test_channel, write_latency) in self.devices.drain(..) {
+      // Reconnecting devices wait for a later scan, once their earlier copy has been found.
+      if !found_addresses.insert(device.address.clone()) {
+        later_devices.push((device, test_channel, write_latency));
+        continue;
+      }
+      let device_creator = new_uninitialized_ble_test_device(&device, test_channel, write_latency);
 
       events.push(HardwareCommunicationManagerEvent::DeviceFound {
         name: device.name.clone(),
@@ -157,6 +218,7 @@ impl HardwareCommunicationManager for TestDeviceCommunicationManager {
         creator: Box::new(device_creator),
       });
     }
+    self.devices = later_devices;
     let device_sender = self.device_sender.clone();
     let is_scanning = self.is_scanning.clone();
     async move {